@@ -6,6 +6,7 @@
 use crate::ai::feature_extractor::AudioFeatures;
 use anyhow::{Context, Result};
 use std::collections::VecDeque;
+use std::time::Duration;
 
 /// Smart volume normalizer with AI-based content awareness
 pub struct VolumeNormalizer {
@@ -31,7 +32,7 @@ impl VolumeNormalizer {
             history_size: 48000,
             content_analyzer: ContentAnalyzer::new(),
             dynamics_processor: DynamicsProcessor::new()?,
-            loudness_meter: LoudnessMeter::new()?,
+            loudness_meter: LoudnessMeter::new(48000, 1).with_window(Duration::from_secs(30)),
         })
     }
 
@@ -531,6 +532,7 @@ impl Gate {
 
 /// K-weighting filter implementing ITU-R BS.1770-4 standard
 /// Two-stage filtering: pre-filter (high-shelf) + RLB filter (high-pass)
+#[derive(Debug, Clone)]
 struct KWeightingFilter {
     // Pre-filter (high-shelf) coefficients
     pre_b0: f32,
@@ -565,19 +567,20 @@ impl KWeightingFilter {
         use std::f32::consts::PI;
 
         // Stage 1: Pre-filter (high-shelf)
-        // fc = 1681.97 Hz, Q = 0.7071, Gain = 3.999843853973347 dB
-        let fc_pre = 1681.97;
+        // fc = 1681.97 Hz, Q = 0.7071752, Gain = 3.999843853973347 dB
+        let fc_pre = 1681.9745;
+        let q_pre = 0.707_175_24;
         let gain_db = 3.999843853973347;
         let k_pre = (PI * fc_pre / sample_rate).tan();
         let vh = 10.0_f32.powf(gain_db / 20.0);
-        let sqrt_vh = vh.sqrt();
+        let vb = vh.powf(0.499_666_78);
 
-        let denominator_pre = 1.0 + 2.0_f32.sqrt() * k_pre + k_pre * k_pre;
-        let pre_b0 = (vh + sqrt_vh * k_pre + k_pre * k_pre) / denominator_pre;
+        let denominator_pre = 1.0 + k_pre / q_pre + k_pre * k_pre;
+        let pre_b0 = (vh + vb * k_pre / q_pre + k_pre * k_pre) / denominator_pre;
         let pre_b1 = 2.0 * (k_pre * k_pre - vh) / denominator_pre;
-        let pre_b2 = (vh - sqrt_vh * k_pre + k_pre * k_pre) / denominator_pre;
+        let pre_b2 = (vh - vb * k_pre / q_pre + k_pre * k_pre) / denominator_pre;
         let pre_a1 = 2.0 * (k_pre * k_pre - 1.0) / denominator_pre;
-        let pre_a2 = (1.0 - 2.0_f32.sqrt() * k_pre + k_pre * k_pre) / denominator_pre;
+        let pre_a2 = (1.0 - k_pre / q_pre + k_pre * k_pre) / denominator_pre;
 
         // Stage 2: RLB filter (high-pass)
         // fc = 38.13547 Hz, Q = 0.5003270373253953
@@ -585,10 +588,11 @@ impl KWeightingFilter {
         let q_rlb = 0.5003270373253953;
         let k_rlb = (PI * fc_rlb / sample_rate).tan();
 
+        // Unity numerator as in the BS.1770 reference coefficients
         let denominator_rlb = 1.0 + k_rlb / q_rlb + k_rlb * k_rlb;
-        let rlb_b0 = 1.0 / denominator_rlb;
-        let rlb_b1 = -2.0 * rlb_b0;
-        let rlb_b2 = rlb_b0;
+        let rlb_b0 = 1.0;
+        let rlb_b1 = -2.0;
+        let rlb_b2 = 1.0;
         let rlb_a1 = 2.0 * (k_rlb * k_rlb - 1.0) / denominator_rlb;
         let rlb_a2 = (1.0 - k_rlb / q_rlb + k_rlb * k_rlb) / denominator_rlb;
 
@@ -642,107 +646,272 @@ impl KWeightingFilter {
 
         rlb_output
     }
-
-    /// Reset filter state (e.g., between tracks)
-    fn reset(&mut self) {
-        self.pre_x1 = 0.0;
-        self.pre_x2 = 0.0;
-        self.pre_y1 = 0.0;
-        self.pre_y2 = 0.0;
-        self.rlb_x1 = 0.0;
-        self.rlb_x2 = 0.0;
-        self.rlb_y1 = 0.0;
-        self.rlb_y2 = 0.0;
-    }
 }
 
+/// Absolute gate of BS.1770 integrated loudness, in LUFS
+pub(crate) const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Relative gate, in LU below the absolute-gated loudness
+pub(crate) const RELATIVE_GATE_LU: f64 = -10.0;
+/// Gating blocks are 400 ms long and start every 100 ms
+const STEPS_PER_BLOCK: usize = 4;
+const STEP: Duration = Duration::from_millis(100);
+
 /// LUFS/LKFS loudness meter implementing ITU-R BS.1770-4 standard
-struct LoudnessMeter {
+///
+/// Feed interleaved audio with [`process`](Self::process), in chunks of any
+/// size, then read the gated [`integrated_loudness`](Self::integrated_loudness)
+/// and the 4x oversampled [`true_peak`](Self::true_peak). The block energies
+/// of several meters can be pooled with [`gated_loudness`](Self::gated_loudness)
+/// to measure an album as one programme.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
     sample_rate: u32,
-    block_size: usize,
-    blocks: VecDeque<f32>,
-    k_weighting_filter: KWeightingFilter,
-    gate_threshold_absolute: f32, // -70 LKFS
-    gate_threshold_relative: f32, // -10 LU relative to ungated loudness
+    channels: usize,
+    /// One K-weighting filter per channel
+    filters: Vec<KWeightingFilter>,
+    /// BS.1770 channel weights (surrounds +1.5 dB, LFE excluded)
+    weights: Vec<f64>,
+    peak_detectors: Vec<TruePeakDetector>,
+    step_frames: usize,
+    step_energy: f64,
+    step_position: usize,
+    /// Mean-square energy of the last few 100 ms steps
+    recent_steps: VecDeque<f64>,
+    /// Mean-square energy of each 400 ms gating block, 75% overlapped
+    blocks: VecDeque<f64>,
+    /// Only keep this many blocks, for a rolling measurement; `Some(0)`
+    /// keeps none
+    max_blocks: Option<usize>,
 }
 
 impl LoudnessMeter {
-    fn new() -> Result<Self> {
-        let sample_rate = 48000;
-        Ok(Self {
+    /// Create a meter for audio with the given format
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        let weights = (0..channels)
+            .map(|channel| channel_weight(channel, channels))
+            .collect();
+        Self {
             sample_rate,
-            block_size: 400, // 400ms blocks for integrated loudness
-            blocks: VecDeque::with_capacity(75), // 30 seconds of blocks
-            k_weighting_filter: KWeightingFilter::new(sample_rate as f32),
-            gate_threshold_absolute: -70.0,
-            gate_threshold_relative: -10.0,
-        })
+            channels,
+            filters: vec![KWeightingFilter::new(sample_rate as f32); channels],
+            weights,
+            peak_detectors: vec![TruePeakDetector::new(); channels],
+            step_frames: step_frames(sample_rate),
+            step_energy: 0.0,
+            step_position: 0,
+            recent_steps: VecDeque::with_capacity(STEPS_PER_BLOCK),
+            blocks: VecDeque::new(),
+            max_blocks: None,
+        }
     }
 
-    /// Measure integrated loudness using ITU-R BS.1770-4 algorithm
-    fn measure_integrated(&mut self, buffer: &[f32]) -> Result<f32> {
-        // Reset filter state for new measurement
-        self.k_weighting_filter.reset();
-
-        // Apply K-weighting filter to all samples and calculate mean square
-        let mut sum_squares = 0.0;
-        for &sample in buffer {
-            let weighted_sample = self.k_weighting_filter.process(sample);
-            sum_squares += weighted_sample * weighted_sample;
+    /// Only integrate over the most recent `window` of audio
+    pub fn with_window(mut self, window: Duration) -> Self {
+        let blocks = window.as_millis() / STEP.as_millis();
+        self.max_blocks = Some(usize::try_from(blocks).unwrap_or(usize::MAX).max(1));
+        self
+    }
+
+    /// Don't keep gating blocks, for callers that only want the step
+    /// energies from [`process_steps`](Self::process_steps); measuring then
+    /// never allocates
+    pub(crate) fn without_blocks(mut self) -> Self {
+        self.max_blocks = Some(0);
+        self
+    }
+
+    /// Add interleaved samples; a trailing partial frame is ignored
+    pub fn process(&mut self, interleaved: &[f32]) {
+        self.process_steps(interleaved, |_| {});
+    }
+
+    /// [`process`](Self::process), calling `on_step` with the mean-square
+    /// energy of each 100 ms step as it completes
+    pub(crate) fn process_steps(&mut self, interleaved: &[f32], mut on_step: impl FnMut(f64)) {
+        for frame in interleaved.chunks_exact(self.channels) {
+            let mut energy = 0.0;
+            let channels = frame
+                .iter()
+                .zip(&mut self.filters)
+                .zip(&self.weights)
+                .zip(&mut self.peak_detectors);
+            for (((&sample, filter), weight), peak) in channels {
+                let weighted = f64::from(filter.process(sample));
+                energy += weight * weighted * weighted;
+                peak.process(sample);
+            }
+            self.step_energy += energy;
+            self.step_position += 1;
+            if self.step_position == self.step_frames {
+                on_step(self.finish_step());
+            }
         }
+    }
 
-        let mean_square = sum_squares / buffer.len() as f32;
-
-        // Convert to LUFS using ITU-R BS.1770-4 formula
-        // LUFS = -0.691 + 10 * log10(mean_square)
-        let block_loudness = if mean_square > 1e-10 {
-            -0.691 + 10.0 * mean_square.log10()
-        } else {
-            -70.0 // Silence floor
-        };
-
-        // Store block loudness for gated integration
-        self.blocks.push_back(block_loudness);
-        if self.blocks.len() > 75 {
-            self.blocks.pop_front();
+    /// Close the current step, returning its mean-square energy
+    fn finish_step(&mut self) -> f64 {
+        let step = self.step_energy / self.step_frames as f64;
+        self.step_energy = 0.0;
+        self.step_position = 0;
+        if self.max_blocks == Some(0) {
+            return step;
+        }
+        if self.recent_steps.len() == STEPS_PER_BLOCK {
+            self.recent_steps.pop_front();
         }
+        self.recent_steps.push_back(step);
+        if self.recent_steps.len() == STEPS_PER_BLOCK {
+            let block = self.recent_steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64;
+            self.blocks.push_back(block);
+            if self.max_blocks.is_some_and(|max| self.blocks.len() > max) {
+                self.blocks.pop_front();
+            }
+        }
+        step
+    }
 
-        // Apply gating as per ITU-R BS.1770-4
-        // Stage 1: Absolute gate at -70 LKFS
-        let gated_blocks: Vec<f32> = self
-            .blocks
-            .iter()
-            .copied()
-            .filter(|&loudness| loudness > self.gate_threshold_absolute)
-            .collect();
+    /// Gated integrated loudness in LUFS, or `None` for silence or less
+    /// than 400 ms of audio
+    pub fn integrated_loudness(&self) -> Option<f32> {
+        Self::gated_loudness(self.blocks.iter().copied())
+    }
 
-        if gated_blocks.is_empty() {
-            return Ok(-70.0); // All blocks below absolute gate
-        }
+    /// Highest true peak so far (linear, 1.0 = full scale)
+    pub fn true_peak(&self) -> f32 {
+        self.peak_detectors
+            .iter()
+            .map(|detector| detector.peak)
+            .fold(0.0, f32::max)
+    }
 
-        // Stage 2: Relative gate at -10 LU below ungated mean
-        let ungated_mean = gated_blocks.iter().sum::<f32>() / gated_blocks.len() as f32;
-        let relative_gate = ungated_mean + self.gate_threshold_relative;
+    /// Mean-square energies of the gating blocks measured so far
+    pub fn block_energies(&self) -> impl Iterator<Item = f64> + '_ {
+        self.blocks.iter().copied()
+    }
 
-        let relative_gated_blocks: Vec<f32> = gated_blocks
-            .into_iter()
-            .filter(|&loudness| loudness > relative_gate)
-            .collect();
+    /// Apply BS.1770 gating to block energies, possibly pooled from several
+    /// meters, and return the integrated loudness in LUFS
+    pub fn gated_loudness(blocks: impl Iterator<Item = f64> + Clone) -> Option<f32> {
+        let absolute_gate = lufs_to_energy(ABSOLUTE_GATE_LUFS);
+        let ungated = mean(blocks.clone().filter(|&e| e > absolute_gate))?;
+        let relative_gate = lufs_to_energy(energy_to_lufs(ungated) + RELATIVE_GATE_LU);
+        let gate = absolute_gate.max(relative_gate);
+        mean(blocks.filter(|&e| e > gate)).map(|energy| energy_to_lufs(energy) as f32)
+    }
 
-        if relative_gated_blocks.is_empty() {
-            return Ok(ungated_mean); // Fall back to ungated mean
+    /// Forget everything measured so far
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            *filter = KWeightingFilter::new(self.sample_rate as f32);
         }
+        for detector in &mut self.peak_detectors {
+            *detector = TruePeakDetector::new();
+        }
+        self.step_frames = step_frames(self.sample_rate);
+        self.step_energy = 0.0;
+        self.step_position = 0;
+        self.recent_steps.clear();
+        self.blocks.clear();
+    }
 
-        // Final integrated loudness
-        let integrated =
-            relative_gated_blocks.iter().sum::<f32>() / relative_gated_blocks.len() as f32;
-        Ok(integrated)
+    /// Rolling integrated loudness of a mono stream, for real-time use
+    fn measure_integrated(&mut self, buffer: &[f32]) -> Result<f32> {
+        self.process(buffer);
+        if let Some(loudness) = self.integrated_loudness() {
+            return Ok(loudness);
+        }
+        // Less than one block so far: use what there is, ungated
+        let partial = self.step_energy / self.step_position.max(1) as f64;
+        Ok(energy_to_lufs(partial).max(ABSOLUTE_GATE_LUFS) as f32)
     }
 
     /// Set sample rate (requires filter recalculation)
     pub fn set_sample_rate(&mut self, sample_rate: u32) {
         self.sample_rate = sample_rate;
-        self.k_weighting_filter = KWeightingFilter::new(sample_rate as f32);
+        self.reset();
+    }
+}
+
+/// BS.1770 weight of a channel in the usual L, R, C, LFE, Ls, Rs order
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (6.., 3) => 0.0,
+        (6.., 4..) | (5, 3..) => 1.41,
+        _ => 1.0,
+    }
+}
+
+fn step_frames(sample_rate: u32) -> usize {
+    let frames = u128::from(sample_rate) * STEP.as_millis() / 1000;
+    usize::try_from(frames).unwrap_or(usize::MAX).max(1)
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+pub(crate) fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.max(f64::MIN_POSITIVE).log10()
+}
+
+fn lufs_to_energy(lufs: f64) -> f64 {
+    10.0_f64.powf((lufs + 0.691) / 10.0)
+}
+
+/// Taps of the true-peak interpolation filter
+const TRUE_PEAK_TAPS: usize = 12;
+/// True-peak oversampling factor, as in BS.1770-4 Annex 2
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// True-peak detector: 4x oversampling with a Hann-windowed sinc
+#[derive(Debug, Clone)]
+struct TruePeakDetector {
+    /// Interpolation coefficients, one row per oversampling phase
+    phases: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING],
+    /// Last samples, oldest first
+    history: [f32; TRUE_PEAK_TAPS],
+    peak: f32,
+}
+
+impl TruePeakDetector {
+    fn new() -> Self {
+        use std::f32::consts::PI;
+
+        let half = (TRUE_PEAK_TAPS / 2) as f32;
+        let mut phases = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING];
+        for (phase, row) in phases.iter_mut().enumerate() {
+            let offset = phase as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+            for (tap, coefficient) in row.iter_mut().enumerate() {
+                // Distance from the interpolated point to this tap; the point
+                // lies between the two middle taps
+                let x = offset + (half - 1.0) - tap as f32;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let window = 0.5 * (1.0 + (PI * x / half).cos());
+                *coefficient = sinc * window;
+            }
+        }
+        Self {
+            phases,
+            history: [0.0; TRUE_PEAK_TAPS],
+            peak: 0.0,
+        }
+    }
+
+    fn process(&mut self, sample: f32) {
+        self.history.rotate_left(1);
+        if let Some(newest) = self.history.last_mut() {
+            *newest = sample;
+        }
+        for row in &self.phases {
+            let value: f32 = row.iter().zip(&self.history).map(|(c, x)| c * x).sum();
+            self.peak = self.peak.max(value.abs());
+        }
     }
 }
 
@@ -763,3 +932,42 @@ enum MusicGenre {
     Jazz,
     Pop,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_meter_matches_reference_levels() {
+        // A full-scale 997 Hz sine in both channels of a stereo pair reads
+        // 0 LUFS; -20 dBFS reads -20 LUFS
+        let tone: Vec<f32> = (0..48000 * 3)
+            .flat_map(|frame| {
+                let s = 0.1 * (2.0 * PI * 997.0 * frame as f32 / 48000.0).sin();
+                [s, s]
+            })
+            .collect();
+        let mut meter = LoudnessMeter::new(48000, 2);
+        for chunk in tone.chunks(1000) {
+            meter.process(chunk);
+        }
+        let loudness = meter.integrated_loudness().unwrap();
+        assert!((loudness + 20.0).abs() < 0.1, "{}", loudness);
+
+        // Samples of a quarter-rate sine at 45 degrees miss its crest by
+        // 3 dB; the true peak does not
+        let mut meter = LoudnessMeter::new(48000, 1);
+        let quarter: Vec<f32> = (0..4800)
+            .map(|i| (PI / 2.0 * i as f32 + PI / 4.0).sin())
+            .collect();
+        meter.process(&quarter);
+        assert!(quarter.iter().all(|s| s.abs() < 0.71));
+        assert!(meter.true_peak() > 0.95, "{}", meter.true_peak());
+
+        // Silence is gated out entirely
+        let mut meter = LoudnessMeter::new(48000, 1);
+        meter.process(&[0.0; 48000]);
+        assert_eq!(meter.integrated_loudness(), None);
+    }
+}
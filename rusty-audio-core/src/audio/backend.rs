@@ -31,6 +31,14 @@ pub enum AudioBackendError {
     #[error("Backend not available: {0}")]
     BackendNotAvailable(String),
 
+    /// A route would feed a bus back into itself
+    #[error("Routing cycle: {0}")]
+    RoutingCycle(String),
+
+    /// A session file could not be saved, parsed or restored
+    #[error("Session error: {0}")]
+    Session(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
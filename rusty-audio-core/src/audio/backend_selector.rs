@@ -7,6 +7,8 @@ use super::backend::{AudioBackend, AudioBackendError, Result};
 
 #[cfg(not(target_arch = "wasm32"))]
 use super::device::CpalBackend;
+#[cfg(not(target_arch = "wasm32"))]
+use super::virtual_backend::VirtualBackend;
 
 #[cfg(target_os = "windows")]
 use super::asio_backend::{AsioBackend, WindowsBackendType};
@@ -128,6 +130,16 @@ impl BackendSelector {
                 is_low_latency: false,
                 platform: std::env::consts::OS.to_string(),
             });
+
+            // Fake devices on a virtual clock, for tests without audio hardware
+            backends.push(BackendInfo {
+                id: "virtual".to_string(),
+                name: "Virtual".to_string(),
+                description: "Virtual devices for hardware-free testing".to_string(),
+                is_available: true,
+                is_low_latency: false,
+                platform: std::env::consts::OS.to_string(),
+            });
         }
 
         backends
@@ -177,6 +189,7 @@ impl BackendSelector {
                     Ok(Box::new(backend))
                 }
                 "cpal_default" => Ok(Box::new(CpalBackend::new())),
+                "virtual" => Ok(Box::new(VirtualBackend::new())),
                 _ => Err(AudioBackendError::BackendNotAvailable(format!(
                     "Unknown backend: {}",
                     backend_id
@@ -188,6 +201,7 @@ impl BackendSelector {
         {
             match backend_id {
                 "cpal_default" | "coreaudio" | "alsa" => Ok(Box::new(CpalBackend::new())),
+                "virtual" => Ok(Box::new(VirtualBackend::new())),
                 _ => Err(AudioBackendError::BackendNotAvailable(format!(
                     "Backend not available on this platform: {}",
                     backend_id
@@ -303,6 +317,15 @@ mod tests {
         assert!(backends.iter().any(|b| b.id == "cpal_default"));
     }
 
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_create_virtual_backend() {
+        let selector = BackendSelector::new();
+        assert!(selector.get_backend_info("virtual").is_some());
+        let backend = selector.create_backend("virtual").unwrap();
+        assert_eq!(backend.name(), "virtual");
+    }
+
     #[test]
     fn test_create_default_backend() {
         let selector = BackendSelector::new();
@@ -0,0 +1,498 @@
+//! Broadcast WAV writer
+//!
+//! Writes WAV files that can carry an EBU `bext` chunk (originator,
+//! origination date and time, and the time reference in samples since
+//! midnight) and an `iXML` chunk, and that keep working past 4 GB: a `JUNK`
+//! chunk reserves room right after the RIFF header, and once the file
+//! outgrows the 32-bit RIFF sizes the header is rewritten as RF64 with that
+//! chunk turned into `ds64` (EBU Tech 3306). Shorter files stay plain WAV.
+//!
+//! Samples are written as 16- or 24-bit integers or 32-bit floats, with the
+//! same `fmt ` layout hound uses, so hound and symphonia read the RIFF files.
+
+use chrono::{NaiveDateTime, Timelike};
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Length of the `ds64` chunk body, reserved by `JUNK` until it's needed
+const DS64_LENGTH: u32 = 28;
+
+/// Fixed part of the `bext` chunk, before the coding history
+const BEXT_LENGTH: usize = 602;
+
+/// Largest RIFF or data size a plain WAV header can hold
+const RIFF_LIMIT: u64 = u32::MAX as u64;
+
+const KSDATAFORMAT_SUBTYPE_PCM: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: [u8; 16] = [
+    0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Broadcast extension (`bext`) chunk contents
+///
+/// Text fields are ASCII; other characters are written as `?` and fields
+/// are cut to their fixed lengths (256 bytes of description, 32 of
+/// originator and originator reference).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BextChunk {
+    /// Free-form description of the recording
+    pub description: String,
+    /// Name of the originator, such as the recorder or the organisation
+    pub originator: String,
+    /// Unique reference assigned by the originator
+    pub originator_reference: String,
+    /// Local date and time the recording started
+    pub origination: NaiveDateTime,
+    /// First sample's position, in samples since midnight
+    pub time_reference: u64,
+    /// Coding history lines, such as `A=PCM,F=48000,W=24,M=stereo`
+    pub coding_history: String,
+}
+
+impl BextChunk {
+    /// Chunk for a recording starting now, with the time reference taken
+    /// from the local clock at `sample_rate`
+    pub fn now(originator: &str, sample_rate: u32) -> Self {
+        let origination = chrono::Local::now().naive_local();
+        let since_midnight = u64::from(origination.num_seconds_from_midnight()) * 1_000_000_000
+            + u64::from(origination.nanosecond() % 1_000_000_000);
+        Self {
+            description: String::new(),
+            originator: originator.to_string(),
+            originator_reference: String::new(),
+            origination,
+            time_reference: (u128::from(since_midnight) * u128::from(sample_rate) / 1_000_000_000)
+                as u64,
+            coding_history: String::new(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BEXT_LENGTH + self.coding_history.len());
+        push_ascii(&mut bytes, &self.description, 256);
+        push_ascii(&mut bytes, &self.originator, 32);
+        push_ascii(&mut bytes, &self.originator_reference, 32);
+        push_ascii(
+            &mut bytes,
+            &self.origination.format("%Y-%m-%d").to_string(),
+            10,
+        );
+        push_ascii(
+            &mut bytes,
+            &self.origination.format("%H:%M:%S").to_string(),
+            8,
+        );
+        bytes.extend_from_slice(&self.time_reference.to_le_bytes());
+        // Version 1: a UMID may follow, loudness fields are unused
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        // UMID, loudness values and reserved bytes
+        bytes.resize(BEXT_LENGTH, 0);
+        bytes.extend(self.coding_history.chars().map(ascii_byte));
+        bytes
+    }
+}
+
+/// Append `text` as a NUL-padded ASCII field of `length` bytes
+fn push_ascii(bytes: &mut Vec<u8>, text: &str, length: usize) {
+    let start = bytes.len();
+    bytes.extend(text.chars().map(ascii_byte).take(length));
+    bytes.resize(start + length, 0);
+}
+
+fn ascii_byte(c: char) -> u8 {
+    if c.is_ascii() {
+        c as u8
+    } else {
+        b'?'
+    }
+}
+
+/// Streaming WAV/BWF writer that switches to RF64 past 4 GB
+///
+/// Sizes in the header are written by [`update_header`](Self::update_header)
+/// and [`finish`](Self::finish); dropping the writer finishes it too,
+/// ignoring errors.
+#[derive(Debug)]
+pub struct BwfWriter<W: Write + Seek> {
+    writer: W,
+    bits_per_sample: u16,
+    block_align: u64,
+    /// Where the RIFF header starts
+    start: u64,
+    /// Length of everything before the sample data
+    header_length: u64,
+    /// Bytes of sample data written
+    data_length: u64,
+    /// Largest RIFF size before switching to RF64; lowered by tests
+    riff_limit: u64,
+    rf64: bool,
+    finished: bool,
+}
+
+impl<W: Write + Seek> BwfWriter<W> {
+    /// Write the headers and metadata chunks to `writer`, ready for samples
+    ///
+    /// `bits_per_sample` is 16 or 24 for integer samples, or 32 for floats.
+    ///
+    /// # Errors
+    /// `InvalidInput` for other bit depths, no channels or a zero sample
+    /// rate; otherwise any error writing to `writer`
+    pub fn new(
+        mut writer: W,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+        bext: Option<&BextChunk>,
+        ixml: Option<&str>,
+    ) -> io::Result<Self> {
+        if !matches!(bits_per_sample, 16 | 24 | 32) {
+            return Err(invalid_input("WAV bit depth must be 16, 24 or 32"));
+        }
+        if channels == 0 || sample_rate == 0 {
+            return Err(invalid_input(
+                "WAV needs at least one channel and a sample rate",
+            ));
+        }
+        let block_align = u16::try_from(u32::from(channels) * u32::from(bits_per_sample / 8))
+            .map_err(|_| invalid_input("Too many channels for WAV"))?;
+        let bytes_per_second = sample_rate
+            .checked_mul(u32::from(block_align))
+            .ok_or_else(|| invalid_input("Sample rate too high for WAV"))?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+        push_chunk(&mut header, b"JUNK", &[0; DS64_LENGTH as usize]);
+
+        // Same choice of fmt layout as hound: the extensible one when a
+        // plain PCMWAVEFORMAT can't describe the stream
+        let float = bits_per_sample == 32;
+        let extensible = channels > 2 || bits_per_sample == 24;
+        let mut fmt = Vec::with_capacity(40);
+        let format_tag: u16 = match (extensible, float) {
+            (true, _) => 0xFFFE,
+            (false, true) => 3,
+            (false, false) => 1,
+        };
+        fmt.extend_from_slice(&format_tag.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&bytes_per_second.to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+        if extensible {
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+            let mask = if channels <= 18 {
+                (1u32 << channels) - 1
+            } else {
+                0
+            };
+            fmt.extend_from_slice(&mask.to_le_bytes());
+            fmt.extend_from_slice(if float {
+                &KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+            } else {
+                &KSDATAFORMAT_SUBTYPE_PCM
+            });
+        }
+        push_chunk(&mut header, b"fmt ", &fmt);
+
+        if let Some(bext) = bext {
+            push_chunk(&mut header, b"bext", &bext.to_bytes());
+        }
+        if let Some(ixml) = ixml {
+            push_chunk(&mut header, b"iXML", ixml.as_bytes());
+        }
+        header.extend_from_slice(b"data\0\0\0\0");
+
+        let start = writer.stream_position()?;
+        writer.write_all(&header)?;
+        let mut bwf = Self {
+            writer,
+            bits_per_sample,
+            block_align: u64::from(block_align),
+            start,
+            header_length: header.len() as u64,
+            data_length: 0,
+            riff_limit: RIFF_LIMIT,
+            rf64: false,
+            finished: false,
+        };
+        // Readers of a file cut short see a valid, empty WAV
+        bwf.update_header()?;
+        Ok(bwf)
+    }
+
+    /// Write interleaved samples, clamped to -1.0..=1.0 for integer formats
+    ///
+    /// # Errors
+    /// Any error writing to the writer
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * usize::from(self.bits_per_sample / 8));
+        match self.bits_per_sample {
+            16 => {
+                for &sample in samples {
+                    let value = (sample.clamp(-1.0, 1.0) * 32_767.0).round() as i16;
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            24 => {
+                for &sample in samples {
+                    let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+                    let [low, middle, high, _] = value.to_le_bytes();
+                    bytes.extend_from_slice(&[low, middle, high]);
+                }
+            }
+            _ => {
+                for &sample in samples {
+                    bytes.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+        }
+        self.writer.write_all(&bytes)?;
+        self.data_length += bytes.len() as u64;
+
+        if !self.rf64 && self.riff_size() > self.riff_limit {
+            self.update_header()?;
+        }
+        Ok(())
+    }
+
+    /// The underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Sample frames written so far
+    pub fn frames_written(&self) -> u64 {
+        self.data_length / self.block_align
+    }
+
+    /// Whether the file has switched to RF64
+    pub fn is_rf64(&self) -> bool {
+        self.rf64
+    }
+
+    /// Write the current sizes into the header, switching to RF64 if the
+    /// file has outgrown plain WAV
+    ///
+    /// # Errors
+    /// Any error writing to or seeking in the writer
+    pub fn update_header(&mut self) -> io::Result<()> {
+        let riff_size = self.riff_size();
+        self.rf64 |= riff_size > self.riff_limit;
+        let end = self.writer.stream_position()?;
+        let start = self.start;
+        let data_size_position = start + self.header_length - 4;
+
+        if self.rf64 {
+            self.writer.seek(SeekFrom::Start(start))?;
+            self.writer.write_all(b"RF64")?;
+            self.writer.write_all(&u32::MAX.to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(start + 12))?;
+            let mut ds64 = Vec::with_capacity(8 + DS64_LENGTH as usize);
+            ds64.extend_from_slice(b"ds64");
+            ds64.extend_from_slice(&DS64_LENGTH.to_le_bytes());
+            ds64.extend_from_slice(&riff_size.to_le_bytes());
+            ds64.extend_from_slice(&self.data_length.to_le_bytes());
+            ds64.extend_from_slice(&self.frames_written().to_le_bytes());
+            // No table of other large chunks
+            ds64.extend_from_slice(&0u32.to_le_bytes());
+            self.writer.write_all(&ds64)?;
+            self.writer.seek(SeekFrom::Start(data_size_position))?;
+            self.writer.write_all(&u32::MAX.to_le_bytes())?;
+        } else {
+            self.writer.seek(SeekFrom::Start(start + 4))?;
+            self.writer.write_all(&(riff_size as u32).to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(data_size_position))?;
+            self.writer
+                .write_all(&(self.data_length as u32).to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+
+    /// Pad the data chunk to an even length and write the final sizes; does
+    /// nothing if the writer already finished
+    ///
+    /// # Errors
+    /// Any error writing to or seeking in the writer
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        if self.data_length % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        self.update_header()?;
+        self.writer.flush()
+    }
+
+    /// Write the current sizes into the header and flush the writer, so the
+    /// file is valid up to here
+    ///
+    /// # Errors
+    /// Any error writing to, seeking in or flushing the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.update_header()?;
+        self.writer.flush()
+    }
+
+    /// RIFF size: the file length less the RIFF chunk header, counting the
+    /// pad byte an odd-length data chunk needs
+    fn riff_size(&self) -> u64 {
+        self.header_length - 8 + self.data_length + self.data_length % 2
+    }
+}
+
+impl<W: Write + Seek> Drop for BwfWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Append a chunk, NUL-padding an odd-length body
+///
+/// The pad byte is counted in the chunk size, as other BWF writers do for
+/// text chunks, because some readers (hound among them) skip chunks without
+/// RIFF's uncounted pad byte.
+fn push_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    let padded = body.len() + body.len() % 2;
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(padded as u32).to_le_bytes());
+    bytes.extend_from_slice(body);
+    bytes.resize(bytes.len() + padded - body.len(), 0);
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// The chunks of a RIFF or RF64 file as (id, size field, body offset)
+    fn chunks(bytes: &[u8]) -> Vec<([u8; 4], u32, usize)> {
+        let mut chunks = Vec::new();
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+            let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+            chunks.push((id, size, offset + 8));
+            if &id == b"data" {
+                break;
+            }
+            offset += 8 + size as usize + size as usize % 2;
+        }
+        chunks
+    }
+
+    fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_bext_and_ixml() {
+        let bext = BextChunk {
+            description: "Dawn chorus".to_string(),
+            originator: "rusty-audio".to_string(),
+            originator_reference: "RA0001".to_string(),
+            origination: chrono::NaiveDate::from_ymd_opt(2026, 5, 1)
+                .unwrap()
+                .and_hms_opt(5, 30, 0)
+                .unwrap(),
+            time_reference: 19_800 * 48_000,
+            coding_history: "A=PCM,F=48000,W=24,M=stereo\r\n".to_string(),
+        };
+        let ixml = "<BWFXML><PROJECT>Field</PROJECT></BWFXML>";
+        let samples: Vec<f32> = (0..1001).map(|i| ((i as f32) * 0.05).sin() * 0.9).collect();
+
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer =
+                BwfWriter::new(&mut cursor, 48_000, 1, 24, Some(&bext), Some(ixml)).unwrap();
+            writer.write_samples(&samples).unwrap();
+            assert_eq!(writer.frames_written(), 1001);
+            assert!(!writer.is_rf64());
+        }
+        let bytes = cursor.into_inner();
+
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(le_u32(&bytes, 4) as usize, bytes.len() - 8);
+        let chunks = chunks(&bytes);
+        let ids: Vec<&[u8; 4]> = chunks.iter().map(|(id, _, _)| id).collect();
+        assert_eq!(ids, [b"JUNK", b"fmt ", b"bext", b"iXML", b"data"]);
+
+        let (_, size, body) = chunks[2];
+        // Coding history padded to an even length
+        assert_eq!(size as usize, BEXT_LENGTH + bext.coding_history.len() + 1);
+        assert_eq!(&bytes[body..body + 11], b"Dawn chorus");
+        assert_eq!(&bytes[body + 256..body + 267], b"rusty-audio");
+        assert_eq!(&bytes[body + 320..body + 338], b"2026-05-0105:30:00");
+        assert_eq!(le_u64(&bytes, body + 338), 19_800 * 48_000);
+        let (_, size, body) = chunks[3];
+        // 41 bytes of XML, padded to an even length
+        assert_eq!(size, 42);
+        assert_eq!(&bytes[body..body + ixml.len()], ixml.as_bytes());
+        // 1001 three-byte samples, then a pad byte
+        let (_, size, body) = chunks[4];
+        assert_eq!(size, 3003);
+        assert_eq!(bytes.len(), body + 3004);
+
+        let reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        let decoded: Vec<i32> = reader.into_samples().map(|s| s.unwrap()).collect();
+        let expected: Vec<i32> = samples
+            .iter()
+            .map(|&s| (s * 8_388_607.0).round() as i32)
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_rf64_switch() {
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer = BwfWriter::new(&mut cursor, 48_000, 2, 16, None, None).unwrap();
+            writer.riff_limit = 4000;
+            writer.write_samples(&[0.25; 1000]).unwrap();
+            assert!(!writer.is_rf64());
+            // Crossing the limit rewrites the header straight away
+            writer.write_samples(&[-0.25; 1000]).unwrap();
+            assert!(writer.is_rf64());
+            writer.write_samples(&[0.5; 500]).unwrap();
+        }
+        let bytes = cursor.into_inner();
+
+        assert_eq!(&bytes[..4], b"RF64");
+        assert_eq!(le_u32(&bytes, 4), u32::MAX);
+        let chunks = chunks(&bytes);
+        let (id, size, body) = chunks[0];
+        assert_eq!(&id, b"ds64");
+        assert_eq!(size, DS64_LENGTH);
+        assert_eq!(le_u64(&bytes, body) as usize, bytes.len() - 8);
+        assert_eq!(le_u64(&bytes, body + 8), 5000);
+        assert_eq!(le_u64(&bytes, body + 16), 1250);
+        assert_eq!(le_u32(&bytes, body + 24), 0);
+        let (id, size, body) = chunks[chunks.len() - 1];
+        assert_eq!(&id, b"data");
+        assert_eq!(size, u32::MAX);
+        assert_eq!(bytes.len(), body + 5000);
+    }
+
+    #[test]
+    fn test_invalid_formats() {
+        let mut cursor = Cursor::new(Vec::new());
+        assert!(BwfWriter::new(&mut cursor, 48_000, 2, 8, None, None).is_err());
+        assert!(BwfWriter::new(&mut cursor, 48_000, 0, 16, None, None).is_err());
+        assert!(BwfWriter::new(&mut cursor, 0, 2, 16, None, None).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Channel mapping and up/down-mix matrices for router routes
+//!
+//! A [`ChannelMatrix`] describes how each output channel of a route is built
+//! from the source's input channels. Every output channel is a weighted sum
+//! of the input channels of the same frame:
+//!
+//! ```text
+//! out[o] = Σ gains[o][i] * in[i]
+//! ```
+//!
+//! The router picks a sensible matrix automatically from the source and
+//! destination channel counts, and it can be replaced per route with one of
+//! the presets or a custom matrix.
+
+use super::backend::{AudioBackendError, Result};
+
+/// ITU-R BS.775 downmix coefficient for centre and surround channels (-3 dB)
+const ITU_DOWNMIX_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Mixing matrix mapping interleaved input frames to output frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMatrix {
+    input_channels: usize,
+    output_channels: usize,
+    /// Row-major gains: `gains[o * input_channels + i]`
+    gains: Vec<f32>,
+}
+
+impl ChannelMatrix {
+    /// Create a silent matrix (all gains 0.0)
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if either channel count is zero.
+    pub fn new(input_channels: u16, output_channels: u16) -> Result<Self> {
+        if input_channels == 0 || output_channels == 0 {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "Cannot map {} input channel(s) to {} output channel(s)",
+                input_channels, output_channels
+            )));
+        }
+
+        let input_channels = input_channels as usize;
+        let output_channels = output_channels as usize;
+        Ok(Self {
+            input_channels,
+            output_channels,
+            gains: vec![0.0; input_channels * output_channels],
+        })
+    }
+
+    /// Create a matrix from explicit rows of gains, one row per output channel
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if the rows are empty or not all the same length.
+    pub fn from_rows(rows: &[Vec<f32>]) -> Result<Self> {
+        let input_channels = rows.first().map(|row| row.len()).unwrap_or(0);
+        if rows.iter().any(|row| row.len() != input_channels) {
+            return Err(AudioBackendError::UnsupportedFormat(
+                "Channel matrix rows must all have the same length".to_string(),
+            ));
+        }
+
+        let mut matrix = Self::new(input_channels as u16, rows.len() as u16)?;
+        matrix.gains = rows.iter().flatten().copied().collect();
+        Ok(matrix)
+    }
+
+    /// Identity mapping (channel N to channel N)
+    pub fn identity(channels: u16) -> Self {
+        let mut matrix = Self::silent(channels, channels);
+        for ch in 0..matrix.output_channels {
+            matrix.set_gain(ch, ch, 1.0);
+        }
+        matrix
+    }
+
+    /// Mono → stereo: the single input feeds both outputs at unity gain
+    pub fn mono_to_stereo() -> Self {
+        let mut matrix = Self::silent(1, 2);
+        matrix.set_gain(0, 0, 1.0);
+        matrix.set_gain(1, 0, 1.0);
+        matrix
+    }
+
+    /// Stereo → mono: average of left and right
+    pub fn stereo_to_mono() -> Self {
+        let mut matrix = Self::silent(2, 1);
+        matrix.set_gain(0, 0, 0.5);
+        matrix.set_gain(0, 1, 0.5);
+        matrix
+    }
+
+    /// 5.1 → stereo using the ITU-R BS.775 downmix
+    ///
+    /// Expects the SMPTE/WAV channel order L, R, C, LFE, Ls, Rs. The centre
+    /// and surrounds are mixed in at -3 dB and the LFE channel is discarded.
+    pub fn surround_51_to_stereo() -> Self {
+        let mut matrix = Self::silent(6, 2);
+        // Left: L + 0.707 C + 0.707 Ls
+        matrix.set_gain(0, 0, 1.0);
+        matrix.set_gain(0, 2, ITU_DOWNMIX_GAIN);
+        matrix.set_gain(0, 4, ITU_DOWNMIX_GAIN);
+        // Right: R + 0.707 C + 0.707 Rs
+        matrix.set_gain(1, 1, 1.0);
+        matrix.set_gain(1, 2, ITU_DOWNMIX_GAIN);
+        matrix.set_gain(1, 5, ITU_DOWNMIX_GAIN);
+        matrix
+    }
+
+    /// Arbitrary channel pick
+    ///
+    /// Output channel `o` copies input channel `picks[o]`; `None` leaves that
+    /// output silent.
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if a pick is out of range or `picks` is empty.
+    pub fn pick(input_channels: u16, picks: &[Option<u16>]) -> Result<Self> {
+        let mut matrix = Self::new(input_channels, picks.len() as u16)?;
+        for (output, pick) in picks.iter().enumerate() {
+            if let Some(input) = pick {
+                if *input >= input_channels {
+                    return Err(AudioBackendError::UnsupportedFormat(format!(
+                        "Input channel {} out of range for {} channel source",
+                        input, input_channels
+                    )));
+                }
+                matrix.set_gain(output, *input as usize, 1.0);
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// Choose a default matrix for a source/destination channel pair
+    ///
+    /// Uses the mono, stereo and 5.1 presets where they apply; otherwise
+    /// maps channels one-to-one and leaves any extra channels unconnected.
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if either channel count is zero.
+    pub fn auto(input_channels: u16, output_channels: u16) -> Result<Self> {
+        match (input_channels, output_channels) {
+            (0, _) | (_, 0) => Self::new(input_channels, output_channels),
+            (i, o) if i == o => Ok(Self::identity(i)),
+            (1, 2) => Ok(Self::mono_to_stereo()),
+            (2, 1) => Ok(Self::stereo_to_mono()),
+            (6, 2) => Ok(Self::surround_51_to_stereo()),
+            (i, o) => {
+                let picks: Vec<Option<u16>> = (0..o)
+                    .map(|ch| if ch < i { Some(ch) } else { None })
+                    .collect();
+                Self::pick(i, &picks)
+            }
+        }
+    }
+
+    /// Number of input (source) channels
+    pub fn input_channels(&self) -> u16 {
+        self.input_channels as u16
+    }
+
+    /// Number of output (destination) channels
+    pub fn output_channels(&self) -> u16 {
+        self.output_channels as u16
+    }
+
+    /// Get the gain from `input` to `output` (0.0 if out of range)
+    pub fn gain(&self, output: usize, input: usize) -> f32 {
+        if output >= self.output_channels || input >= self.input_channels {
+            return 0.0;
+        }
+        self.gains
+            .get(output * self.input_channels + input)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Set the gain from `input` to `output` (ignored if out of range)
+    pub fn set_gain(&mut self, output: usize, input: usize, gain: f32) {
+        if output >= self.output_channels || input >= self.input_channels {
+            return;
+        }
+        if let Some(slot) = self.gains.get_mut(output * self.input_channels + input) {
+            *slot = gain;
+        }
+    }
+
+    /// Gains as rows, one per output channel (the inverse of [`ChannelMatrix::from_rows`])
+    pub fn rows(&self) -> Vec<Vec<f32>> {
+        self.gains
+            .chunks_exact(self.input_channels)
+            .map(<[f32]>::to_vec)
+            .collect()
+    }
+
+    /// Check whether this matrix passes channels through unchanged
+    pub fn is_identity(&self) -> bool {
+        self.input_channels == self.output_channels
+            && (0..self.output_channels).all(|o| {
+                (0..self.input_channels).all(|i| self.gain(o, i) == if o == i { 1.0 } else { 0.0 })
+            })
+    }
+
+    /// Mix interleaved `input` frames into interleaved `output` frames
+    ///
+    /// Adds `gain`-scaled results into `output` rather than overwriting it, so
+    /// several routes can accumulate into the same destination buffer.
+    /// Processes as many whole frames as both buffers hold and returns the
+    /// number of output samples touched.
+    pub fn mix_into(&self, input: &[f32], output: &mut [f32], gain: f32) -> usize {
+        let frames = (input.len() / self.input_channels).min(output.len() / self.output_channels);
+
+        for (in_frame, out_frame) in input
+            .chunks_exact(self.input_channels)
+            .zip(output.chunks_exact_mut(self.output_channels))
+            .take(frames)
+        {
+            for (out_sample, row) in out_frame
+                .iter_mut()
+                .zip(self.gains.chunks_exact(self.input_channels))
+            {
+                let mixed: f32 = row.iter().zip(in_frame).map(|(g, s)| g * s).sum();
+                *out_sample += mixed * gain;
+            }
+        }
+
+        frames * self.output_channels
+    }
+
+    /// Matrix with valid, non-zero dimensions and all gains 0.0
+    fn silent(input_channels: u16, output_channels: u16) -> Self {
+        let input_channels = input_channels.max(1) as usize;
+        let output_channels = output_channels.max(1) as usize;
+        Self {
+            input_channels,
+            output_channels,
+            gains: vec![0.0; input_channels * output_channels],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mono_to_stereo() {
+        let matrix = ChannelMatrix::mono_to_stereo();
+        let mut output = vec![0.0; 4];
+        assert_eq!(matrix.mix_into(&[0.5, -0.25], &mut output, 1.0), 4);
+        assert_eq!(output, vec![0.5, 0.5, -0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_stereo_to_mono() {
+        let matrix = ChannelMatrix::stereo_to_mono();
+        let mut output = vec![0.0; 2];
+        matrix.mix_into(&[1.0, 0.0, 0.4, 0.6], &mut output, 1.0);
+        assert_eq!(output, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_surround_downmix() {
+        let matrix = ChannelMatrix::surround_51_to_stereo();
+        let mut output = vec![0.0; 2];
+        // Centre only, plus a loud LFE that must be dropped
+        matrix.mix_into(&[0.0, 0.0, 1.0, 1.0, 0.0, 0.0], &mut output, 1.0);
+        assert!((output[0] - ITU_DOWNMIX_GAIN).abs() < 1e-6);
+        assert!((output[1] - ITU_DOWNMIX_GAIN).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pick_and_auto() {
+        let matrix = ChannelMatrix::pick(4, &[Some(3), None, Some(0)]).unwrap();
+        let mut output = vec![0.0; 3];
+        matrix.mix_into(&[0.1, 0.2, 0.3, 0.4], &mut output, 2.0);
+        assert_eq!(output, vec![0.8, 0.0, 0.2]);
+
+        assert!(ChannelMatrix::pick(2, &[Some(2)]).is_err());
+        assert!(ChannelMatrix::auto(2, 2).unwrap().is_identity());
+        assert_eq!(
+            ChannelMatrix::auto(1, 2).unwrap(),
+            ChannelMatrix::mono_to_stereo()
+        );
+        assert_eq!(ChannelMatrix::auto(4, 2).unwrap().gain(1, 1), 1.0);
+        assert!(ChannelMatrix::auto(0, 2).is_err());
+    }
+}
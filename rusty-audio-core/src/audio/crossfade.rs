@@ -0,0 +1,192 @@
+//! Track-to-track crossfade settings
+//!
+//! [`CrossfadeConfig`] holds the user's choice of curve and duration and
+//! decides, per transition, whether to fade at all: tracks that follow each
+//! other on the same album (consecutive track numbers) are treated as a
+//! continuous album and keep their gapless join. The resulting
+//! [`Crossfade`] is applied by
+//! [`GaplessHandle`](super::gapless::GaplessHandle) for router playback and
+//! by `WebAudioEngine` for web-audio playback.
+
+use crate::metadata::TrackMetadata;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+/// Default crossfade length
+pub const DEFAULT_CROSSFADE: Duration = Duration::from_secs(5);
+
+/// Shape of the fade-out and fade-in gain curves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossfadeCurve {
+    /// Straight ramps; dips by 6 dB in the middle for uncorrelated material
+    Linear,
+    /// Sine/cosine ramps that keep the summed power constant
+    #[default]
+    EqualPower,
+    /// Smoothstep ramps that ease in and out at both ends
+    SCurve,
+}
+
+impl CrossfadeCurve {
+    /// All curves, for selection lists
+    pub const ALL: [CrossfadeCurve; 3] = [
+        CrossfadeCurve::Linear,
+        CrossfadeCurve::EqualPower,
+        CrossfadeCurve::SCurve,
+    ];
+
+    /// Gains `(outgoing, incoming)` at `progress` (0.0 to 1.0) through the
+    /// fade
+    pub fn gains(self, progress: f32) -> (f32, f32) {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            CrossfadeCurve::Linear => (1.0 - t, t),
+            CrossfadeCurve::EqualPower => ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin()),
+            CrossfadeCurve::SCurve => {
+                let s = t * t * (3.0 - 2.0 * t);
+                (1.0 - s, s)
+            }
+        }
+    }
+
+    /// Display name
+    pub fn name(self) -> &'static str {
+        match self {
+            CrossfadeCurve::Linear => "Linear",
+            CrossfadeCurve::EqualPower => "Equal power",
+            CrossfadeCurve::SCurve => "S-curve",
+        }
+    }
+}
+
+/// A single crossfade between two tracks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crossfade {
+    /// Shape of the fade
+    pub curve: CrossfadeCurve,
+    /// Length of the overlap; shortened if either track is shorter
+    pub duration: Duration,
+}
+
+/// User crossfade preferences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrossfadeConfig {
+    /// Whether tracks crossfade instead of playing back to back
+    pub enabled: bool,
+    /// Shape of the fade
+    pub curve: CrossfadeCurve,
+    /// Length of the overlap
+    pub duration: Duration,
+}
+
+impl Default for CrossfadeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            curve: CrossfadeCurve::default(),
+            duration: DEFAULT_CROSSFADE,
+        }
+    }
+}
+
+impl CrossfadeConfig {
+    /// The crossfade to use from `from` into `to`, if any
+    ///
+    /// `None` when crossfading is off or the tracks belong to a continuous
+    /// album. Tracks without metadata are crossfaded.
+    pub fn between(
+        &self,
+        from: Option<&TrackMetadata>,
+        to: Option<&TrackMetadata>,
+    ) -> Option<Crossfade> {
+        if !self.enabled || self.duration.is_zero() {
+            return None;
+        }
+        if let (Some(from), Some(to)) = (from, to) {
+            if is_continuous(from, to) {
+                return None;
+            }
+        }
+        Some(Crossfade {
+            curve: self.curve,
+            duration: self.duration,
+        })
+    }
+}
+
+/// Whether `to` directly follows `from` on the same album
+///
+/// Live albums, DJ mixes and classical works often run one track into the
+/// next, where a crossfade would be wrong.
+pub fn is_continuous(from: &TrackMetadata, to: &TrackMetadata) -> bool {
+    let same_album = from.album == to.album
+        && from.album != TrackMetadata::default().album
+        && match (&from.album_artist, &to.album_artist) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+    let consecutive = matches!(
+        (from.track_number, to.track_number),
+        (Some(a), Some(b)) if a.checked_add(1) == Some(b)
+    );
+    same_album && consecutive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(album: &str, number: Option<u32>) -> TrackMetadata {
+        TrackMetadata {
+            album: album.to_string(),
+            track_number: number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_curve_gains() {
+        for curve in CrossfadeCurve::ALL {
+            assert_eq!(curve.gains(0.0), (1.0, 0.0));
+            let (out, incoming) = curve.gains(1.0);
+            assert!(out.abs() < 1e-6 && (incoming - 1.0).abs() < 1e-6);
+        }
+
+        let (out, incoming) = CrossfadeCurve::EqualPower.gains(0.5);
+        assert!((out * out + incoming * incoming - 1.0).abs() < 1e-6);
+        assert_eq!(CrossfadeCurve::Linear.gains(0.25), (0.75, 0.25));
+        // The S-curve starts slower than linear
+        assert!(CrossfadeCurve::SCurve.gains(0.1).1 < 0.1);
+    }
+
+    #[test]
+    fn test_continuous_albums_skip_the_fade() {
+        let config = CrossfadeConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let one = track("Live at the Roundhouse", Some(1));
+        let two = track("Live at the Roundhouse", Some(2));
+        let other = track("Another Album", Some(2));
+
+        assert!(is_continuous(&one, &two));
+        assert_eq!(config.between(Some(&one), Some(&two)), None);
+        assert!(config.between(Some(&two), Some(&one)).is_some());
+        assert!(config.between(Some(&one), Some(&other)).is_some());
+        assert!(config.between(Some(&one), None).is_some());
+        assert!(!is_continuous(
+            &track("Live at the Roundhouse", Some(u32::MAX)),
+            &two
+        ));
+
+        // Untagged files never count as one album
+        assert!(!is_continuous(
+            &TrackMetadata::default(),
+            &TrackMetadata::default()
+        ));
+        assert_eq!(
+            CrossfadeConfig::default().between(Some(&one), Some(&other)),
+            None
+        );
+    }
+}
@@ -5,7 +5,12 @@
 
 use super::backend::{AudioBackendError, Result};
 use super::router::AudioDestination;
+use super::session::NodeKind;
+use crate::ai::volume_normalizer::{
+    energy_to_lufs, LoudnessMeter, ABSOLUTE_GATE_LUFS, RELATIVE_GATE_LU,
+};
 use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 
 /// Ring buffer destination
@@ -120,6 +125,10 @@ impl AudioDestination for RingBufferDestination {
         // Ring buffer doesn't need explicit flushing
         Ok(())
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("ring_buffer_destination")
+    }
 }
 
 /// Level meter destination
@@ -224,6 +233,382 @@ impl AudioDestination for LevelMeterDestination {
         }
         Ok(())
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("level_meter")
+    }
+}
+
+/// Momentary loudness covers the last 4 steps of 100 ms, short-term loudness
+/// the last 30
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+/// Relative gate of the loudness range, in LU below the absolute-gated
+/// short-term loudness (EBU Tech 3342)
+const RANGE_GATE_LU: f64 = -20.0;
+/// Percentiles of the gated short-term loudness that bound the range
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+/// Loudness histograms have 0.1 LU bins from the absolute gate to +10 LUFS
+const HISTOGRAM_BIN_LU: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 800;
+
+/// Readings of a [`LoudnessMeterDestination`]
+///
+/// Each value is `None` until enough audio has been measured.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LoudnessReadings {
+    /// Loudness of the last 400 ms, in LUFS
+    pub momentary: Option<f32>,
+    /// Loudness of the last 3 s, in LUFS
+    pub short_term: Option<f32>,
+    /// Gated loudness since the last reset, in LUFS
+    pub integrated: Option<f32>,
+    /// Loudness range (LRA) since the last reset, in LU
+    pub range: Option<f32>,
+    /// Highest true peak since the last reset, in dBTP
+    pub true_peak: Option<f32>,
+}
+
+/// Readings and controls shared by a loudness meter and its handles
+///
+/// Readings are stored as `f32` bits, with NaN for "no reading".
+#[derive(Debug)]
+struct LoudnessShared {
+    momentary: AtomicU32,
+    short_term: AtomicU32,
+    integrated: AtomicU32,
+    range: AtomicU32,
+    /// Linear true peak
+    true_peak: AtomicU32,
+    paused: AtomicBool,
+    reset_requested: AtomicBool,
+}
+
+impl LoudnessShared {
+    fn new() -> Self {
+        let none = f32::NAN.to_bits();
+        Self {
+            momentary: AtomicU32::new(none),
+            short_term: AtomicU32::new(none),
+            integrated: AtomicU32::new(none),
+            range: AtomicU32::new(none),
+            true_peak: AtomicU32::new(0.0_f32.to_bits()),
+            paused: AtomicBool::new(false),
+            reset_requested: AtomicBool::new(false),
+        }
+    }
+
+    fn store(value: &AtomicU32, reading: Option<f32>) {
+        value.store(reading.unwrap_or(f32::NAN).to_bits(), Ordering::Relaxed);
+    }
+
+    fn load(value: &AtomicU32) -> Option<f32> {
+        let reading = f32::from_bits(value.load(Ordering::Relaxed));
+        (!reading.is_nan()).then_some(reading)
+    }
+
+    fn clear(&self) {
+        Self::store(&self.momentary, None);
+        Self::store(&self.short_term, None);
+        Self::store(&self.integrated, None);
+        Self::store(&self.range, None);
+        self.true_peak.store(0.0_f32.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Lock-free access to a [`LoudnessMeterDestination`] from the UI thread
+#[derive(Debug, Clone)]
+pub struct LoudnessMeterHandle {
+    shared: Arc<LoudnessShared>,
+}
+
+impl LoudnessMeterHandle {
+    /// Latest readings
+    pub fn readings(&self) -> LoudnessReadings {
+        let shared = &self.shared;
+        let true_peak = f32::from_bits(shared.true_peak.load(Ordering::Relaxed));
+        LoudnessReadings {
+            momentary: LoudnessShared::load(&shared.momentary),
+            short_term: LoudnessShared::load(&shared.short_term),
+            integrated: LoudnessShared::load(&shared.integrated),
+            range: LoudnessShared::load(&shared.range),
+            true_peak: (true_peak > 0.0).then(|| 20.0 * true_peak.log10()),
+        }
+    }
+
+    /// Stop or resume measuring; audio keeps passing through while paused
+    pub fn set_paused(&self, paused: bool) {
+        self.shared.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Whether measuring is paused
+    pub fn is_paused(&self) -> bool {
+        self.shared.paused.load(Ordering::Relaxed)
+    }
+
+    /// Clear all readings and restart the measurement with the next buffer
+    pub fn reset(&self) {
+        self.shared.reset_requested.store(true, Ordering::Release);
+        self.shared.clear();
+    }
+}
+
+/// Distribution of block loudness in 0.1 LU bins
+///
+/// Gating and percentiles only need the number of blocks and their summed
+/// energy per bin, so a programme of any length fits in a fixed size.
+#[derive(Debug, Clone)]
+struct LoudnessHistogram {
+    /// Block count and summed mean-square energy, per bin
+    bins: Vec<(u64, f64)>,
+}
+
+impl LoudnessHistogram {
+    fn new() -> Self {
+        Self {
+            bins: vec![(0, 0.0); HISTOGRAM_BINS],
+        }
+    }
+
+    /// Add a block; blocks below the absolute gate are dropped
+    fn add(&mut self, energy: f64) {
+        let loudness = energy_to_lufs(energy);
+        if loudness <= ABSOLUTE_GATE_LUFS {
+            return;
+        }
+        if let Some((count, sum)) = self.bins.get_mut(bin_index(loudness)) {
+            *count += 1;
+            *sum += energy;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bins.fill((0, 0.0));
+    }
+
+    /// Bins above a gate `gate_lu` below the loudness of all blocks
+    fn relative_gated(
+        &self,
+        gate_lu: f64,
+    ) -> Option<impl Iterator<Item = (u64, f64)> + Clone + '_> {
+        let (count, energy) = totals(self.bins.iter().copied());
+        if count == 0 {
+            return None;
+        }
+        let gate = energy_to_lufs(energy / count as f64) + gate_lu;
+        Some(self.bins.iter().copied().skip(bin_index(gate)))
+    }
+
+    /// Integrated loudness of the blocks, in LUFS (BS.1770)
+    fn integrated(&self) -> Option<f32> {
+        let (count, energy) = totals(self.relative_gated(RELATIVE_GATE_LU)?);
+        (count > 0).then(|| energy_to_lufs(energy / count as f64) as f32)
+    }
+
+    /// Loudness range of the blocks, in LU (EBU Tech 3342)
+    fn range(&self) -> Option<f32> {
+        let gated = self.relative_gated(RANGE_GATE_LU)?;
+        let (count, _) = totals(gated.clone());
+        let low = percentile(gated.clone(), count, RANGE_LOW_PERCENTILE)?;
+        let high = percentile(gated, count, RANGE_HIGH_PERCENTILE)?;
+        Some((high - low) as f32)
+    }
+}
+
+/// Histogram bin holding a loudness; out-of-range values go to the end bins
+fn bin_index(loudness: f64) -> usize {
+    let index = ((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_BIN_LU)
+        .floor()
+        .max(0.0) as usize;
+    index.min(HISTOGRAM_BINS - 1)
+}
+
+fn totals(bins: impl Iterator<Item = (u64, f64)>) -> (u64, f64) {
+    bins.fold((0, 0.0), |(count, sum), (n, energy)| {
+        (count + n, sum + energy)
+    })
+}
+
+/// Loudness of the bin holding the given percentile of `count` blocks
+fn percentile(bins: impl Iterator<Item = (u64, f64)>, count: u64, fraction: f64) -> Option<f64> {
+    let rank = (count.checked_sub(1)? as f64 * fraction).round() as u64;
+    let mut seen = 0;
+    for (n, energy) in bins {
+        seen += n;
+        if seen > rank {
+            return Some(energy_to_lufs(energy / n as f64));
+        }
+    }
+    None
+}
+
+/// Momentary and short-term windows over the 100 ms steps of a meter, and
+/// the histograms their blocks are gated in
+#[derive(Debug, Clone)]
+struct LoudnessWindows {
+    /// Mean-square energy of the last 30 steps of 100 ms, oldest first
+    steps: [f64; SHORT_TERM_STEPS],
+    /// How many of `steps` have been measured since the last reset
+    steps_measured: usize,
+    /// Momentary blocks, gated for the integrated loudness
+    momentary_blocks: LoudnessHistogram,
+    /// Short-term blocks, gated for the loudness range
+    short_term_blocks: LoudnessHistogram,
+}
+
+impl LoudnessWindows {
+    fn new() -> Self {
+        Self {
+            steps: [0.0; SHORT_TERM_STEPS],
+            steps_measured: 0,
+            momentary_blocks: LoudnessHistogram::new(),
+            short_term_blocks: LoudnessHistogram::new(),
+        }
+    }
+
+    /// Add a step and publish the readings it completes
+    fn add_step(&mut self, energy: f64, shared: &LoudnessShared) {
+        self.steps.rotate_left(1);
+        if let Some(newest) = self.steps.last_mut() {
+            *newest = energy;
+        }
+        self.steps_measured = (self.steps_measured + 1).min(SHORT_TERM_STEPS);
+
+        let momentary = self.recent_energy(MOMENTARY_STEPS);
+        let short_term = self.recent_energy(SHORT_TERM_STEPS);
+        if let Some(energy) = momentary {
+            self.momentary_blocks.add(energy);
+        }
+        if let Some(energy) = short_term {
+            self.short_term_blocks.add(energy);
+        }
+
+        let loudness = |energy: f64| energy_to_lufs(energy) as f32;
+        LoudnessShared::store(&shared.momentary, momentary.map(loudness));
+        LoudnessShared::store(&shared.short_term, short_term.map(loudness));
+        LoudnessShared::store(&shared.integrated, self.momentary_blocks.integrated());
+        LoudnessShared::store(&shared.range, self.short_term_blocks.range());
+    }
+
+    /// Mean energy of the last `steps` steps, once that many were measured
+    fn recent_energy(&self, steps: usize) -> Option<f64> {
+        (self.steps_measured >= steps)
+            .then(|| self.steps.iter().rev().take(steps).sum::<f64>() / steps as f64)
+    }
+
+    fn clear(&mut self) {
+        self.steps = [0.0; SHORT_TERM_STEPS];
+        self.steps_measured = 0;
+        self.momentary_blocks.clear();
+        self.short_term_blocks.clear();
+    }
+}
+
+/// EBU R128 loudness meter destination
+///
+/// Measures momentary, short-term and gated integrated loudness, loudness
+/// range and 4x oversampled true peak (ITU-R BS.1770-4, EBU Tech 3341 and
+/// 3342) while passing through. K-weighting and true peak come from a
+/// [`LoudnessMeter`]; readings are updated every 100 ms and read through a
+/// [`LoudnessMeterHandle`]. Measuring does not allocate.
+pub struct LoudnessMeterDestination {
+    inner: Option<Box<dyn AudioDestination>>,
+    sample_rate: u32,
+    channels: u16,
+    /// Only used for its 100 ms steps and true peak; it keeps no blocks
+    meter: LoudnessMeter,
+    windows: LoudnessWindows,
+    shared: Arc<LoudnessShared>,
+}
+
+impl LoudnessMeterDestination {
+    /// Create a new loudness meter destination
+    ///
+    /// # Arguments
+    /// * `inner` - Optional inner destination to pass samples through
+    /// * `sample_rate` - Sample rate
+    /// * `channels` - Number of channels, in the usual L, R, C, LFE, Ls, Rs order
+    pub fn new(inner: Option<Box<dyn AudioDestination>>, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            inner,
+            sample_rate,
+            channels,
+            meter: LoudnessMeter::new(sample_rate, usize::from(channels)).without_blocks(),
+            windows: LoudnessWindows::new(),
+            shared: Arc::new(LoudnessShared::new()),
+        }
+    }
+
+    /// Get a handle for reading the meter and controlling it from other threads
+    pub fn handle(&self) -> LoudnessMeterHandle {
+        LoudnessMeterHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn measure(&mut self, buffer: &[f32]) {
+        let windows = &mut self.windows;
+        let shared = &self.shared;
+        self.meter
+            .process_steps(buffer, |energy| windows.add_step(energy, shared));
+        self.shared
+            .true_peak
+            .store(self.meter.true_peak().to_bits(), Ordering::Relaxed);
+    }
+
+    fn reset_measurement(&mut self) {
+        self.meter.reset();
+        self.windows.clear();
+        self.shared.clear();
+    }
+}
+
+impl std::fmt::Debug for LoudnessMeterDestination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoudnessMeterDestination")
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field("readings", &self.handle().readings())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AudioDestination for LoudnessMeterDestination {
+    fn write_samples(&mut self, buffer: &[f32]) -> Result<()> {
+        if self.shared.reset_requested.swap(false, Ordering::Acquire) {
+            self.reset_measurement();
+        }
+        if !self.shared.paused.load(Ordering::Relaxed) {
+            self.measure(buffer);
+        }
+
+        // Pass through to inner destination if present
+        if let Some(ref mut inner) = self.inner {
+            inner.write_samples(buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(ref mut inner) = self.inner {
+            inner.flush()?;
+        }
+        Ok(())
+    }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("loudness_meter")
+    }
 }
 
 /// Null destination (discards audio)
@@ -269,6 +654,10 @@ impl AudioDestination for NullDestination {
     fn channels(&self) -> u16 {
         self.channels
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("null")
+    }
 }
 
 /// Multi-destination splitter
@@ -324,11 +713,16 @@ impl AudioDestination for SplitterDestination {
         }
         Ok(())
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("splitter")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::signal_generators::{SignalGenerator, SineGenerator};
 
     #[test]
     fn test_ring_buffer_destination() {
@@ -405,4 +799,134 @@ mod tests {
 
         splitter.write_samples(&[1.0, 2.0, 3.0]).unwrap();
     }
+
+    /// Stereo 1 kHz sine in sections of (dBFS, seconds), as in the EBU Tech
+    /// 3341 and 3342 test signals
+    fn tech_signal(sections: &[(f32, f32)]) -> Vec<f32> {
+        sections
+            .iter()
+            .flat_map(|&(dbfs, seconds)| {
+                SineGenerator::new(1000.0)
+                    .with_amplitude(10.0_f32.powf(dbfs / 20.0))
+                    .generate(seconds, 48000.0)
+            })
+            .flat_map(|sample| [sample, sample])
+            .collect()
+    }
+
+    fn measure(meter: &mut LoudnessMeterDestination, samples: &[f32]) -> LoudnessReadings {
+        for chunk in samples.chunks(1024) {
+            meter.write_samples(chunk).unwrap();
+        }
+        meter.handle().readings()
+    }
+
+    fn assert_close(reading: Option<f32>, expected: f32, tolerance: f32) {
+        let value = reading.expect("no reading");
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "expected {expected} ± {tolerance}, got {value}"
+        );
+    }
+
+    fn integrated(sections: &[(f32, f32)]) -> Option<f32> {
+        let mut meter = LoudnessMeterDestination::new(None, 48000, 2);
+        measure(&mut meter, &tech_signal(sections)).integrated
+    }
+
+    fn range(sections: &[(f32, f32)]) -> Option<f32> {
+        let mut meter = LoudnessMeterDestination::new(None, 48000, 2);
+        measure(&mut meter, &tech_signal(sections)).range
+    }
+
+    #[test]
+    fn test_loudness_meter_tech_3341_steady() {
+        // Cases 1 and 2: steady sines read the same on every time scale
+        for level in [-23.0, -33.0] {
+            let mut meter = LoudnessMeterDestination::new(None, 48000, 2);
+            let readings = measure(&mut meter, &tech_signal(&[(level, 20.0)]));
+            assert_close(readings.momentary, level, 0.1);
+            assert_close(readings.short_term, level, 0.1);
+            assert_close(readings.integrated, level, 0.1);
+        }
+    }
+
+    #[test]
+    fn test_loudness_meter_tech_3341_relative_gate() {
+        // Cases 3 and 5: quieter sections fall under the relative gate
+        let case_3 = integrated(&[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)]);
+        assert_close(case_3, -23.0, 0.1);
+        let case_5 = integrated(&[(-26.0, 20.0), (-20.0, 20.1), (-26.0, 20.0)]);
+        assert_close(case_5, -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_loudness_meter_tech_3341_absolute_gate() {
+        // Case 4: near-silence falls under the absolute gate
+        let case_4 = integrated(&[
+            (-72.0, 10.0),
+            (-36.0, 10.0),
+            (-23.0, 60.0),
+            (-36.0, 10.0),
+            (-72.0, 10.0),
+        ]);
+        assert_close(case_4, -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_loudness_range_tech_3342() {
+        // Cases 1 to 3: two levels, 20 s each
+        assert_close(range(&[(-20.0, 20.0), (-30.0, 20.0)]), 10.0, 1.0);
+        assert_close(range(&[(-20.0, 20.0), (-15.0, 20.0)]), 5.0, 1.0);
+        assert_close(range(&[(-40.0, 20.0), (-20.0, 20.0)]), 20.0, 1.0);
+    }
+
+    #[test]
+    fn test_loudness_range_tech_3342_gated() {
+        // Case 4: the -50 dBFS ends fall under the relative gate
+        let case_4 = range(&[
+            (-50.0, 20.0),
+            (-35.0, 20.0),
+            (-20.0, 20.0),
+            (-35.0, 20.0),
+            (-50.0, 20.0),
+        ]);
+        assert_close(case_4, 15.0, 1.0);
+    }
+
+    #[test]
+    fn test_loudness_meter_true_peak() {
+        // A quarter-rate sine sampled 45° off its peaks: -3 dB sample peak,
+        // 0 dBTP true peak
+        let samples: Vec<f32> = SineGenerator::new(12000.0)
+            .with_phase(std::f32::consts::FRAC_PI_4)
+            .generate(0.05, 48000.0)
+            .into_iter()
+            .flat_map(|sample| [sample, sample])
+            .collect();
+        assert!(samples.iter().all(|sample| sample.abs() < 0.71));
+
+        let mut meter = LoudnessMeterDestination::new(None, 48000, 2);
+        let true_peak = measure(&mut meter, &samples).true_peak.unwrap();
+        assert!((-0.4..=0.2).contains(&true_peak), "{true_peak} dBTP");
+    }
+
+    #[test]
+    fn test_loudness_meter_pause_and_reset() {
+        let mut meter = LoudnessMeterDestination::new(None, 48000, 2);
+        let handle = meter.handle();
+        assert_eq!(handle.readings(), LoudnessReadings::default());
+
+        measure(&mut meter, &tech_signal(&[(-23.0, 5.0)]));
+        handle.set_paused(true);
+        let paused = measure(&mut meter, &tech_signal(&[(-33.0, 5.0)]));
+        assert_close(paused.integrated, -23.0, 0.1);
+
+        handle.reset();
+        assert_eq!(handle.readings(), LoudnessReadings::default());
+        handle.set_paused(false);
+        let readings = measure(&mut meter, &tech_signal(&[(-33.0, 5.0)]));
+        assert_close(readings.integrated, -33.0, 0.1);
+        assert_close(readings.true_peak, -33.0, 0.2);
+    }
 }
@@ -3,9 +3,10 @@
 //! This module provides AudioDestination implementations that write to audio
 //! output devices (speakers, headphones, etc.) via CPAL/ASIO.
 
-use super::backend::{AudioBackend, AudioBackendError, AudioConfig, Result};
+use super::backend::{AudioBackend, AudioBackendError, AudioConfig, Result, StreamDirection};
 use super::destinations::RingBufferDestination;
 use super::router::AudioDestination;
+use super::session::NodeKind;
 use parking_lot::Mutex;
 use std::sync::Arc;
 
@@ -16,6 +17,7 @@ use std::sync::Arc;
 pub struct OutputDeviceDestination {
     ring_buffer: RingBufferDestination,
     device_id: String,
+    device_name: String,
     config: AudioConfig,
     _stream: Arc<Mutex<Option<Box<dyn super::backend::AudioStream>>>>,
 }
@@ -50,7 +52,7 @@ impl OutputDeviceDestination {
         let reader = ring_buffer.get_reader();
 
         // Create output stream with callback that reads from ring buffer
-        let stream = backend.create_output_stream_with_callback(
+        let mut stream = backend.create_output_stream_with_callback(
             device_id,
             config.clone(),
             Box::new(move |output_buffer: &mut [f32]| {
@@ -64,12 +66,23 @@ impl OutputDeviceDestination {
             }),
         )?;
 
+        // Backends do not all start streams on creation
+        stream.play()?;
+
         // Store stream to keep it alive
         let stream = Arc::new(Mutex::new(Some(stream)));
 
+        // Sessions find devices again by name
+        let device_name = backend
+            .enumerate_devices(StreamDirection::Output)
+            .ok()
+            .and_then(|devices| devices.into_iter().find(|device| device.id == device_id))
+            .map_or_else(|| device_id.to_string(), |device| device.name);
+
         Ok(Self {
             ring_buffer,
             device_id: device_id.to_string(),
+            device_name,
             config,
             _stream: stream,
         })
@@ -80,6 +93,11 @@ impl OutputDeviceDestination {
         &self.device_id
     }
 
+    /// Get the device name
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
     /// Get the audio configuration
     pub fn config(&self) -> &AudioConfig {
         &self.config
@@ -112,6 +130,12 @@ impl AudioDestination for OutputDeviceDestination {
     fn flush(&mut self) -> Result<()> {
         self.ring_buffer.flush()
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Device {
+            name: self.device_name.clone(),
+        }
+    }
 }
 
 #[cfg(test)]
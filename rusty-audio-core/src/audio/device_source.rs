@@ -5,6 +5,7 @@
 
 use super::backend::{AudioBackend, AudioBackendError, AudioConfig, Result, StreamDirection};
 use super::router::AudioSource;
+use super::session::NodeKind;
 use super::sources::RingBufferSource;
 use parking_lot::Mutex;
 use std::sync::Arc;
@@ -16,6 +17,7 @@ use std::sync::Arc;
 pub struct InputDeviceSource {
     ring_buffer: RingBufferSource,
     device_id: String,
+    device_name: String,
     config: AudioConfig,
     _stream: Arc<Mutex<Option<Box<dyn super::backend::AudioStream>>>>,
 }
@@ -40,7 +42,7 @@ impl InputDeviceSource {
         let writer = ring_buffer.get_writer();
 
         // Create input stream with callback that writes to ring buffer
-        let stream = backend.create_input_stream_with_callback(
+        let mut stream = backend.create_input_stream_with_callback(
             device_id,
             config.clone(),
             Box::new(move |data: &[f32]| {
@@ -49,12 +51,23 @@ impl InputDeviceSource {
             }),
         )?;
 
+        // Backends do not all start streams on creation
+        stream.play()?;
+
         // Store stream to keep it alive
         let stream = Arc::new(Mutex::new(Some(stream)));
 
+        // Sessions find devices again by name
+        let device_name = backend
+            .enumerate_devices(StreamDirection::Input)
+            .ok()
+            .and_then(|devices| devices.into_iter().find(|device| device.id == device_id))
+            .map_or_else(|| device_id.to_string(), |device| device.name);
+
         Ok(Self {
             ring_buffer,
             device_id: device_id.to_string(),
+            device_name,
             config,
             _stream: stream,
         })
@@ -65,6 +78,11 @@ impl InputDeviceSource {
         &self.device_id
     }
 
+    /// Get the device name
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
     /// Get the audio configuration
     pub fn config(&self) -> &AudioConfig {
         &self.config
@@ -97,6 +115,12 @@ impl AudioSource for InputDeviceSource {
     fn has_more_samples(&self) -> bool {
         true // Continuous input stream
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Device {
+            name: self.device_name.clone(),
+        }
+    }
 }
 
 // Note: Downcast helper trait no longer needed since we added
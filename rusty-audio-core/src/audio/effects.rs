@@ -0,0 +1,570 @@
+//! Insertable effect processing for router routes and destinations
+//!
+//! An [`AudioProcessor`] transforms interleaved audio in place. Processors
+//! are grouped into a [`ProcessorChain`] and inserted on a route (after
+//! channel mapping, before the route's gain), on a bus (before its fader) or
+//! in front of a destination (after all routes are mixed, before soft
+//! clipping). Each
+//! insert in a chain has its own bypass switch and wet/dry mix.
+//!
+//! # Built-in processors
+//!
+//! - [`EqProcessor`] - multi-band peaking EQ built on [`OptimizedEqProcessor`]
+//! - [`LimiterProcessor`] - hearing/hardware protection built on [`AudioSafetyLimiter`]
+
+use super::router::{BusId, DestId, RouteId};
+use crate::audio_performance::OptimizedEqProcessor;
+use crate::security::audio_safety::{AudioConfig as SafetyConfig, AudioSafetyLimiter};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Audio effect that can be inserted into the routing graph
+///
+/// `prepare` is called on the control thread before the processor is handed
+/// to the audio thread and may allocate. `process` and `reset` run on the
+/// audio thread and must not allocate or block.
+pub trait AudioProcessor: Send {
+    /// Human-readable name for UI display
+    fn name(&self) -> &str;
+
+    /// Prepare for processing
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Sample rate of the audio that will be processed
+    /// * `channels` - Number of interleaved channels
+    /// * `max_frames` - Largest number of frames passed to a single `process` call
+    fn prepare(&mut self, sample_rate: u32, channels: u16, max_frames: usize);
+
+    /// Process interleaved samples in place
+    fn process(&mut self, buffer: &mut [f32]);
+
+    /// Clear internal state such as filter memories and envelopes
+    fn reset(&mut self);
+}
+
+/// Where a processor chain is inserted in the routing graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InsertPoint {
+    /// After a route's channel mapping, before its gain
+    Route(RouteId),
+    /// After all routes into a destination are mixed, before it is written
+    Destination(DestId),
+    /// After all routes into a bus are mixed, before the bus fader
+    Bus(BusId),
+}
+
+/// Snapshot of one insert in a chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsertInfo {
+    /// Processor name
+    pub name: String,
+    /// Whether the processor is skipped
+    pub bypassed: bool,
+    /// Wet/dry mix (0.0 = dry only, 1.0 = fully processed)
+    pub mix: f32,
+}
+
+/// A processor with its bypass and wet/dry settings
+struct Insert {
+    processor: Box<dyn AudioProcessor>,
+    bypassed: bool,
+    mix: f32,
+}
+
+/// Ordered chain of prepared processors
+pub struct ProcessorChain {
+    inserts: Vec<Insert>,
+    /// Copy of the input for wet/dry blending
+    dry: Vec<f32>,
+}
+
+impl std::fmt::Debug for ProcessorChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessorChain")
+            .field("inserts", &self.info())
+            .finish()
+    }
+}
+
+impl ProcessorChain {
+    /// Prepare processors and build a chain from them
+    ///
+    /// # Arguments
+    /// * `processors` - Processors in processing order
+    /// * `sample_rate` - Sample rate of the audio passing through the chain
+    /// * `channels` - Number of interleaved channels
+    /// * `max_frames` - Largest number of frames processed at once
+    pub fn new(
+        processors: Vec<Box<dyn AudioProcessor>>,
+        sample_rate: u32,
+        channels: u16,
+        max_frames: usize,
+    ) -> Self {
+        let inserts = processors
+            .into_iter()
+            .map(|mut processor| {
+                processor.prepare(sample_rate, channels, max_frames);
+                Insert {
+                    processor,
+                    bypassed: false,
+                    mix: 1.0,
+                }
+            })
+            .collect();
+
+        Self {
+            inserts,
+            dry: vec![0.0; max_frames * channels as usize],
+        }
+    }
+
+    /// Number of processors in the chain
+    pub fn len(&self) -> usize {
+        self.inserts.len()
+    }
+
+    /// Check whether the chain has no processors
+    pub fn is_empty(&self) -> bool {
+        self.inserts.is_empty()
+    }
+
+    /// Describe every insert in the chain
+    pub fn info(&self) -> Vec<InsertInfo> {
+        self.inserts
+            .iter()
+            .map(|insert| InsertInfo {
+                name: insert.processor.name().to_string(),
+                bypassed: insert.bypassed,
+                mix: insert.mix,
+            })
+            .collect()
+    }
+
+    /// Bypass or re-enable the processor at `index`
+    pub fn set_bypassed(&mut self, index: usize, bypassed: bool) {
+        if let Some(insert) = self.inserts.get_mut(index) {
+            insert.bypassed = bypassed;
+        }
+    }
+
+    /// Set the wet/dry mix of the processor at `index` (clamped to 0.0-1.0)
+    pub fn set_mix(&mut self, index: usize, mix: f32) {
+        if let Some(insert) = self.inserts.get_mut(index) {
+            insert.mix = mix.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Run every active processor over `buffer` in order
+    pub fn process(&mut self, buffer: &mut [f32]) {
+        for insert in self.inserts.iter_mut() {
+            if insert.bypassed || insert.mix <= 0.0 {
+                continue;
+            }
+
+            if insert.mix >= 1.0 {
+                insert.processor.process(buffer);
+                continue;
+            }
+
+            // Wet/dry blend; blocks larger than the prepared size run fully wet
+            let Some(dry) = self.dry.get_mut(..buffer.len()) else {
+                insert.processor.process(buffer);
+                continue;
+            };
+            dry.copy_from_slice(buffer);
+            insert.processor.process(buffer);
+
+            let (wet_gain, dry_gain) = (insert.mix, 1.0 - insert.mix);
+            for (sample, dry) in buffer.iter_mut().zip(dry.iter()) {
+                *sample = *sample * wet_gain + dry * dry_gain;
+            }
+        }
+    }
+
+    /// Reset every processor in the chain
+    pub fn reset(&mut self) {
+        for insert in self.inserts.iter_mut() {
+            insert.processor.reset();
+        }
+    }
+}
+
+/// Settings for one peaking EQ band
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    /// Centre frequency in Hz
+    pub frequency: f32,
+    /// Quality factor
+    pub q: f32,
+    /// Boost or cut in dB
+    pub gain_db: f32,
+}
+
+impl EqBand {
+    /// Create a new band
+    pub fn new(frequency: f32, q: f32, gain_db: f32) -> Self {
+        Self {
+            frequency,
+            q,
+            gain_db,
+        }
+    }
+}
+
+/// Lock-free storage for one band's settings
+struct EqBandParams {
+    frequency: AtomicU32,
+    q: AtomicU32,
+    gain_db: AtomicU32,
+    version: AtomicU64,
+}
+
+impl EqBandParams {
+    fn new(band: EqBand) -> Self {
+        Self {
+            frequency: AtomicU32::new(band.frequency.to_bits()),
+            q: AtomicU32::new(band.q.to_bits()),
+            gain_db: AtomicU32::new(band.gain_db.to_bits()),
+            version: AtomicU64::new(1),
+        }
+    }
+
+    fn load(&self) -> EqBand {
+        EqBand {
+            frequency: f32::from_bits(self.frequency.load(Ordering::Relaxed)),
+            q: f32::from_bits(self.q.load(Ordering::Relaxed)),
+            gain_db: f32::from_bits(self.gain_db.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn store(&self, band: EqBand) {
+        self.frequency
+            .store(band.frequency.to_bits(), Ordering::Relaxed);
+        self.q.store(band.q.to_bits(), Ordering::Relaxed);
+        self.gain_db
+            .store(band.gain_db.to_bits(), Ordering::Relaxed);
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Handle for changing [`EqProcessor`] bands while it runs on the audio thread
+#[derive(Clone)]
+pub struct EqHandle {
+    bands: Arc<[EqBandParams]>,
+}
+
+impl std::fmt::Debug for EqHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.bands.iter().map(EqBandParams::load))
+            .finish()
+    }
+}
+
+impl EqHandle {
+    /// Number of bands
+    pub fn band_count(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Get the current settings of a band
+    pub fn band(&self, index: usize) -> Option<EqBand> {
+        self.bands.get(index).map(EqBandParams::load)
+    }
+
+    /// Change a band; picked up at the start of the next processed block
+    pub fn set_band(&self, index: usize, band: EqBand) {
+        if let Some(params) = self.bands.get(index) {
+            params.store(band);
+        }
+    }
+}
+
+/// Multi-band peaking EQ processor
+///
+/// Runs one [`OptimizedEqProcessor`] per channel. Band settings are shared
+/// through an [`EqHandle`] so they can be changed without touching the
+/// router.
+pub struct EqProcessor {
+    bands: Arc<[EqBandParams]>,
+    applied_versions: Vec<u64>,
+    filters: Vec<OptimizedEqProcessor>,
+    channel_in: Vec<f32>,
+    channel_out: Vec<f32>,
+}
+
+impl std::fmt::Debug for EqProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EqProcessor")
+            .field("bands", &self.handle())
+            .field("channels", &self.filters.len())
+            .finish()
+    }
+}
+
+impl EqProcessor {
+    /// Create an EQ with the given bands
+    pub fn new(bands: &[EqBand]) -> Self {
+        Self {
+            bands: bands.iter().copied().map(EqBandParams::new).collect(),
+            applied_versions: vec![0; bands.len()],
+            filters: Vec::new(),
+            channel_in: Vec::new(),
+            channel_out: Vec::new(),
+        }
+    }
+
+    /// Get a handle for changing bands while processing
+    pub fn handle(&self) -> EqHandle {
+        EqHandle {
+            bands: self.bands.clone(),
+        }
+    }
+
+    /// Recompute coefficients for bands that changed since the last block
+    fn apply_band_changes(&mut self) {
+        for (index, (params, applied)) in self
+            .bands
+            .iter()
+            .zip(self.applied_versions.iter_mut())
+            .enumerate()
+        {
+            let version = params.version.load(Ordering::Acquire);
+            if version == *applied {
+                continue;
+            }
+            *applied = version;
+
+            let band = params.load();
+            for filter in self.filters.iter_mut() {
+                filter.update_band(index, band.frequency, band.q, band.gain_db);
+            }
+        }
+    }
+}
+
+impl AudioProcessor for EqProcessor {
+    fn name(&self) -> &str {
+        "EQ"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16, max_frames: usize) {
+        self.filters = (0..channels.max(1))
+            .map(|_| {
+                let mut filter = OptimizedEqProcessor::new(self.bands.len(), sample_rate as f32);
+                filter.prepare(max_frames);
+                filter
+            })
+            .collect();
+        self.channel_in = vec![0.0; max_frames];
+        self.channel_out = vec![0.0; max_frames];
+
+        // Force every band to be recomputed for the new sample rate
+        self.applied_versions.fill(0);
+        self.apply_band_changes();
+    }
+
+    fn process(&mut self, buffer: &mut [f32]) {
+        self.apply_band_changes();
+
+        let channels = self.filters.len();
+        if channels == 0 {
+            return;
+        }
+        let frames = (buffer.len() / channels).min(self.channel_in.len());
+        let (Some(channel_in), Some(channel_out)) = (
+            self.channel_in.get_mut(..frames),
+            self.channel_out.get_mut(..frames),
+        ) else {
+            return;
+        };
+
+        for (ch, filter) in self.filters.iter_mut().enumerate() {
+            for (dest, frame) in channel_in.iter_mut().zip(buffer.chunks_exact(channels)) {
+                *dest = frame.get(ch).copied().unwrap_or(0.0);
+            }
+
+            filter.process(channel_in, channel_out);
+
+            for (frame, sample) in buffer.chunks_exact_mut(channels).zip(channel_out.iter()) {
+                if let Some(dest) = frame.get_mut(ch) {
+                    *dest = *sample;
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+    }
+}
+
+/// Safety limiter processor
+///
+/// Wraps [`AudioSafetyLimiter`] so its volume cap, soft-knee limiting and
+/// hearing protection can sit at the end of any chain. The limiter applies
+/// `volume` before limiting, exactly as it does for the player's master
+/// volume. Blocks the limiter rejects are counted rather than logged, since
+/// processing runs on the audio thread; read the count through
+/// [`LimiterProcessor::rejected_blocks`].
+pub struct LimiterProcessor {
+    limiter: AudioSafetyLimiter,
+    volume: f32,
+    /// Blocks the limiter refused to process
+    rejected: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for LimiterProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LimiterProcessor")
+            .field("volume", &self.volume)
+            .field("rejected", &self.rejected.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl LimiterProcessor {
+    /// Create a limiter with the given safety configuration
+    ///
+    /// # Arguments
+    /// * `config` - Limiter threshold and volume cap
+    /// * `volume` - Volume applied before limiting (clamped to 0.0-1.0)
+    pub fn new(config: SafetyConfig, volume: f32) -> Self {
+        Self {
+            limiter: AudioSafetyLimiter::new(config),
+            volume: volume.clamp(0.0, 1.0),
+            rejected: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Access the underlying limiter (for levels and violation counts)
+    pub fn limiter(&self) -> &AudioSafetyLimiter {
+        &self.limiter
+    }
+
+    /// Shared count of blocks the limiter rejected, readable from the UI
+    /// while the processor runs on the audio thread
+    pub fn rejected_blocks(&self) -> Arc<AtomicU64> {
+        self.rejected.clone()
+    }
+}
+
+impl Default for LimiterProcessor {
+    fn default() -> Self {
+        let config = SafetyConfig::default();
+        let volume = config.default_volume;
+        Self::new(config, volume)
+    }
+}
+
+impl AudioProcessor for LimiterProcessor {
+    fn name(&self) -> &str {
+        "Safety Limiter"
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _channels: u16, _max_frames: usize) {}
+
+    fn process(&mut self, buffer: &mut [f32]) {
+        if self.limiter.process_audio(buffer, self.volume).is_err() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.limiter.reset_violations();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Multiplies every sample by a fixed factor
+    struct Scale(f32);
+
+    impl AudioProcessor for Scale {
+        fn name(&self) -> &str {
+            "Scale"
+        }
+
+        fn prepare(&mut self, _sample_rate: u32, _channels: u16, _max_frames: usize) {}
+
+        fn process(&mut self, buffer: &mut [f32]) {
+            for sample in buffer.iter_mut() {
+                *sample *= self.0;
+            }
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn test_chain_order_bypass_and_mix() {
+        let mut chain = ProcessorChain::new(
+            vec![Box::new(Scale(2.0)), Box::new(Scale(3.0))],
+            48000,
+            1,
+            4,
+        );
+        let mut buffer = vec![1.0; 4];
+        chain.process(&mut buffer);
+        assert_eq!(buffer, vec![6.0; 4]);
+
+        chain.set_bypassed(1, true);
+        let mut buffer = vec![1.0; 4];
+        chain.process(&mut buffer);
+        assert_eq!(buffer, vec![2.0; 4]);
+
+        // 50% wet: 0.5 * 2.0 + 0.5 * 1.0
+        chain.set_mix(0, 0.5);
+        let mut buffer = vec![1.0; 4];
+        chain.process(&mut buffer);
+        assert_eq!(buffer, vec![1.5; 4]);
+
+        let info = chain.info();
+        assert_eq!(info.len(), 2);
+        assert!(info[1].bypassed);
+        assert_eq!(info[0].mix, 0.5);
+    }
+
+    #[test]
+    fn test_eq_processor_boosts_band() {
+        let mut eq = EqProcessor::new(&[EqBand::new(1000.0, 1.0, 12.0)]);
+        eq.prepare(48000, 2, 4800);
+
+        let tone: Vec<f32> = (0..4800)
+            .flat_map(|i| {
+                let s = (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 48000.0).sin() * 0.1;
+                [s, s]
+            })
+            .collect();
+        let mut buffer = tone.clone();
+        eq.process(&mut buffer);
+
+        let peak = |samples: &[f32]| {
+            samples
+                .iter()
+                .skip(2000)
+                .fold(0.0f32, |m, s| m.max(s.abs()))
+        };
+        assert!(peak(&buffer) > peak(&tone) * 3.0);
+
+        // Flattening the band through the handle takes effect immediately
+        eq.handle().set_band(0, EqBand::new(1000.0, 1.0, 0.0));
+        eq.reset();
+        let mut buffer = tone.clone();
+        eq.process(&mut buffer);
+        assert!((peak(&buffer) - peak(&tone)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_limiter_processor_caps_peaks() {
+        let mut limiter = LimiterProcessor::default();
+        limiter.prepare(48000, 2, 512);
+        let mut buffer = vec![1.5, -2.0, 0.1, -0.1];
+        limiter.process(&mut buffer);
+        assert!(buffer.iter().all(|s| s.abs() <= 1.0));
+    }
+}
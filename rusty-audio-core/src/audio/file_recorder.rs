@@ -1,28 +1,52 @@
 //! File-based audio recording destination
 //!
-//! This module provides an AudioDestination that writes audio to WAV files.
+//! This module provides an AudioDestination that writes audio to WAV or FLAC
+//! files. WAV files can carry Broadcast WAV `bext` and `iXML` chunks and
+//! switch to RF64 when they grow past 4 GB.
 
 use super::backend::Result;
 use super::router::AudioDestination;
+use super::session::NodeKind;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[cfg(not(target_arch = "wasm32"))]
-use hound::{WavSpec, WavWriter};
+use super::bwf::{BextChunk, BwfWriter};
+#[cfg(not(target_arch = "wasm32"))]
+use super::flac_encoder::{FlacConfig, FlacEncoder};
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::BufWriter;
 
+/// Suggested interval for [`FileRecorderDestination::set_sync_interval`]
+/// when recording live, bounding what a crash can lose
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Encoder behind a [`FileRecorderDestination`]
+#[cfg(not(target_arch = "wasm32"))]
+enum FileWriter {
+    Wav(BwfWriter<BufWriter<File>>),
+    Flac(FlacEncoder<BufWriter<File>>),
+}
+
 /// File recorder destination
 ///
-/// Writes audio samples to a WAV file.
+/// Writes audio samples to a WAV or FLAC file. The header is only brought
+/// up to date when the file is finalized, unless a sync interval is set;
+/// syncing blocks on the disk, so it belongs on a writer thread rather than
+/// the audio thread.
 #[cfg(not(target_arch = "wasm32"))]
 pub struct FileRecorderDestination {
-    writer: WavWriter<BufWriter<File>>,
+    writer: FileWriter,
     path: PathBuf,
     sample_rate: u32,
     channels: u16,
     samples_written: u64,
+    /// Samples between syncs, 0 to sync only when finalized
+    sync_interval: u64,
+    /// Samples written since the last sync
+    unsynced: u64,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -43,30 +67,116 @@ impl FileRecorderDestination {
         channels: u16,
         bits_per_sample: u16,
     ) -> Result<Self> {
-        let spec = WavSpec {
+        Self::create_wav(
+            path.as_ref(),
+            sample_rate,
             channels,
+            bits_per_sample,
+            None,
+            None,
+        )
+    }
+
+    /// Create a file recorder that writes Broadcast WAV
+    ///
+    /// # Arguments
+    /// * `path` - Path to the output WAV file
+    /// * `sample_rate` - Sample rate
+    /// * `channels` - Number of channels
+    /// * `bits_per_sample` - Bits per sample (16, 24, or 32)
+    /// * `bext` - Originator, origination date/time and time reference
+    /// * `ixml` - Optional iXML document
+    ///
+    /// # Errors
+    /// Fails if the file can't be created or the format isn't supported
+    pub fn new_bwf<P: AsRef<Path>>(
+        path: P,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+        bext: &BextChunk,
+        ixml: Option<&str>,
+    ) -> Result<Self> {
+        Self::create_wav(
+            path.as_ref(),
             sample_rate,
+            channels,
             bits_per_sample,
-            sample_format: if bits_per_sample == 32 {
-                hound::SampleFormat::Float
-            } else {
-                hound::SampleFormat::Int
-            },
-        };
+            Some(bext),
+            ixml,
+        )
+    }
 
-        let writer = WavWriter::create(path.as_ref(), spec).map_err(|e| {
-            super::backend::AudioBackendError::Other(anyhow::anyhow!(
-                "Failed to create WAV file: {}",
-                e
-            ))
-        })?;
+    fn create_wav(
+        path: &Path,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+        bext: Option<&BextChunk>,
+        ixml: Option<&str>,
+    ) -> Result<Self> {
+        let writer = File::create(path)
+            .and_then(|file| {
+                BwfWriter::new(
+                    BufWriter::new(file),
+                    sample_rate,
+                    channels,
+                    bits_per_sample,
+                    bext,
+                    ixml,
+                )
+            })
+            .map_err(|e| {
+                super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                    "Failed to create WAV file: {}",
+                    e
+                ))
+            })?;
+
+        Ok(Self {
+            writer: FileWriter::Wav(writer),
+            path: path.to_path_buf(),
+            sample_rate,
+            channels,
+            samples_written: 0,
+            sync_interval: 0,
+            unsynced: 0,
+        })
+    }
+
+    /// Create a file recorder that encodes to FLAC
+    ///
+    /// # Arguments
+    /// * `path` - Path to the output FLAC file
+    /// * `sample_rate` - Sample rate
+    /// * `channels` - Number of channels
+    /// * `config` - Compression level, bit depth (16 or 24) and tags
+    ///
+    /// # Errors
+    /// Fails if the file can't be created or FLAC can't hold the format
+    pub fn new_flac<P: AsRef<Path>>(
+        path: P,
+        sample_rate: u32,
+        channels: u16,
+        config: &FlacConfig,
+    ) -> Result<Self> {
+        let encoder = File::create(path.as_ref())
+            .and_then(|file| FlacEncoder::new(BufWriter::new(file), sample_rate, channels, config))
+            .map_err(|e| {
+                super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                    "Failed to create FLAC file: {}",
+                    e
+                ))
+            })?;
 
         Ok(Self {
-            writer,
+            writer: FileWriter::Flac(encoder),
             path: path.as_ref().to_path_buf(),
             sample_rate,
             channels,
             samples_written: 0,
+            sync_interval: 0,
+            unsynced: 0,
         })
     }
 
@@ -90,6 +200,37 @@ impl FileRecorderDestination {
         self.samples_written
     }
 
+    /// Set how often the header is updated and the file synced to disk;
+    /// zero only does so when the file is finalized
+    pub fn set_sync_interval(&mut self, interval: Duration) {
+        self.sync_interval = sync_samples(interval, self.sample_rate, self.channels);
+    }
+
+    /// Update the header for everything written so far and sync the file to
+    /// disk
+    ///
+    /// FLAC files are synced up to the last whole block.
+    ///
+    /// # Errors
+    /// Fails if the file can't be written or synced
+    pub fn sync(&mut self) -> Result<()> {
+        let result = match &mut self.writer {
+            FileWriter::Wav(writer) => writer
+                .flush()
+                .and_then(|()| writer.get_ref().get_ref().sync_data()),
+            FileWriter::Flac(encoder) => encoder
+                .flush()
+                .and_then(|()| encoder.get_ref().get_ref().sync_data()),
+        };
+        self.unsynced = 0;
+        result.map_err(|e| {
+            super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                "Failed to sync recording: {}",
+                e
+            ))
+        })
+    }
+
     /// Get the duration of recorded audio in seconds
     pub fn duration_seconds(&self) -> f64 {
         self.samples_written as f64 / (self.sample_rate as f64 * self.channels as f64)
@@ -97,27 +238,45 @@ impl FileRecorderDestination {
 
     /// Finalize the file (automatically called on drop, but can be called manually)
     pub fn finalize(self) -> Result<()> {
-        self.writer.finalize().map_err(|e| {
-            super::backend::AudioBackendError::Other(anyhow::anyhow!(
-                "Failed to finalize WAV file: {}",
-                e
-            ))
-        })
+        match self.writer {
+            FileWriter::Wav(mut writer) => writer.finish().map_err(|e| {
+                super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                    "Failed to finalize WAV file: {}",
+                    e
+                ))
+            }),
+            FileWriter::Flac(mut encoder) => encoder.finish().map_err(|e| {
+                super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                    "Failed to finalize FLAC file: {}",
+                    e
+                ))
+            }),
+        }
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 impl AudioDestination for FileRecorderDestination {
     fn write_samples(&mut self, buffer: &[f32]) -> Result<()> {
-        for &sample in buffer {
-            self.writer.write_sample(sample).map_err(|e| {
+        match &mut self.writer {
+            FileWriter::Wav(writer) => writer.write_samples(buffer).map_err(|e| {
                 super::backend::AudioBackendError::Other(anyhow::anyhow!(
-                    "Failed to write sample: {}",
+                    "Failed to write samples: {}",
                     e
                 ))
-            })?;
+            })?,
+            FileWriter::Flac(encoder) => encoder.write_samples(buffer).map_err(|e| {
+                super::backend::AudioBackendError::Other(anyhow::anyhow!(
+                    "Failed to write samples: {}",
+                    e
+                ))
+            })?,
         }
         self.samples_written += buffer.len() as u64;
+        self.unsynced += buffer.len() as u64;
+        if self.sync_interval > 0 && self.unsynced >= self.sync_interval {
+            self.sync()?;
+        }
         Ok(())
     }
 
@@ -130,17 +289,31 @@ impl AudioDestination for FileRecorderDestination {
     }
 
     fn flush(&mut self) -> Result<()> {
-        self.writer.flush().map_err(|e| {
+        let result = match &mut self.writer {
+            FileWriter::Wav(writer) => writer.flush(),
+            FileWriter::Flac(encoder) => encoder.flush(),
+        };
+        result.map_err(|e| {
             super::backend::AudioBackendError::Other(anyhow::anyhow!(
-                "Failed to flush WAV file: {}",
+                "Failed to flush recording: {}",
                 e
             ))
         })
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("file_recorder")
+    }
 }
 
-// Note: Drop implementation removed - hound::WavWriter automatically handles
-// finalization in its own Drop implementation
+/// Interleaved samples in `interval` of audio
+#[cfg(not(target_arch = "wasm32"))]
+fn sync_samples(interval: Duration, sample_rate: u32, channels: u16) -> u64 {
+    (interval.as_secs_f64() * f64::from(sample_rate) * f64::from(channels)) as u64
+}
+
+// Note: Drop implementation removed - BwfWriter and FlacEncoder
+// automatically handle finalization in their own Drop implementations
 
 // WASM stub (file I/O not supported)
 #[cfg(target_arch = "wasm32")]
@@ -177,6 +350,10 @@ impl AudioDestination for FileRecorderDestination {
     fn channels(&self) -> u16 {
         2
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("file_recorder")
+    }
 }
 
 #[cfg(test)]
@@ -228,4 +405,91 @@ mod tests {
         // File should exist and be readable
         assert!(path.exists());
     }
+
+    #[test]
+    fn test_file_recorder_sync() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let mut recorder = FileRecorderDestination::new_i16(path, 1000, 2).unwrap();
+        recorder.set_sync_interval(Duration::from_millis(100));
+        recorder.write_samples(&[0.5; 150]).unwrap();
+        // Without finalizing, the header covers the audio up to the last sync
+        assert_eq!(hound::WavReader::open(path).unwrap().duration(), 0);
+        recorder.write_samples(&[0.5; 100]).unwrap();
+        assert_eq!(hound::WavReader::open(path).unwrap().duration(), 125);
+        recorder.sync().unwrap();
+        std::mem::forget(recorder);
+        let reader = hound::WavReader::open(path).unwrap();
+        assert_eq!(reader.duration(), 125);
+    }
+
+    #[test]
+    fn test_file_recorder_bwf() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let bext = BextChunk {
+            description: "Take 1".to_string(),
+            time_reference: 3_600 * 48000,
+            ..BextChunk::now("rusty-audio", 48000)
+        };
+        let ixml = "<BWFXML><TAKE>1</TAKE></BWFXML>";
+        let mut recorder =
+            FileRecorderDestination::new_bwf(path, 48000, 4, 24, &bext, Some(ixml)).unwrap();
+        recorder.write_samples(&[0.5; 4800]).unwrap();
+        recorder.finalize().unwrap();
+
+        let reader = hound::WavReader::open(path).unwrap();
+        assert_eq!(reader.spec().channels, 4);
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.duration(), 1200);
+
+        // Read the stored bext chunk back from the file
+        let bytes = std::fs::read(path).unwrap();
+        let body = bytes.windows(4).position(|id| id == b"bext").unwrap() + 8;
+        let field = |offset: usize, length: usize| {
+            let raw = &bytes[body + offset..body + offset + length];
+            String::from_utf8_lossy(raw)
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        assert_eq!(field(0, 256), "Take 1");
+        assert_eq!(field(256, 32), "rusty-audio");
+        let time_reference = u64::from_le_bytes(bytes[body + 338..body + 346].try_into().unwrap());
+        assert_eq!(time_reference, 3_600 * 48000);
+    }
+
+    #[test]
+    fn test_file_recorder_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("take.flac");
+        let config = FlacConfig {
+            bits_per_sample: 16,
+            ..FlacConfig::default()
+        }
+        .with_tag("TITLE", "Take 1");
+
+        let samples: Vec<f32> = (0..9000).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+        let mut recorder = FileRecorderDestination::new_flac(&path, 48000, 2, &config).unwrap();
+        for chunk in samples.chunks(512) {
+            recorder.write_samples(chunk).unwrap();
+        }
+        assert_eq!(recorder.samples_written(), 9000);
+        recorder.finalize().unwrap();
+
+        let mut hint = symphonia::core::probe::Hint::new();
+        hint.with_extension("flac");
+        let stream = symphonia::core::io::MediaSourceStream::new(
+            Box::new(File::open(&path).unwrap()),
+            Default::default(),
+        );
+        let probed = symphonia::default::get_probe()
+            .format(&hint, stream, &Default::default(), &Default::default())
+            .unwrap();
+        let params = &probed.format.default_track().unwrap().codec_params;
+        assert_eq!(params.sample_rate, Some(48000));
+        assert_eq!(params.bits_per_sample, Some(16));
+        assert_eq!(params.n_frames, Some(4500));
+    }
 }
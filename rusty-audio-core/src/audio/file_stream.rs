@@ -0,0 +1,843 @@
+//! Streaming file source
+//!
+//! [`FileStreamSource`] plays an audio file through the router without
+//! decoding it into memory first. A background thread decodes packets with
+//! symphonia into a lock-free ring buffer holding a couple of seconds of
+//! audio, and [`AudioSource::read_samples`] only copies out of that ring, so
+//! hour-long files start instantly, use bounded memory and never make the
+//! audio thread wait on disk or codec work.
+//!
+//! Positions, lengths and seek targets are in interleaved samples, like
+//! every other [`AudioSource`]. Seeks are sample-accurate: the decoder seeks
+//! to the nearest packet and drops the frames before the target. Once the
+//! source is inside a router, use a [`FileStreamHandle`] to seek it and read
+//! its position.
+
+use super::backend::{AudioBackendError, Result};
+use super::router::AudioSource;
+use super::session::NodeKind;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{JoinHandle, Thread};
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+
+/// Audio buffered ahead of playback by default
+pub const DEFAULT_STREAM_BUFFER: Duration = Duration::from_secs(2);
+
+/// How long the decoder thread sleeps when the ring is full or the file
+/// has ended
+const DECODER_IDLE: Duration = Duration::from_millis(5);
+
+/// Marks a length that is not known (yet)
+const UNKNOWN_LENGTH: u64 = u64::MAX;
+
+/// State shared by the source, its handles and the decoder thread
+struct StreamShared {
+    sample_rate: u32,
+    channels: u16,
+    /// Total length in interleaved samples, or [`UNKNOWN_LENGTH`]
+    length: AtomicU64,
+    /// Playback position in interleaved samples
+    position: AtomicU64,
+    /// Requested seek target in interleaved samples
+    seek_target: AtomicU64,
+    /// Bumped for every seek request
+    seek_requested: AtomicU64,
+    /// Latest seek request the decoder has carried out
+    seek_served: AtomicU64,
+    /// Samples written to the ring before the latest served seek; anything
+    /// before this count is stale
+    seek_boundary: AtomicU64,
+    /// Position (interleaved samples) the latest served seek landed on
+    seek_position: AtomicU64,
+    /// The decoder reached the end of the file
+    finished: AtomicBool,
+    shutdown: AtomicBool,
+}
+
+impl StreamShared {
+    fn length(&self) -> Option<u64> {
+        Some(self.length.load(Ordering::Relaxed)).filter(|&length| length != UNKNOWN_LENGTH)
+    }
+
+    fn request_seek(&self, sample: u64, decoder: &Thread) -> Result<()> {
+        if self.length().is_some_and(|length| sample >= length) {
+            return Err(AudioBackendError::Other(anyhow::anyhow!(
+                "Seek position out of range"
+            )));
+        }
+        self.seek_target.store(sample, Ordering::Relaxed);
+        self.seek_requested.fetch_add(1, Ordering::Release);
+        decoder.unpark();
+        Ok(())
+    }
+
+    fn position(&self) -> u64 {
+        if self.seek_requested.load(Ordering::Acquire) != self.seek_served.load(Ordering::Acquire) {
+            // Report where playback is about to continue
+            self.seek_target.load(Ordering::Relaxed)
+        } else {
+            self.position.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// Audio source streaming a file from disk
+///
+/// Supports every container and codec symphonia is built with. The file's
+/// own sample rate and channel count are kept; the router resamples and
+/// remaps as needed.
+pub struct FileStreamSource {
+    shared: Arc<StreamShared>,
+    consumer: Consumer<f32>,
+    path: PathBuf,
+    /// Samples taken out of the ring, including skipped stale ones
+    read_count: u64,
+    /// Latest seek this side has caught up with
+    generation: u64,
+    decoder: Option<JoinHandle<()>>,
+    decoder_thread: Thread,
+}
+
+impl FileStreamSource {
+    /// Open a file for streaming with [`DEFAULT_STREAM_BUFFER`] of read-ahead
+    ///
+    /// # Errors
+    /// - `Other` if the file cannot be opened
+    /// - `UnsupportedFormat` if symphonia cannot find or decode an audio
+    ///   track in it
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_buffer(path, DEFAULT_STREAM_BUFFER)
+    }
+
+    /// Open a file for streaming with `buffer` of read-ahead
+    ///
+    /// # Errors
+    /// See [`FileStreamSource::open`]
+    pub fn with_buffer(path: impl AsRef<Path>, buffer: Duration) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut decoder = StreamDecoder::open(&path)?;
+
+        // Decode the first packet now so the format is known and broken
+        // files fail here rather than on the audio thread
+        if !decoder.decode_next()? {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "No audio in {}",
+                path.display()
+            )));
+        }
+
+        let channels = decoder.channels;
+        let frames = (buffer.as_secs_f64() * f64::from(decoder.sample_rate)).ceil() as usize;
+        let (producer, consumer) = RingBuffer::new(frames.max(1) * usize::from(channels));
+
+        let length = decoder
+            .n_frames
+            .map_or(UNKNOWN_LENGTH, |frames| frames * u64::from(channels));
+        let shared = Arc::new(StreamShared {
+            sample_rate: decoder.sample_rate,
+            channels,
+            length: AtomicU64::new(length),
+            position: AtomicU64::new(0),
+            seek_target: AtomicU64::new(0),
+            seek_requested: AtomicU64::new(0),
+            seek_served: AtomicU64::new(0),
+            seek_boundary: AtomicU64::new(0),
+            seek_position: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+        });
+
+        // Some containers do not store their length; count packets instead
+        if length == UNKNOWN_LENGTH {
+            let scan_shared = shared.clone();
+            let scan_path = path.clone();
+            std::thread::Builder::new()
+                .name("file-stream-scan".to_string())
+                .spawn(move || scan_length(&scan_path, &scan_shared))
+                .map_err(|e| {
+                    AudioBackendError::InitializationFailed(format!(
+                        "Failed to start length scan: {}",
+                        e
+                    ))
+                })?;
+        }
+
+        let thread_shared = shared.clone();
+        let handle = std::thread::Builder::new()
+            .name("file-stream".to_string())
+            .spawn(move || decoder.run(&thread_shared, producer))
+            .map_err(|e| {
+                AudioBackendError::InitializationFailed(format!(
+                    "Failed to start decoder thread: {}",
+                    e
+                ))
+            })?;
+        let decoder_thread = handle.thread().clone();
+
+        Ok(Self {
+            shared,
+            consumer,
+            path,
+            read_count: 0,
+            generation: 0,
+            decoder: Some(handle),
+            decoder_thread,
+        })
+    }
+
+    /// Get the file being streamed
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get a handle for seeking and tracking this source from other threads
+    pub fn handle(&self) -> FileStreamHandle {
+        FileStreamHandle {
+            shared: self.shared.clone(),
+            decoder_thread: self.decoder_thread.clone(),
+        }
+    }
+
+    /// Catch up with a seek the decoder has carried out
+    ///
+    /// Drops the audio decoded before the seek. Returns false while a seek
+    /// is still waiting for the decoder.
+    fn sync_seek(&mut self) -> bool {
+        let served = self.shared.seek_served.load(Ordering::Acquire);
+        if served != self.generation {
+            let boundary = self.shared.seek_boundary.load(Ordering::Relaxed);
+            let stale = boundary.saturating_sub(self.read_count) as usize;
+            let stale = stale.min(self.consumer.slots());
+            if let Ok(chunk) = self.consumer.read_chunk(stale) {
+                chunk.commit_all();
+            }
+            self.read_count += stale as u64;
+            if self.read_count < boundary {
+                // The rest of the stale audio is still on its way
+                return false;
+            }
+            self.generation = served;
+            let position = self.shared.seek_position.load(Ordering::Relaxed);
+            self.shared.position.store(position, Ordering::Relaxed);
+        }
+        self.shared.seek_requested.load(Ordering::Acquire) == self.generation
+    }
+}
+
+impl AudioSource for FileStreamSource {
+    fn read_samples(&mut self, buffer: &mut [f32]) -> usize {
+        if !self.sync_seek() {
+            return 0;
+        }
+
+        // Whole frames only, so channels never shift on an underrun
+        let channels = usize::from(self.shared.channels.max(1));
+        let available = self.consumer.slots().min(buffer.len());
+        let count = available - available % channels;
+        let Ok(chunk) = self.consumer.read_chunk(count) else {
+            return 0;
+        };
+        let (first, second) = chunk.as_slices();
+        let (head, tail) = buffer.split_at_mut(first.len());
+        head.copy_from_slice(first);
+        if let Some(tail) = tail.get_mut(..second.len()) {
+            tail.copy_from_slice(second);
+        }
+        chunk.commit_all();
+
+        self.read_count += count as u64;
+        self.shared
+            .position
+            .fetch_add(count as u64, Ordering::Relaxed);
+        count
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.shared.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.shared.channels
+    }
+
+    fn has_more_samples(&self) -> bool {
+        if self.shared.seek_requested.load(Ordering::Acquire) != self.generation {
+            return true;
+        }
+        !(self.shared.finished.load(Ordering::Acquire) && self.consumer.is_empty())
+    }
+
+    fn seek(&mut self, sample: u64) -> Result<()> {
+        self.shared.request_seek(sample, &self.decoder_thread)
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.shared.position())
+    }
+
+    fn length(&self) -> Option<u64> {
+        self.shared.length()
+    }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("file_stream")
+    }
+}
+
+impl Drop for FileStreamSource {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.decoder_thread.unpark();
+        if let Some(decoder) = self.decoder.take() {
+            let _ = decoder.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for FileStreamSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileStreamSource")
+            .field("path", &self.path)
+            .field("sample_rate", &self.shared.sample_rate)
+            .field("channels", &self.shared.channels)
+            .field("position", &self.shared.position.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Seeks and tracks a [`FileStreamSource`] from another thread
+#[derive(Clone)]
+pub struct FileStreamHandle {
+    shared: Arc<StreamShared>,
+    decoder_thread: Thread,
+}
+
+impl std::fmt::Debug for FileStreamHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileStreamHandle")
+            .field("position", &self.shared.position.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileStreamHandle {
+    /// Seek to an interleaved sample position
+    ///
+    /// Playback goes silent until the decoder has caught up, then continues
+    /// from exactly `sample`.
+    ///
+    /// # Errors
+    /// `Other` if the length is known and `sample` is past the end
+    pub fn seek(&self, sample: u64) -> Result<()> {
+        self.shared.request_seek(sample, &self.decoder_thread)
+    }
+
+    /// Seek to a time position
+    ///
+    /// # Errors
+    /// See [`FileStreamHandle::seek`]
+    pub fn seek_to(&self, time: Duration) -> Result<()> {
+        let frames = (time.as_secs_f64() * f64::from(self.shared.sample_rate)).round() as u64;
+        self.seek(frames * u64::from(self.shared.channels))
+    }
+
+    /// Get the playback position in interleaved samples
+    pub fn position(&self) -> u64 {
+        self.shared.position()
+    }
+
+    /// Get the total length in interleaved samples, once known
+    pub fn length(&self) -> Option<u64> {
+        self.shared.length()
+    }
+
+    /// Get the sample rate of the file
+    pub fn sample_rate(&self) -> u32 {
+        self.shared.sample_rate
+    }
+
+    /// Get the number of channels of the file
+    pub fn channels(&self) -> u16 {
+        self.shared.channels
+    }
+}
+
+/// Decodes a whole file on the calling thread, packet by packet
+///
+/// For offline work such as loudness scanning, which wants every sample as
+/// fast as possible rather than [`FileStreamSource`]'s bounded read-ahead.
+pub struct FileDecoder {
+    decoder: StreamDecoder,
+    /// The first packet, decoded by `open`, has not been handed out yet
+    primed: bool,
+}
+
+impl FileDecoder {
+    /// Open a file for decoding
+    ///
+    /// # Errors
+    /// See [`FileStreamSource::open`]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut decoder = StreamDecoder::open(path)?;
+        if !decoder.decode_next()? {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "No audio in {}",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            decoder,
+            primed: true,
+        })
+    }
+
+    /// Get the sample rate of the file
+    pub fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate
+    }
+
+    /// Get the number of channels of the file
+    pub fn channels(&self) -> u16 {
+        self.decoder.channels
+    }
+
+    /// Decode the next packet, returning its interleaved samples, or `None`
+    /// at the end of the file
+    ///
+    /// # Errors
+    /// `StreamError` if reading or decoding fails; corrupt packets are
+    /// skipped instead
+    pub fn next_chunk(&mut self) -> Result<Option<&[f32]>> {
+        if !std::mem::take(&mut self.primed) && !self.decoder.decode_next()? {
+            return Ok(None);
+        }
+        Ok(Some(&self.decoder.pending))
+    }
+}
+
+impl std::fmt::Debug for FileDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileDecoder")
+            .field("sample_rate", &self.decoder.sample_rate)
+            .field("channels", &self.decoder.channels)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Open a file and pick its first decodable track
+fn open_format(path: &Path) -> Result<(Box<dyn FormatReader>, u32)> {
+    let file = File::open(path).map_err(|e| {
+        AudioBackendError::Other(anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+    })?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let format_options = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &format_options, &MetadataOptions::default())
+        .map_err(|e| AudioBackendError::UnsupportedFormat(format!("{}: {}", path.display(), e)))?;
+
+    let format = probed.format;
+    let track_id = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .map(|track| track.id)
+        .ok_or_else(|| {
+            AudioBackendError::UnsupportedFormat(format!("No audio track in {}", path.display()))
+        })?;
+    Ok((format, track_id))
+}
+
+/// Count the frames of a file by reading its packets without decoding them
+fn scan_length(path: &Path, shared: &StreamShared) {
+    let Ok((mut format, track_id)) = open_format(path) else {
+        return;
+    };
+    let mut frames = 0u64;
+    loop {
+        if shared.shutdown.load(Ordering::Acquire) {
+            return;
+        }
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => frames += packet.dur(),
+            Ok(_) => {}
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => {
+                log::warn!("Length scan of {} stopped: {}", path.display(), e);
+                return;
+            }
+        }
+    }
+    shared
+        .length
+        .store(frames * u64::from(shared.channels), Ordering::Relaxed);
+}
+
+/// Decoder side of a stream, owned by the background thread
+struct StreamDecoder {
+    path: PathBuf,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    sample_rate: u32,
+    channels: u16,
+    n_frames: Option<u64>,
+    decoded: Option<SampleBuffer<f32>>,
+    /// Decoded samples not yet in the ring
+    pending: Vec<f32>,
+    pending_offset: usize,
+    /// Timestamp the last seek asked for; earlier frames are dropped
+    seek_ts: Option<u64>,
+    /// Samples pushed into the ring so far
+    written: u64,
+}
+
+impl StreamDecoder {
+    fn open(path: &Path) -> Result<Self> {
+        let (format, track_id) = open_format(path)?;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.id == track_id)
+            .ok_or_else(|| AudioBackendError::UnsupportedFormat(path.display().to_string()))?;
+        let params = track.codec_params.clone();
+        let decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(|e| {
+                AudioBackendError::UnsupportedFormat(format!("{}: {}", path.display(), e))
+            })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            decoder,
+            track_id,
+            time_base: params.time_base,
+            sample_rate: params.sample_rate.unwrap_or(0),
+            channels: params.channels.map_or(0, |c| c.count() as u16),
+            n_frames: params.n_frames,
+            decoded: None,
+            pending: Vec::new(),
+            pending_offset: 0,
+            seek_ts: None,
+            written: 0,
+        })
+    }
+
+    fn run(mut self, shared: &StreamShared, mut producer: Producer<f32>) {
+        let mut generation = 0;
+        while !shared.shutdown.load(Ordering::Acquire) {
+            let requested = shared.seek_requested.load(Ordering::Acquire);
+            if requested != generation {
+                generation = requested;
+                let target = shared.seek_target.load(Ordering::Relaxed);
+                let channels = u64::from(self.channels.max(1));
+                let finished = !self.seek(target / channels);
+                shared.finished.store(finished, Ordering::Release);
+                shared
+                    .seek_position
+                    .store(target - target % channels, Ordering::Relaxed);
+                shared.seek_boundary.store(self.written, Ordering::Relaxed);
+                shared.seek_served.store(generation, Ordering::Release);
+                continue;
+            }
+
+            if self.pending_offset < self.pending.len() {
+                if !self.push_pending(&mut producer) {
+                    std::thread::park_timeout(DECODER_IDLE);
+                }
+                continue;
+            }
+
+            if shared.finished.load(Ordering::Relaxed) {
+                std::thread::park_timeout(DECODER_IDLE);
+                continue;
+            }
+
+            match self.decode_next() {
+                Ok(true) => {}
+                Ok(false) => shared.finished.store(true, Ordering::Release),
+                Err(e) => {
+                    log::warn!("Streaming {} stopped: {}", self.path.display(), e);
+                    shared.finished.store(true, Ordering::Release);
+                }
+            }
+        }
+    }
+
+    /// Move as much pending audio into the ring as fits
+    ///
+    /// Returns false if the ring was full.
+    fn push_pending(&mut self, producer: &mut Producer<f32>) -> bool {
+        let remaining = self.pending.get(self.pending_offset..).unwrap_or(&[]);
+        let count = producer.slots().min(remaining.len());
+        if count == 0 {
+            return false;
+        }
+        if let Ok(mut chunk) = producer.write_chunk(count) {
+            let (first, second) = chunk.as_mut_slices();
+            let (head, tail) = remaining.split_at(first.len());
+            first.copy_from_slice(head);
+            second.copy_from_slice(tail.get(..second.len()).unwrap_or(&[]));
+            chunk.commit_all();
+        }
+        self.pending_offset += count;
+        self.written += count as u64;
+        true
+    }
+
+    /// Seek to a frame; returns false if the file cannot seek there
+    fn seek(&mut self, frame: u64) -> bool {
+        self.pending.clear();
+        self.pending_offset = 0;
+        let ts = self.frames_to_ts(frame);
+        let seek = self.format.seek(
+            SeekMode::Accurate,
+            SeekTo::TimeStamp {
+                ts,
+                track_id: self.track_id,
+            },
+        );
+        match seek {
+            Ok(seeked) => {
+                self.decoder.reset();
+                self.seek_ts = Some(seeked.required_ts);
+                true
+            }
+            Err(e) => {
+                log::warn!("Seek in {} failed: {}", self.path.display(), e);
+                false
+            }
+        }
+    }
+
+    /// Decode the next packet into `pending`; returns false at the end
+    fn decode_next(&mut self) -> Result<bool> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false)
+                }
+                Err(e) => {
+                    return Err(AudioBackendError::StreamError(format!(
+                        "Failed to read {}: {}",
+                        self.path.display(),
+                        e
+                    )))
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(e)) => {
+                    log::warn!("Skipping corrupt packet in {}: {}", self.path.display(), e);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(AudioBackendError::StreamError(format!(
+                        "Failed to decode {}: {}",
+                        self.path.display(),
+                        e
+                    )))
+                }
+            };
+
+            let spec = *decoded.spec();
+            if self.sample_rate == 0 {
+                self.sample_rate = spec.rate;
+            }
+            if self.channels == 0 {
+                self.channels = spec.channels.count() as u16;
+            }
+            if self
+                .decoded
+                .as_ref()
+                .is_none_or(|buffer| buffer.capacity() < decoded.capacity() * spec.channels.count())
+            {
+                self.decoded = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            let Some(buffer) = self.decoded.as_mut() else {
+                continue;
+            };
+            buffer.copy_interleaved_ref(decoded);
+
+            // Drop the frames before an accurate seek target
+            let mut skip = 0;
+            if let Some(target) = self.seek_ts {
+                if packet.ts() < target {
+                    skip = ts_to_frames(self.time_base, self.sample_rate, target - packet.ts());
+                } else {
+                    self.seek_ts = None;
+                }
+            }
+
+            let source_channels = spec.channels.count().max(1);
+            let channels = usize::from(self.channels.max(1));
+            let samples = buffer
+                .samples()
+                .get(skip as usize * source_channels..)
+                .unwrap_or(&[]);
+            self.pending.clear();
+            self.pending_offset = 0;
+            if source_channels == channels {
+                self.pending.extend_from_slice(samples);
+            } else {
+                // The channel layout changed mid-stream; keep the original
+                for frame in samples.chunks(source_channels) {
+                    self.pending
+                        .extend((0..channels).filter_map(|c| frame.get(c % frame.len()).copied()));
+                }
+            }
+            return Ok(true);
+        }
+    }
+
+    fn frames_to_ts(&self, frames: u64) -> u64 {
+        match self.time_base {
+            Some(time_base) if self.sample_rate > 0 => {
+                let seconds = frames / u64::from(self.sample_rate);
+                let frac =
+                    (frames % u64::from(self.sample_rate)) as f64 / f64::from(self.sample_rate);
+                time_base.calc_timestamp(Time::new(seconds, frac))
+            }
+            _ => frames,
+        }
+    }
+}
+
+fn ts_to_frames(time_base: Option<TimeBase>, sample_rate: u32, ts: u64) -> u64 {
+    match time_base {
+        Some(time_base) => {
+            let time = time_base.calc_time(ts);
+            ((time.seconds as f64 + time.frac) * f64::from(sample_rate)).round() as u64
+        }
+        None => ts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Write a stereo 16-bit WAV in `dir` whose left channel counts frames
+    fn write_wav(dir: &Path, frames: u32) -> PathBuf {
+        let path = dir.join("stream.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for frame in 0..frames {
+            writer.write_sample((frame % 30000) as i16).unwrap();
+            writer.write_sample(-1000i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    /// Read until `count` samples arrived or the source ends
+    fn read(source: &mut FileStreamSource, count: usize) -> Vec<f32> {
+        let mut samples = Vec::new();
+        let mut buffer = [0.0; 256];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while samples.len() < count && source.has_more_samples() && Instant::now() < deadline {
+            let wanted = (count - samples.len()).min(buffer.len());
+            let n = source.read_samples(&mut buffer[..wanted]);
+            samples.extend_from_slice(&buffer[..n]);
+            if n == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+        samples
+    }
+
+    fn frame_number(sample: f32) -> u32 {
+        (sample * 32768.0).round() as u32
+    }
+
+    #[test]
+    fn test_streams_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_wav(dir.path(), 20000);
+        let mut source = FileStreamSource::with_buffer(&path, Duration::from_millis(100)).unwrap();
+        assert_eq!(source.sample_rate(), 8000);
+        assert_eq!(source.channels(), 2);
+        assert_eq!(source.length(), Some(40000));
+
+        let samples = read(&mut source, usize::MAX);
+        assert_eq!(samples.len(), 40000);
+        assert!(samples
+            .chunks(2)
+            .enumerate()
+            .all(|(i, frame)| frame_number(frame[0]) == i as u32));
+        assert!(!source.has_more_samples());
+        assert_eq!(source.position(), Some(40000));
+    }
+
+    #[test]
+    fn test_seek_is_sample_accurate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_wav(dir.path(), 20000);
+        let mut source = FileStreamSource::open(&path).unwrap();
+        let handle = source.handle();
+        read(&mut source, 1000);
+
+        handle.seek(2 * 12345).unwrap();
+        assert_eq!(handle.position(), 2 * 12345);
+        let samples = read(&mut source, 10);
+        assert_eq!(frame_number(samples[0]), 12345);
+        assert_eq!(source.position(), Some(2 * 12345 + 10));
+
+        // Backwards through the trait
+        source.seek(2 * 10).unwrap();
+        let samples = read(&mut source, 2);
+        assert_eq!(frame_number(samples[0]), 10);
+
+        assert!(handle.seek(40000).is_err());
+    }
+
+    #[test]
+    fn test_open_errors() {
+        assert!(FileStreamSource::open("/nonexistent/file.wav").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("junk.wav");
+        std::fs::write(&path, b"not audio at all").unwrap();
+        assert!(matches!(
+            FileStreamSource::open(&path),
+            Err(AudioBackendError::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,689 @@
+//! Sample-accurate track transitions for router playback
+//!
+//! [`GaplessSource`] is a router source that plays one track after another
+//! without a gap. The control side hands it the next track ahead of time
+//! through a [`GaplessHandle`]; usually a
+//! [`FileStreamSource`](super::file_stream::FileStreamSource), which starts
+//! decoding as soon as it is opened. When the current track runs out
+//! partway through a buffer, the rest of that buffer is filled from the
+//! next track, so the splice lands on the exact sample.
+//!
+//! ```rust,no_run
+//! # use rusty_audio_core::audio::{AudioRouter, FileStreamSource, GaplessSource, PlayQueue};
+//! # fn example(router: &AudioRouter, queue: &mut PlayQueue) -> rusty_audio_core::audio::Result<()> {
+//! let first = FileStreamSource::open(queue.current().unwrap())?;
+//! let (source, mut handle) = GaplessSource::new(Box::new(first));
+//! router.add_source(Box::new(source));
+//!
+//! // From the UI loop: keep the next track queued and follow transitions
+//! let mut seen = 0;
+//! if handle.tracks_started() > seen {
+//!     seen = handle.tracks_started();
+//!     queue.advance();
+//! }
+//! if !handle.has_next() {
+//!     if let Some(path) = queue.upcoming() {
+//!         handle.queue_next(Box::new(FileStreamSource::open(path)?), None)?;
+//!     }
+//! }
+//! handle.collect_garbage();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A transition can instead overlap the two tracks with a
+//! [`Crossfade`]: the next track then starts that long before the current
+//! one ends, and [`GaplessHandle::skip_to`] fades the same way when the user
+//! changes track. Fades need the current track's length; without it the
+//! transition stays a gapless splice.
+//!
+//! Splicing needs matching formats, so [`GaplessHandle::queue_next`]
+//! rejects a track whose sample rate or channel count differs from the
+//! first one; players fall back to starting a new source for it.
+
+use super::backend::{AudioBackendError, Result};
+use super::crossfade::{Crossfade, CrossfadeCurve};
+use super::router::AudioSource;
+use super::session::NodeKind;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Commands and retired sources in flight at once
+const QUEUE_CAPACITY: usize = 8;
+
+/// Most sources a single command can retire: an interrupted fade and the
+/// track it was fading from
+const RETIRED_PER_COMMAND: usize = 2;
+
+/// Retired sources that can wait on the audio side for room in the retire
+/// queue. Commands wait while the queue is nearly full, so only the queued
+/// and outgoing tracks can be retired then.
+const RETIRE_BACKLOG: usize = 2;
+
+/// Samples of the outgoing track mixed per step of a crossfade
+const MIX_CHUNK: usize = 256;
+
+enum GaplessCommand {
+    /// Play this source after the current one, replacing any queued source
+    QueueNext(Box<dyn AudioSource>, Option<Fade>),
+    /// Switch to this source now
+    SkipTo(Box<dyn AudioSource>, Option<Fade>),
+    /// Forget the queued source
+    ClearNext,
+}
+
+/// A crossfade resolved to samples for this source's format
+#[derive(Clone, Copy)]
+struct Fade {
+    curve: CrossfadeCurve,
+    /// Interleaved samples, a whole number of frames
+    samples: u64,
+}
+
+/// A track fading out underneath the current one
+struct Outgoing {
+    source: Box<dyn AudioSource>,
+    curve: CrossfadeCurve,
+    total_frames: u64,
+    done_frames: u64,
+}
+
+/// State shared between the source and its handle
+#[derive(Default)]
+struct GaplessShared {
+    /// Transitions into a queued track so far
+    tracks_started: AtomicU64,
+    /// Whether the audio side holds a queued track
+    has_next: AtomicBool,
+    /// Times a retired source found the retire queue full
+    retire_waits: AtomicU64,
+}
+
+/// Router source that splices queued tracks together without a gap
+pub struct GaplessSource {
+    current: Box<dyn AudioSource>,
+    next: Option<Box<dyn AudioSource>>,
+    /// How to move into `next`; `None` splices
+    next_fade: Option<Fade>,
+    outgoing: Option<Outgoing>,
+    /// Scratch space for the outgoing track during a crossfade
+    mix: [f32; MIX_CHUNK],
+    commands: Consumer<GaplessCommand>,
+    /// Sources the audio thread is done with, dropped by the handle
+    retired: Producer<Box<dyn AudioSource>>,
+    /// Retired sources waiting for room in `retired`
+    backlog: [Option<Box<dyn AudioSource>>; RETIRE_BACKLOG],
+    shared: Arc<GaplessShared>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// Control side of a [`GaplessSource`]
+pub struct GaplessHandle {
+    commands: Producer<GaplessCommand>,
+    retired: Consumer<Box<dyn AudioSource>>,
+    shared: Arc<GaplessShared>,
+    sample_rate: u32,
+    channels: u16,
+    /// Whether a queued source may still be waiting in the command queue
+    pending_next: bool,
+    /// Retire queue overflows already logged
+    reported_retire_waits: u64,
+}
+
+impl GaplessSource {
+    /// Create a gapless source starting with `first`
+    ///
+    /// The format of `first` is the format of every following track.
+    pub fn new(first: Box<dyn AudioSource>) -> (Self, GaplessHandle) {
+        let (command_tx, command_rx) = RingBuffer::new(QUEUE_CAPACITY);
+        let (retired_tx, retired_rx) = RingBuffer::new(QUEUE_CAPACITY);
+        let shared = Arc::new(GaplessShared::default());
+        let sample_rate = first.sample_rate();
+        let channels = first.channels();
+        let source = Self {
+            current: first,
+            next: None,
+            next_fade: None,
+            outgoing: None,
+            mix: [0.0; MIX_CHUNK],
+            commands: command_rx,
+            retired: retired_tx,
+            backlog: Default::default(),
+            shared: Arc::clone(&shared),
+            sample_rate,
+            channels,
+        };
+        let handle = GaplessHandle {
+            commands: command_tx,
+            retired: retired_rx,
+            shared,
+            sample_rate,
+            channels,
+            pending_next: false,
+            reported_retire_waits: 0,
+        };
+        (source, handle)
+    }
+
+    /// Apply queued commands while the retire queue has room for whatever
+    /// they replace; the rest wait for the handle to collect garbage
+    fn apply_commands(&mut self) {
+        self.flush_backlog();
+        while self.retired.slots() >= RETIRED_PER_COMMAND {
+            let Ok(command) = self.commands.pop() else {
+                break;
+            };
+            match command {
+                GaplessCommand::QueueNext(source, fade) => {
+                    if let Some(replaced) = self.next.replace(source) {
+                        self.retire(replaced);
+                    }
+                    self.next_fade = fade;
+                }
+                GaplessCommand::SkipTo(source, fade) => {
+                    let previous = std::mem::replace(&mut self.current, source);
+                    match fade {
+                        Some(fade) => self.begin_fade(previous, fade.curve, fade.samples),
+                        None => self.retire(previous),
+                    }
+                }
+                GaplessCommand::ClearNext => {
+                    if let Some(replaced) = self.next.take() {
+                        self.retire(replaced);
+                    }
+                    self.next_fade = None;
+                }
+            }
+            self.shared
+                .has_next
+                .store(self.next.is_some(), Ordering::Release);
+        }
+    }
+
+    /// Hand a source to the handle for dropping
+    ///
+    /// If the retire queue is full the source waits in the backlog and is
+    /// retried on the next callback, so nothing is freed on this thread.
+    fn retire(&mut self, source: Box<dyn AudioSource>) {
+        let Err(rtrb::PushError::Full(source)) = self.retired.push(source) else {
+            return;
+        };
+        self.shared.retire_waits.fetch_add(1, Ordering::Relaxed);
+        match self.backlog.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(source),
+            // Can't happen while commands wait for room; leak rather than
+            // free on the audio thread
+            None => std::mem::forget(source),
+        }
+    }
+
+    /// Retry retiring sources that found the retire queue full
+    fn flush_backlog(&mut self) {
+        for slot in &mut self.backlog {
+            let Some(source) = slot.take() else {
+                continue;
+            };
+            if let Err(rtrb::PushError::Full(source)) = self.retired.push(source) {
+                *slot = Some(source);
+                break;
+            }
+        }
+    }
+
+    /// Samples of the current track left before a queued crossfade starts
+    fn samples_until_fade(&self) -> Option<u64> {
+        let fade = self.next_fade.filter(|_| self.next.is_some())?;
+        Some(remaining(self.current.as_ref())?.saturating_sub(fade.samples))
+    }
+
+    /// Make the queued track current, fading if one was requested and
+    /// there is time left for it
+    fn start_next(&mut self, crossfade: bool) {
+        let Some(next) = self.next.take() else {
+            return;
+        };
+        let fade = self.next_fade.take().filter(|_| crossfade);
+        let previous = std::mem::replace(&mut self.current, next);
+        self.shared.has_next.store(false, Ordering::Release);
+        self.shared.tracks_started.fetch_add(1, Ordering::AcqRel);
+        match fade {
+            Some(fade) => {
+                let samples = remaining(previous.as_ref())
+                    .map_or(fade.samples, |left| left.min(fade.samples));
+                self.begin_fade(previous, fade.curve, samples);
+            }
+            None => self.retire(previous),
+        }
+    }
+
+    fn begin_fade(&mut self, source: Box<dyn AudioSource>, curve: CrossfadeCurve, samples: u64) {
+        if let Some(interrupted) = self.outgoing.take() {
+            self.retire(interrupted.source);
+        }
+        let total_frames = samples / u64::from(self.channels.max(1));
+        if total_frames == 0 {
+            self.retire(source);
+            return;
+        }
+        self.outgoing = Some(Outgoing {
+            source,
+            curve,
+            total_frames,
+            done_frames: 0,
+        });
+    }
+
+    /// Read the current track into `buffer`, mixing in the outgoing track
+    /// with the fade curve applied
+    fn read_crossfade(&mut self, buffer: &mut [f32]) -> usize {
+        let channels = usize::from(self.channels.max(1));
+        let read = self.current.read_samples(buffer);
+        let incoming_ended = read < buffer.len() && !self.current.has_more_samples();
+
+        let Some(outgoing) = self.outgoing.as_mut() else {
+            return read;
+        };
+        let chunk_limit = (MIX_CHUNK / channels).max(1) * channels;
+        let mut offset = 0;
+        while offset < read && outgoing.done_frames < outgoing.total_frames {
+            let frames_left = (outgoing.total_frames - outgoing.done_frames) as usize;
+            let chunk = (read - offset).min(chunk_limit).min(frames_left * channels);
+            let Some(mix) = self.mix.get_mut(..chunk) else {
+                break;
+            };
+            let got = outgoing.source.read_samples(mix);
+            if let Some(silence) = mix.get_mut(got..) {
+                // The outgoing track ended early or its decoder fell behind
+                silence.fill(0.0);
+            }
+            let Some(target) = buffer.get_mut(offset..offset + chunk) else {
+                break;
+            };
+            for (frame, (incoming, outgoing_frame)) in target
+                .chunks_exact_mut(channels)
+                .zip(mix.chunks_exact(channels))
+                .enumerate()
+            {
+                let progress =
+                    (outgoing.done_frames + frame as u64) as f32 / outgoing.total_frames as f32;
+                let (out_gain, in_gain) = outgoing.curve.gains(progress);
+                for (sample, &old) in incoming.iter_mut().zip(outgoing_frame) {
+                    *sample = *sample * in_gain + old * out_gain;
+                }
+            }
+            outgoing.done_frames += (chunk / channels) as u64;
+            offset += chunk;
+        }
+
+        if outgoing.done_frames >= outgoing.total_frames || incoming_ended {
+            if let Some(finished) = self.outgoing.take() {
+                self.retire(finished.source);
+            }
+        }
+        read
+    }
+}
+
+/// Interleaved samples left in `source`, if it knows its length
+fn remaining(source: &dyn AudioSource) -> Option<u64> {
+    Some(source.length()?.saturating_sub(source.position()?))
+}
+
+impl AudioSource for GaplessSource {
+    fn read_samples(&mut self, buffer: &mut [f32]) -> usize {
+        self.apply_commands();
+
+        let mut filled = 0;
+        while let Some(rest) = buffer.get_mut(filled..) {
+            if rest.is_empty() {
+                break;
+            }
+            if self.outgoing.is_some() {
+                let read = self.read_crossfade(rest);
+                filled += read;
+                if read == 0 {
+                    break;
+                }
+                continue;
+            }
+
+            // Stop reading where a queued crossfade has to begin
+            let until_fade = self.samples_until_fade();
+            if until_fade == Some(0) {
+                self.start_next(true);
+                continue;
+            }
+            let limit = until_fade.map_or(rest.len(), |left| rest.len().min(left as usize));
+            let (head, _) = rest.split_at_mut(limit);
+            let read = self.current.read_samples(head);
+            filled += read;
+            if read == limit || self.current.has_more_samples() {
+                // Segment done, or the current track is only waiting on its
+                // decoder
+                if read < limit {
+                    break;
+                }
+                continue;
+            }
+            if self.next.is_none() {
+                break;
+            }
+            self.start_next(false);
+        }
+        filled
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn has_more_samples(&self) -> bool {
+        self.current.has_more_samples() || self.next.is_some() || self.outgoing.is_some()
+    }
+
+    /// Seek within the current track
+    fn seek(&mut self, sample: u64) -> Result<()> {
+        self.current.seek(sample)
+    }
+
+    /// Position within the current track
+    fn position(&self) -> Option<u64> {
+        self.current.position()
+    }
+
+    /// Length of the current track
+    fn length(&self) -> Option<u64> {
+        self.current.length()
+    }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("gapless")
+    }
+}
+
+impl GaplessHandle {
+    /// Queue `source` to follow the current track
+    ///
+    /// Without a crossfade it starts the moment the current track ends;
+    /// with one it starts `crossfade.duration` earlier and the two overlap.
+    /// Replaces any track queued earlier, which is then dropped by
+    /// [`collect_garbage`](Self::collect_garbage).
+    ///
+    /// # Errors
+    /// - `UnsupportedFormat` if the sample rate or channel count differs
+    ///   from the first track
+    /// - `StreamError` if the audio thread has not picked up earlier
+    ///   commands yet
+    pub fn queue_next(
+        &mut self,
+        source: Box<dyn AudioSource>,
+        crossfade: Option<Crossfade>,
+    ) -> Result<()> {
+        self.check_format(source.as_ref())?;
+        let fade = crossfade.map(|crossfade| self.fade(crossfade));
+        self.send(GaplessCommand::QueueNext(source, fade))?;
+        self.pending_next = true;
+        Ok(())
+    }
+
+    /// Switch to `source` straight away, e.g. when the user skips
+    ///
+    /// The current track fades out over the crossfade, or stops on the next
+    /// sample without one. A queued track stays queued, and the switch does
+    /// not count towards [`tracks_started`](Self::tracks_started).
+    ///
+    /// # Errors
+    /// As for [`queue_next`](Self::queue_next).
+    pub fn skip_to(
+        &mut self,
+        source: Box<dyn AudioSource>,
+        crossfade: Option<Crossfade>,
+    ) -> Result<()> {
+        self.check_format(source.as_ref())?;
+        let fade = crossfade.map(|crossfade| self.fade(crossfade));
+        self.send(GaplessCommand::SkipTo(source, fade))
+    }
+
+    /// Forget the queued track, e.g. after the queue order changed
+    ///
+    /// # Errors
+    /// `StreamError` if the audio thread has not picked up earlier commands
+    /// yet
+    pub fn clear_next(&mut self) -> Result<()> {
+        self.send(GaplessCommand::ClearNext)?;
+        self.pending_next = false;
+        Ok(())
+    }
+
+    /// Whether a track is queued and has not started yet
+    pub fn has_next(&self) -> bool {
+        let in_flight = self.commands.slots() < QUEUE_CAPACITY;
+        self.shared.has_next.load(Ordering::Acquire) || (self.pending_next && in_flight)
+    }
+
+    /// Number of times playback has moved on to a queued track
+    ///
+    /// Compare against the last value seen to advance a
+    /// [`PlayQueue`](super::play_queue::PlayQueue) in step with the audio.
+    /// With a crossfade this counts up when the fade begins.
+    pub fn tracks_started(&self) -> u64 {
+        self.shared.tracks_started.load(Ordering::Acquire)
+    }
+
+    /// Format every queued track must match
+    pub fn format(&self) -> (u32, u16) {
+        (self.sample_rate, self.channels)
+    }
+
+    /// Drop finished and replaced tracks away from the audio thread
+    ///
+    /// Call regularly from the control thread; file sources join their
+    /// decoder thread when dropped.
+    pub fn collect_garbage(&mut self) {
+        while let Ok(source) = self.retired.pop() {
+            drop(source);
+        }
+        let waits = self.shared.retire_waits.load(Ordering::Relaxed);
+        if waits > self.reported_retire_waits {
+            log::warn!(
+                "Gapless retire queue filled up {} times; playback changes waited for garbage collection",
+                waits - self.reported_retire_waits
+            );
+            self.reported_retire_waits = waits;
+        }
+    }
+
+    fn check_format(&self, source: &dyn AudioSource) -> Result<()> {
+        if source.sample_rate() != self.sample_rate || source.channels() != self.channels {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "Gapless playback needs {} Hz, {} channels (got {} Hz, {} channels)",
+                self.sample_rate,
+                self.channels,
+                source.sample_rate(),
+                source.channels()
+            )));
+        }
+        Ok(())
+    }
+
+    fn fade(&self, crossfade: Crossfade) -> Fade {
+        let frames = (crossfade.duration.as_secs_f64() * f64::from(self.sample_rate)).round();
+        Fade {
+            curve: crossfade.curve,
+            samples: frames as u64 * u64::from(self.channels.max(1)),
+        }
+    }
+
+    fn send(&mut self, command: GaplessCommand) -> Result<()> {
+        self.commands
+            .push(command)
+            .map_err(|_| AudioBackendError::StreamError("Gapless command queue full".to_string()))
+    }
+}
+
+impl std::fmt::Debug for GaplessSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GaplessSource")
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field("has_next", &self.next.is_some())
+            .field("crossfading", &self.outgoing.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for GaplessHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GaplessHandle")
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field(
+                "tracks_started",
+                &self.shared.tracks_started.load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::sources::{SignalGeneratorSource, SilenceSource};
+
+    fn track(value: f32, samples: usize) -> Box<dyn AudioSource> {
+        Box::new(SignalGeneratorSource::from_buffer(
+            vec![value; samples],
+            48000.0,
+            false,
+        ))
+    }
+
+    #[test]
+    fn test_splice_is_sample_accurate() {
+        let (mut source, mut handle) = GaplessSource::new(track(0.1, 300));
+        handle.queue_next(track(0.2, 200), None).unwrap();
+        assert!(handle.has_next());
+
+        let mut buffer = vec![0.0; 256];
+        assert_eq!(source.read_samples(&mut buffer), 256);
+        assert!(buffer.iter().all(|&s| s == 0.1));
+        assert_eq!(handle.tracks_started(), 0);
+
+        // The first track ends 44 samples into this buffer
+        assert_eq!(source.read_samples(&mut buffer), 244);
+        let (tail, head) = buffer.split_at(44);
+        assert!(tail.iter().all(|&s| s == 0.1));
+        assert!(head.iter().take(200).all(|&s| s == 0.2));
+        assert_eq!(handle.tracks_started(), 1);
+        assert!(!handle.has_next());
+        assert!(!source.has_more_samples());
+
+        handle.collect_garbage();
+        assert!(handle.retired.is_empty());
+    }
+
+    #[test]
+    fn test_queue_replace_and_clear() {
+        let (mut source, mut handle) = GaplessSource::new(track(0.1, 100));
+        handle.queue_next(track(0.2, 100), None).unwrap();
+        handle.queue_next(track(0.3, 100), None).unwrap();
+        handle.clear_next().unwrap();
+        assert!(!handle.has_next());
+
+        let mut buffer = vec![0.0; 256];
+        assert_eq!(source.read_samples(&mut buffer), 100);
+        assert_eq!(handle.tracks_started(), 0);
+        handle.collect_garbage();
+
+        handle.queue_next(track(0.3, 100), None).unwrap();
+        assert_eq!(source.read_samples(&mut buffer), 100);
+        assert_eq!(buffer.first(), Some(&0.3));
+        assert_eq!(handle.tracks_started(), 1);
+
+        // Mismatched formats cannot be spliced
+        assert!(matches!(
+            handle.queue_next(Box::new(SilenceSource::new(44100, 2)), None),
+            Err(AudioBackendError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_crossfade_overlaps_track_ends() {
+        // 1 ms at 48 kHz mono is 48 samples
+        let crossfade = Crossfade {
+            curve: CrossfadeCurve::Linear,
+            duration: std::time::Duration::from_millis(1),
+        };
+        let (mut source, mut handle) = GaplessSource::new(track(1.0, 300));
+        handle.queue_next(track(0.5, 300), Some(crossfade)).unwrap();
+
+        let mut buffer = vec![0.0; 256];
+        assert_eq!(source.read_samples(&mut buffer), 256);
+        assert_eq!(handle.tracks_started(), 1);
+        // The fade starts 48 samples before the first track ends
+        assert!(buffer.iter().take(252).all(|&s| s == 1.0));
+        assert_eq!(buffer.get(252), Some(&1.0));
+        let mid = buffer.get(253).copied().unwrap();
+        assert!(mid < 1.0 && mid > 0.5);
+
+        assert_eq!(source.read_samples(&mut buffer), 256);
+        let levels: Vec<f32> = buffer.iter().take(44).copied().collect();
+        assert!(levels.windows(2).all(|w| w[1] <= w[0]));
+        // After the overlap only the second track remains, at full level
+        assert!(buffer.iter().skip(44).all(|&s| s == 0.5));
+        assert!(source.outgoing.is_none());
+        handle.collect_garbage();
+    }
+
+    #[test]
+    fn test_skip_with_crossfade() {
+        let crossfade = Crossfade {
+            curve: CrossfadeCurve::EqualPower,
+            duration: std::time::Duration::from_millis(2),
+        };
+        let (mut source, mut handle) = GaplessSource::new(track(1.0, 10_000));
+        handle.skip_to(track(1.0, 10_000), Some(crossfade)).unwrap();
+
+        let mut buffer = vec![0.0; 256];
+        assert_eq!(source.read_samples(&mut buffer), 256);
+        // Equal power sums above unity for correlated material, never below
+        assert!(buffer.iter().take(96).all(|&s| s >= 1.0 - 1e-6));
+        assert!(buffer.iter().skip(96).all(|&s| s == 1.0));
+        assert_eq!(handle.tracks_started(), 0);
+        assert_eq!(source.position(), Some(256));
+
+        handle.skip_to(track(0.25, 10_000), None).unwrap();
+        assert_eq!(source.read_samples(&mut buffer), 256);
+        assert!(buffer.iter().all(|&s| s == 0.25));
+    }
+
+    #[test]
+    fn test_full_retire_queue_defers_drops() {
+        let (mut source, mut handle) = GaplessSource::new(track(0.1, 10_000));
+        for _ in 0..=QUEUE_CAPACITY {
+            source.retire(track(0.0, 1));
+        }
+        assert_eq!(handle.shared.retire_waits.load(Ordering::Relaxed), 1);
+        assert_eq!(source.backlog.iter().flatten().count(), 1);
+
+        // Commands wait too, since they may retire sources
+        handle.skip_to(track(0.2, 10_000), None).unwrap();
+        let mut buffer = vec![0.0; 64];
+        assert_eq!(source.read_samples(&mut buffer), 64);
+        assert!(buffer.iter().all(|&s| s == 0.1));
+
+        handle.collect_garbage();
+        assert_eq!(handle.reported_retire_waits, 1);
+        assert_eq!(source.read_samples(&mut buffer), 64);
+        assert!(buffer.iter().all(|&s| s == 0.2));
+        assert_eq!(source.backlog.iter().flatten().count(), 0);
+        handle.collect_garbage();
+        assert!(handle.retired.is_empty());
+    }
+}
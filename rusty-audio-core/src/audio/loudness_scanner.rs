@@ -0,0 +1,334 @@
+//! ReplayGain 2.0 loudness scanner
+//!
+//! [`LoudnessScanner`] decodes audio files, measures their integrated
+//! loudness and true peak with the BS.1770 [`LoudnessMeter`], and writes the
+//! results back as `REPLAYGAIN_*` tags. Files are measured in parallel on
+//! the rayon pool. Every folder counts as one album: its album gain comes
+//! from the pooled gating blocks of all its tracks, as if they were played
+//! back to back, not from an average of track values.
+//!
+//! Albums whose files all carry track and album gain already are skipped
+//! unless [`ScanConfig::force`] is set. One untagged file makes the whole
+//! folder rescan, since its album gain depends on every track.
+
+use super::backend::{AudioBackendError, Result};
+use super::file_stream::FileDecoder;
+use super::replay_gain::ReplayGain;
+use crate::ai::volume_normalizer::LoudnessMeter;
+use crate::metadata::read_replay_gain;
+use lofty::config::WriteOptions;
+use lofty::file::TaggedFileExt;
+use lofty::tag::{ItemKey, Tag, TagExt};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Loudness that ReplayGain 2.0 gains bring tracks to
+pub const REFERENCE_LOUDNESS_LUFS: f32 = -18.0;
+
+/// Scanner options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanConfig {
+    /// Rescan files that are already tagged
+    pub force: bool,
+    /// Write the results to the files; otherwise only report them
+    pub write_tags: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            force: false,
+            write_tags: true,
+        }
+    }
+}
+
+/// Measured loudness of a single file
+#[derive(Debug, Clone)]
+pub struct TrackLoudness {
+    /// Integrated loudness in LUFS, `None` if the file is silent
+    pub loudness_lufs: Option<f32>,
+    /// True peak (linear, 1.0 = full scale)
+    pub true_peak: f32,
+    /// Gating block energies, pooled for the album measurement
+    blocks: Vec<f64>,
+}
+
+impl TrackLoudness {
+    /// Decode `path` and measure it
+    ///
+    /// # Errors
+    /// See [`FileDecoder::open`] and [`FileDecoder::next_chunk`]
+    pub fn measure(path: impl AsRef<Path>) -> Result<Self> {
+        let mut decoder = FileDecoder::open(path)?;
+        let mut meter = LoudnessMeter::new(decoder.sample_rate(), usize::from(decoder.channels()));
+        while let Some(chunk) = decoder.next_chunk()? {
+            meter.process(chunk);
+        }
+        Ok(Self {
+            loudness_lufs: meter.integrated_loudness(),
+            true_peak: meter.true_peak(),
+            blocks: meter.block_energies().collect(),
+        })
+    }
+}
+
+/// One scanned file
+#[derive(Debug, Clone)]
+pub struct ScannedTrack {
+    /// The file
+    pub path: PathBuf,
+    /// Its measurement
+    pub loudness: TrackLoudness,
+    /// Gains and peaks for the track and its album, as tagged
+    pub replay_gain: ReplayGain,
+}
+
+/// Outcome of a scan
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// Files measured (and tagged, if enabled)
+    pub tracks: Vec<ScannedTrack>,
+    /// Files left alone because their album was already tagged
+    pub skipped: Vec<PathBuf>,
+    /// Files that could not be measured or tagged, with the reason
+    pub failed: Vec<(PathBuf, AudioBackendError)>,
+}
+
+/// Measures files and tags them with ReplayGain 2.0 values
+#[derive(Debug, Clone, Default)]
+pub struct LoudnessScanner {
+    config: ScanConfig,
+}
+
+impl LoudnessScanner {
+    /// Create a scanner with the given options
+    pub fn new(config: ScanConfig) -> Self {
+        Self { config }
+    }
+
+    /// Scan `paths`, grouping them into albums by folder
+    ///
+    /// Blocks until every file is done; run it off the UI thread.
+    pub fn scan(&self, paths: &[PathBuf]) -> ScanReport {
+        let mut albums: BTreeMap<&Path, Vec<&Path>> = BTreeMap::new();
+        for path in paths {
+            let folder = path.parent().unwrap_or(Path::new(""));
+            albums.entry(folder).or_default().push(path);
+        }
+
+        let mut report = ScanReport::default();
+        let (to_scan, tagged): (Vec<_>, Vec<_>) = albums
+            .into_values()
+            .partition(|tracks| self.config.force || !tracks.par_iter().all(|t| is_tagged(t)));
+        report
+            .skipped
+            .extend(tagged.into_iter().flatten().map(Path::to_path_buf));
+
+        // Measure every file of every album at once, for the best use of
+        // the pool when albums are small
+        let measured: Vec<(&Path, Result<TrackLoudness>)> = to_scan
+            .par_iter()
+            .flatten()
+            .map(|&path| (path, TrackLoudness::measure(path)))
+            .collect();
+
+        let mut measured = measured.into_iter();
+        for album in &to_scan {
+            let mut tracks = Vec::with_capacity(album.len());
+            for (path, result) in measured.by_ref().take(album.len()) {
+                match result {
+                    Ok(loudness) => tracks.push((path, loudness)),
+                    Err(e) => report.failed.push((path.to_path_buf(), e)),
+                }
+            }
+            report.tracks.extend(album_replay_gain(tracks));
+        }
+
+        if self.config.write_tags {
+            let failed: Vec<_> = report
+                .tracks
+                .par_iter()
+                .filter_map(|track| {
+                    write_tags(&track.path, &track.replay_gain)
+                        .err()
+                        .map(|e| (track.path.clone(), e))
+                })
+                .collect();
+            report
+                .tracks
+                .retain(|track| !failed.iter().any(|(path, _)| *path == track.path));
+            report.failed.extend(failed);
+        }
+
+        log::info!(
+            "Loudness scan: {} measured, {} skipped, {} failed",
+            report.tracks.len(),
+            report.skipped.len(),
+            report.failed.len()
+        );
+        report
+    }
+}
+
+/// Work out track and album values for the measured tracks of one album
+fn album_replay_gain(tracks: Vec<(&Path, TrackLoudness)>) -> Vec<ScannedTrack> {
+    let album_loudness = LoudnessMeter::gated_loudness(
+        tracks
+            .iter()
+            .flat_map(|(_, track)| track.blocks.iter().copied()),
+    );
+    let album_peak = tracks
+        .iter()
+        .map(|(_, track)| track.true_peak)
+        .fold(0.0, f32::max);
+
+    tracks
+        .into_iter()
+        .map(|(path, loudness)| ScannedTrack {
+            path: path.to_path_buf(),
+            replay_gain: ReplayGain {
+                track_gain_db: loudness.loudness_lufs.map(gain_for),
+                track_peak: Some(loudness.true_peak),
+                album_gain_db: album_loudness.map(gain_for),
+                album_peak: Some(album_peak),
+            },
+            loudness,
+        })
+        .collect()
+}
+
+fn gain_for(loudness_lufs: f32) -> f32 {
+    REFERENCE_LOUDNESS_LUFS - loudness_lufs
+}
+
+/// Whether a file already has track and album gain
+fn is_tagged(path: &Path) -> bool {
+    lofty::read_from_path(path).is_ok_and(|file| {
+        let replay_gain = read_replay_gain(&file);
+        replay_gain.track_gain_db.is_some() && replay_gain.album_gain_db.is_some()
+    })
+}
+
+/// Write ReplayGain 2.0 fields to the file's primary tag, creating it if
+/// needed; values that could not be measured are removed
+fn write_tags(path: &Path, replay_gain: &ReplayGain) -> Result<()> {
+    let tag_error = |e: lofty::error::LoftyError| {
+        AudioBackendError::Other(anyhow::anyhow!("Failed to tag {}: {}", path.display(), e))
+    };
+    let mut tagged_file = lofty::read_from_path(path).map_err(tag_error)?;
+    let tag_type = tagged_file.primary_tag_type();
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.tag_mut(tag_type) else {
+        return Err(AudioBackendError::UnsupportedFormat(format!(
+            "Cannot tag {}",
+            path.display()
+        )));
+    };
+
+    let fields = [
+        (
+            ItemKey::ReplayGainTrackGain,
+            replay_gain.track_gain_db.map(format_gain),
+        ),
+        (
+            ItemKey::ReplayGainTrackPeak,
+            replay_gain.track_peak.map(format_peak),
+        ),
+        (
+            ItemKey::ReplayGainAlbumGain,
+            replay_gain.album_gain_db.map(format_gain),
+        ),
+        (
+            ItemKey::ReplayGainAlbumPeak,
+            replay_gain.album_peak.map(format_peak),
+        ),
+    ];
+    for (key, value) in fields {
+        match value {
+            Some(value) => {
+                tag.insert_text(key, value);
+            }
+            None => tag.remove_key(&key),
+        }
+    }
+    tag.save_to_path(path, WriteOptions::default())
+        .map_err(tag_error)
+}
+
+fn format_gain(gain_db: f32) -> String {
+    format!("{:.2} dB", gain_db)
+}
+
+fn format_peak(peak: f32) -> String {
+    format!("{:.6}", peak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{LoftyMetadataExtractor, MetadataExtractorInterface};
+    use std::f32::consts::PI;
+
+    /// Write a stereo 48 kHz WAV of a 997 Hz sine at `amplitude`
+    fn write_sine(path: &Path, amplitude: f32, seconds: f32) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for frame in 0..(48000.0 * seconds) as u32 {
+            let sample = amplitude * (2.0 * PI * 997.0 * frame as f32 / 48000.0).sin();
+            let sample = (sample * 32767.0).round() as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_scan_tags_albums_and_skips_tagged_files() {
+        let folder = tempfile::tempdir().unwrap();
+        let quiet = folder.path().join("quiet.wav");
+        let loud = folder.path().join("loud.wav");
+        write_sine(&quiet, 0.05, 2.0);
+        write_sine(&loud, 0.5, 2.0);
+        let paths = vec![quiet.clone(), loud.clone()];
+
+        let report = LoudnessScanner::default().scan(&paths);
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert_eq!(report.tracks.len(), 2);
+
+        // -26 dBFS reads -26 LUFS, 8 dB below the reference
+        let quiet_gain = report.tracks[0].replay_gain;
+        assert!((quiet_gain.track_gain_db.unwrap() - 8.0).abs() < 0.1);
+        // Pooled blocks are gated like one long programme: the quiet track
+        // falls under the relative gate and the album measures as the loud one
+        let album_gain = quiet_gain.album_gain_db.unwrap();
+        assert!((album_gain + 12.0).abs() < 0.1, "{}", album_gain);
+        assert!((quiet_gain.album_peak.unwrap() - 0.5).abs() < 0.01);
+
+        let tags = LoftyMetadataExtractor::new()
+            .extract_metadata(&loud)
+            .unwrap()
+            .replay_gain;
+        assert!((tags.track_gain_db.unwrap() + 12.0).abs() < 0.1);
+        assert!((tags.album_gain_db.unwrap() - album_gain).abs() < 0.01);
+        assert!(tags.track_peak.is_some() && tags.album_peak.is_some());
+
+        // Already tagged: skipped unless forced
+        let report = LoudnessScanner::default().scan(&paths);
+        assert_eq!(report.skipped.len(), 2);
+        let forced = LoudnessScanner::new(ScanConfig {
+            force: true,
+            write_tags: false,
+        })
+        .scan(&paths);
+        assert_eq!(forced.tracks.len(), 2);
+    }
+}
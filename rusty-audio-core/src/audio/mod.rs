@@ -9,7 +9,13 @@
 
 pub mod backend;
 pub mod backend_selector;
+pub mod bwf;
+pub mod channel_matrix;
+pub mod crossfade;
 pub mod destinations;
+pub mod effects;
+pub mod flac_encoder;
+pub mod gapless;
 
 // Native-only modules (use CPAL, hound, etc.)
 #[cfg(not(target_arch = "wasm32"))]
@@ -21,13 +27,29 @@ pub mod device_source;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod file_recorder;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod file_stream;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod hybrid;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod loudness_scanner;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod manager;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod offline_render;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod recorder;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recording_recovery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod virtual_backend;
 
+pub mod play_queue;
+pub mod recording_trigger;
+pub mod replay_gain;
+pub mod resampler;
 pub mod router;
+pub mod router_processor;
+pub mod session;
 pub mod sources;
 
 // Web bridge is native-only (bridges web-audio-api to CPAL hardware)
@@ -65,8 +87,10 @@ pub use hybrid::{BackendHealth, FallbackPolicy, HybridAudioBackend, HybridMode};
 pub use manager::AudioDeviceManager;
 #[cfg(not(target_arch = "wasm32"))]
 pub use recorder::{
-    AudioRecorder, MonitoringMode, RecordingConfig, RecordingFormat, RecordingState,
+    AudioRecorder, MonitoringMode, PreRollBuffer, RecordingConfig, RecordingFormat, RecordingState,
 };
+#[cfg(not(target_arch = "wasm32"))]
+pub use recording_recovery::{recover_recordings, repair_wav, RecoveredRecording};
 
 // Web bridge is native-only
 #[cfg(not(target_arch = "wasm32"))]
@@ -75,16 +99,39 @@ pub use web_bridge::{WebAudioBridge, WebAudioBridgeConfig};
 #[cfg(target_os = "windows")]
 pub use asio_backend::{AsioBackend, WindowsBackendType};
 pub use backend_selector::{BackendInfo, BackendSelector};
+pub use bwf::{BextChunk, BwfWriter};
 
 #[cfg(target_os = "windows")]
 pub use mmcss::{MmcssHandle, MmcssTaskCategory};
 
-pub use router::{AudioDestination, AudioRouter, AudioSource, DestId, Route, RouteId, SourceId};
+pub use channel_matrix::ChannelMatrix;
+pub use crossfade::{Crossfade, CrossfadeConfig, CrossfadeCurve, DEFAULT_CROSSFADE};
+pub use effects::{
+    AudioProcessor, EqBand, EqHandle, EqProcessor, InsertInfo, InsertPoint, LimiterProcessor,
+    ProcessorChain,
+};
+pub use flac_encoder::{FlacConfig, FlacEncoder};
+pub use gapless::{GaplessHandle, GaplessSource};
+pub use play_queue::{PlayQueue, RepeatMode};
+pub use recording_trigger::{TriggerConfig, TriggerDetector, TriggerEvent, TriggerMode};
+pub use replay_gain::{ReplayGain, ReplayGainConfig, ReplayGainMode};
+pub use resampler::{ResamplerQuality, RouteResampler};
+pub use router::{
+    AudioDestination, AudioRouter, AudioSource, Bus, BusId, DestId, NodeInfo, Route, RouteId,
+    SourceId, DEFAULT_GAIN_RAMP,
+};
+pub use router_processor::RouterProcessor;
+#[cfg(not(target_arch = "wasm32"))]
+pub use session::DeviceResolver;
+pub use session::{
+    NodeKind, NodeResolver, PlaceholderDestination, PlaceholderSource, RestoredSession,
+    RouterSession, SessionBus, SessionDestination, SessionRoute, SessionSource,
+};
 
 // Audio sources and destinations
 pub use destinations::{
-    LevelMeterDestination, NullDestination, RingBufferDestination, RingBufferReader,
-    SplitterDestination,
+    LevelMeterDestination, LoudnessMeterDestination, LoudnessMeterHandle, LoudnessReadings,
+    NullDestination, RingBufferDestination, RingBufferReader, SplitterDestination,
 };
 pub use sources::{RingBufferSource, RingBufferWriter, SignalGeneratorSource, SilenceSource};
 
@@ -94,7 +141,20 @@ pub use device_destination::OutputDeviceDestination;
 #[cfg(not(target_arch = "wasm32"))]
 pub use device_source::InputDeviceSource;
 #[cfg(not(target_arch = "wasm32"))]
-pub use file_recorder::FileRecorderDestination;
+pub use file_recorder::{FileRecorderDestination, DEFAULT_SYNC_INTERVAL};
+#[cfg(not(target_arch = "wasm32"))]
+pub use file_stream::{FileDecoder, FileStreamHandle, FileStreamSource, DEFAULT_STREAM_BUFFER};
+#[cfg(not(target_arch = "wasm32"))]
+pub use loudness_scanner::{
+    LoudnessScanner, ScanConfig, ScanReport, ScannedTrack, TrackLoudness, REFERENCE_LOUDNESS_LUFS,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use offline_render::{OfflineRenderer, RenderConfig, RenderProgress, RenderReport, StopReason};
+#[cfg(not(target_arch = "wasm32"))]
+pub use virtual_backend::{
+    CaptureBuffer, VirtualBackend, VirtualCapture, VirtualClock, VirtualInputDevice,
+    VirtualOutputDevice, VirtualSignal, DEFAULT_VIRTUAL_INPUT, DEFAULT_VIRTUAL_OUTPUT,
+};
 
 // Web Audio API backend and destination (WASM only)
 #[cfg(target_arch = "wasm32")]
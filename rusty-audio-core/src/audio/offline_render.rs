@@ -0,0 +1,314 @@
+//! Faster-than-realtime rendering of a router graph
+//!
+//! [`OfflineRenderer`] drives [`AudioRouter::process`] in a tight loop with
+//! no audio hardware involved, typically into a
+//! [`FileRecorderDestination`](super::file_recorder::FileRecorderDestination):
+//!
+//! ```rust,no_run
+//! # use rusty_audio_core::audio::{AudioRouter, FileRecorderDestination, SignalGeneratorSource};
+//! # use rusty_audio_core::audio::offline_render::{OfflineRenderer, RenderConfig};
+//! # fn example(tone: Vec<f32>) -> rusty_audio_core::audio::Result<()> {
+//! let router = AudioRouter::new(1024);
+//! let source = router.add_source(Box::new(SignalGeneratorSource::from_buffer(tone, 48000.0, false)));
+//! let bounce = router.add_destination(Box::new(FileRecorderDestination::new_f32("bounce.wav", 48000, 1)?));
+//! router.create_route(source, bounce, 1.0)?;
+//!
+//! let report = OfflineRenderer::new(RenderConfig::default()).render(&router, bounce, |progress| {
+//!     println!("{:.1}s rendered at {:.0}x", progress.rendered.as_secs_f64(), progress.speed);
+//! })?;
+//! println!("Done in {:?} ({:.0}x realtime)", report.elapsed, report.speed);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Rendering stops once every routed source that reports a length has run
+//! out (plus an optional tail), or when a maximum duration is reached. Audio
+//! is rendered in whole router buffers, so a duration limit is rounded up to
+//! the next buffer.
+
+use super::backend::{AudioBackendError, Result};
+use super::router::{AudioRouter, DestId};
+use std::time::{Duration, Instant};
+
+/// Offline render settings
+#[derive(Debug, Clone, Default)]
+pub struct RenderConfig {
+    /// Stop after this much audio even if sources are still playing
+    ///
+    /// Required when no routed source has a known length.
+    pub max_duration: Option<Duration>,
+    /// Extra audio rendered after the last finite source ends, for
+    /// resampler, effect and reverb tails
+    pub tail: Duration,
+}
+
+/// Why a render stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Every finite source ran out (and the tail was rendered)
+    SourcesFinished,
+    /// The maximum duration was reached
+    DurationReached,
+}
+
+/// Progress of a running render
+#[derive(Debug, Clone, Copy)]
+pub struct RenderProgress {
+    /// Audio rendered so far
+    pub rendered: Duration,
+    /// Fraction done (0.0 to 1.0), if the total length is known
+    pub fraction: Option<f64>,
+    /// Rendered audio time divided by wall-clock time
+    pub speed: f64,
+}
+
+/// Summary of a finished render
+#[derive(Debug, Clone, Copy)]
+pub struct RenderReport {
+    /// Frames written to the output destination
+    pub frames: u64,
+    /// Audio rendered
+    pub rendered: Duration,
+    /// Wall-clock time taken
+    pub elapsed: Duration,
+    /// Rendered audio time divided by wall-clock time
+    pub speed: f64,
+    /// Why the render stopped
+    pub stop_reason: StopReason,
+}
+
+/// Renders a router graph as fast as the CPU allows
+#[derive(Debug, Clone, Default)]
+pub struct OfflineRenderer {
+    config: RenderConfig,
+}
+
+impl OfflineRenderer {
+    /// Create a renderer with the given settings
+    pub fn new(config: RenderConfig) -> Self {
+        Self { config }
+    }
+
+    /// Render `router` until its finite sources end or the duration limit
+    ///
+    /// `output` sets the time base (its sample rate and channel count) and
+    /// is removed from the router afterwards, so a file destination is
+    /// closed and finalized by the time this returns. `on_progress` is
+    /// called after every buffer.
+    ///
+    /// # Errors
+    /// - `DeviceNotFound` if `output` is not a destination of `router`
+    /// - `InitializationFailed` if nothing would stop the render (no finite
+    ///   sources and no `max_duration`)
+    /// - Any error from processing, e.g. a destination failing to write
+    pub fn render(
+        &self,
+        router: &AudioRouter,
+        output: DestId,
+        mut on_progress: impl FnMut(&RenderProgress),
+    ) -> Result<RenderReport> {
+        let info = router.get_destination_info(output).ok_or_else(|| {
+            AudioBackendError::DeviceNotFound(format!("Destination {:?} not found", output))
+        })?;
+        let sample_rate = info.sample_rate.max(1);
+        let frames_per_buffer = (router.buffer_size() / info.channels.max(1) as usize) as u64;
+
+        // Longest routed source with a known length, in seconds
+        let longest_source = router
+            .get_routes()
+            .iter()
+            .filter(|route| route.enabled)
+            .filter_map(|route| router.get_source_info(route.source))
+            .filter_map(|source| {
+                let samples_per_second =
+                    f64::from(source.sample_rate) * f64::from(source.channels.max(1));
+                let length = source.length?;
+                Some(length as f64 / samples_per_second.max(1.0))
+            })
+            .reduce(f64::max);
+        if longest_source.is_none() && self.config.max_duration.is_none() {
+            return Err(AudioBackendError::InitializationFailed(
+                "Offline render needs a finite source or a maximum duration".to_string(),
+            ));
+        }
+
+        let max_frames = self
+            .config
+            .max_duration
+            .map(|duration| duration_to_frames(duration, sample_rate));
+        let tail_frames = duration_to_frames(self.config.tail, sample_rate);
+        let expected_seconds = match (longest_source, self.config.max_duration) {
+            (Some(source), Some(limit)) => {
+                Some((source + self.config.tail.as_secs_f64()).min(limit.as_secs_f64()))
+            }
+            (Some(source), None) => Some(source + self.config.tail.as_secs_f64()),
+            (None, limit) => limit.map(|limit| limit.as_secs_f64()),
+        };
+
+        let start = Instant::now();
+        let mut frames = 0u64;
+        let mut sources_ended_at = None;
+        let stop_reason = loop {
+            if frames_per_buffer == 0 {
+                break StopReason::DurationReached;
+            }
+            router.process()?;
+            frames += frames_per_buffer;
+
+            let rendered = frames_to_duration(frames, sample_rate);
+            let speed = speed(rendered, start.elapsed());
+            let fraction = expected_seconds
+                .filter(|&total| total > 0.0)
+                .map(|total| (rendered.as_secs_f64() / total).min(1.0));
+            on_progress(&RenderProgress {
+                rendered,
+                fraction,
+                speed,
+            });
+
+            if longest_source.is_some()
+                && sources_ended_at.is_none()
+                && router.finite_sources_playing() == 0
+            {
+                sources_ended_at = Some(frames);
+            }
+            if let Some(ended) = sources_ended_at {
+                if frames - ended >= tail_frames {
+                    break StopReason::SourcesFinished;
+                }
+            }
+            if max_frames.is_some_and(|max| frames >= max) {
+                break StopReason::DurationReached;
+            }
+        };
+
+        // Drop the output so a recorder finalizes its file before we return
+        router.remove_destination(output);
+        router.apply_edits()?;
+        router.collect_garbage();
+
+        let rendered = frames_to_duration(frames, sample_rate);
+        let elapsed = start.elapsed();
+        Ok(RenderReport {
+            frames,
+            rendered,
+            elapsed,
+            speed: speed(rendered, elapsed),
+            stop_reason,
+        })
+    }
+}
+
+fn duration_to_frames(duration: Duration, sample_rate: u32) -> u64 {
+    (duration.as_secs_f64() * f64::from(sample_rate)).round() as u64
+}
+
+fn frames_to_duration(frames: u64, sample_rate: u32) -> Duration {
+    Duration::from_secs_f64(frames as f64 / f64::from(sample_rate))
+}
+
+/// Render speed factor, treating an unmeasurably short render as very fast
+fn speed(rendered: Duration, elapsed: Duration) -> f64 {
+    let elapsed = elapsed.as_secs_f64();
+    if elapsed > 0.0 {
+        rendered.as_secs_f64() / elapsed
+    } else {
+        f64::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::effects::{EqBand, EqProcessor, InsertPoint};
+    use crate::audio::file_recorder::FileRecorderDestination;
+    use crate::audio::sources::{SignalGeneratorSource, SilenceSource};
+
+    #[test]
+    fn test_render_stops_when_sources_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("finish.wav");
+        let router = AudioRouter::new(256);
+        let tone = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.25; 4800],
+            48000.0,
+            false,
+        )));
+        let bounce = router.add_destination(Box::new(
+            FileRecorderDestination::new_f32(&path, 48000, 2).unwrap(),
+        ));
+        let route = router.create_route(tone, bounce, 1.0).unwrap();
+        router
+            .set_processor_chain(
+                InsertPoint::Route(route),
+                vec![Box::new(EqProcessor::new(&[EqBand::new(1000.0, 1.0, 3.0)]))],
+            )
+            .unwrap();
+
+        let mut updates = Vec::new();
+        let renderer = OfflineRenderer::new(RenderConfig {
+            tail: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let report = renderer
+            .render(&router, bounce, |progress| updates.push(*progress))
+            .unwrap();
+
+        assert_eq!(report.stop_reason, StopReason::SourcesFinished);
+        // 100 ms of source plus at least 10 ms of tail, in 128-frame buffers
+        assert!(report.frames >= 5280 && report.frames < 5280 + 2 * 128);
+        assert!(report.speed > 1.0);
+        assert_eq!(updates.len() as u64, report.frames / 128);
+        assert!(updates.windows(2).all(|w| w[0].rendered < w[1].rendered));
+        assert!(updates.iter().all(|p| p.fraction.is_some()));
+
+        // The bounce destination was removed and its file finalized
+        assert!(router.get_destination_info(bounce).is_none());
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration() as u64, report.frames);
+    }
+
+    #[test]
+    fn test_render_duration_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("limit.wav");
+        let router = AudioRouter::new(100);
+        let looping = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.5; 100],
+            1000.0,
+            true,
+        )));
+        let bounce = router.add_destination(Box::new(
+            FileRecorderDestination::new_f32(&path, 1000, 1).unwrap(),
+        ));
+        router.create_route(looping, bounce, 1.0).unwrap();
+
+        let renderer = OfflineRenderer::new(RenderConfig {
+            max_duration: Some(Duration::from_millis(450)),
+            ..Default::default()
+        });
+        let report = renderer.render(&router, bounce, |_| {}).unwrap();
+        assert_eq!(report.stop_reason, StopReason::DurationReached);
+        assert_eq!(report.frames, 500);
+    }
+
+    #[test]
+    fn test_render_needs_an_end() {
+        let router = AudioRouter::new(64);
+        let silence = router.add_source(Box::new(SilenceSource::new(1000, 1)));
+        let dest = router.add_destination(Box::new(crate::audio::NullDestination::new(1000, 1)));
+        router.create_route(silence, dest, 1.0).unwrap();
+
+        let renderer = OfflineRenderer::default();
+        assert!(matches!(
+            renderer.render(&router, dest, |_| {}),
+            Err(AudioBackendError::InitializationFailed(_))
+        ));
+
+        router.remove_destination(dest);
+        assert!(matches!(
+            renderer.render(&router, dest, |_| {}),
+            Err(AudioBackendError::DeviceNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,360 @@
+//! Play queue ordering with shuffle and repeat
+//!
+//! [`PlayQueue`] only decides *which* track plays next; it holds no audio.
+//! Players ask it for [`upcoming`](PlayQueue::upcoming) while the current
+//! track is still playing, pre-decode that track, and call
+//! [`advance`](PlayQueue::advance) at the moment playback crosses into it.
+//! [`GaplessSource`](super::gapless::GaplessSource) does the splicing for
+//! router playback.
+//!
+//! The queue distinguishes a track ending on its own from the user skipping:
+//! with [`RepeatMode::One`], [`advance`](PlayQueue::advance) replays the
+//! current track, while [`next_track`](PlayQueue::next_track) and
+//! [`previous_track`](PlayQueue::previous_track) still move through the queue.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::path::{Path, PathBuf};
+
+/// What happens when playback reaches the end of a track or the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    /// Stop after the last track
+    #[default]
+    Off,
+    /// Replay the current track until the user skips
+    One,
+    /// Start over from the first track after the last one
+    All,
+}
+
+impl RepeatMode {
+    /// The mode after this one when cycling with a single button
+    /// (off, all, one, off)
+    pub fn cycle(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
+/// Ordered list of tracks with a play cursor
+///
+/// Track indices (as used by [`play_index`](Self::play_index) and
+/// [`remove`](Self::remove)) always refer to the order tracks were added.
+/// Shuffle only changes the play order, which is reshuffled each time
+/// shuffle is switched on. The current track is kept at the front of a new
+/// shuffle so switching modes never interrupts playback.
+#[derive(Debug, Clone, Default)]
+pub struct PlayQueue {
+    tracks: Vec<PathBuf>,
+    /// Play order as indices into `tracks`
+    order: Vec<usize>,
+    /// Position of the current track in `order`
+    cursor: Option<usize>,
+    shuffle: bool,
+    repeat: RepeatMode,
+}
+
+impl PlayQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of tracks in the queue
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Whether the queue has no tracks
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// All tracks, in the order they were added
+    pub fn tracks(&self) -> &[PathBuf] {
+        &self.tracks
+    }
+
+    /// The current track
+    pub fn current(&self) -> Option<&Path> {
+        self.current_index()
+            .and_then(|index| self.tracks.get(index))
+            .map(PathBuf::as_path)
+    }
+
+    /// Index of the current track, in the order tracks were added
+    pub fn current_index(&self) -> Option<usize> {
+        self.cursor
+            .and_then(|cursor| self.order.get(cursor).copied())
+    }
+
+    /// Add a track to the end of the queue
+    ///
+    /// While shuffled, the track is placed at a random point after the
+    /// current track. The first track added becomes the current one.
+    pub fn push(&mut self, path: impl Into<PathBuf>) {
+        let index = self.tracks.len();
+        self.tracks.push(path.into());
+        if self.shuffle {
+            let earliest = self.cursor.map_or(0, |cursor| cursor + 1);
+            let at = rand::thread_rng().gen_range(earliest..=self.order.len());
+            self.order.insert(at, index);
+        } else {
+            self.order.push(index);
+        }
+        if self.cursor.is_none() {
+            self.cursor = self.order.iter().position(|&i| i == index);
+        }
+    }
+
+    /// Add several tracks to the end of the queue
+    pub fn extend<I, P>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        for path in paths {
+            self.push(path);
+        }
+    }
+
+    /// Remove a track by index
+    ///
+    /// Removing the current track makes the following track current.
+    pub fn remove(&mut self, index: usize) -> Option<PathBuf> {
+        if index >= self.tracks.len() {
+            return None;
+        }
+        let removed = self.tracks.remove(index);
+        let position = self.order.iter().position(|&i| i == index)?;
+        self.order.remove(position);
+        for i in &mut self.order {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        self.cursor = match self.cursor {
+            _ if self.order.is_empty() => None,
+            Some(cursor) if cursor > position => Some(cursor - 1),
+            Some(cursor) if cursor == position => match self.repeat {
+                RepeatMode::All => Some(cursor % self.order.len()),
+                _ => Some(cursor.min(self.order.len() - 1)),
+            },
+            cursor => cursor,
+        };
+        Some(removed)
+    }
+
+    /// Remove every track
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.order.clear();
+        self.cursor = None;
+    }
+
+    /// Make the track at `index` current
+    pub fn play_index(&mut self, index: usize) -> Option<&Path> {
+        let position = self.order.iter().position(|&i| i == index)?;
+        self.cursor = Some(position);
+        self.current()
+    }
+
+    /// The track that will play when the current one ends on its own
+    ///
+    /// Use this to pre-decode the next track for a gapless transition.
+    pub fn upcoming(&self) -> Option<&Path> {
+        self.upcoming_index()
+            .and_then(|index| self.tracks.get(index))
+            .map(PathBuf::as_path)
+    }
+
+    /// Index of the upcoming track, in the order tracks were added
+    pub fn upcoming_index(&self) -> Option<usize> {
+        let cursor = self.upcoming_cursor()?;
+        self.order.get(cursor).copied()
+    }
+
+    /// Move on because the current track ended
+    ///
+    /// Returns the new current track, which is the track
+    /// [`upcoming`](Self::upcoming) reported, or `None` at the end of the
+    /// queue (the cursor then stays on the last track).
+    pub fn advance(&mut self) -> Option<&Path> {
+        let cursor = self.upcoming_cursor()?;
+        self.cursor = Some(cursor);
+        self.current()
+    }
+
+    /// Skip to the next track at the user's request
+    ///
+    /// Ignores [`RepeatMode::One`]. Returns `None` at the end of the queue
+    /// unless repeating all.
+    pub fn next_track(&mut self) -> Option<&Path> {
+        let cursor = self.step(1)?;
+        self.cursor = Some(cursor);
+        self.current()
+    }
+
+    /// Go back to the previous track at the user's request
+    ///
+    /// Wraps to the last track when repeating all; otherwise stays on the
+    /// first track and returns it so the player can restart it.
+    pub fn previous_track(&mut self) -> Option<&Path> {
+        if let Some(cursor) = self.step(-1) {
+            self.cursor = Some(cursor);
+        }
+        self.current()
+    }
+
+    /// Whether the play order is shuffled
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Switch shuffle on (with a fresh order) or off (back to added order)
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        let current = self.current_index();
+        self.shuffle = shuffle;
+        self.order = (0..self.tracks.len()).collect();
+        if shuffle {
+            let mut rng = rand::thread_rng();
+            self.order.shuffle(&mut rng);
+            if let Some(current) = current {
+                // Keep the current track first so the rest of the shuffle
+                // is still ahead of it
+                if let Some(position) = self.order.iter().position(|&i| i == current) {
+                    self.order.swap(0, position);
+                }
+            }
+        }
+        self.cursor = current.and_then(|current| self.order.iter().position(|&i| i == current));
+    }
+
+    /// Current repeat mode
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    /// Set the repeat mode
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    fn upcoming_cursor(&self) -> Option<usize> {
+        match self.repeat {
+            RepeatMode::One => self.cursor,
+            _ => self.step(1),
+        }
+    }
+
+    /// Cursor `delta` tracks away, wrapping only when repeating all
+    fn step(&self, delta: isize) -> Option<usize> {
+        let cursor = self.cursor? as isize;
+        let len = self.order.len() as isize;
+        let target = cursor + delta;
+        if (0..len).contains(&target) {
+            Some(target as usize)
+        } else if self.repeat == RepeatMode::All && len > 0 {
+            Some(target.rem_euclid(len) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(names: &[&str]) -> PlayQueue {
+        let mut queue = PlayQueue::new();
+        queue.extend(names.iter().map(PathBuf::from));
+        queue
+    }
+
+    fn name(path: Option<&Path>) -> Option<&str> {
+        path.and_then(|path| path.to_str())
+    }
+
+    #[test]
+    fn test_queue_order_and_repeat() {
+        let mut queue = queue(&["a", "b", "c"]);
+        assert_eq!(name(queue.current()), Some("a"));
+        assert_eq!(name(queue.upcoming()), Some("b"));
+        assert_eq!(queue.upcoming_index(), Some(1));
+        assert_eq!(name(queue.advance()), Some("b"));
+        assert_eq!(name(queue.next_track()), Some("c"));
+        assert_eq!(queue.upcoming(), None);
+        assert_eq!(queue.advance(), None);
+        assert_eq!(name(queue.current()), Some("c"));
+
+        queue.set_repeat(RepeatMode::All);
+        assert_eq!(name(queue.upcoming()), Some("a"));
+        assert_eq!(name(queue.advance()), Some("a"));
+        assert_eq!(name(queue.previous_track()), Some("c"));
+
+        // Repeat one replays on its own but still skips on request
+        queue.set_repeat(RepeatMode::One);
+        assert_eq!(name(queue.advance()), Some("c"));
+        assert_eq!(queue.next_track(), None);
+        assert_eq!(name(queue.previous_track()), Some("b"));
+
+        queue.set_repeat(RepeatMode::Off);
+        queue.play_index(0);
+        assert_eq!(name(queue.previous_track()), Some("a"));
+        assert_eq!(RepeatMode::Off.cycle().cycle().cycle(), RepeatMode::Off);
+    }
+
+    #[test]
+    fn test_shuffle_keeps_current_track() {
+        let names: Vec<String> = (0..20).map(|i| format!("track{}", i)).collect();
+        let mut queue = PlayQueue::new();
+        queue.extend(&names);
+        queue.play_index(7);
+
+        queue.set_shuffle(true);
+        assert_eq!(queue.current_index(), Some(7));
+        let mut played = vec![7];
+        while queue.advance().is_some() {
+            played.push(queue.current_index().unwrap());
+        }
+        played.sort_unstable();
+        assert_eq!(played, (0..20).collect::<Vec<_>>());
+
+        // Tracks added while shuffled are still ahead of the current one
+        queue.play_index(3);
+        queue.push("late");
+        let mut ahead = Vec::new();
+        while queue.advance().is_some() {
+            ahead.push(queue.current_index().unwrap());
+        }
+        assert!(ahead.contains(&20));
+
+        queue.set_shuffle(false);
+        queue.play_index(3);
+        assert_eq!(queue.upcoming(), Some(Path::new("track4")));
+    }
+
+    #[test]
+    fn test_remove_tracks() {
+        let mut queue = queue(&["a", "b", "c"]);
+        queue.play_index(1);
+        assert_eq!(queue.remove(0), Some(PathBuf::from("a")));
+        assert_eq!(name(queue.current()), Some("b"));
+        assert_eq!(queue.current_index(), Some(0));
+
+        // Removing the current track moves on to the following one
+        assert_eq!(queue.remove(0), Some(PathBuf::from("b")));
+        assert_eq!(name(queue.current()), Some("c"));
+        assert_eq!(queue.remove(5), None);
+
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.current(), None);
+        assert_eq!(queue.next_track(), None);
+    }
+}
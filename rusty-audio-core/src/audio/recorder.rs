@@ -5,13 +5,25 @@
 //! - Multi-channel support (stereo by default)
 //! - State management (Idle, Recording, Paused, Stopped)
 //! - Monitoring modes (Off, Direct, Routed)
-//! - WAV file export (32-bit float)
+//! - WAV file export (32-bit float) and FLAC export (16/24-bit)
+//! - Crash-safe streaming of each take to disk as it's recorded
+//! - Retroactive pre-roll while armed, prepended to takes or captured alone
+//! - Threshold- and voice-activated takes driven by the level meters
 //! - SIMD-accelerated level metering (AVX2/SSE)
 
 use super::backend::{AudioBackend, AudioConfig, AudioStream, SampleFormat};
+use super::bwf::BextChunk;
 use super::device::CpalBackend;
+use super::file_recorder::{FileRecorderDestination, DEFAULT_SYNC_INTERVAL};
+use super::flac_encoder::{FlacConfig, FlacEncoder};
+use super::recording_trigger::{TriggerConfig, TriggerDetector, TriggerEvent, TriggerMode};
+use super::router::AudioDestination;
 use anyhow::{anyhow, Context, Result};
+use rtrb::{Producer, RingBuffer};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
 #[cfg(target_arch = "x86_64")]
@@ -22,7 +34,7 @@ use std::arch::x86_64::*;
 pub enum RecordingFormat {
     /// WAV format with 32-bit float samples
     Wav,
-    /// FLAC format (lossless compression) - TODO Phase 4.1
+    /// FLAC format (lossless compression, 16 or 24-bit)
     Flac,
 }
 
@@ -61,6 +73,19 @@ pub struct RecordingConfig {
     pub buffer_size: usize,
     /// Maximum recording duration in seconds (0 = unlimited)
     pub max_duration_secs: u64,
+    /// Directory takes are streamed to while recording, so a crash loses at
+    /// most the last sync interval (None = memory only)
+    pub recordings_dir: Option<PathBuf>,
+    /// Seconds of input kept while armed and prepended to the next take
+    /// (0 = no pre-roll)
+    pub pre_roll_secs: u64,
+    /// Level trigger that starts and stops takes while armed
+    pub trigger: TriggerConfig,
+    /// Format takes are streamed to disk in
+    pub format: RecordingFormat,
+    /// Bit depth of streamed takes: 16, 24 or 32 (float) for WAV, 16 or 24
+    /// for FLAC
+    pub bits_per_sample: u16,
 }
 
 impl Default for RecordingConfig {
@@ -70,10 +95,119 @@ impl Default for RecordingConfig {
             channels: 2,                   // Stereo
             buffer_size: 1024 * 1024 * 10, // ~10MB buffer (~3.5 minutes stereo)
             max_duration_secs: 0,          // Unlimited
+            recordings_dir: None,
+            pre_roll_secs: 0,
+            trigger: TriggerConfig::default(),
+            format: RecordingFormat::Wav,
+            bits_per_sample: 32,
         }
     }
 }
 
+impl RecordingConfig {
+    /// Default directory for streamed takes, in the user's data directory
+    pub fn default_recordings_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rusty-audio").join("recordings"))
+    }
+}
+
+/// Seconds of audio the disk writer's ring holds before samples are dropped
+const DISK_RING_SECONDS: usize = 4;
+
+/// How long the disk writer sleeps when it has caught up
+const DISK_WRITER_IDLE: Duration = Duration::from_millis(20);
+
+/// Audio thread's end of the ring feeding the disk writer
+#[derive(Default)]
+struct DiskFeed {
+    /// Ring into the disk writer, while a take is being streamed
+    producer: Mutex<Option<Producer<f32>>>,
+    /// Frames of the current take that didn't fit in the ring
+    dropped_frames: AtomicU64,
+}
+
+/// Thread streaming the current take to a file
+struct DiskWriter {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<()>>,
+}
+
+/// Copy samples into the disk writer's ring, if a take is being streamed
+///
+/// Never blocks the audio thread; whole frames that don't fit are left out
+/// of the file and counted in `dropped_frames`.
+fn feed_disk(feed: &DiskFeed, data: &[f32], channels: usize) {
+    let channels = channels.max(1);
+    let Ok(mut producer) = feed.producer.try_lock() else {
+        feed.dropped_frames
+            .fetch_add((data.len() / channels) as u64, Ordering::Relaxed);
+        return;
+    };
+    let Some(producer) = producer.as_mut() else {
+        return;
+    };
+    let count = producer.slots().min(data.len());
+    let count = count - count % channels;
+    let dropped = (data.len() - count) / channels;
+    if dropped > 0 {
+        feed.dropped_frames
+            .fetch_add(dropped as u64, Ordering::Relaxed);
+    }
+    if count == 0 {
+        return;
+    }
+    let Ok(mut chunk) = producer.write_chunk(count) else {
+        return;
+    };
+    let (first, second) = chunk.as_mut_slices();
+    let (head, tail) = data.split_at(first.len());
+    first.copy_from_slice(head);
+    second.copy_from_slice(tail.get(..second.len()).unwrap_or(&[]));
+    chunk.commit_all();
+}
+
+/// Route a block of input from the audio thread: into the pre-roll while
+/// armed, and into the take and its file while recording
+fn capture_input(
+    state: &Mutex<RecordingState>,
+    armed: &AtomicBool,
+    pre_roll: &PreRollBuffer,
+    buffer: &LockFreeRecordingBuffer,
+    disk_feed: &DiskFeed,
+    data: &[f32],
+) {
+    let Ok(state) = state.lock() else {
+        return;
+    };
+    // Written under the state lock, so `start` can't both prepend these
+    // samples and see them recorded
+    let armed = armed.load(Ordering::Relaxed);
+    if armed {
+        pre_roll.write(data);
+    }
+    if *state == RecordingState::Recording {
+        drop(state); // Release state lock (no buffer lock needed - lock-free)
+        buffer.write(data);
+        feed_disk(disk_feed, data, buffer.channels);
+    } else if armed {
+        // Keep the meters moving while waiting to record
+        buffer.meter(data);
+    }
+}
+
+/// A file name with `extension` for a new take in `dir` that isn't taken yet
+fn new_take_path(dir: &Path, extension: &str) -> PathBuf {
+    let stem = format!("Take {}", chrono::Local::now().format("%Y-%m-%d %H-%M-%S"));
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut number = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).{}", stem, number, extension));
+        number += 1;
+    }
+    path
+}
+
 /// Recording buffer with circular buffer and level metering
 pub struct RecordingBuffer {
     /// Interleaved audio samples (LRLRLR...)
@@ -328,7 +462,7 @@ impl LockFreeRecordingBuffer {
             let mut current_rms = self.rms_levels[ch].load(std::sync::atomic::Ordering::Relaxed);
             loop {
                 let current_f32 = f32::from_bits(current_rms);
-                let new_rms = (current_f32 * 0.99 + sample_sq * 0.01).sqrt();
+                let new_rms = (current_f32 * current_f32 * 0.99 + sample_sq * 0.01).sqrt();
                 match self.rms_levels[ch].compare_exchange_weak(
                     current_rms,
                     new_rms.to_bits(),
@@ -410,7 +544,7 @@ impl LockFreeRecordingBuffer {
                     self.rms_levels[ch].load(std::sync::atomic::Ordering::Relaxed);
                 loop {
                     let current_f32 = f32::from_bits(current_rms);
-                    let new_rms = (current_f32 * 0.99 + sample_sq * 0.01).sqrt();
+                    let new_rms = (current_f32 * current_f32 * 0.99 + sample_sq * 0.01).sqrt();
                     match self.rms_levels[ch].compare_exchange_weak(
                         current_rms,
                         new_rms.to_bits(),
@@ -488,7 +622,7 @@ impl LockFreeRecordingBuffer {
                     self.rms_levels[ch].load(std::sync::atomic::Ordering::Relaxed);
                 loop {
                     let current_f32 = f32::from_bits(current_rms);
-                    let new_rms = (current_f32 * 0.99 + sample_sq * 0.01).sqrt();
+                    let new_rms = (current_f32 * current_f32 * 0.99 + sample_sq * 0.01).sqrt();
                     match self.rms_levels[ch].compare_exchange_weak(
                         current_rms,
                         new_rms.to_bits(),
@@ -507,6 +641,12 @@ impl LockFreeRecordingBuffer {
         }
     }
 
+    /// Update the level meters from samples that aren't being recorded
+    #[inline(always)]
+    pub fn meter(&self, data: &[f32]) {
+        self.update_levels_lockfree(data);
+    }
+
     /// Get peak level for channel (lock-free read)
     #[inline(always)]
     pub fn peak_level(&self, channel: usize) -> f32 {
@@ -547,6 +687,7 @@ impl LockFreeRecordingBuffer {
 
     /// Clear the buffer (note: not fully atomic, use only when recording is stopped)
     pub fn clear(&self) {
+        self.ring_buffer.clear();
         self.total_samples
             .store(0, std::sync::atomic::Ordering::Relaxed);
         for level in &self.peak_levels {
@@ -594,6 +735,9 @@ impl LockFreeRecordingBuffer {
     }
 
     /// Get all recorded samples (reads from ring buffer - not lock-free, use when stopped)
+    ///
+    /// The samples stay in the buffer, so they can be read again, e.g. to
+    /// export a take after showing it. Returns how many were copied.
     pub fn get_samples(&self, output: &mut Vec<f32>) -> usize {
         let total = self
             .total_samples
@@ -604,8 +748,159 @@ impl LockFreeRecordingBuffer {
         let samples_to_read = total.min(capacity);
         output.resize(samples_to_read, 0.0);
 
-        // Read from ring buffer
-        self.ring_buffer.read(output)
+        // Copy from ring buffer, leaving the samples in place
+        self.ring_buffer.peek(output)
+    }
+}
+
+/// Always-on circular buffer of the most recent input
+///
+/// The audio thread overwrites the oldest samples as it goes, so the buffer
+/// always holds the last `capacity` worth of input. Reads copy out the
+/// newest samples without blocking the writer, seqlock-style: the writer
+/// announces how far it is about to write before touching the ring, and a
+/// reader checks that announcement after copying to drop anything that was
+/// overwritten underneath it.
+pub struct PreRollBuffer {
+    /// Ring storage, as `f32` bits
+    samples: Box<[AtomicU32]>,
+    /// Samples written so far; positions only ever grow
+    written: AtomicUsize,
+    /// Position the writer is writing up to, ahead of `written` while a
+    /// block is being stored
+    writing: AtomicUsize,
+    /// Position of the last clear; nothing before it is read
+    cleared_at: AtomicUsize,
+    /// Number of channels
+    channels: usize,
+    /// Sample rate
+    sample_rate: u32,
+}
+
+impl PreRollBuffer {
+    /// Create a buffer holding `length` of audio
+    pub fn new(length: Duration, channels: usize, sample_rate: u32) -> Self {
+        let channels = channels.max(1);
+        let frames = (length.as_secs_f64() * f64::from(sample_rate)) as usize;
+        Self {
+            samples: (0..frames * channels).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicUsize::new(0),
+            writing: AtomicUsize::new(0),
+            cleared_at: AtomicUsize::new(0),
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Write interleaved samples, overwriting the oldest (single writer)
+    #[inline]
+    pub fn write(&self, data: &[f32]) {
+        let capacity = self.samples.len();
+        if capacity == 0 {
+            return;
+        }
+        let written = self.written.load(Ordering::Relaxed);
+        let end = written.wrapping_add(data.len());
+        // Announced before any slot changes, so readers can tell which of
+        // the samples they copied may be torn
+        self.writing.store(end, Ordering::Relaxed);
+        fence(Ordering::Release);
+        // Only the last `capacity` samples of a long block survive anyway
+        let skip = data.len().saturating_sub(capacity);
+        for (offset, sample) in data.iter().enumerate().skip(skip) {
+            if let Some(slot) = self.samples.get((written + offset) % capacity) {
+                slot.store(sample.to_bits(), Ordering::Relaxed);
+            }
+        }
+        self.written.store(end, Ordering::Release);
+    }
+
+    /// Copy out up to the last `length` of audio, oldest sample first
+    ///
+    /// Samples the writer overwrote while they were being copied are left
+    /// out, so the result may be a little shorter than asked for.
+    pub fn latest(&self, length: Duration) -> Vec<f32> {
+        self.latest_with_end(length).0
+    }
+
+    /// [`latest`](Self::latest), along with the write position it ends at
+    fn latest_with_end(&self, length: Duration) -> (Vec<f32>, usize) {
+        let end = self.written.load(Ordering::Acquire);
+        let wanted = (length.as_secs_f64() * f64::from(self.sample_rate)) as usize;
+        (
+            self.copy_until(end, wanted.saturating_mul(self.channels)),
+            end,
+        )
+    }
+
+    /// Copy out what was written after write position `position`, as far
+    /// back as the buffer reaches, along with the write position it ends at
+    fn since(&self, position: usize) -> (Vec<f32>, usize) {
+        let end = self.written.load(Ordering::Acquire);
+        (self.copy_until(end, end.saturating_sub(position)), end)
+    }
+
+    /// Copy out up to `count` samples ending at write position `end`
+    fn copy_until(&self, end: usize, count: usize) -> Vec<f32> {
+        let capacity = self.samples.len();
+        let held = end.saturating_sub(self.cleared_at.load(Ordering::Acquire));
+        let count = count.min(held).min(capacity);
+        let count = count - count % self.channels;
+        let start = end - count;
+
+        let mut output: Vec<f32> = (start..end)
+            .filter_map(|position| self.samples.get(position % capacity))
+            .map(|slot| f32::from_bits(slot.load(Ordering::Relaxed)))
+            .collect();
+
+        // Pairs with the writer's fence: if any copied slot came from a
+        // block written after `end`, that block's announcement is visible
+        // here, and everything it reaches a lap behind was overwritten
+        fence(Ordering::Acquire);
+        let overwritten = self
+            .writing
+            .load(Ordering::Relaxed)
+            .saturating_sub(capacity)
+            .saturating_sub(start);
+        let overwritten = overwritten.div_ceil(self.channels) * self.channels;
+        output.drain(..overwritten.min(output.len()));
+        output
+    }
+
+    /// Length of audio currently held
+    pub fn duration(&self) -> Duration {
+        let held = self
+            .written
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.cleared_at.load(Ordering::Relaxed))
+            .min(self.samples.len());
+        Duration::from_secs_f64((held / self.channels) as f64 / f64::from(self.sample_rate.max(1)))
+    }
+
+    /// Length of audio the buffer can hold
+    pub fn capacity(&self) -> Duration {
+        Duration::from_secs_f64(
+            (self.samples.len() / self.channels) as f64 / f64::from(self.sample_rate.max(1)),
+        )
+    }
+
+    /// Forget the buffered audio (the writer may still be running)
+    ///
+    /// Only the clearing side moves the clear position, and the writer only
+    /// moves its own, so a write in progress can't undo the clear.
+    pub fn clear(&self) {
+        let written = self.written.load(Ordering::Acquire);
+        self.cleared_at.store(written, Ordering::Release);
+    }
+}
+
+impl std::fmt::Debug for PreRollBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreRollBuffer")
+            .field("capacity", &self.capacity())
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
     }
 }
 
@@ -631,6 +926,23 @@ pub struct AudioRecorder {
     input_stream: Option<Box<dyn AudioStream>>,
     /// CPAL backend for audio I/O
     cpal_backend: Option<CpalBackend>,
+    /// Ring into the disk writer, shared with the input callback
+    disk_feed: Arc<DiskFeed>,
+    /// Thread streaming the current take to disk
+    disk_writer: Option<DiskWriter>,
+    /// File the last take was streamed to
+    take_path: Option<PathBuf>,
+    /// Whether input is kept in the pre-roll and metered while not recording
+    armed: Arc<AtomicBool>,
+    /// Most recent input while armed
+    pre_roll: Arc<PreRollBuffer>,
+    /// Decides when level-triggered takes start and stop
+    trigger: TriggerDetector,
+    /// Pre-roll position the trigger has measured up to
+    trigger_position: usize,
+    /// Input peak seen by threshold mode, decayed separately from the
+    /// buffer's meters so the UI keeps its own
+    trigger_peak: f32,
 }
 
 impl AudioRecorder {
@@ -647,6 +959,12 @@ impl AudioRecorder {
             config.channels as usize,
             config.sample_rate,
         ));
+        let pre_roll = Arc::new(PreRollBuffer::new(
+            Duration::from_secs(config.pre_roll_secs),
+            config.channels as usize,
+            config.sample_rate,
+        ));
+        let trigger = TriggerDetector::new(config.trigger);
 
         Self {
             config,
@@ -659,6 +977,14 @@ impl AudioRecorder {
             monitoring_gain: 1.0,
             input_stream: None,
             cpal_backend: Some(CpalBackend::new()),
+            disk_feed: Arc::new(DiskFeed::default()),
+            disk_writer: None,
+            take_path: None,
+            armed: Arc::new(AtomicBool::new(false)),
+            pre_roll,
+            trigger,
+            trigger_position: 0,
+            trigger_peak: 0.0,
         }
     }
 
@@ -683,15 +1009,20 @@ impl AudioRecorder {
         // Create clones for the callback closure
         let buffer_clone = self.buffer.clone();
         let state_clone = self.state.clone();
+        let disk_clone = self.disk_feed.clone();
+        let armed_clone = self.armed.clone();
+        let pre_roll_clone = self.pre_roll.clone();
 
         // Create callback that writes to buffer when recording
         let callback = move |data: &[f32]| {
-            if let Ok(state) = state_clone.lock() {
-                if *state == RecordingState::Recording {
-                    drop(state); // Release state lock (no buffer lock needed - lock-free)
-                    buffer_clone.write(data);
-                }
-            }
+            capture_input(
+                &state_clone,
+                &armed_clone,
+                &pre_roll_clone,
+                &buffer_clone,
+                &disk_clone,
+                data,
+            );
         };
 
         // Create input stream with callback
@@ -713,36 +1044,318 @@ impl AudioRecorder {
     }
 
     /// Start recording
+    ///
+    /// While armed, the pre-roll is prepended to the take.
+    ///
+    /// # Errors
+    /// Fails if the recorder is already recording or paused, or the take's
+    /// file can't be created
     pub fn start(&mut self) -> Result<()> {
-        // Check current state and update it
-        let current_state = {
-            let mut state = self.lock_state()?;
-            let old_state = *state;
+        self.start_take(self.pre_roll.capacity())
+    }
 
-            match old_state {
-                RecordingState::Idle | RecordingState::Stopped => {
-                    *state = RecordingState::Recording;
-                    old_state
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Cannot start recording from {:?} state",
-                        old_state
-                    ));
-                }
-            }
-        }; // MutexGuard dropped here
+    /// Start a take, beginning with up to `pre_roll` of buffered input if
+    /// armed
+    fn start_take(&mut self, pre_roll: Duration) -> Result<()> {
+        let old_state = *self.lock_state()?;
+        if !matches!(old_state, RecordingState::Idle | RecordingState::Stopped) {
+            return Err(anyhow::anyhow!(
+                "Cannot start recording from {:?} state",
+                old_state
+            ));
+        }
 
-        // Now we can safely modify other fields without holding the lock
-        if current_state == RecordingState::Stopped {
+        // The take's file is created and the pre-roll written to it before
+        // taking the state lock, which the input callback waits on
+        let armed = self.is_armed();
+        let (mut samples, position) = if armed {
+            self.pre_roll.latest_with_end(pre_roll)
+        } else {
+            (Vec::new(), 0)
+        };
+        let writer = match self.config.recordings_dir.clone() {
+            Some(dir) => Some(self.start_disk_writer(&dir, &samples)?),
+            None => None,
+        };
+        if old_state == RecordingState::Stopped {
             self.buffer.clear();
         }
-        self.start_time = Some(Instant::now());
+        self.buffer.write(&samples);
+
+        let state = self.state.clone();
+        let mut state = match state.lock() {
+            Ok(state) => state,
+            Err(_) => {
+                if let Some((writer, _)) = writer {
+                    writer.stop.store(true, Ordering::Release);
+                    writer.thread.thread().unpark();
+                }
+                return Err(anyhow!("Recorder state lock poisoned"));
+            }
+        };
+        // Input the callback kept in the pre-roll since it was read; nothing
+        // more arrives until the lock is released
+        let tail = if armed {
+            self.pre_roll.since(position).0
+        } else {
+            Vec::new()
+        };
+        self.buffer.write(&tail);
+        if let Some((writer, mut producer)) = writer {
+            for &sample in &tail {
+                let _ = producer.push(sample);
+            }
+            if let Ok(mut feed) = self.disk_feed.producer.lock() {
+                *feed = Some(producer);
+            }
+            self.disk_feed.dropped_frames.store(0, Ordering::Relaxed);
+            self.disk_writer = Some(writer);
+        }
+        *state = RecordingState::Recording;
+        drop(state);
+        samples.extend_from_slice(&tail);
+
+        // The take's duration includes its pre-roll
+        let pre_roll_duration = self.frames_to_duration(samples.len());
+        let now = Instant::now();
+        self.start_time = Some(now.checked_sub(pre_roll_duration).unwrap_or(now));
         self.pause_duration = Duration::ZERO;
 
         Ok(())
     }
 
+    /// Length of `samples` interleaved samples at the configured format
+    fn frames_to_duration(&self, samples: usize) -> Duration {
+        let frames = samples / usize::from(self.config.channels.max(1));
+        Duration::from_secs_f64(frames as f64 / f64::from(self.config.sample_rate.max(1)))
+    }
+
+    /// Open a file for the new take in `dir` beginning with `pre_roll`, and
+    /// start a thread streaming to it from the returned ring
+    fn start_disk_writer(
+        &self,
+        dir: &Path,
+        pre_roll: &[f32],
+    ) -> Result<(DiskWriter, Producer<f32>)> {
+        let (path, mut destination) = self.create_take_file(dir, pre_roll)?;
+        // Syncs block on the disk, which is fine on the writer thread
+        destination.set_sync_interval(DEFAULT_SYNC_INTERVAL);
+        let sample_rate = self.config.sample_rate;
+        let channels = self.config.channels;
+
+        let capacity = sample_rate as usize * usize::from(channels) * DISK_RING_SECONDS;
+        let (producer, mut consumer) = RingBuffer::new(capacity.max(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("recording-writer".to_string())
+            .spawn(move || -> Result<()> {
+                loop {
+                    // Checked before draining, so nothing pushed before the
+                    // stop request is left behind
+                    let stopping = thread_stop.load(Ordering::Acquire);
+                    let count = consumer.slots();
+                    if count > 0 {
+                        if let Ok(chunk) = consumer.read_chunk(count) {
+                            let (first, second) = chunk.as_slices();
+                            destination.write_samples(first)?;
+                            destination.write_samples(second)?;
+                            chunk.commit_all();
+                        }
+                    } else if stopping {
+                        break;
+                    } else {
+                        std::thread::park_timeout(DISK_WRITER_IDLE);
+                    }
+                }
+                destination.finalize()?;
+                Ok(())
+            })
+            .context("Failed to start recording writer")?;
+
+        Ok((DiskWriter { path, stop, thread }, producer))
+    }
+
+    /// Create a file for a new take in `dir` in the configured format,
+    /// starting with `samples` that were captured before now
+    fn create_take_file(
+        &self,
+        dir: &Path,
+        samples: &[f32],
+    ) -> Result<(PathBuf, FileRecorderDestination)> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let sample_rate = self.config.sample_rate;
+        let channels = self.config.channels;
+        let bits_per_sample = self.config.bits_per_sample;
+        let (path, mut destination) = match self.config.format {
+            RecordingFormat::Wav => {
+                let path = new_take_path(dir, "wav");
+                let mut bext = BextChunk::now("rusty-audio", sample_rate);
+                let frames = samples.len() / usize::from(channels.max(1));
+                bext.time_reference = bext.time_reference.saturating_sub(frames as u64);
+                let destination = FileRecorderDestination::new_bwf(
+                    &path,
+                    sample_rate,
+                    channels,
+                    bits_per_sample,
+                    &bext,
+                    None,
+                )?;
+                (path, destination)
+            }
+            RecordingFormat::Flac => {
+                let path = new_take_path(dir, "flac");
+                let config = FlacConfig {
+                    bits_per_sample,
+                    ..FlacConfig::default()
+                };
+                let destination =
+                    FileRecorderDestination::new_flac(&path, sample_rate, channels, &config)?;
+                (path, destination)
+            }
+        };
+        destination.write_samples(samples)?;
+        Ok((path, destination))
+    }
+
+    /// Stop streaming to disk and wait for the file to be finalized
+    fn finish_disk_writer(&mut self) -> Result<()> {
+        let Some(writer) = self.disk_writer.take() else {
+            return Ok(());
+        };
+        if let Ok(mut feed) = self.disk_feed.producer.lock() {
+            feed.take();
+        }
+        writer.stop.store(true, Ordering::Release);
+        writer.thread.thread().unpark();
+        let result = writer
+            .thread
+            .join()
+            .map_err(|_| anyhow!("Recording writer panicked"))?;
+        self.take_path = Some(writer.path);
+        result.context("Failed to write recording to disk")
+    }
+
+    /// File the last take was streamed to, once it has stopped
+    pub fn take_path(&self) -> Option<&Path> {
+        self.take_path.as_deref()
+    }
+
+    /// Frames of the current or last streamed take missing from its file
+    /// because the disk writer fell too far behind
+    pub fn dropped_frames(&self) -> u64 {
+        self.disk_feed.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    /// Change the format and bit depth the next take is streamed in
+    pub fn set_take_format(&mut self, format: RecordingFormat, bits_per_sample: u16) {
+        self.config.format = format;
+        self.config.bits_per_sample = bits_per_sample;
+    }
+
+    /// Start keeping input in the pre-roll and metering it while not
+    /// recording
+    pub fn arm(&mut self) {
+        if !self.armed.swap(true, Ordering::Relaxed) {
+            self.pre_roll.clear();
+            self.trigger_peak = 0.0;
+        }
+    }
+
+    /// Stop keeping input in the pre-roll
+    pub fn disarm(&mut self) {
+        self.armed.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the recorder is armed
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Relaxed)
+    }
+
+    /// Change the level trigger
+    pub fn set_trigger(&mut self, trigger: TriggerConfig) {
+        self.config.trigger = trigger;
+        self.trigger.set_config(trigger);
+    }
+
+    /// Run the level trigger, starting or stopping a take as it decides
+    ///
+    /// Call regularly with the time since the previous call. The trigger
+    /// only listens while armed, since that's when input outside takes is
+    /// metered, and leaves paused takes alone. Triggered takes start with
+    /// the attack and the trigger's pre-roll, as far as the pre-roll buffer
+    /// reaches. Voice mode measures the input that arrived in the pre-roll
+    /// since the previous call, so it needs one.
+    ///
+    /// # Errors
+    /// Fails if a triggered take can't be started or stopped
+    pub fn update_trigger(&mut self, elapsed: Duration) -> Result<Option<TriggerEvent>> {
+        let mode = self.config.trigger.mode;
+        if mode == TriggerMode::Manual || !self.is_armed() {
+            return Ok(None);
+        }
+        let state = self.state();
+        if state == RecordingState::Paused {
+            return Ok(None);
+        }
+        // Takes started or stopped by hand count too
+        self.trigger
+            .set_triggered(state == RecordingState::Recording);
+
+        let (samples, end) = self.pre_roll.since(self.trigger_position);
+        self.trigger_position = end;
+        let event = if mode == TriggerMode::Voice {
+            self.trigger
+                .update_samples(&samples, self.config.channels, self.config.sample_rate)
+        } else {
+            // Hold the peak and let it fall 60dB per second, like the
+            // meters, so the trigger sees the input go quiet
+            let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            self.trigger_peak = peak.max(self.trigger_peak * 0.001_f32.powf(elapsed.as_secs_f32()));
+            let level_db = 20.0 * self.trigger_peak.max(1e-6).log10();
+            self.trigger.update(level_db, elapsed)
+        };
+        match event {
+            Some(TriggerEvent::Started { .. }) => self.start_take(self.config.trigger.lead_in())?,
+            Some(TriggerEvent::Stopped { .. }) => self.stop()?,
+            None => {}
+        }
+        Ok(event)
+    }
+
+    /// Get reference to the pre-roll buffer
+    pub fn pre_roll(&self) -> Arc<PreRollBuffer> {
+        self.pre_roll.clone()
+    }
+
+    /// Write the last `length` of pre-roll straight to a take
+    ///
+    /// The take is saved as a new file in the recordings directory, if
+    /// there is one, and becomes [`take_path`](Self::take_path). Returns
+    /// the captured interleaved samples, which may be shorter than `length`
+    /// if the recorder hasn't been armed that long.
+    ///
+    /// # Errors
+    /// Fails if the recorder isn't armed, the pre-roll is empty, or the
+    /// take file can't be written
+    pub fn capture_pre_roll(&mut self, length: Duration) -> Result<Vec<f32>> {
+        if !self.is_armed() {
+            return Err(anyhow!("Recorder is not armed"));
+        }
+        let samples = self.pre_roll.latest(length);
+        if samples.is_empty() {
+            return Err(anyhow!("Nothing in the pre-roll yet"));
+        }
+
+        if let Some(dir) = &self.config.recordings_dir {
+            let (path, destination) = self.create_take_file(dir, &samples)?;
+            destination.finalize()?;
+            self.take_path = Some(path);
+        }
+        Ok(samples)
+    }
+
     /// Stop recording
     pub fn stop(&mut self) -> Result<()> {
         // Update state while holding the lock
@@ -768,7 +1381,7 @@ impl AudioRecorder {
             self.pause_time = None;
         }
 
-        Ok(())
+        self.finish_disk_writer()
     }
 
     /// Pause recording
@@ -872,17 +1485,21 @@ impl AudioRecorder {
 
     /// Write audio samples to the buffer (called from audio thread)
     pub fn write_samples(&mut self, samples: &[f32]) {
-        if let Ok(state) = self.state.lock() {
-            if *state == RecordingState::Recording {
-                self.buffer.write(samples);
-            }
-        }
+        capture_input(
+            &self.state,
+            &self.armed,
+            &self.pre_roll,
+            &self.buffer,
+            &self.disk_feed,
+            samples,
+        );
     }
 
     /// Export recording to WAV file
     pub fn save_to_wav(&self, path: &std::path::Path) -> Result<()> {
         let mut samples = Vec::new();
-        self.buffer.get_samples(&mut samples);
+        let read = self.buffer.get_samples(&mut samples);
+        samples.truncate(read);
 
         let spec = hound::WavSpec {
             channels: self.config.channels,
@@ -904,6 +1521,40 @@ impl AudioRecorder {
 
         Ok(())
     }
+
+    /// Export recording to FLAC file
+    ///
+    /// # Errors
+    /// Fails if the file can't be created or written, or `config` asks for a
+    /// bit depth other than 16 or 24
+    pub fn save_to_flac(&self, path: &std::path::Path, config: &FlacConfig) -> Result<()> {
+        let mut samples = Vec::new();
+        let read = self.buffer.get_samples(&mut samples);
+        samples.truncate(read);
+
+        let file = std::fs::File::create(path).context("Failed to create FLAC file")?;
+        let mut encoder = FlacEncoder::new(
+            std::io::BufWriter::new(file),
+            self.config.sample_rate,
+            self.config.channels,
+            config,
+        )
+        .context("Failed to create FLAC file")?;
+
+        encoder
+            .write_samples(&samples)
+            .context("Failed to write samples to FLAC file")?;
+        encoder.finish().context("Failed to finalize FLAC file")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        // Finalize a take still being streamed
+        let _ = self.finish_disk_writer();
+    }
 }
 
 #[cfg(test)]
@@ -976,4 +1627,398 @@ mod tests {
         recorder.set_monitoring_gain(0.5);
         assert_eq!(recorder.monitoring_gain(), 0.5);
     }
+
+    #[test]
+    fn test_stream_to_disk() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            recordings_dir: Some(dir.path().to_path_buf()),
+            ..RecordingConfig::default()
+        });
+
+        recorder.start()?;
+        recorder.write_samples(&[0.25; 4800]);
+        recorder.pause()?;
+        // Paused audio isn't recorded
+        recorder.write_samples(&[0.5; 4800]);
+        recorder.resume()?;
+        recorder.write_samples(&[0.25; 4800]);
+        recorder.stop()?;
+
+        let path = recorder.take_path().context("No take file")?.to_path_buf();
+        assert!(path.starts_with(dir.path()));
+        let mut reader = hound::WavReader::open(&path)?;
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.duration(), 4800);
+        assert!(reader.samples::<f32>().all(|s| s.is_ok_and(|s| s == 0.25)));
+
+        // Each take gets its own file
+        recorder.start()?;
+        recorder.write_samples(&[0.25; 960]);
+        recorder.stop()?;
+        assert_ne!(recorder.take_path(), Some(path.as_path()));
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_to_disk_flac() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            recordings_dir: Some(dir.path().to_path_buf()),
+            format: RecordingFormat::Flac,
+            bits_per_sample: 16,
+            ..RecordingConfig::default()
+        });
+
+        recorder.start()?;
+        recorder.write_samples(&[0.25; 4800]);
+        recorder.stop()?;
+
+        let path = recorder.take_path().context("No take file")?;
+        assert_eq!(path.extension(), Some("flac".as_ref()));
+        let params = probe_flac(path)?;
+        assert_eq!(params.n_frames, Some(2400));
+        assert_eq!(params.bits_per_sample, Some(16));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropped_frames() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 20,
+            recordings_dir: Some(dir.path().to_path_buf()),
+            ..RecordingConfig::default()
+        });
+
+        // More than the disk ring holds, in one block
+        let frames = 48000 * (DISK_RING_SECONDS + 1);
+        recorder.start()?;
+        recorder.write_samples(&vec![0.25; frames * 2]);
+        let dropped = recorder.dropped_frames();
+        recorder.stop()?;
+
+        assert!(dropped > 0);
+        assert_eq!(recorder.dropped_frames(), dropped);
+        let path = recorder.take_path().context("No take file")?;
+        let written = u64::from(hound::WavReader::open(path)?.duration());
+        assert_eq!(written + dropped, frames as u64);
+
+        // Each take starts counting afresh
+        recorder.start()?;
+        recorder.write_samples(&[0.25; 960]);
+        assert_eq!(recorder.dropped_frames(), 0);
+        recorder.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_pre_roll_buffer() {
+        let pre_roll = PreRollBuffer::new(Duration::from_millis(1), 2, 8000);
+        assert_eq!(pre_roll.capacity(), Duration::from_millis(1));
+        assert!(pre_roll.latest(Duration::from_secs(1)).is_empty());
+
+        let samples: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        pre_roll.write(&samples[..6]);
+        assert_eq!(pre_roll.latest(Duration::from_secs(1)), samples[..6]);
+        // Older samples are overwritten once it's full
+        pre_roll.write(&samples[6..]);
+        assert_eq!(pre_roll.duration(), Duration::from_millis(1));
+        assert_eq!(pre_roll.latest(Duration::from_secs(1)), samples[4..]);
+        assert_eq!(pre_roll.latest(Duration::from_micros(250)), samples[16..]);
+
+        pre_roll.clear();
+        assert!(pre_roll.latest(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_pre_roll_reads_while_writing() -> Result<()> {
+        // Each sample holds its own position, so torn reads show up as gaps
+        let pre_roll = Arc::new(PreRollBuffer::new(Duration::from_millis(4), 2, 8000));
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (pre_roll, stop) = (pre_roll.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let mut position = 0u32;
+                while !stop.load(Ordering::Relaxed) && position < 1 << 23 {
+                    let block: Vec<f32> = (position..position + 16).map(|i| i as f32).collect();
+                    pre_roll.write(&block);
+                    position += 16;
+                }
+            })
+        };
+
+        for _ in 0..10_000 {
+            let samples = pre_roll.latest(Duration::from_secs(1));
+            assert_eq!(samples.len() % 2, 0);
+            assert!(samples.windows(2).all(|pair| pair[1] == pair[0] + 1.0));
+        }
+        stop.store(true, Ordering::Relaxed);
+        writer
+            .join()
+            .map_err(|_| anyhow!("Pre-roll writer panicked"))
+    }
+
+    #[test]
+    fn test_pre_roll_recording() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            recordings_dir: Some(dir.path().to_path_buf()),
+            pre_roll_secs: 1,
+            ..RecordingConfig::default()
+        });
+
+        // Nothing is kept until the recorder is armed
+        recorder.write_samples(&[0.1; 960]);
+        assert!(recorder.capture_pre_roll(Duration::from_secs(1)).is_err());
+        recorder.arm();
+        assert!(recorder.capture_pre_roll(Duration::from_secs(1)).is_err());
+
+        // Input while armed is metered and kept, up to the pre-roll length
+        recorder.write_samples(&[0.5; 48_000]);
+        assert!(recorder.buffer().peak_level(0) > 0.0);
+        assert_eq!(recorder.buffer().position(), 0);
+        recorder.write_samples(&[0.25; 96_000]);
+
+        let captured = recorder.capture_pre_roll(Duration::from_millis(10))?;
+        assert_eq!(captured, [0.25; 960]);
+        let path = recorder.take_path().context("No capture file")?;
+        assert_eq!(hound::WavReader::open(path)?.duration(), 480);
+
+        // The whole pre-roll starts the next take
+        recorder.start()?;
+        recorder.write_samples(&[0.75; 960]);
+        recorder.stop()?;
+        let mut samples = Vec::new();
+        recorder.buffer().get_samples(&mut samples);
+        assert_eq!(samples.len(), 96_000 + 960);
+        assert!(samples[..96_000].iter().all(|&s| s == 0.25));
+        assert!(samples[96_000..].iter().all(|&s| s == 0.75));
+        let path = recorder.take_path().context("No take file")?;
+        assert_eq!(hound::WavReader::open(path)?.duration(), 48_000 + 480);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_triggered_takes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            recordings_dir: Some(dir.path().to_path_buf()),
+            pre_roll_secs: 1,
+            trigger: TriggerConfig {
+                mode: TriggerMode::Threshold,
+                threshold_db: -30.0,
+                hysteresis_db: 10.0,
+                attack_ms: 40,
+                hold_ms: 100,
+                pre_roll_ms: 0,
+            },
+            ..RecordingConfig::default()
+        });
+        // 20ms of stereo input at a time
+        let frame = Duration::from_millis(20);
+        let loud = [0.5; 1920];
+        let quiet = [0.0; 1920];
+
+        // The trigger only listens while armed
+        recorder.write_samples(&loud);
+        assert_eq!(recorder.update_trigger(frame)?, None);
+        recorder.arm();
+
+        for take in 1..=2 {
+            recorder.write_samples(&quiet);
+            assert_eq!(recorder.update_trigger(frame)?, None);
+            recorder.write_samples(&loud);
+            assert_eq!(recorder.update_trigger(frame)?, None);
+            recorder.write_samples(&loud);
+            assert!(matches!(
+                recorder.update_trigger(frame)?,
+                Some(TriggerEvent::Started { .. })
+            ));
+            assert_eq!(recorder.state(), RecordingState::Recording);
+            // The take starts with the attack
+            assert_eq!(recorder.buffer().position(), 3840);
+            assert!((recorder.buffer().rms_level(0) - 0.5).abs() < 0.01);
+
+            // The held peak falls away, then the hold runs out
+            let mut stopped = None;
+            for _ in 0..100 {
+                // The trigger leaves the meters to the UI
+                assert_eq!(recorder.buffer().peak_level(0), 0.5);
+                recorder.write_samples(&quiet);
+                if let Some(event) = recorder.update_trigger(frame)? {
+                    stopped = Some(event);
+                    break;
+                }
+            }
+            assert!(matches!(stopped, Some(TriggerEvent::Stopped { .. })));
+            assert_eq!(recorder.state(), RecordingState::Stopped);
+            assert_eq!(std::fs::read_dir(dir.path())?.count(), take);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_voice_triggered_take() -> Result<()> {
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            pre_roll_secs: 1,
+            trigger: TriggerConfig {
+                mode: TriggerMode::Voice,
+                threshold_db: -30.0,
+                hysteresis_db: 10.0,
+                attack_ms: 0,
+                hold_ms: 100,
+                pre_roll_ms: 0,
+            },
+            ..RecordingConfig::default()
+        });
+        recorder.arm();
+        // 20ms of stereo input at a time
+        let frame = Duration::from_millis(20);
+
+        // A 2ms click every 100ms never adds up to a voice
+        let mut click = [0.0; 1920];
+        click[..192].fill(1.0);
+        let quiet = [0.0; 1920];
+        for block in 0..20 {
+            recorder.write_samples(if block % 5 == 0 { &click } else { &quiet });
+            assert_eq!(recorder.update_trigger(frame)?, None);
+        }
+
+        let voice: Vec<f32> = (0..1920).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+        recorder.write_samples(&voice);
+        recorder.write_samples(&voice);
+        assert_eq!(recorder.update_trigger(frame)?, None);
+        recorder.write_samples(&voice);
+        assert!(matches!(
+            recorder.update_trigger(frame)?,
+            Some(TriggerEvent::Started { .. })
+        ));
+        assert_eq!(recorder.state(), RecordingState::Recording);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_to_flac() -> Result<()> {
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            ..RecordingConfig::default()
+        });
+        recorder.start()?;
+        let samples: Vec<f32> = (0..8000).map(|i| (i as f32 * 0.02).sin() * 0.5).collect();
+        recorder.write_samples(&samples);
+        recorder.stop()?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("take.flac");
+        let config = FlacConfig::default().with_tag("TITLE", "Take 1");
+        recorder.save_to_flac(&path, &config)?;
+
+        let params = probe_flac(&path)?;
+        assert_eq!(params.n_frames, Some(4000));
+        assert_eq!(params.bits_per_sample, Some(24));
+
+        // Lossless, down to the 24-bit quantization
+        let scale = ((1 << 23) - 1) as f32;
+        let expected: Vec<i32> = samples.iter().map(|s| (s * scale).round() as i32).collect();
+        assert_eq!(decode_flac(&path)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_after_reading_take() -> Result<()> {
+        let mut recorder = AudioRecorder::new(RecordingConfig {
+            buffer_size: 1 << 16,
+            ..RecordingConfig::default()
+        });
+        recorder.start()?;
+        let samples: Vec<f32> = (0..8000).map(|i| (i as f32 * 0.02).sin() * 0.5).collect();
+        recorder.write_samples(&samples);
+        recorder.stop()?;
+
+        // The panel copies the take out when it stops, before any save
+        let mut shown = Vec::new();
+        let read = recorder.buffer().get_samples(&mut shown);
+        assert_eq!(&shown[..read], &samples[..]);
+
+        let dir = tempfile::tempdir()?;
+        let wav_path = dir.path().join("take.wav");
+        recorder.save_to_wav(&wav_path)?;
+        let saved: Vec<f32> = hound::WavReader::open(&wav_path)?
+            .into_samples::<f32>()
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(saved, samples);
+
+        let flac_path = dir.path().join("take.flac");
+        recorder.save_to_flac(&flac_path, &FlacConfig::default())?;
+        let decoded = decode_flac(&flac_path)?;
+        assert_eq!(decoded.len(), samples.len());
+        assert!(decoded.iter().any(|&s| s != 0));
+
+        // Clearing drops the take for good
+        recorder.buffer().clear();
+        assert_eq!(recorder.buffer().get_samples(&mut shown), 0);
+        Ok(())
+    }
+
+    /// Codec parameters of the FLAC file at `path`
+    fn probe_flac(path: &Path) -> Result<symphonia::core::codecs::CodecParameters> {
+        let mut hint = symphonia::core::probe::Hint::new();
+        hint.with_extension("flac");
+        let stream = symphonia::core::io::MediaSourceStream::new(
+            Box::new(std::fs::File::open(path)?),
+            Default::default(),
+        );
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &Default::default(),
+            &Default::default(),
+        )?;
+        Ok(probed
+            .format
+            .default_track()
+            .context("No track")?
+            .codec_params
+            .clone())
+    }
+
+    /// Interleaved samples of the FLAC file at `path`, at its bit depth
+    fn decode_flac(path: &Path) -> Result<Vec<i32>> {
+        let stream = symphonia::core::io::MediaSourceStream::new(
+            Box::new(std::fs::File::open(path)?),
+            Default::default(),
+        );
+        let mut hint = symphonia::core::probe::Hint::new();
+        hint.with_extension("flac");
+        let mut format = symphonia::default::get_probe()
+            .format(&hint, stream, &Default::default(), &Default::default())?
+            .format;
+        let params = format
+            .default_track()
+            .context("No track")?
+            .codec_params
+            .clone();
+        let shift = 32 - params.bits_per_sample.context("No bit depth")?;
+        let mut decoder = symphonia::default::get_codecs().make(&params, &Default::default())?;
+
+        let mut samples = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            let decoded = decoder.decode(&packet)?;
+            let mut buffer = symphonia::core::audio::SampleBuffer::<i32>::new(
+                decoded.capacity() as u64,
+                *decoded.spec(),
+            );
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend(buffer.samples().iter().map(|&s| s >> shift));
+        }
+        Ok(samples)
+    }
 }
@@ -0,0 +1,326 @@
+//! Recovery of recordings cut short by a crash
+//!
+//! Recordings are streamed to disk as WAV with their header sizes updated
+//! every so often, so after a crash the audio is on disk but the header
+//! describes less of it than there is (or none, if the crash came before
+//! the first update). [`recover_recordings`] finds such files in a
+//! recordings directory and rewrites their RIFF and data sizes, switching
+//! to RF64 through the reserved `JUNK` chunk when they're past 4 GB.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Largest RIFF or data size a plain WAV header can hold
+const RIFF_LIMIT: u64 = u32::MAX as u64;
+
+/// A recording whose header was repaired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredRecording {
+    /// The repaired file
+    pub path: PathBuf,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Sample frames the file now holds
+    pub frames: u64,
+}
+
+impl RecoveredRecording {
+    /// Length of the recovered audio
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames as f64 / f64::from(self.sample_rate.max(1)))
+    }
+}
+
+/// Repair every partial WAV recording in `dir`, in file name order
+///
+/// Files that aren't WAV, or whose headers already match their length, are
+/// left alone. A missing directory has nothing to recover.
+///
+/// # Errors
+/// Any error listing `dir`; files that can't be read or repaired are
+/// skipped with a warning
+pub fn recover_recordings(dir: &Path) -> io::Result<Vec<RecoveredRecording>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+    paths.sort();
+
+    let mut recovered = Vec::new();
+    for path in paths {
+        match repair_wav(&path) {
+            Ok(Some(recording)) => recovered.push(recording),
+            Ok(None) => {}
+            Err(e) => log::warn!("Could not recover {}: {}", path.display(), e),
+        }
+    }
+    Ok(recovered)
+}
+
+/// Header facts needed to repair a WAV file
+struct WavLayout {
+    rf64: bool,
+    /// Where the body of a `ds64` chunk, or a `JUNK` chunk reserving room
+    /// for one, starts
+    ds64: Option<u64>,
+    sample_rate: u32,
+    channels: u16,
+    block_align: u64,
+    /// Where the data chunk's size field is
+    data_size_position: u64,
+    /// Size the header gives the data
+    declared: u64,
+}
+
+/// Rewrite the header of a WAV file whose sizes don't cover its audio
+///
+/// Returns `None` if the file isn't WAV or its header is already right. A
+/// trailing partial sample frame is cut off.
+///
+/// # Errors
+/// Any error reading or writing the file
+pub fn repair_wav(path: &Path) -> io::Result<Option<RecoveredRecording>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_length = file.metadata()?.len();
+    let Some(layout) = read_layout(&mut file, file_length)? else {
+        return Ok(None);
+    };
+
+    let data_start = layout.data_size_position + 4;
+    let available = file_length.saturating_sub(data_start);
+    if !is_partial(&mut file, &layout, data_start, available)? {
+        return Ok(None);
+    }
+
+    let data_length = available - available % layout.block_align;
+    if data_length < available {
+        file.set_len(data_start + data_length)?;
+    }
+    let pad = data_length % 2;
+    if pad == 1 {
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&[0])?;
+    }
+    let riff_size = data_start - 8 + data_length + pad;
+    let frames = data_length / layout.block_align;
+
+    match layout.ds64 {
+        Some(ds64) if layout.rf64 || riff_size > RIFF_LIMIT => {
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(b"RF64")?;
+            file.write_all(&u32::MAX.to_le_bytes())?;
+            file.seek(SeekFrom::Start(ds64 - 8))?;
+            file.write_all(b"ds64")?;
+            file.write_all(&28u32.to_le_bytes())?;
+            file.write_all(&riff_size.to_le_bytes())?;
+            file.write_all(&data_length.to_le_bytes())?;
+            file.write_all(&frames.to_le_bytes())?;
+            file.write_all(&0u32.to_le_bytes())?;
+            file.seek(SeekFrom::Start(layout.data_size_position))?;
+            file.write_all(&u32::MAX.to_le_bytes())?;
+        }
+        _ => {
+            // Without room for ds64, sizes past 4 GB can only be capped
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&(riff_size.min(RIFF_LIMIT) as u32).to_le_bytes())?;
+            file.seek(SeekFrom::Start(layout.data_size_position))?;
+            file.write_all(&(data_length.min(RIFF_LIMIT) as u32).to_le_bytes())?;
+        }
+    }
+    file.sync_all()?;
+
+    Ok(Some(RecoveredRecording {
+        path: path.to_path_buf(),
+        sample_rate: layout.sample_rate,
+        channels: layout.channels,
+        frames,
+    }))
+}
+
+/// Walk the chunks up to `data`; `None` if the file isn't WAV or has no
+/// format or data chunk
+fn read_layout(file: &mut File, file_length: u64) -> io::Result<Option<WavLayout>> {
+    let mut header = [0u8; 12];
+    if file_length < 12 {
+        return Ok(None);
+    }
+    file.read_exact(&mut header)?;
+    let rf64 = match (header.get(..4), header.get(8..)) {
+        (Some(b"RIFF"), Some(b"WAVE")) => false,
+        (Some(b"RF64"), Some(b"WAVE")) => true,
+        _ => return Ok(None),
+    };
+
+    let mut ds64 = None;
+    let mut ds64_data_size = None;
+    let mut format = None;
+    let mut position = 12u64;
+    while position + 8 <= file_length {
+        let mut chunk = [0u8; 8];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut chunk)?;
+        let [a, b, c, d, s0, s1, s2, s3] = chunk;
+        let size = u32::from_le_bytes([s0, s1, s2, s3]);
+        let body = position + 8;
+        match &[a, b, c, d] {
+            b"ds64" | b"JUNK" if size >= 28 && ds64.is_none() => {
+                ds64 = Some(body);
+                if a == b'd' {
+                    let mut sizes = [0u8; 16];
+                    file.read_exact(&mut sizes)?;
+                    let [_, _, _, _, _, _, _, _, data @ ..] = sizes;
+                    ds64_data_size = Some(u64::from_le_bytes(data));
+                }
+            }
+            b"fmt " if size >= 16 => {
+                let mut fmt = [0u8; 16];
+                file.read_exact(&mut fmt)?;
+                let [_, _, c0, c1, r0, r1, r2, r3, _, _, _, _, b0, b1, _, _] = fmt;
+                let channels = u16::from_le_bytes([c0, c1]);
+                let block_align = u16::from_le_bytes([b0, b1]);
+                if channels == 0 || block_align == 0 {
+                    return Ok(None);
+                }
+                format = Some((
+                    u32::from_le_bytes([r0, r1, r2, r3]),
+                    channels,
+                    u64::from(block_align),
+                ));
+            }
+            b"data" => {
+                let Some((sample_rate, channels, block_align)) = format else {
+                    return Ok(None);
+                };
+                let declared = match ds64_data_size {
+                    Some(data_size) if rf64 && size == u32::MAX => data_size,
+                    _ => u64::from(size),
+                };
+                return Ok(Some(WavLayout {
+                    rf64,
+                    ds64,
+                    sample_rate,
+                    channels,
+                    block_align,
+                    data_size_position: position + 4,
+                    declared,
+                }));
+            }
+            _ => {}
+        }
+        position = body + u64::from(size) + u64::from(size % 2);
+    }
+    Ok(None)
+}
+
+/// Whether the header gives the data a size other than what's on disk
+///
+/// Data past the declared size counts only if it isn't a chunk that
+/// follows the audio, such as a `LIST` chunk another program appended.
+fn is_partial(
+    file: &mut File,
+    layout: &WavLayout,
+    data_start: u64,
+    available: u64,
+) -> io::Result<bool> {
+    if layout.declared > available {
+        return Ok(true);
+    }
+    let end = layout.declared + layout.declared % 2;
+    if end >= available {
+        return Ok(false);
+    }
+    let mut chunk = [0u8; 8];
+    file.seek(SeekFrom::Start(data_start + end))?;
+    if available - end < 8 || file.read_exact(&mut chunk).is_err() {
+        return Ok(true);
+    }
+    let [a, b, c, d, s0, s1, s2, s3] = chunk;
+    let id_is_text = [a, b, c, d]
+        .iter()
+        .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ');
+    let fits = u64::from(u32::from_le_bytes([s0, s1, s2, s3])) + 8 <= available - end;
+    Ok(layout.declared == 0 || !(id_is_text && fits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::bwf::BwfWriter;
+    use std::io::{BufWriter, Cursor};
+
+    /// A recording as a crash leaves it: samples on disk, header sizes
+    /// from the last update
+    fn crashed_recording(path: &Path, channels: u16, synced: &[f32], unsynced: &[f32]) {
+        let mut bytes = Cursor::new(Vec::new());
+        {
+            let mut writer = BwfWriter::new(&mut bytes, 48_000, channels, 16, None, None).unwrap();
+            writer.write_samples(synced).unwrap();
+            writer.flush().unwrap();
+            writer.write_samples(unsynced).unwrap();
+            std::mem::forget(writer);
+        }
+        std::fs::write(path, bytes.into_inner()).unwrap();
+    }
+
+    #[test]
+    fn test_repair_partial_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("take.wav");
+        crashed_recording(&path, 2, &[0.5; 4800], &[0.25; 9601]);
+        assert_eq!(hound::WavReader::open(&path).unwrap().duration(), 2400);
+
+        let recovered = recover_recordings(dir.path()).unwrap();
+        assert_eq!(
+            recovered,
+            [RecoveredRecording {
+                path: path.clone(),
+                sample_rate: 48_000,
+                channels: 2,
+                frames: 7200,
+            }]
+        );
+        assert_eq!(recovered[0].duration(), Duration::from_millis(150));
+        // The half frame at the end was cut off
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 7200);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 80 + 7200 * 4);
+
+        // Repaired files, finished files and other files are left alone
+        let finished = dir.path().join("finished.wav");
+        let file = BufWriter::new(File::create(&finished).unwrap());
+        let mut writer = BwfWriter::new(file, 48_000, 1, 24, None, None).unwrap();
+        writer.write_samples(&[0.1; 999]).unwrap();
+        drop(writer);
+        std::fs::write(dir.path().join("notes.wav"), b"not a recording").unwrap();
+        assert!(recover_recordings(dir.path()).unwrap().is_empty());
+        assert!(recover_recordings(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_repair_never_updated_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("take.wav");
+        crashed_recording(&path, 1, &[], &[0.5; 1001]);
+
+        let recovered = repair_wav(&path).unwrap().unwrap();
+        assert_eq!(recovered.frames, 1001);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 1001);
+        assert!(reader.samples::<i16>().all(|s| s.unwrap() == 16_384));
+        assert!(repair_wav(&path).unwrap().is_none());
+    }
+}
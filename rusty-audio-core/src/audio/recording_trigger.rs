@@ -0,0 +1,330 @@
+//! Level-triggered recording
+//!
+//! A [`TriggerDetector`] watches the input level and decides when a take
+//! should start and stop. A take starts once the level has stayed at or
+//! above the threshold for the attack time, and stops once it has stayed
+//! below the threshold less the hysteresis for the hold time. The gap
+//! between the two levels keeps a signal hovering around the threshold
+//! from starting and stopping takes over and over.
+//!
+//! Voice mode measures the input itself, in frames about as long as a
+//! syllable's onset, and needs a run of loud frames at least
+//! [`VOICE_MIN_ATTACK`] long to start; a click fills a frame or two and is
+//! ignored, while speech keeps going.
+
+use std::time::Duration;
+
+/// What starts and stops takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TriggerMode {
+    /// Takes start and stop only when asked
+    #[default]
+    Manual,
+    /// Takes follow the input's peak level
+    Threshold,
+    /// Takes follow the RMS level of [`VOICE_FRAME`]-long frames of input,
+    /// which ignores short clicks and tracks sustained sound such as speech
+    Voice,
+}
+
+impl TriggerMode {
+    /// Name shown in the UI
+    pub fn label(&self) -> &'static str {
+        match self {
+            TriggerMode::Manual => "Manual",
+            TriggerMode::Threshold => "Threshold",
+            TriggerMode::Voice => "Voice",
+        }
+    }
+}
+
+/// Length of the frames voice mode measures the input in
+pub const VOICE_FRAME: Duration = Duration::from_millis(20);
+
+/// Shortest attack voice mode uses, whatever the settings say: three frames,
+/// longer than a click but shorter than a syllable
+pub const VOICE_MIN_ATTACK: Duration = Duration::from_millis(60);
+
+/// Trigger settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerConfig {
+    /// What starts and stops takes
+    pub mode: TriggerMode,
+    /// Level that starts a take, in dBFS
+    pub threshold_db: f32,
+    /// How far below the threshold the level must fall to count as silence,
+    /// in dB
+    pub hysteresis_db: f32,
+    /// How long the level must stay at or above the threshold to start a
+    /// take, in milliseconds
+    pub attack_ms: u64,
+    /// How long silence must last to stop a take, in milliseconds
+    pub hold_ms: u64,
+    /// Audio from before the attack to start each triggered take with, in
+    /// milliseconds (0 = start from the attack)
+    pub pre_roll_ms: u64,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            mode: TriggerMode::Manual,
+            threshold_db: -40.0,
+            hysteresis_db: 6.0,
+            attack_ms: 50,
+            hold_ms: 1500,
+            pre_roll_ms: 500,
+        }
+    }
+}
+
+impl TriggerConfig {
+    /// Level a take's input must fall below to count as silence, in dBFS
+    pub fn release_db(&self) -> f32 {
+        self.threshold_db - self.hysteresis_db.max(0.0)
+    }
+
+    /// Audio from before the trigger fired that a triggered take starts
+    /// with: the attack plus the pre-roll
+    pub fn lead_in(&self) -> Duration {
+        Duration::from_millis(self.attack_ms.saturating_add(self.pre_roll_ms))
+    }
+}
+
+/// A trigger decision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerEvent {
+    /// The input rose above the threshold and a take started
+    Started {
+        /// Input level when the take started, in dBFS
+        level_db: f32,
+    },
+    /// The input fell silent and the take stopped
+    Stopped {
+        /// Input level when the take stopped, in dBFS
+        level_db: f32,
+    },
+}
+
+/// Decides when a level-triggered take starts and stops
+#[derive(Debug, Clone)]
+pub struct TriggerDetector {
+    config: TriggerConfig,
+    /// Whether a take is running
+    triggered: bool,
+    /// How long the level has been past the start or stop level
+    elapsed: Duration,
+    /// Summed squares of the voice frame being measured
+    frame_energy: f64,
+    /// Samples in the voice frame being measured
+    frame_samples: usize,
+}
+
+impl TriggerDetector {
+    /// Create a detector with no take running
+    pub fn new(config: TriggerConfig) -> Self {
+        Self {
+            config,
+            triggered: false,
+            elapsed: Duration::ZERO,
+            frame_energy: 0.0,
+            frame_samples: 0,
+        }
+    }
+
+    /// Trigger settings
+    pub fn config(&self) -> &TriggerConfig {
+        &self.config
+    }
+
+    /// Change the settings, keeping whether a take is running
+    pub fn set_config(&mut self, config: TriggerConfig) {
+        self.config = config;
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Whether the detector considers a take to be running
+    pub fn is_triggered(&self) -> bool {
+        self.triggered
+    }
+
+    /// Tell the detector whether a take is running, such as after one was
+    /// started or stopped by hand
+    pub fn set_triggered(&mut self, triggered: bool) {
+        if self.triggered != triggered {
+            self.triggered = triggered;
+            self.elapsed = Duration::ZERO;
+        }
+    }
+
+    /// Feed the level measured `elapsed` after the previous one
+    ///
+    /// Returns the decision, if the level has now been past the start or
+    /// stop level for long enough. Always `None` in manual mode.
+    pub fn update(&mut self, level_db: f32, elapsed: Duration) -> Option<TriggerEvent> {
+        if self.config.mode == TriggerMode::Manual {
+            return None;
+        }
+
+        let (past, needed) = if self.triggered {
+            (
+                level_db < self.config.release_db(),
+                Duration::from_millis(self.config.hold_ms),
+            )
+        } else {
+            let attack = Duration::from_millis(self.config.attack_ms);
+            (
+                level_db >= self.config.threshold_db,
+                if self.config.mode == TriggerMode::Voice {
+                    attack.max(VOICE_MIN_ATTACK)
+                } else {
+                    attack
+                },
+            )
+        };
+        if !past {
+            self.elapsed = Duration::ZERO;
+            return None;
+        }
+        self.elapsed += elapsed;
+        if self.elapsed < needed {
+            return None;
+        }
+
+        self.triggered = !self.triggered;
+        self.elapsed = Duration::ZERO;
+        Some(if self.triggered {
+            TriggerEvent::Started { level_db }
+        } else {
+            TriggerEvent::Stopped { level_db }
+        })
+    }
+
+    /// Feed interleaved input, measured in [`VOICE_FRAME`]s for voice mode
+    ///
+    /// Frames carry over between calls. Returns the first decision; the
+    /// rest of `samples` after it is not measured.
+    pub fn update_samples(
+        &mut self,
+        samples: &[f32],
+        channels: u16,
+        sample_rate: u32,
+    ) -> Option<TriggerEvent> {
+        let frame_frames = VOICE_FRAME.as_secs_f64() * f64::from(sample_rate);
+        let frame_len = (frame_frames as usize * usize::from(channels.max(1))).max(1);
+        for &sample in samples {
+            self.frame_energy += f64::from(sample) * f64::from(sample);
+            self.frame_samples += 1;
+            if self.frame_samples < frame_len {
+                continue;
+            }
+            let rms = (self.frame_energy / self.frame_samples as f64).sqrt() as f32;
+            self.frame_energy = 0.0;
+            self.frame_samples = 0;
+            let event = self.update(20.0 * rms.max(1e-6).log10(), VOICE_FRAME);
+            if event.is_some() {
+                return event;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(20);
+
+    fn detector() -> TriggerDetector {
+        TriggerDetector::new(TriggerConfig {
+            mode: TriggerMode::Threshold,
+            threshold_db: -30.0,
+            hysteresis_db: 10.0,
+            attack_ms: 40,
+            hold_ms: 100,
+            pre_roll_ms: 0,
+        })
+    }
+
+    #[test]
+    fn test_attack_and_hold() {
+        let mut detector = detector();
+        // A single loud frame isn't enough
+        assert_eq!(detector.update(-10.0, FRAME), None);
+        assert_eq!(detector.update(-50.0, FRAME), None);
+        assert_eq!(detector.update(-20.0, FRAME), None);
+        assert_eq!(
+            detector.update(-20.0, FRAME),
+            Some(TriggerEvent::Started { level_db: -20.0 })
+        );
+        assert!(detector.is_triggered());
+
+        // Silence has to last the whole hold time
+        for _ in 0..4 {
+            assert_eq!(detector.update(-60.0, FRAME), None);
+        }
+        assert_eq!(detector.update(-20.0, FRAME), None);
+        for _ in 0..4 {
+            assert_eq!(detector.update(-60.0, FRAME), None);
+        }
+        assert_eq!(
+            detector.update(-60.0, FRAME),
+            Some(TriggerEvent::Stopped { level_db: -60.0 })
+        );
+        assert!(!detector.is_triggered());
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut detector = detector();
+        detector.update(-25.0, FRAME);
+        assert!(detector.update(-25.0, FRAME).is_some());
+
+        // Hovering just under the threshold doesn't stop the take
+        for _ in 0..20 {
+            assert_eq!(detector.update(-35.0, FRAME), None);
+        }
+        assert!(detector.is_triggered());
+
+        // Nor does the level between threshold and release start one
+        detector.set_triggered(false);
+        for _ in 0..20 {
+            assert_eq!(detector.update(-35.0, FRAME), None);
+        }
+
+        // Manual mode never triggers
+        detector.set_config(TriggerConfig {
+            mode: TriggerMode::Manual,
+            ..*detector.config()
+        });
+        for _ in 0..20 {
+            assert_eq!(detector.update(0.0, FRAME), None);
+        }
+    }
+
+    #[test]
+    fn test_voice_ignores_clicks() {
+        let mut detector = TriggerDetector::new(TriggerConfig {
+            mode: TriggerMode::Voice,
+            attack_ms: 0,
+            ..detector().config
+        });
+        // 1 s of mono at 8 kHz: silence with a 5 ms full-scale click every
+        // 100 ms, straddling frame boundaries too
+        let mut clicks = vec![0.0f32; 8000];
+        for start in (0..8000).step_by(800).map(|start| start + 140) {
+            clicks[start..start + 40].fill(1.0);
+        }
+        assert_eq!(detector.update_samples(&clicks, 1, 8000), None);
+        assert!(!detector.is_triggered());
+
+        // A held vowel starts a take once it has lasted the minimum attack
+        let voice: Vec<f32> = (0..800).map(|i| (i as f32 * 0.2).sin() * 0.3).collect();
+        assert_eq!(detector.update_samples(&voice[..320], 1, 8000), None);
+        assert!(matches!(
+            detector.update_samples(&voice[320..], 1, 8000),
+            Some(TriggerEvent::Started { .. })
+        ));
+    }
+}
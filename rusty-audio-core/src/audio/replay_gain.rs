@@ -0,0 +1,261 @@
+//! ReplayGain and EBU R128 loudness normalization
+//!
+//! [`ReplayGain`] holds the gain and peak values read from a track's tags
+//! (`REPLAYGAIN_*`, or the Opus-style `R128_*` when those are missing), and
+//! [`ReplayGainConfig`] turns them into the linear playback gain for the
+//! selected [`ReplayGainMode`], with preamp and clipping prevention.
+//!
+//! All gains are relative to the ReplayGain 2.0 reference of -18 LUFS. R128
+//! tags are relative to -23 LUFS and are shifted by +5 dB when read.
+
+/// Track gain field, as named in Vorbis comments and APE tags; in dB, such
+/// as `-6.50 dB`
+pub const TRACK_GAIN_KEY: &str = "REPLAYGAIN_TRACK_GAIN";
+/// Track peak as a linear sample value, such as `0.988`
+pub const TRACK_PEAK_KEY: &str = "REPLAYGAIN_TRACK_PEAK";
+/// Album gain in dB
+pub const ALBUM_GAIN_KEY: &str = "REPLAYGAIN_ALBUM_GAIN";
+/// Album peak as a linear sample value
+pub const ALBUM_PEAK_KEY: &str = "REPLAYGAIN_ALBUM_PEAK";
+/// Opus track gain, in 1/256 dB relative to -23 LUFS
+pub const R128_TRACK_GAIN_KEY: &str = "R128_TRACK_GAIN";
+/// Opus album gain, in 1/256 dB relative to -23 LUFS
+pub const R128_ALBUM_GAIN_KEY: &str = "R128_ALBUM_GAIN";
+
+/// Offset from the R128 reference (-23 LUFS) to ReplayGain's (-18 LUFS)
+const R128_TO_REPLAY_GAIN_DB: f32 = 5.0;
+
+/// Loudness values stored in a track's tags
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReplayGain {
+    /// Gain in dB that brings the track to the reference loudness
+    pub track_gain_db: Option<f32>,
+    /// Sample peak of the track (1.0 = full scale)
+    pub track_peak: Option<f32>,
+    /// Gain in dB that brings the album to the reference loudness
+    pub album_gain_db: Option<f32>,
+    /// Sample peak of the whole album
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Read the values through `lookup`, which returns a tag field's text by
+    /// its name (one of the `*_KEY` constants)
+    ///
+    /// Unparseable fields are ignored.
+    pub fn from_tags<'a>(lookup: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let gain = |key: &str, r128_key: &str| {
+            lookup(key).and_then(parse_gain_db).or_else(|| {
+                lookup(r128_key)
+                    .and_then(parse_r128_gain)
+                    .map(|gain| gain + R128_TO_REPLAY_GAIN_DB)
+            })
+        };
+        Self {
+            track_gain_db: gain(TRACK_GAIN_KEY, R128_TRACK_GAIN_KEY),
+            track_peak: lookup(TRACK_PEAK_KEY).and_then(parse_peak),
+            album_gain_db: gain(ALBUM_GAIN_KEY, R128_ALBUM_GAIN_KEY),
+            album_peak: lookup(ALBUM_PEAK_KEY).and_then(parse_peak),
+        }
+    }
+
+    /// Whether the track carries any gain value
+    pub fn is_tagged(&self) -> bool {
+        self.track_gain_db.is_some() || self.album_gain_db.is_some()
+    }
+}
+
+/// Parse a ReplayGain gain such as `"-6.54 dB"`
+pub fn parse_gain_db(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let number = text
+        .strip_suffix("dB")
+        .or_else(|| text.strip_suffix("db"))
+        .unwrap_or(text);
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|gain| gain.is_finite())
+}
+
+/// Parse an R128 gain: a Q7.8 fixed-point integer in dB relative to -23 LUFS
+pub fn parse_r128_gain(text: &str) -> Option<f32> {
+    text.trim()
+        .parse::<i16>()
+        .ok()
+        .map(|gain| f32::from(gain) / 256.0)
+}
+
+fn parse_peak(text: &str) -> Option<f32> {
+    text.trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|peak| peak.is_finite() && *peak > 0.0)
+}
+
+/// Which gain to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayGainMode {
+    /// Play at the level on the file
+    #[default]
+    Off,
+    /// Make every track equally loud
+    Track,
+    /// Keep the level differences between tracks of an album
+    Album,
+    /// Album gain while playing an album in order, track gain otherwise
+    Auto,
+}
+
+impl ReplayGainMode {
+    /// All modes, for selection lists
+    pub const ALL: [ReplayGainMode; 4] = [
+        ReplayGainMode::Off,
+        ReplayGainMode::Track,
+        ReplayGainMode::Album,
+        ReplayGainMode::Auto,
+    ];
+
+    /// Display name
+    pub fn name(self) -> &'static str {
+        match self {
+            ReplayGainMode::Off => "Off",
+            ReplayGainMode::Track => "Track",
+            ReplayGainMode::Album => "Album",
+            ReplayGainMode::Auto => "Auto",
+        }
+    }
+}
+
+/// User ReplayGain preferences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayGainConfig {
+    /// Which gain to apply, if any
+    pub mode: ReplayGainMode,
+    /// Extra gain in dB applied to tagged tracks
+    pub preamp_db: f32,
+    /// Lower the gain where the tagged peak would otherwise clip
+    pub prevent_clipping: bool,
+}
+
+impl Default for ReplayGainConfig {
+    fn default() -> Self {
+        Self {
+            mode: ReplayGainMode::default(),
+            preamp_db: 0.0,
+            prevent_clipping: true,
+        }
+    }
+}
+
+impl ReplayGainConfig {
+    /// Linear gain to play a track with the given tags at
+    ///
+    /// `album_order` tells [`ReplayGainMode::Auto`] whether the track is
+    /// being played as part of its album. Track and album mode fall back to
+    /// the other value when theirs is missing; untagged tracks play at unity
+    /// gain.
+    pub fn gain(&self, tags: &ReplayGain, album_order: bool) -> f32 {
+        let track = tags.track_gain_db.map(|gain| (gain, tags.track_peak));
+        let album = tags.album_gain_db.map(|gain| (gain, tags.album_peak));
+        let use_album = match self.mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Track => false,
+            ReplayGainMode::Album => true,
+            ReplayGainMode::Auto => album_order,
+        };
+        let chosen = if use_album {
+            album.or(track)
+        } else {
+            track.or(album)
+        };
+        let Some((gain_db, peak)) = chosen else {
+            return 1.0;
+        };
+
+        let gain = db_to_linear(gain_db + self.preamp_db);
+        match peak {
+            Some(peak) if self.prevent_clipping => gain.min(1.0 / peak),
+            _ => gain,
+        }
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn tags(fields: &[(&'static str, &'static str)]) -> ReplayGain {
+        let fields: HashMap<&str, &str> = fields.iter().copied().collect();
+        ReplayGain::from_tags(|key| fields.get(key).copied())
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let rg = tags(&[
+            (TRACK_GAIN_KEY, "-6.54 dB"),
+            (TRACK_PEAK_KEY, "0.988312"),
+            (ALBUM_GAIN_KEY, "+1.5dB"),
+            (ALBUM_PEAK_KEY, "garbage"),
+        ]);
+        assert_eq!(rg.track_gain_db, Some(-6.54));
+        assert_eq!(rg.track_peak, Some(0.988312));
+        assert_eq!(rg.album_gain_db, Some(1.5));
+        assert_eq!(rg.album_peak, None);
+
+        // R128 is Q7.8 relative to -23 LUFS, and only used as a fallback
+        let opus = tags(&[(R128_TRACK_GAIN_KEY, "-512"), (R128_ALBUM_GAIN_KEY, "256")]);
+        assert_eq!(opus.track_gain_db, Some(3.0));
+        assert_eq!(opus.album_gain_db, Some(6.0));
+        let both = tags(&[(TRACK_GAIN_KEY, "-1 dB"), (R128_TRACK_GAIN_KEY, "0")]);
+        assert_eq!(both.track_gain_db, Some(-1.0));
+
+        assert!(!tags(&[]).is_tagged());
+    }
+
+    #[test]
+    fn test_gain_modes() {
+        let rg = ReplayGain {
+            track_gain_db: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain_db: Some(-3.0),
+            album_peak: Some(0.9),
+        };
+        let mut config = ReplayGainConfig::default();
+        assert_eq!(config.gain(&rg, true), 1.0);
+
+        config.mode = ReplayGainMode::Track;
+        let track_gain = config.gain(&rg, true);
+        assert!((track_gain - 0.501).abs() < 0.001);
+        config.mode = ReplayGainMode::Album;
+        let album_gain = config.gain(&rg, false);
+        assert!((album_gain - 0.708).abs() < 0.001);
+        config.mode = ReplayGainMode::Auto;
+        assert_eq!(config.gain(&rg, true), album_gain);
+        assert_eq!(config.gain(&rg, false), track_gain);
+
+        // +12 dB would take a 0.5 peak to 2.0; clipping prevention caps the
+        // gain so the peak stays at full scale
+        config.mode = ReplayGainMode::Track;
+        config.preamp_db = 18.0;
+        assert!((config.gain(&rg, false) - 2.0).abs() < 1e-6);
+        config.prevent_clipping = false;
+        assert!(config.gain(&rg, false) > 3.9);
+
+        // Missing values fall back to the other gain, or unity
+        let track_only = ReplayGain {
+            track_gain_db: Some(0.0),
+            ..Default::default()
+        };
+        config.mode = ReplayGainMode::Album;
+        config.preamp_db = 0.0;
+        assert_eq!(config.gain(&track_only, true), 1.0);
+        assert_eq!(config.gain(&ReplayGain::default(), true), 1.0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Streaming sample-rate conversion for router routes
+//!
+//! This module wraps rubato's fixed-output resamplers so that a route whose
+//! source and destination run at different sample rates can still deliver
+//! exactly one router buffer per processing cycle.
+//!
+//! The resampler keeps a small interleaved input FIFO. The router asks how
+//! many more input samples are needed for the next output chunk, pushes them
+//! in, and then pulls one chunk of interleaved output.
+
+use super::backend::{AudioBackendError, Result};
+use rubato::{
+    FastFixedOut, PolynomialDegree, Resampler, SincFixedOut, SincInterpolationParameters,
+    SincInterpolationType, WindowFunction,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Maximum number of output chunks worth of input kept in the FIFO
+///
+/// Only reached when a source fans out to destinations running at different
+/// rates; the oldest samples are dropped to keep latency bounded.
+const MAX_BUFFERED_CHUNKS: usize = 4;
+
+/// Quality level for route sample-rate conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ResamplerQuality {
+    /// Cubic polynomial interpolation (lowest CPU, lowest latency)
+    Fast,
+    /// 64-tap windowed sinc interpolation
+    #[default]
+    Balanced,
+    /// 256-tap windowed sinc interpolation (best stopband rejection)
+    High,
+}
+
+impl ResamplerQuality {
+    /// All quality levels, in ascending order of cost
+    pub const ALL: [ResamplerQuality; 3] = [
+        ResamplerQuality::Fast,
+        ResamplerQuality::Balanced,
+        ResamplerQuality::High,
+    ];
+
+    /// Human-readable name for UI display
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResamplerQuality::Fast => "Fast",
+            ResamplerQuality::Balanced => "Balanced",
+            ResamplerQuality::High => "High",
+        }
+    }
+
+    fn sinc_parameters(&self) -> SincInterpolationParameters {
+        let (sinc_len, oversampling_factor, window) = match self {
+            ResamplerQuality::High => (256, 256, WindowFunction::BlackmanHarris2),
+            _ => (64, 128, WindowFunction::Blackman2),
+        };
+
+        SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: rubato::calculate_cutoff(sinc_len, window),
+            oversampling_factor,
+            interpolation: SincInterpolationType::Cubic,
+            window,
+        }
+    }
+}
+
+/// Concrete rubato engine behind a route resampler
+///
+/// `rubato::Resampler` has generic methods and cannot be boxed, so the two
+/// engine types are dispatched through this enum instead.
+enum Engine {
+    Fast(FastFixedOut<f32>),
+    Sinc(SincFixedOut<f32>),
+}
+
+macro_rules! with_engine {
+    ($engine:expr, $r:ident => $body:expr) => {
+        match $engine {
+            Engine::Fast($r) => $body,
+            Engine::Sinc($r) => $body,
+        }
+    };
+}
+
+/// Streaming resampler for a single route
+///
+/// Converts interleaved audio from `input_rate` to `output_rate`, producing
+/// a fixed number of output frames per call to [`RouteResampler::process`].
+pub struct RouteResampler {
+    engine: Engine,
+    quality: ResamplerQuality,
+    input_rate: u32,
+    output_rate: u32,
+    channels: usize,
+    chunk_frames: usize,
+    input_fifo: Vec<f32>,
+    planar_in: Vec<Vec<f32>>,
+    planar_out: Vec<Vec<f32>>,
+    /// Chunks the engine failed to produce, counted on the audio thread
+    failures: Arc<AtomicU64>,
+}
+
+impl RouteResampler {
+    /// Create a new route resampler
+    ///
+    /// # Arguments
+    /// * `input_rate` - Sample rate of the route's source
+    /// * `output_rate` - Sample rate of the route's destination
+    /// * `channels` - Number of interleaved channels
+    /// * `chunk_frames` - Number of output frames produced per `process` call
+    /// * `quality` - Interpolation quality
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if the rates or channel count are invalid.
+    pub fn new(
+        input_rate: u32,
+        output_rate: u32,
+        channels: u16,
+        chunk_frames: usize,
+        quality: ResamplerQuality,
+    ) -> Result<Self> {
+        if input_rate == 0 || output_rate == 0 || channels == 0 || chunk_frames == 0 {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "Cannot resample {} Hz -> {} Hz with {} channel(s) and {} frame chunks",
+                input_rate, output_rate, channels, chunk_frames
+            )));
+        }
+
+        let channels = channels as usize;
+        let ratio = output_rate as f64 / input_rate as f64;
+        let map_err = |e: rubato::ResamplerConstructionError| {
+            AudioBackendError::UnsupportedFormat(format!(
+                "Cannot resample {} Hz -> {} Hz: {}",
+                input_rate, output_rate, e
+            ))
+        };
+
+        let engine = match quality {
+            ResamplerQuality::Fast => Engine::Fast(
+                FastFixedOut::<f32>::new(
+                    ratio,
+                    1.0,
+                    PolynomialDegree::Cubic,
+                    chunk_frames,
+                    channels,
+                )
+                .map_err(map_err)?,
+            ),
+            ResamplerQuality::Balanced | ResamplerQuality::High => Engine::Sinc(
+                SincFixedOut::<f32>::new(
+                    ratio,
+                    1.0,
+                    quality.sinc_parameters(),
+                    chunk_frames,
+                    channels,
+                )
+                .map_err(map_err)?,
+            ),
+        };
+
+        let planar_in = with_engine!(&engine, r => r.input_buffer_allocate(true));
+        let planar_out = with_engine!(&engine, r => r.output_buffer_allocate(true));
+        let input_capacity =
+            with_engine!(&engine, r => r.input_frames_max()) * channels * MAX_BUFFERED_CHUNKS;
+
+        Ok(Self {
+            engine,
+            quality,
+            input_rate,
+            output_rate,
+            channels,
+            chunk_frames,
+            input_fifo: Vec::with_capacity(input_capacity),
+            planar_in,
+            planar_out,
+            failures: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Get the configured quality level
+    pub fn quality(&self) -> ResamplerQuality {
+        self.quality
+    }
+
+    /// Get the input (source) sample rate
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    /// Get the output (destination) sample rate
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Count failed chunks in `counter` instead of a private counter
+    ///
+    /// Lets a router share one counter between all of its routes and report
+    /// failures from the control thread.
+    pub fn set_failure_counter(&mut self, counter: Arc<AtomicU64>) {
+        self.failures = counter;
+    }
+
+    /// Number of chunks the resampler failed to produce
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of interleaved input samples still required before the next
+    /// output chunk can be produced
+    pub fn input_samples_needed(&self) -> usize {
+        let needed = with_engine!(&self.engine, r => r.input_frames_next()) * self.channels;
+        needed.saturating_sub(self.input_fifo.len())
+    }
+
+    /// Largest number of interleaved input samples a single output chunk
+    /// can ask for
+    pub fn max_input_samples(&self) -> usize {
+        with_engine!(&self.engine, r => r.input_frames_max()) * self.channels
+    }
+
+    /// Append interleaved input samples to the FIFO
+    ///
+    /// The FIFO never grows past its preallocated capacity, so this does not
+    /// allocate; the oldest whole frames are dropped instead.
+    pub fn push_input(&mut self, samples: &[f32]) {
+        let limit = self.max_input_samples() * MAX_BUFFERED_CHUNKS;
+
+        let skip = samples.len().saturating_sub(limit).div_ceil(self.channels) * self.channels;
+        let samples = samples.get(skip..).unwrap_or_default();
+
+        let overflow = (self.input_fifo.len() + samples.len()).saturating_sub(limit);
+        if overflow > 0 {
+            // Keep whole frames so channels stay aligned
+            let overflow = overflow.div_ceil(self.channels) * self.channels;
+            self.input_fifo.drain(..overflow.min(self.input_fifo.len()));
+        }
+
+        self.input_fifo.extend_from_slice(samples);
+    }
+
+    /// Produce one chunk of interleaved output
+    ///
+    /// Writes at most `chunk_frames * channels` samples into `output` and
+    /// returns the number of samples written. Returns 0 if not enough input
+    /// has been buffered yet, or if the engine failed; failures are counted
+    /// (see [`RouteResampler::failures`]) rather than logged, since this runs
+    /// on the audio thread.
+    pub fn process(&mut self, output: &mut [f32]) -> usize {
+        let frames_in = with_engine!(&self.engine, r => r.input_frames_next());
+        let Some(input) = self.input_fifo.get(..frames_in * self.channels) else {
+            return 0;
+        };
+
+        for (ch, planar) in self.planar_in.iter_mut().enumerate() {
+            planar.clear();
+            planar.extend(input.iter().skip(ch).step_by(self.channels));
+        }
+
+        let result = with_engine!(&mut self.engine, r => {
+            r.process_into_buffer(&self.planar_in, &mut self.planar_out, None)
+        });
+        let (consumed, produced) = match result {
+            Ok(counts) => counts,
+            Err(_) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                return 0;
+            }
+        };
+
+        self.input_fifo.drain(..consumed * self.channels);
+
+        let frames = produced.min(output.len() / self.channels);
+        for (frame, out) in output
+            .chunks_exact_mut(self.channels)
+            .take(frames)
+            .enumerate()
+        {
+            for (sample, planar) in out.iter_mut().zip(&self.planar_out) {
+                *sample = planar.get(frame).copied().unwrap_or(0.0);
+            }
+        }
+
+        frames * self.channels
+    }
+
+    /// Number of output frames produced per call to `process`
+    pub fn chunk_frames(&self) -> usize {
+        self.chunk_frames
+    }
+
+    /// Latency added by the resampler, in output frames
+    pub fn latency_frames(&self) -> usize {
+        with_engine!(&self.engine, r => r.output_delay())
+    }
+
+    /// Latency added by the resampler, in milliseconds
+    pub fn latency_ms(&self) -> f32 {
+        self.latency_frames() as f32 / self.output_rate as f32 * 1000.0
+    }
+
+    /// Clear all buffered input and internal filter state
+    pub fn reset(&mut self) {
+        with_engine!(&mut self.engine, r => r.reset());
+        self.input_fifo.clear();
+    }
+}
+
+impl std::fmt::Debug for RouteResampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouteResampler")
+            .field("quality", &self.quality)
+            .field("input_rate", &self.input_rate)
+            .field("output_rate", &self.output_rate)
+            .field("channels", &self.channels)
+            .field("chunk_frames", &self.chunk_frames)
+            .field("buffered_samples", &self.input_fifo.len())
+            .field("failures", &self.failures())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_resampler_produces_fixed_chunks() {
+        for quality in ResamplerQuality::ALL {
+            let mut resampler = RouteResampler::new(44100, 48000, 1, 256, quality).unwrap();
+            let input = sine(1000.0, 44100, 44100);
+            let mut offset = 0;
+            let mut output = vec![0.0; 256];
+
+            for _ in 0..10 {
+                let needed = resampler.input_samples_needed();
+                resampler.push_input(&input[offset..offset + needed]);
+                offset += needed;
+                assert_eq!(resampler.process(&mut output), 256);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resampler_rate_ratio() {
+        let mut resampler =
+            RouteResampler::new(48000, 44100, 2, 512, ResamplerQuality::Fast).unwrap();
+        let mut consumed = 0;
+        let mut output = vec![0.0; 1024];
+
+        for _ in 0..100 {
+            let needed = resampler.input_samples_needed();
+            resampler.push_input(&vec![0.0; needed]);
+            consumed += needed;
+            resampler.process(&mut output);
+        }
+
+        // 100 chunks of 512 output frames at 44.1 kHz ≈ 55,728 input frames at 48 kHz
+        let frames_in = consumed / 2;
+        let expected = 100.0 * 512.0 * 48000.0 / 44100.0;
+        assert!((frames_in as f64 - expected).abs() < 600.0);
+    }
+
+    #[test]
+    fn test_resampler_latency() {
+        let fast = RouteResampler::new(44100, 48000, 1, 256, ResamplerQuality::Fast).unwrap();
+        let high = RouteResampler::new(44100, 48000, 1, 256, ResamplerQuality::High).unwrap();
+
+        assert!(high.latency_frames() > fast.latency_frames());
+        assert!(high.latency_ms() > 0.0);
+    }
+
+    #[test]
+    fn test_resampler_invalid_rates() {
+        assert!(RouteResampler::new(0, 48000, 2, 256, ResamplerQuality::Fast).is_err());
+        assert!(RouteResampler::new(44100, 48000, 0, 256, ResamplerQuality::Fast).is_err());
+    }
+}
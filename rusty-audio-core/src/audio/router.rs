@@ -48,24 +48,107 @@
 //! // Process audio
 //! router.process();
 //! ```
+//!
+//! # Sample-rate conversion
+//!
+//! When a route connects a source and a destination with different sample
+//! rates, the router inserts a streaming [`RouteResampler`] automatically.
+//! Each destination always receives `buffer_size` samples per cycle; the
+//! source is read for however many samples its most demanding route needs.
+//! The quality can be changed per route with
+//! [`AudioRouter::set_route_resampler_quality`], and the added delay is
+//! reported by [`AudioRouter::route_latency_frames`].
+//!
+//! # Channel mapping
+//!
+//! Every route carries a [`ChannelMatrix`] that maps the source's channels
+//! onto the destination's. A default is chosen from the two channel counts
+//! (mono→stereo, stereo→mono, 5.1→stereo, or one-to-one), and it can be
+//! replaced with [`AudioRouter::set_route_channel_matrix`].
+//!
+//! # Buses
+//!
+//! A [`Bus`] added with [`AudioRouter::add_bus`] is a destination and a
+//! source at once, so submixes can be chained (e.g. drums → drum bus →
+//! master bus → device). Each cycle the router mixes routes in topological
+//! order: every route into a bus runs before the bus, and every route out of
+//! it runs after. Routes that would feed a bus back into itself are rejected
+//! with [`AudioBackendError::RoutingCycle`].
+//!
+//! # Gain smoothing
+//!
+//! Route and bus gain changes, mutes and route removals ramp linearly over
+//! [`AudioRouter::gain_ramp`] (10 ms by default) instead of jumping, so
+//! dragging a fader or muting a route does not click. A removed route keeps
+//! playing until it has faded out. [`AudioRouter::crossfade_routes`] fades
+//! one route out and another in, starting on the same sample.
+//!
+//! # Insert effects
+//!
+//! A [`ProcessorChain`] of [`AudioProcessor`]s can be inserted on any route
+//! (after channel mapping, before the route gain) or in front of any
+//! destination (after mixing, before soft clipping) with
+//! [`AudioRouter::set_processor_chain`]. Each insert can be bypassed or
+//! blended wet/dry while audio is running.
+//!
+//! # Sessions
+//!
+//! The graph can be saved as a
+//! [`RouterSession`](super::session::RouterSession) and instantiated into a
+//! new router later. Sources and destinations describe themselves through
+//! [`AudioSource::node_kind`] and [`AudioDestination::node_kind`].
+//!
+//! # Real-time processing
+//!
+//! Graph edits never touch the audio thread directly. `AudioRouter` keeps a
+//! shadow copy of the graph, builds every node, buffer and resampler up
+//! front, and sends them to the [`RouterProcessor`] over a lock-free command
+//! queue together with a compiled execution plan. Processing a buffer takes
+//! no locks and performs no heap allocation; memory the processor releases
+//! is handed back and freed on the next graph edit.
+//!
+//! ```rust,no_run
+//! # use rusty_audio_core::audio::router::AudioRouter;
+//! let router = AudioRouter::new(512);
+//! let mut processor = router.take_processor().unwrap();
+//!
+//! // Inside the audio callback:
+//! processor.process().unwrap();
+//! ```
 
-use super::backend::{AudioBackendError, AudioConfig, Result};
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use super::backend::{AudioBackendError, Result};
+use super::channel_matrix::ChannelMatrix;
+use super::effects::{AudioProcessor, InsertInfo, InsertPoint, ProcessorChain};
+use super::resampler::{ResamplerQuality, RouteResampler};
+use super::router_processor::{
+    BusNode, ChainTarget, Conversion, DestNode, ExecutionPlan, GainChange, Garbage, PlanStep,
+    RouteNode, RouterCommand, RouterProcessor, SlotCapacity, Slots, SourceNode,
+};
+use super::session::NodeKind;
+use parking_lot::{Mutex, MutexGuard};
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Unique identifier for audio sources
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SourceId(u64);
 
 /// Unique identifier for audio destinations
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct DestId(u64);
 
 /// Unique identifier for routes
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct RouteId(pub u64); // Make field public for construction
 
+/// Unique identifier for mix buses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BusId(u64);
+
 /// Audio source trait
 ///
 /// Implementors provide audio samples that can be routed to destinations.
@@ -106,6 +189,16 @@ pub trait AudioSource: Send {
     fn length(&self) -> Option<u64> {
         None
     }
+
+    /// Describe this source for session files
+    ///
+    /// Device-backed sources should return [`NodeKind::Device`] so a saved
+    /// session can find the device again by name; other sources return a
+    /// stable [`NodeKind::Other`] name. Sources that don't are saved as
+    /// [`NodeKind::Unknown`].
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Unknown
+    }
 }
 
 /// Audio destination trait
@@ -131,6 +224,16 @@ pub trait AudioDestination: Send {
     fn flush(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// Describe this destination for session files
+    ///
+    /// Device-backed destinations should return [`NodeKind::Device`] so a
+    /// saved session can find the device again by name; other destinations
+    /// return a stable [`NodeKind::Other`] name. Destinations that don't are
+    /// saved as [`NodeKind::Unknown`].
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Unknown
+    }
 }
 
 /// A route connecting a source to a destination
@@ -142,6 +245,10 @@ pub struct Route {
     pub gain: f32,
     pub enabled: bool,
     pub muted: bool,
+    /// Quality used if this route needs sample-rate conversion
+    pub resampler_quality: ResamplerQuality,
+    /// Mapping from source channels to destination channels
+    pub channel_matrix: ChannelMatrix,
 }
 
 impl Route {
@@ -155,26 +262,622 @@ impl Route {
     }
 }
 
-/// Audio router state
-struct RouterState {
-    sources: HashMap<SourceId, Box<dyn AudioSource>>,
-    destinations: HashMap<DestId, Box<dyn AudioDestination>>,
-    routes: HashMap<RouteId, Route>,
+/// A mix bus
+///
+/// A bus is a destination and a source at once: routes feed its `input`,
+/// the mix passes through the bus inserts and fader, and further routes
+/// read it from its `output`.
+#[derive(Debug, Clone)]
+pub struct Bus {
+    /// Unique bus identifier
+    pub id: BusId,
+    /// Display name
+    pub name: String,
+    /// Destination that routes into the bus target
+    pub input: DestId,
+    /// Source that routes out of the bus read from
+    pub output: SourceId,
+    /// Sample rate the bus mixes at
+    pub sample_rate: u32,
+    /// Number of channels in the mix
+    pub channels: u16,
+    /// Fader gain (linear, 0.0 and up)
+    pub gain: f32,
+    /// Whether the bus output is silenced
+    pub muted: bool,
+}
+
+impl Bus {
+    /// Get effective gain (0.0 if muted, otherwise gain)
+    pub fn effective_gain(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.gain
+        }
+    }
+}
+
+/// What the router knows about a source or destination
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeInfo {
+    /// What the node is, as saved in sessions
+    pub kind: NodeKind,
+    /// Sample rate of the node
+    pub sample_rate: u32,
+    /// Number of channels of the node
+    pub channels: u16,
+    /// Total length in samples, for sources that report one
+    pub length: Option<u64>,
+    /// Bus this node is the input or output of
+    pub bus: Option<BusId>,
+}
+
+/// Initial slot capacity for sources and destinations
+const INITIAL_NODE_SLOTS: usize = 16;
+
+/// Initial slot capacity for buses
+const INITIAL_BUS_SLOTS: usize = 8;
+
+/// Initial slot capacity for routes
+const INITIAL_ROUTE_SLOTS: usize = 64;
+
+/// Capacity of the command and garbage queues between the two threads
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Default ramp time for gain changes
+pub const DEFAULT_GAIN_RAMP: Duration = Duration::from_millis(10);
+
+/// Control-side record of a source
+struct SourceInfo {
+    slot: usize,
+    kind: NodeKind,
+    sample_rate: u32,
+    channels: u16,
+    length: Option<u64>,
+    /// Length of the read buffer the processor currently holds
+    buffer_len: usize,
+    /// Bus this source is the output of
+    bus: Option<BusId>,
+}
+
+/// Control-side record of a destination
+struct DestInfo {
+    slot: usize,
+    kind: NodeKind,
+    sample_rate: u32,
+    channels: u16,
+    inserts: Vec<InsertInfo>,
+    /// Bus this destination is the input of
+    bus: Option<BusId>,
+}
+
+/// Control-side record of a route
+struct RouteInfo {
+    route: Route,
+    slot: usize,
+    /// Generation of `slot` when the route was given it
+    generation: u64,
+    /// Largest number of source samples the route can ask for in one cycle
+    max_demand: usize,
+    /// Resampler latency as (frames, milliseconds), if the route converts rates
+    latency: Option<(usize, f32)>,
+    inserts: Vec<InsertInfo>,
+}
+
+/// Control-side record of a bus
+struct BusInfo {
+    bus: Bus,
+    slot: usize,
+    /// Peak level published by the processor, as `f32` bits
+    peak: Arc<AtomicU32>,
+}
+
+/// Hands out processor slot indices, reusing released ones first
+#[derive(Default)]
+struct SlotAllocator {
+    free: Vec<usize>,
+    next: usize,
+    /// Times each slot has been released, so messages about an earlier
+    /// occupant of a reused slot can be told apart
+    generations: Vec<u64>,
+}
+
+impl SlotAllocator {
+    fn allocate(&mut self) -> usize {
+        self.free.pop().unwrap_or_else(|| {
+            self.next += 1;
+            self.generations.push(0);
+            self.next - 1
+        })
+    }
+
+    fn release(&mut self, slot: usize) {
+        if let Some(generation) = self.generations.get_mut(slot) {
+            *generation += 1;
+        }
+        self.free.push(slot);
+    }
+
+    /// Current generation of an allocated slot
+    fn generation(&self, slot: usize) -> u64 {
+        self.generations.get(slot).copied().unwrap_or(0)
+    }
+}
+
+/// Control-side router state
+///
+/// Mirrors the graph owned by the [`RouterProcessor`] so that queries never
+/// touch the audio thread, and turns every edit into [`RouterCommand`]s.
+struct RouterControl {
+    sources: HashMap<SourceId, SourceInfo>,
+    destinations: HashMap<DestId, DestInfo>,
+    routes: HashMap<RouteId, RouteInfo>,
+    /// Removed routes still fading out, by slot
+    retiring: HashMap<usize, RouteInfo>,
+    buses: HashMap<BusId, BusInfo>,
+    source_slots: SlotAllocator,
+    dest_slots: SlotAllocator,
+    route_slots: SlotAllocator,
+    bus_slots: SlotAllocator,
+    /// Slot capacity of the processor
+    capacity: SlotCapacity,
+    commands: Producer<RouterCommand>,
+    /// Commands that did not fit in the queue yet, in order
+    pending: VecDeque<RouterCommand>,
+    garbage: Consumer<Garbage>,
+    /// Set by the processor when edits or retired routes waited for room in
+    /// the garbage queue
+    garbage_stalled: Arc<AtomicBool>,
+    /// Failed resampler chunks across all routes, counted by the processor
+    resampler_failures: Arc<AtomicU64>,
+    /// Value of `resampler_failures` when it was last logged
+    reported_resampler_failures: u64,
+    buffer_size: usize,
+    /// Ramp time for gain changes
+    gain_ramp: Duration,
     next_source_id: u64,
     next_dest_id: u64,
     next_route_id: u64,
+    next_bus_id: u64,
 }
 
-impl RouterState {
-    fn new() -> Self {
-        Self {
-            sources: HashMap::new(),
-            destinations: HashMap::new(),
-            routes: HashMap::new(),
-            next_source_id: 1,
-            next_dest_id: 1,
-            next_route_id: 1,
+impl RouterControl {
+    /// Queue a command for the processor
+    fn send(&mut self, command: RouterCommand) {
+        self.pending.push_back(command);
+        self.flush();
+    }
+
+    /// Move as many pending commands into the queue as will fit
+    fn flush(&mut self) {
+        while let Some(command) = self.pending.pop_front() {
+            if let Err(rtrb::PushError::Full(command)) = self.commands.push(command) {
+                self.pending.push_front(command);
+                break;
+            }
+        }
+    }
+
+    /// Drop everything the processor has released
+    ///
+    /// Routes that finished fading out free their slot and leave the plan,
+    /// unless the slot has been released and reused since they were retired.
+    fn collect_garbage(&mut self) {
+        if self.garbage_stalled.swap(false, Ordering::Relaxed) {
+            log::warn!("Router garbage queue filled up; edits were held back until now");
+        }
+        let failures = self.resampler_failures.load(Ordering::Relaxed);
+        if failures > self.reported_resampler_failures {
+            log::warn!(
+                "Route resamplers failed to produce {} chunk(s)",
+                failures - self.reported_resampler_failures
+            );
+            self.reported_resampler_failures = failures;
+        }
+        let mut retired = false;
+        while let Ok(item) = self.garbage.pop() {
+            if let Garbage::RetiredRoute(slot, node) = &item {
+                let current = self
+                    .retiring
+                    .get(slot)
+                    .is_some_and(|info| info.generation == node.generation);
+                if current {
+                    self.retiring.remove(slot);
+                    self.route_slots.release(*slot);
+                    retired = true;
+                }
+            }
+            drop(item);
+        }
+        if retired {
+            self.compile();
+        }
+    }
+
+    /// Grow the processor's slot storage if a new slot index exceeds it
+    fn ensure_capacity(&mut self) {
+        let grow = |current: usize, needed: usize| {
+            if needed > current {
+                (current * 2).max(needed)
+            } else {
+                current
+            }
+        };
+        let capacity = SlotCapacity {
+            sources: grow(self.capacity.sources, self.source_slots.next),
+            destinations: grow(self.capacity.destinations, self.dest_slots.next),
+            routes: grow(self.capacity.routes, self.route_slots.next),
+            buses: grow(self.capacity.buses, self.bus_slots.next),
+        };
+        if capacity == self.capacity {
+            return;
+        }
+
+        self.capacity = capacity;
+        self.send(RouterCommand::GrowSlots(Box::new(Slots::with_capacity(
+            capacity,
+        ))));
+    }
+
+    /// Remove a route and free its slot
+    fn remove_route(&mut self, id: RouteId) -> bool {
+        match self.routes.remove(&id) {
+            Some(info) => {
+                self.send(RouterCommand::RemoveRoute(info.slot));
+                self.route_slots.release(info.slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a route, fading it out first if it is audible
+    ///
+    /// A fading route keeps its slot until the processor hands it back.
+    fn retire_route(&mut self, id: RouteId) -> bool {
+        let Some(info) = self.routes.get(&id) else {
+            return false;
+        };
+        let ramp_frames = self.ramp_frames(info.route.destination, self.gain_ramp);
+        if ramp_frames == 0 || info.route.effective_gain() == 0.0 {
+            return self.remove_route(id);
+        }
+
+        let Some(info) = self.routes.remove(&id) else {
+            return false;
+        };
+        self.send(RouterCommand::RetireRoute(GainChange {
+            slot: info.slot,
+            gain: 0.0,
+            ramp_frames,
+        }));
+        self.retiring.insert(info.slot, info);
+        true
+    }
+
+    /// Remove every route, live or fading, that matches `filter`
+    fn remove_routes_where(&mut self, filter: impl Fn(&Route) -> bool) {
+        let routes: Vec<RouteId> = self
+            .routes
+            .values()
+            .filter(|info| filter(&info.route))
+            .map(|info| info.route.id)
+            .collect();
+        for route in routes {
+            self.remove_route(route);
+        }
+
+        let retiring: Vec<usize> = self
+            .retiring
+            .iter()
+            .filter(|(_, info)| filter(&info.route))
+            .map(|(&slot, _)| slot)
+            .collect();
+        for slot in retiring {
+            self.retiring.remove(&slot);
+            self.send(RouterCommand::RemoveRoute(slot));
+            self.route_slots.release(slot);
+        }
+    }
+
+    /// Live and fading routes; both still run on the processor
+    fn active_routes(&self) -> impl Iterator<Item = &RouteInfo> {
+        self.routes.values().chain(self.retiring.values())
+    }
+
+    /// Convert a ramp time to frames at a destination's sample rate
+    fn ramp_frames(&self, destination: DestId, ramp: Duration) -> usize {
+        let sample_rate = self
+            .destinations
+            .get(&destination)
+            .map_or(0, |info| info.sample_rate);
+        duration_to_frames(ramp, sample_rate)
+    }
+
+    /// Build the gain change that moves a route to its current effective gain
+    fn route_gain_change(&self, id: RouteId, ramp: Duration) -> Result<GainChange> {
+        let info = self.routes.get(&id).ok_or_else(|| {
+            AudioBackendError::DeviceNotFound(format!("Route {:?} not found", id))
+        })?;
+        Ok(GainChange {
+            slot: info.slot,
+            gain: info.route.effective_gain(),
+            ramp_frames: self.ramp_frames(info.route.destination, ramp),
+        })
+    }
+
+    /// Remove a bus, every route touching it and both of its endpoints
+    fn remove_bus(&mut self, id: BusId) -> bool {
+        let Some(info) = self.buses.remove(&id) else {
+            return false;
+        };
+        let (input, output) = (info.bus.input, info.bus.output);
+
+        self.remove_routes_where(|route| route.source == output || route.destination == input);
+
+        self.send(RouterCommand::RemoveBus(info.slot));
+        self.bus_slots.release(info.slot);
+        if let Some(source) = self.sources.remove(&output) {
+            self.send(RouterCommand::RemoveSource(source.slot));
+            self.source_slots.release(source.slot);
+        }
+        if let Some(dest) = self.destinations.remove(&input) {
+            self.send(RouterCommand::RemoveDestination(dest.slot));
+            self.dest_slots.release(dest.slot);
+        }
+        true
+    }
+
+    /// Bus-to-bus edges formed by routes, as (from, to)
+    fn bus_edges(&self) -> Vec<(BusId, BusId)> {
+        self.active_routes()
+            .filter_map(|info| {
+                let from = self.sources.get(&info.route.source)?.bus?;
+                let to = self.destinations.get(&info.route.destination)?.bus?;
+                Some((from, to))
+            })
+            .collect()
+    }
+
+    /// Check whether `to` can already reach `from` through existing routes,
+    /// so that a route from `from` into `to` would close a loop
+    fn creates_cycle(&self, from: BusId, to: BusId) -> bool {
+        let edges = self.bus_edges();
+        let mut visited = HashSet::new();
+        let mut stack = vec![to];
+        while let Some(bus) = stack.pop() {
+            if bus == from {
+                return true;
+            }
+            if visited.insert(bus) {
+                stack.extend(edges.iter().filter(|(a, _)| *a == bus).map(|(_, b)| *b));
+            }
+        }
+        false
+    }
+
+    /// Order buses so every bus comes after all buses feeding it
+    fn bus_order(&self) -> Vec<BusId> {
+        let edges = self.bus_edges();
+        let mut incoming: HashMap<BusId, usize> = self.buses.keys().map(|&id| (id, 0)).collect();
+        for (_, to) in &edges {
+            if let Some(count) = incoming.get_mut(to) {
+                *count += 1;
+            }
+        }
+
+        // Kahn's algorithm, taking the lowest id first for a stable order
+        let mut ready: Vec<BusId> = incoming
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(&id, _)| id)
+            .collect();
+        let mut order = Vec::with_capacity(self.buses.len());
+        while !ready.is_empty() {
+            ready.sort_unstable_by(|a, b| b.cmp(a));
+            let Some(bus) = ready.pop() else {
+                break;
+            };
+            order.push(bus);
+            for (_, to) in edges.iter().filter(|(from, _)| *from == bus) {
+                if let Some(count) = incoming.get_mut(to) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(*to);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// Get a route or a "not found" error
+    fn route_mut(&mut self, id: RouteId) -> Result<&mut RouteInfo> {
+        self.routes
+            .get_mut(&id)
+            .ok_or_else(|| AudioBackendError::DeviceNotFound(format!("Route {:?} not found", id)))
+    }
+
+    /// Resolve an insert point
+    ///
+    /// Returns the processor-side target, the sample rate and channel count
+    /// of the audio passing through it, and its control-side insert list.
+    fn insert_mut(
+        &mut self,
+        point: InsertPoint,
+    ) -> Result<(ChainTarget, u32, u16, &mut Vec<InsertInfo>)> {
+        match point {
+            InsertPoint::Route(id) => {
+                let info = self.routes.get_mut(&id).ok_or_else(|| {
+                    AudioBackendError::DeviceNotFound(format!("Route {:?} not found", id))
+                })?;
+                let dest = self
+                    .destinations
+                    .get(&info.route.destination)
+                    .ok_or_else(|| {
+                        AudioBackendError::DeviceNotFound(format!(
+                            "Destination {:?} not found",
+                            info.route.destination
+                        ))
+                    })?;
+                Ok((
+                    ChainTarget::Route(info.slot),
+                    dest.sample_rate,
+                    dest.channels,
+                    &mut info.inserts,
+                ))
+            }
+            InsertPoint::Bus(id) => {
+                let input = self
+                    .buses
+                    .get(&id)
+                    .map(|info| info.bus.input)
+                    .ok_or_else(|| {
+                        AudioBackendError::DeviceNotFound(format!("Bus {:?} not found", id))
+                    })?;
+                self.insert_mut(InsertPoint::Destination(input))
+            }
+            InsertPoint::Destination(id) => {
+                let info = self.destinations.get_mut(&id).ok_or_else(|| {
+                    AudioBackendError::DeviceNotFound(format!("Destination {:?} not found", id))
+                })?;
+                Ok((
+                    ChainTarget::Destination(info.slot),
+                    info.sample_rate,
+                    info.channels,
+                    &mut info.inserts,
+                ))
+            }
+        }
+    }
+
+    /// Resolve an insert point and check that `index` names one of its inserts
+    fn insert_at_mut(
+        &mut self,
+        point: InsertPoint,
+        index: usize,
+    ) -> Result<(ChainTarget, &mut InsertInfo)> {
+        let (target, _, _, inserts) = self.insert_mut(point)?;
+        let insert = inserts.get_mut(index).ok_or_else(|| {
+            AudioBackendError::DeviceNotFound(format!("Insert {} not found on {:?}", index, point))
+        })?;
+        Ok((target, insert))
+    }
+
+    /// Build the conversion stage for a route if its endpoints run at
+    /// different rates
+    ///
+    /// Returns the conversion (if any) and the route's maximum per-cycle
+    /// demand on its source.
+    fn build_conversion(
+        &self,
+        source: SourceId,
+        destination: DestId,
+        quality: ResamplerQuality,
+    ) -> Result<(Option<Conversion>, usize)> {
+        let (Some(source), Some(destination)) = (
+            self.sources.get(&source),
+            self.destinations.get(&destination),
+        ) else {
+            return Ok((None, 0));
+        };
+
+        // The resampler runs before channel mapping, so it produces one
+        // destination buffer's worth of frames in the source's channel layout
+        let frames = (self.buffer_size / destination.channels.max(1) as usize).max(1);
+
+        if source.sample_rate == destination.sample_rate {
+            return Ok((None, frames * source.channels as usize));
+        }
+
+        let mut resampler = RouteResampler::new(
+            source.sample_rate,
+            destination.sample_rate,
+            source.channels,
+            frames,
+            quality,
+        )?;
+        resampler.set_failure_counter(Arc::clone(&self.resampler_failures));
+        let max_demand = resampler.max_input_samples();
+        Ok((
+            Some(Conversion::new(resampler, source.channels)),
+            max_demand,
+        ))
+    }
+
+    /// Send a larger read buffer to a source if one of its routes needs it
+    fn fit_source_buffer(&mut self, source: SourceId) {
+        let required = self
+            .active_routes()
+            .filter(|info| info.route.source == source)
+            .map(|info| info.max_demand)
+            .max()
+            .unwrap_or(0);
+
+        let slot = match self.sources.get_mut(&source) {
+            Some(info) if required > info.buffer_len => {
+                info.buffer_len = required;
+                info.slot
+            }
+            _ => return,
+        };
+        self.send(RouterCommand::SetSourceBuffer(slot, vec![0.0; required]));
+    }
+
+    /// Compile the execution plan and send it to the processor
+    fn compile(&mut self) {
+        let mut routes: Vec<&RouteInfo> = self
+            .active_routes()
+            .filter(|info| info.route.enabled)
+            .collect();
+        routes.sort_by_key(|info| info.route.id.0);
+
+        let source_bus = |info: &RouteInfo| {
+            self.sources
+                .get(&info.route.source)
+                .and_then(|source| source.bus)
+        };
+
+        // Routes from plain sources first, then each bus in topological
+        // order followed by the routes reading from it
+        let mut steps: Vec<PlanStep> = routes
+            .iter()
+            .filter(|info| source_bus(info).is_none())
+            .map(|info| PlanStep::Route(info.slot))
+            .collect();
+        for bus in self.bus_order() {
+            let Some(bus_info) = self.buses.get(&bus) else {
+                continue;
+            };
+            steps.push(PlanStep::Bus(bus_info.slot));
+            steps.extend(
+                routes
+                    .iter()
+                    .filter(|info| source_bus(info) == Some(bus))
+                    .map(|info| PlanStep::Route(info.slot)),
+            );
         }
+
+        let mut sources: Vec<usize> = routes
+            .iter()
+            .filter_map(|info| self.sources.get(&info.route.source))
+            .filter(|source| source.bus.is_none())
+            .map(|source| source.slot)
+            .collect();
+        sources.sort_unstable();
+        sources.dedup();
+
+        let mut destinations: Vec<usize> =
+            self.destinations.values().map(|dest| dest.slot).collect();
+        destinations.sort_unstable();
+
+        let plan = ExecutionPlan {
+            sources,
+            steps,
+            destinations,
+        };
+        self.send(RouterCommand::SetPlan(Box::new(plan)));
     }
 }
 
@@ -182,9 +885,31 @@ impl RouterState {
 ///
 /// Manages audio sources, destinations, and routes between them.
 /// Supports multiple simultaneous routes with independent gain control.
+///
+/// The router is split in two halves. `AudioRouter` is the control half: all
+/// graph edits and queries go through it and never block the audio thread.
+/// The [`RouterProcessor`] is the real-time half; it can be driven through
+/// [`AudioRouter::process`] or moved into an audio callback with
+/// [`AudioRouter::take_processor`].
 pub struct AudioRouter {
-    state: Arc<RwLock<RouterState>>,
+    control: Mutex<RouterControl>,
+    processor: Mutex<Option<RouterProcessor>>,
     buffer_size: usize,
+    /// Finite sources still playing, published by the processor
+    finite_sources: Arc<AtomicUsize>,
+    /// Calls to `process` that found another thread processing
+    skipped_blocks: AtomicU64,
+    /// Failed resampler chunks, shared with every route's resampler
+    resampler_failures: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for AudioRouter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioRouter")
+            .field("buffer_size", &self.buffer_size)
+            .field("finite_sources_playing", &self.finite_sources_playing())
+            .finish_non_exhaustive()
+    }
 }
 
 impl AudioRouter {
@@ -193,12 +918,90 @@ impl AudioRouter {
     /// # Arguments
     /// * `buffer_size` - Size of internal processing buffers
     pub fn new(buffer_size: usize) -> Self {
+        let (commands, command_queue) = RingBuffer::new(QUEUE_CAPACITY);
+        let (garbage_queue, garbage) = RingBuffer::new(QUEUE_CAPACITY);
+
+        let capacity = SlotCapacity {
+            sources: INITIAL_NODE_SLOTS,
+            destinations: INITIAL_NODE_SLOTS,
+            routes: INITIAL_ROUTE_SLOTS,
+            buses: INITIAL_BUS_SLOTS,
+        };
+        let slots = Slots::with_capacity(capacity);
+        let finite_sources = Arc::new(AtomicUsize::new(0));
+        let garbage_stalled = Arc::new(AtomicBool::new(false));
+        let resampler_failures = Arc::new(AtomicU64::new(0));
+        let processor = RouterProcessor::new(
+            command_queue,
+            garbage_queue,
+            slots,
+            Arc::clone(&finite_sources),
+            Arc::clone(&garbage_stalled),
+        );
+
+        let control = RouterControl {
+            sources: HashMap::new(),
+            destinations: HashMap::new(),
+            routes: HashMap::new(),
+            retiring: HashMap::new(),
+            buses: HashMap::new(),
+            source_slots: SlotAllocator::default(),
+            dest_slots: SlotAllocator::default(),
+            route_slots: SlotAllocator::default(),
+            bus_slots: SlotAllocator::default(),
+            capacity,
+            commands,
+            pending: VecDeque::new(),
+            garbage,
+            garbage_stalled,
+            resampler_failures: Arc::clone(&resampler_failures),
+            reported_resampler_failures: 0,
+            buffer_size,
+            gain_ramp: DEFAULT_GAIN_RAMP,
+            next_source_id: 1,
+            next_dest_id: 1,
+            next_route_id: 1,
+            next_bus_id: 1,
+        };
+
         Self {
-            state: Arc::new(RwLock::new(RouterState::new())),
+            control: Mutex::new(control),
+            processor: Mutex::new(Some(processor)),
             buffer_size,
+            finite_sources,
+            skipped_blocks: AtomicU64::new(0),
+            resampler_failures,
         }
     }
 
+    /// Lock the control state, flushing queued commands and released memory
+    fn control(&self) -> MutexGuard<'_, RouterControl> {
+        let mut control = self.control.lock();
+        control.collect_garbage();
+        control.flush();
+        control
+    }
+
+    /// Take the real-time processor so it can be owned by the audio thread
+    ///
+    /// After this, [`AudioRouter::process`] returns an error and the returned
+    /// processor must be driven instead. Graph edits made through this router
+    /// keep reaching it.
+    ///
+    /// # Returns
+    /// The processor, or `None` if it has already been taken
+    pub fn take_processor(&self) -> Option<RouterProcessor> {
+        self.processor.lock().take()
+    }
+
+    /// Free memory released by the processor
+    ///
+    /// Every graph edit already does this; call it periodically from a
+    /// non-audio thread if the graph is left untouched for long periods.
+    pub fn collect_garbage(&self) {
+        drop(self.control());
+    }
+
     /// Add an audio source to the router
     ///
     /// # Arguments
@@ -207,10 +1010,28 @@ impl AudioRouter {
     /// # Returns
     /// Unique identifier for the added source
     pub fn add_source(&self, source: Box<dyn AudioSource>) -> SourceId {
-        let mut state = self.state.write();
-        let id = SourceId(state.next_source_id);
-        state.next_source_id += 1;
-        state.sources.insert(id, source);
+        let mut control = self.control();
+        let id = SourceId(control.next_source_id);
+        control.next_source_id += 1;
+
+        let slot = control.source_slots.allocate();
+        control.ensure_capacity();
+        control.sources.insert(
+            id,
+            SourceInfo {
+                slot,
+                kind: source.node_kind(),
+                sample_rate: source.sample_rate(),
+                channels: source.channels(),
+                length: source.length(),
+                buffer_len: self.buffer_size,
+                bus: None,
+            },
+        );
+        control.send(RouterCommand::InsertSource(
+            slot,
+            Box::new(SourceNode::new(source, self.buffer_size)),
+        ));
         id
     }
 
@@ -222,10 +1043,28 @@ impl AudioRouter {
     /// # Returns
     /// Unique identifier for the added destination
     pub fn add_destination(&self, destination: Box<dyn AudioDestination>) -> DestId {
-        let mut state = self.state.write();
-        let id = DestId(state.next_dest_id);
-        state.next_dest_id += 1;
-        state.destinations.insert(id, destination);
+        let mut control = self.control();
+        let id = DestId(control.next_dest_id);
+        control.next_dest_id += 1;
+
+        let slot = control.dest_slots.allocate();
+        control.ensure_capacity();
+        control.destinations.insert(
+            id,
+            DestInfo {
+                slot,
+                kind: destination.node_kind(),
+                sample_rate: destination.sample_rate(),
+                channels: destination.channels(),
+                inserts: Vec::new(),
+                bus: None,
+            },
+        );
+        control.send(RouterCommand::InsertDestination(
+            slot,
+            Box::new(DestNode::new(destination, self.buffer_size)),
+        ));
+        control.compile();
         id
     }
 
@@ -235,23 +1074,31 @@ impl AudioRouter {
     /// * `id` - ID of the source to remove
     ///
     /// # Returns
-    /// true if source was removed, false if not found
+    /// true if source was removed, false if not found or if it is a bus
+    /// output (use [`AudioRouter::remove_bus`] for those)
     pub fn remove_source(&self, id: SourceId) -> bool {
-        let mut state = self.state.write();
+        let mut control = self.control();
+        if control
+            .sources
+            .get(&id)
+            .is_some_and(|info| info.bus.is_some())
+        {
+            return false;
+        }
 
         // Remove all routes using this source
-        let routes_to_remove: Vec<RouteId> = state
-            .routes
-            .iter()
-            .filter(|(_, route)| route.source == id)
-            .map(|(route_id, _)| *route_id)
-            .collect();
-
-        for route_id in routes_to_remove {
-            state.routes.remove(&route_id);
-        }
+        control.remove_routes_where(|route| route.source == id);
 
-        state.sources.remove(&id).is_some()
+        let removed = match control.sources.remove(&id) {
+            Some(info) => {
+                control.send(RouterCommand::RemoveSource(info.slot));
+                control.source_slots.release(info.slot);
+                true
+            }
+            None => false,
+        };
+        control.compile();
+        removed
     }
 
     /// Remove an audio destination
@@ -260,23 +1107,205 @@ impl AudioRouter {
     /// * `id` - ID of the destination to remove
     ///
     /// # Returns
-    /// true if destination was removed, false if not found
+    /// true if destination was removed, false if not found or if it is a
+    /// bus input (use [`AudioRouter::remove_bus`] for those)
     pub fn remove_destination(&self, id: DestId) -> bool {
-        let mut state = self.state.write();
+        let mut control = self.control();
+        if control
+            .destinations
+            .get(&id)
+            .is_some_and(|info| info.bus.is_some())
+        {
+            return false;
+        }
 
         // Remove all routes using this destination
-        let routes_to_remove: Vec<RouteId> = state
-            .routes
-            .iter()
-            .filter(|(_, route)| route.destination == id)
-            .map(|(route_id, _)| *route_id)
-            .collect();
+        control.remove_routes_where(|route| route.destination == id);
+
+        let removed = match control.destinations.remove(&id) {
+            Some(info) => {
+                control.send(RouterCommand::RemoveDestination(info.slot));
+                control.dest_slots.release(info.slot);
+                true
+            }
+            None => false,
+        };
+        control.compile();
+        removed
+    }
 
-        for route_id in routes_to_remove {
-            state.routes.remove(&route_id);
+    /// Add a mix bus
+    ///
+    /// The bus gets its own destination and source IDs (see [`Bus::input`]
+    /// and [`Bus::output`]) that are used with [`AudioRouter::create_route`]
+    /// like any other endpoint.
+    ///
+    /// # Arguments
+    /// * `name` - Display name, e.g. for mixer channel strips
+    /// * `sample_rate` - Sample rate of the bus mix
+    /// * `channels` - Number of channels in the bus mix
+    ///
+    /// # Errors
+    /// Returns `UnsupportedFormat` if the sample rate or channel count is zero.
+    pub fn add_bus(&self, name: &str, sample_rate: u32, channels: u16) -> Result<BusId> {
+        if sample_rate == 0 || channels == 0 {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "Bus needs a non-zero format, got {} Hz with {} channel(s)",
+                sample_rate, channels
+            )));
         }
 
-        state.destinations.remove(&id).is_some()
+        let mut control = self.control();
+        let id = BusId(control.next_bus_id);
+        control.next_bus_id += 1;
+        let input = DestId(control.next_dest_id);
+        control.next_dest_id += 1;
+        let output = SourceId(control.next_source_id);
+        control.next_source_id += 1;
+
+        let bus_slot = control.bus_slots.allocate();
+        let dest_slot = control.dest_slots.allocate();
+        let source_slot = control.source_slots.allocate();
+        control.ensure_capacity();
+
+        control.destinations.insert(
+            input,
+            DestInfo {
+                slot: dest_slot,
+                kind: NodeKind::other("bus"),
+                sample_rate,
+                channels,
+                inserts: Vec::new(),
+                bus: Some(id),
+            },
+        );
+        control.sources.insert(
+            output,
+            SourceInfo {
+                slot: source_slot,
+                kind: NodeKind::other("bus"),
+                sample_rate,
+                channels,
+                length: None,
+                buffer_len: self.buffer_size,
+                bus: Some(id),
+            },
+        );
+
+        let bus = Bus {
+            id,
+            name: name.to_string(),
+            input,
+            output,
+            sample_rate,
+            channels,
+            gain: 1.0,
+            muted: false,
+        };
+        let peak = Arc::new(AtomicU32::new(0));
+
+        control.send(RouterCommand::InsertDestination(
+            dest_slot,
+            Box::new(DestNode::bus_input(self.buffer_size)),
+        ));
+        control.send(RouterCommand::InsertSource(
+            source_slot,
+            Box::new(SourceNode::bus_output(self.buffer_size)),
+        ));
+        control.send(RouterCommand::InsertBus(
+            bus_slot,
+            Box::new(BusNode::new(
+                dest_slot,
+                source_slot,
+                channels,
+                bus.effective_gain(),
+                Arc::clone(&peak),
+            )),
+        ));
+        control.buses.insert(
+            id,
+            BusInfo {
+                bus,
+                slot: bus_slot,
+                peak,
+            },
+        );
+        control.compile();
+        Ok(id)
+    }
+
+    /// Remove a bus along with every route into or out of it
+    ///
+    /// # Returns
+    /// true if the bus was removed, false if not found
+    pub fn remove_bus(&self, id: BusId) -> bool {
+        let mut control = self.control();
+        let removed = control.remove_bus(id);
+        if removed {
+            control.compile();
+        }
+        removed
+    }
+
+    /// Set a bus fader gain
+    ///
+    /// # Errors
+    /// Returns `DeviceNotFound` if the bus does not exist.
+    pub fn set_bus_gain(&self, id: BusId, gain: f32) -> Result<()> {
+        self.update_bus(id, |bus| bus.gain = gain.max(0.0))
+    }
+
+    /// Set a bus muted state
+    ///
+    /// # Errors
+    /// Returns `DeviceNotFound` if the bus does not exist.
+    pub fn set_bus_muted(&self, id: BusId, muted: bool) -> Result<()> {
+        self.update_bus(id, |bus| bus.muted = muted)
+    }
+
+    /// Apply `update` to a bus and send its new effective gain
+    fn update_bus(&self, id: BusId, update: impl FnOnce(&mut Bus)) -> Result<()> {
+        let mut control = self.control();
+        let info = control
+            .buses
+            .get_mut(&id)
+            .ok_or_else(|| AudioBackendError::DeviceNotFound(format!("Bus {:?} not found", id)))?;
+        update(&mut info.bus);
+        let (slot, gain, sample_rate) =
+            (info.slot, info.bus.effective_gain(), info.bus.sample_rate);
+        let change = GainChange {
+            slot,
+            gain,
+            ramp_frames: duration_to_frames(control.gain_ramp, sample_rate),
+        };
+        control.send(RouterCommand::SetBusGain(change));
+        Ok(())
+    }
+
+    /// Get bus information
+    pub fn get_bus(&self, id: BusId) -> Option<Bus> {
+        let control = self.control();
+        control.buses.get(&id).map(|info| info.bus.clone())
+    }
+
+    /// Get all buses, in creation order
+    pub fn get_buses(&self) -> Vec<Bus> {
+        let control = self.control();
+        let mut buses: Vec<Bus> = control
+            .buses
+            .values()
+            .map(|info| info.bus.clone())
+            .collect();
+        buses.sort_by_key(|bus| bus.id);
+        buses
+    }
+
+    /// Get the peak level a bus produced in the last processed buffer,
+    /// after its fader
+    pub fn bus_peak(&self, id: BusId) -> Option<f32> {
+        let control = self.control();
+        let info = control.buses.get(&id)?;
+        Some(f32::from_bits(info.peak.load(Ordering::Relaxed)))
     }
 
     /// Create a route from source to destination
@@ -286,248 +1315,606 @@ impl AudioRouter {
     /// * `destination` - Destination ID
     /// * `gain` - Gain factor (0.0 to 1.0, can exceed 1.0 for amplification)
     ///
+    /// Either end may be a bus (its `output` as the source, its `input` as
+    /// the destination). A bus produces one buffer per cycle, so routes out
+    /// of a bus must target the bus's sample rate.
+    ///
     /// # Returns
     /// Route ID if successful, error if source or destination not found
+    ///
+    /// # Errors
+    /// - `DeviceNotFound` if the source or destination does not exist
+    /// - `UnsupportedFormat` if a route out of a bus would need resampling
+    /// - `RoutingCycle` if the route would feed a bus back into itself
     pub fn create_route(
         &self,
         source: SourceId,
         destination: DestId,
         gain: f32,
     ) -> Result<RouteId> {
-        let mut state = self.state.write();
+        let mut control = self.control();
 
         // Verify source and destination exist
-        if !state.sources.contains_key(&source) {
+        let Some(source_info) = control.sources.get(&source) else {
             return Err(AudioBackendError::DeviceNotFound(format!(
                 "Source {:?} not found",
                 source
             )));
+        };
+        let Some(dest_info) = control.destinations.get(&destination) else {
+            return Err(AudioBackendError::DeviceNotFound(format!(
+                "Destination {:?} not found",
+                destination
+            )));
+        };
+
+        if let Some(bus) = source_info.bus {
+            if source_info.sample_rate != dest_info.sample_rate {
+                return Err(AudioBackendError::UnsupportedFormat(format!(
+                    "Bus {:?} runs at {} Hz but destination {:?} runs at {} Hz",
+                    bus, source_info.sample_rate, destination, dest_info.sample_rate
+                )));
+            }
+            if let Some(target) = dest_info.bus {
+                if control.creates_cycle(bus, target) {
+                    return Err(AudioBackendError::RoutingCycle(format!(
+                        "Routing bus {:?} into bus {:?} would create a feedback loop",
+                        bus, target
+                    )));
+                }
+            }
+        }
+
+        let (source_slot, dest_slot) = (source_info.slot, dest_info.slot);
+        let frames = self.buffer_size / dest_info.channels.max(1) as usize;
+        let channel_matrix = ChannelMatrix::auto(source_info.channels, dest_info.channels)?;
+
+        let resampler_quality = ResamplerQuality::default();
+        let (conversion, max_demand) =
+            control.build_conversion(source, destination, resampler_quality)?;
+        let latency = conversion
+            .as_ref()
+            .map(|c| (c.latency_frames(), c.latency_ms()));
+
+        let route_id = RouteId(control.next_route_id);
+        control.next_route_id += 1;
+
+        let route = Route {
+            id: route_id,
+            source,
+            destination,
+            gain: gain.max(0.0), // Clamp to non-negative
+            enabled: true,
+            muted: false,
+            resampler_quality,
+            channel_matrix,
+        };
+
+        let slot = control.route_slots.allocate();
+        let generation = control.route_slots.generation(slot);
+        control.ensure_capacity();
+        control.send(RouterCommand::InsertRoute(
+            slot,
+            Box::new(RouteNode::new(
+                generation,
+                source_slot,
+                dest_slot,
+                frames,
+                route.effective_gain(),
+                route.channel_matrix.clone(),
+                conversion.map(Box::new),
+            )),
+        ));
+        control.routes.insert(
+            route_id,
+            RouteInfo {
+                route,
+                slot,
+                generation,
+                max_demand,
+                latency,
+                inserts: Vec::new(),
+            },
+        );
+        control.fit_source_buffer(source);
+        control.compile();
+        Ok(route_id)
+    }
+
+    /// Remove a route
+    ///
+    /// An audible route fades out over [`AudioRouter::gain_ramp`] before the
+    /// processor drops it; it disappears from queries immediately.
+    ///
+    /// # Arguments
+    /// * `id` - Route ID to remove
+    ///
+    /// # Returns
+    /// true if route was removed, false if not found
+    pub fn remove_route(&self, id: RouteId) -> bool {
+        let mut control = self.control();
+        let removed = control.retire_route(id);
+        if removed {
+            control.compile();
+        }
+        removed
+    }
+
+    /// Set route gain
+    ///
+    /// The change ramps over [`AudioRouter::gain_ramp`].
+    ///
+    /// # Arguments
+    /// * `id` - Route ID
+    /// * `gain` - New gain value (0.0 to 1.0+)
+    pub fn set_route_gain(&self, id: RouteId, gain: f32) -> Result<()> {
+        let mut control = self.control();
+        control.route_mut(id)?.route.gain = gain.max(0.0);
+        let change = control.route_gain_change(id, control.gain_ramp)?;
+        control.send(RouterCommand::SetRouteGain(change));
+        Ok(())
+    }
+
+    /// Set route enabled state
+    ///
+    /// Enabling fades the route in; disabling takes it out of processing
+    /// straight away (use [`AudioRouter::set_route_muted`] to fade out).
+    pub fn set_route_enabled(&self, id: RouteId, enabled: bool) -> Result<()> {
+        let mut control = self.control();
+        control.route_mut(id)?.route.enabled = enabled;
+        let ramp = if enabled {
+            control.gain_ramp
+        } else {
+            Duration::ZERO
+        };
+        let change = control.route_gain_change(id, ramp)?;
+        control.send(RouterCommand::SetRouteGain(change));
+        control.compile();
+        Ok(())
+    }
+
+    /// Set route muted state
+    ///
+    /// The change ramps over [`AudioRouter::gain_ramp`].
+    pub fn set_route_muted(&self, id: RouteId, muted: bool) -> Result<()> {
+        let mut control = self.control();
+        control.route_mut(id)?.route.muted = muted;
+        let change = control.route_gain_change(id, control.gain_ramp)?;
+        control.send(RouterCommand::SetRouteGain(change));
+        Ok(())
+    }
+
+    /// Crossfade from one route to another
+    ///
+    /// `from` fades to silence and ends up muted, while `to` fades from its
+    /// current level to its gain and ends up enabled and unmuted. Both ramps
+    /// start on the same sample and last `duration`, so e.g. switching a
+    /// destination from a generator to a file does not glitch.
+    ///
+    /// # Errors
+    /// - `DeviceNotFound` if either route does not exist
+    /// - `Other` if `from` and `to` are the same route
+    pub fn crossfade_routes(&self, from: RouteId, to: RouteId, duration: Duration) -> Result<()> {
+        if from == to {
+            return Err(AudioBackendError::Other(anyhow::anyhow!(
+                "Cannot crossfade route {:?} into itself",
+                from
+            )));
         }
+        let mut control = self.control();
+        if !control.routes.contains_key(&from) {
+            return Err(AudioBackendError::DeviceNotFound(format!(
+                "Route {:?} not found",
+                from
+            )));
+        }
+        let to_info = control.route_mut(to)?;
+        let was_enabled = to_info.route.enabled;
+        to_info.route.enabled = true;
+        to_info.route.muted = false;
+        control.route_mut(from)?.route.muted = true;
+
+        let from = control.route_gain_change(from, duration)?;
+        let to = control.route_gain_change(to, duration)?;
+        control.send(RouterCommand::Crossfade { from, to });
+        if !was_enabled {
+            control.compile();
+        }
+        Ok(())
+    }
+
+    /// Set the ramp time used for route and bus gain changes, mutes and
+    /// route removal
+    ///
+    /// `Duration::ZERO` makes changes take effect on the next sample.
+    pub fn set_gain_ramp(&self, ramp: Duration) {
+        self.control().gain_ramp = ramp;
+    }
+
+    /// Get the ramp time used for gain changes
+    pub fn gain_ramp(&self) -> Duration {
+        self.control().gain_ramp
+    }
+
+    /// Set the sample-rate conversion quality for a route
+    ///
+    /// Rebuilds the route's resampler if it has one; the new quality is
+    /// remembered either way.
+    ///
+    /// # Errors
+    /// - `DeviceNotFound` if the route does not exist
+    /// - `UnsupportedFormat` if the resampler cannot be built for the route's
+    ///   rates and channel count
+    pub fn set_route_resampler_quality(
+        &self,
+        id: RouteId,
+        quality: ResamplerQuality,
+    ) -> Result<()> {
+        let mut control = self.control();
+        let (source, destination) = {
+            let info = control.route_mut(id)?;
+            (info.route.source, info.route.destination)
+        };
 
-        if !state.destinations.contains_key(&destination) {
-            return Err(AudioBackendError::DeviceNotFound(format!(
-                "Destination {:?} not found",
-                destination
+        let (conversion, max_demand) = control.build_conversion(source, destination, quality)?;
+        let latency = conversion
+            .as_ref()
+            .map(|c| (c.latency_frames(), c.latency_ms()));
+
+        let info = control.route_mut(id)?;
+        info.route.resampler_quality = quality;
+        info.max_demand = max_demand;
+        info.latency = latency;
+        let slot = info.slot;
+
+        control.send(RouterCommand::SetRouteConversion(
+            slot,
+            conversion.map(Box::new),
+        ));
+        control.fit_source_buffer(source);
+        Ok(())
+    }
+
+    /// Set the channel mapping for a route
+    ///
+    /// The matrix must take the source's channel count as input and produce
+    /// the destination's channel count as output.
+    ///
+    /// # Errors
+    /// - `DeviceNotFound` if the route does not exist
+    /// - `UnsupportedFormat` if the matrix does not match the route's channel
+    ///   counts
+    pub fn set_route_channel_matrix(&self, id: RouteId, matrix: ChannelMatrix) -> Result<()> {
+        let mut control = self.control();
+        let (source, destination) = {
+            let info = control.route_mut(id)?;
+            (info.route.source, info.route.destination)
+        };
+
+        let source_channels = control.sources.get(&source).map(|s| s.channels);
+        let dest_channels = control.destinations.get(&destination).map(|d| d.channels);
+        if source_channels != Some(matrix.input_channels())
+            || dest_channels != Some(matrix.output_channels())
+        {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "Channel matrix is {}→{} but route {:?} is {}→{}",
+                matrix.input_channels(),
+                matrix.output_channels(),
+                id,
+                source_channels.unwrap_or(0),
+                dest_channels.unwrap_or(0)
             )));
         }
 
-        let route_id = RouteId(state.next_route_id);
-        state.next_route_id += 1;
+        let info = control.route_mut(id)?;
+        info.route.channel_matrix = matrix.clone();
+        let slot = info.slot;
+        control.send(RouterCommand::SetRouteMatrix(slot, Box::new(matrix)));
+        Ok(())
+    }
 
-        let route = Route {
-            id: route_id,
-            source,
-            destination,
-            gain: gain.max(0.0), // Clamp to non-negative
-            enabled: true,
-            muted: false,
+    /// Replace the insert chain on a route or destination
+    ///
+    /// Processors are prepared here, off the audio thread, for the
+    /// destination's sample rate and channel count. Passing an empty list
+    /// removes the chain.
+    ///
+    /// # Errors
+    /// Returns `DeviceNotFound` if the route or destination does not exist.
+    pub fn set_processor_chain(
+        &self,
+        point: InsertPoint,
+        processors: Vec<Box<dyn AudioProcessor>>,
+    ) -> Result<()> {
+        let mut control = self.control();
+        let (target, sample_rate, channels, inserts) = control.insert_mut(point)?;
+
+        let chain = if processors.is_empty() {
+            inserts.clear();
+            None
+        } else {
+            let max_frames = self.buffer_size.div_ceil(channels.max(1) as usize);
+            let chain = ProcessorChain::new(processors, sample_rate, channels, max_frames);
+            *inserts = chain.info();
+            Some(Box::new(chain))
         };
 
-        state.routes.insert(route_id, route);
-        Ok(route_id)
+        control.send(RouterCommand::SetChain(target, chain));
+        Ok(())
     }
 
-    /// Remove a route
+    /// Get the inserts on a route or destination, in processing order
     ///
-    /// # Arguments
-    /// * `id` - Route ID to remove
+    /// Returns `None` if the route or destination does not exist.
+    pub fn get_processor_chain(&self, point: InsertPoint) -> Option<Vec<InsertInfo>> {
+        let mut control = self.control();
+        let (_, _, _, inserts) = control.insert_mut(point).ok()?;
+        Some(inserts.clone())
+    }
+
+    /// Bypass or re-enable one insert
     ///
-    /// # Returns
-    /// true if route was removed, false if not found
-    pub fn remove_route(&self, id: RouteId) -> bool {
-        let mut state = self.state.write();
-        state.routes.remove(&id).is_some()
+    /// # Errors
+    /// Returns `DeviceNotFound` if the insert point or index does not exist.
+    pub fn set_processor_bypassed(
+        &self,
+        point: InsertPoint,
+        index: usize,
+        bypassed: bool,
+    ) -> Result<()> {
+        let mut control = self.control();
+        let (target, insert) = control.insert_at_mut(point, index)?;
+        insert.bypassed = bypassed;
+        control.send(RouterCommand::SetInsertBypassed(target, index, bypassed));
+        Ok(())
     }
 
-    /// Set route gain
+    /// Set the wet/dry mix of one insert (clamped to 0.0-1.0)
     ///
-    /// # Arguments
-    /// * `id` - Route ID
-    /// * `gain` - New gain value (0.0 to 1.0+)
-    pub fn set_route_gain(&self, id: RouteId, gain: f32) -> Result<()> {
-        let mut state = self.state.write();
-        if let Some(route) = state.routes.get_mut(&id) {
-            route.gain = gain.max(0.0);
-            Ok(())
-        } else {
-            Err(AudioBackendError::DeviceNotFound(format!(
-                "Route {:?} not found",
-                id
-            )))
-        }
+    /// # Errors
+    /// Returns `DeviceNotFound` if the insert point or index does not exist.
+    pub fn set_processor_mix(&self, point: InsertPoint, index: usize, mix: f32) -> Result<()> {
+        let mut control = self.control();
+        let (target, insert) = control.insert_at_mut(point, index)?;
+        let mix = mix.clamp(0.0, 1.0);
+        insert.mix = mix;
+        control.send(RouterCommand::SetInsertMix(target, index, mix));
+        Ok(())
     }
 
-    /// Set route enabled state
-    pub fn set_route_enabled(&self, id: RouteId, enabled: bool) -> Result<()> {
-        let mut state = self.state.write();
-        if let Some(route) = state.routes.get_mut(&id) {
-            route.enabled = enabled;
-            Ok(())
-        } else {
-            Err(AudioBackendError::DeviceNotFound(format!(
-                "Route {:?} not found",
-                id
-            )))
-        }
+    /// Clear the internal state of every processor on a route or destination
+    ///
+    /// # Errors
+    /// Returns `DeviceNotFound` if the route or destination does not exist.
+    pub fn reset_processors(&self, point: InsertPoint) -> Result<()> {
+        let mut control = self.control();
+        let (target, _, _, _) = control.insert_mut(point)?;
+        control.send(RouterCommand::ResetChain(target));
+        Ok(())
     }
 
-    /// Set route muted state
-    pub fn set_route_muted(&self, id: RouteId, muted: bool) -> Result<()> {
-        let mut state = self.state.write();
-        if let Some(route) = state.routes.get_mut(&id) {
-            route.muted = muted;
-            Ok(())
-        } else {
-            Err(AudioBackendError::DeviceNotFound(format!(
-                "Route {:?} not found",
-                id
-            )))
-        }
+    /// Check whether a route is converting between sample rates
+    pub fn route_is_resampling(&self, id: RouteId) -> bool {
+        let control = self.control();
+        control
+            .routes
+            .get(&id)
+            .is_some_and(|info| info.latency.is_some())
+    }
+
+    /// Get the latency added by a route's resampler, in destination frames
+    ///
+    /// Returns `Some(0)` for routes that do not need conversion and `None`
+    /// if the route does not exist.
+    pub fn route_latency_frames(&self, id: RouteId) -> Option<usize> {
+        let control = self.control();
+        let info = control.routes.get(&id)?;
+        Some(info.latency.map_or(0, |(frames, _)| frames))
+    }
+
+    /// Get the latency added by a route's resampler, in milliseconds
+    pub fn route_latency_ms(&self, id: RouteId) -> Option<f32> {
+        let control = self.control();
+        let info = control.routes.get(&id)?;
+        Some(info.latency.map_or(0.0, |(_, ms)| ms))
     }
 
     /// Get route information
     pub fn get_route(&self, id: RouteId) -> Option<Route> {
-        let state = self.state.read();
-        state.routes.get(&id).cloned()
+        let control = self.control();
+        control.routes.get(&id).map(|info| info.route.clone())
     }
 
     /// Get all routes
     pub fn get_routes(&self) -> Vec<Route> {
-        let state = self.state.read();
-        state.routes.values().cloned().collect()
+        let control = self.control();
+        control
+            .routes
+            .values()
+            .map(|info| info.route.clone())
+            .collect()
     }
 
     /// Get all routes for a specific source
     pub fn get_routes_for_source(&self, source: SourceId) -> Vec<Route> {
-        let state = self.state.read();
-        state
+        let control = self.control();
+        control
             .routes
             .values()
-            .filter(|route| route.source == source)
-            .cloned()
+            .filter(|info| info.route.source == source)
+            .map(|info| info.route.clone())
             .collect()
     }
 
     /// Get all routes for a specific destination
     pub fn get_routes_for_destination(&self, destination: DestId) -> Vec<Route> {
-        let state = self.state.read();
-        state
+        let control = self.control();
+        control
             .routes
             .values()
-            .filter(|route| route.destination == destination)
-            .cloned()
+            .filter(|info| info.route.destination == destination)
+            .map(|info| info.route.clone())
             .collect()
     }
 
     /// Get source IDs
     pub fn get_source_ids(&self) -> Vec<SourceId> {
-        let state = self.state.read();
-        state.sources.keys().copied().collect()
+        let control = self.control();
+        control.sources.keys().copied().collect()
     }
 
     /// Get destination IDs
     pub fn get_destination_ids(&self) -> Vec<DestId> {
-        let state = self.state.read();
-        state.destinations.keys().copied().collect()
+        let control = self.control();
+        control.destinations.keys().copied().collect()
+    }
+
+    /// Get source information
+    pub fn get_source_info(&self, id: SourceId) -> Option<NodeInfo> {
+        let control = self.control();
+        control.sources.get(&id).map(|info| NodeInfo {
+            kind: info.kind.clone(),
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+            length: info.length,
+            bus: info.bus,
+        })
+    }
+
+    /// Get destination information
+    pub fn get_destination_info(&self, id: DestId) -> Option<NodeInfo> {
+        let control = self.control();
+        control.destinations.get(&id).map(|info| NodeInfo {
+            kind: info.kind.clone(),
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+            length: None,
+            bus: info.bus,
+        })
+    }
+
+    /// Get the size of the buffer each destination receives per cycle
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
     }
 
     /// Process audio routing for one buffer
     ///
     /// This reads from all sources, applies routing and gain, and writes to destinations.
     /// Should be called regularly (typically in an audio callback).
+    ///
+    /// This never blocks: if another thread is already processing, the block
+    /// is skipped and counted in [`AudioRouter::skipped_blocks`]. Real-time
+    /// callers that want to own the processor outright should use
+    /// [`AudioRouter::take_processor`] instead.
     pub fn process(&self) -> Result<()> {
-        let mut state = self.state.write();
-
-        // Temporary buffers for mixing
-        let mut source_buffer = vec![0.0f32; self.buffer_size];
-        let mut dest_buffers: HashMap<DestId, Vec<f32>> = HashMap::new();
-
-        // Initialize destination buffers
-        for dest_id in state.destinations.keys() {
-            dest_buffers.insert(*dest_id, vec![0.0f32; self.buffer_size]);
-        }
-
-        // Cache for source samples (read each source once for proper fan-out)
-        // Key: source_id, Value: (samples_read, Vec<f32>)
-        let mut source_cache: HashMap<SourceId, (usize, Vec<f32>)> = HashMap::new();
-
-        // Collect unique source IDs from enabled routes first (to avoid borrow conflicts)
-        let unique_source_ids: Vec<SourceId> = state
-            .routes
-            .values()
-            .filter(|route| route.enabled)
-            .map(|route| route.source)
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect();
-
-        // First pass: Read from each unique source once
-        for source_id in unique_source_ids {
-            if let Some(source) = state.sources.get_mut(&source_id) {
-                let samples_read = source.read_samples(&mut source_buffer);
-                // Store a copy of the samples
-                source_cache.insert(source_id, (samples_read, source_buffer.clone()));
-            }
+        let Some(mut processor) = self.processor.try_lock() else {
+            self.skipped_blocks.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        };
+        match processor.as_mut() {
+            Some(processor) => processor.process(),
+            None => Err(AudioBackendError::StreamError(
+                "Router processor has been moved to the audio thread".to_string(),
+            )),
         }
+    }
 
-        // Second pass: Route cached samples to destinations
-        for route in state.routes.values() {
-            if !route.enabled {
-                continue;
-            }
-
-            let gain = route.effective_gain();
-            if gain == 0.0 {
-                continue;
-            }
-
-            // Use cached samples from source
-            if let Some((samples_read, source_samples)) = source_cache.get(&route.source) {
-                if *samples_read > 0 {
-                    // Mix into destination buffer with gain
-                    if let Some(dest_buffer) = dest_buffers.get_mut(&route.destination) {
-                        for i in 0..*samples_read.min(&self.buffer_size) {
-                            dest_buffer[i] += source_samples[i] * gain;
-                        }
-                    }
-                }
+    /// Apply queued graph edits without processing audio
+    ///
+    /// Nodes removed before this call are freed by the next
+    /// [`AudioRouter::collect_garbage`], e.g. to close a recorded file at a
+    /// known point.
+    ///
+    /// # Errors
+    /// Returns `StreamError` if the processor has been taken.
+    pub fn apply_edits(&self) -> Result<()> {
+        // Move any commands still waiting for queue space
+        drop(self.control());
+        match self.processor.lock().as_mut() {
+            Some(processor) => {
+                processor.apply_commands();
+                Ok(())
             }
+            None => Err(AudioBackendError::StreamError(
+                "Router processor has been moved to the audio thread".to_string(),
+            )),
         }
+    }
 
-        // Write to destinations
-        for (dest_id, buffer) in dest_buffers.iter() {
-            if let Some(destination) = state.destinations.get_mut(dest_id) {
-                // Apply soft clipping to prevent severe distortion
-                let clipped_buffer: Vec<f32> =
-                    buffer.iter().map(|&sample| soft_clip(sample)).collect();
+    /// Number of routed sources with a known length (see
+    /// [`AudioSource::length`]) that had samples left after the last
+    /// processed buffer
+    pub fn finite_sources_playing(&self) -> usize {
+        self.finite_sources.load(Ordering::Relaxed)
+    }
 
-                destination.write_samples(&clipped_buffer)?;
-            }
-        }
+    /// Number of [`AudioRouter::process`] calls that skipped their block
+    /// because another thread was processing at the time
+    pub fn skipped_blocks(&self) -> u64 {
+        self.skipped_blocks.load(Ordering::Relaxed)
+    }
 
-        Ok(())
+    /// Number of chunks that route resamplers failed to produce
+    ///
+    /// Failures are counted on the audio thread and logged from the control
+    /// thread by the next edit or [`AudioRouter::collect_garbage`].
+    pub fn resampler_failures(&self) -> u64 {
+        self.resampler_failures.load(Ordering::Relaxed)
     }
 
     /// Clear all routes (keep sources and destinations)
+    ///
+    /// Audible routes fade out as with [`AudioRouter::remove_route`].
     pub fn clear_routes(&self) {
-        let mut state = self.state.write();
-        state.routes.clear();
+        let mut control = self.control();
+        let route_ids: Vec<RouteId> = control.routes.keys().copied().collect();
+        for route_id in route_ids {
+            control.retire_route(route_id);
+        }
+        control.compile();
     }
 
-    /// Clear everything (sources, destinations, routes)
+    /// Clear everything (sources, destinations, buses, routes)
     pub fn clear_all(&self) {
-        let mut state = self.state.write();
-        state.sources.clear();
-        state.destinations.clear();
-        state.routes.clear();
+        let mut control = self.control();
+        control.remove_routes_where(|_| true);
+
+        let bus_ids: Vec<BusId> = control.buses.keys().copied().collect();
+        for bus_id in bus_ids {
+            control.remove_bus(bus_id);
+        }
+
+        let sources: Vec<usize> = control.sources.drain().map(|(_, info)| info.slot).collect();
+        for slot in sources {
+            control.send(RouterCommand::RemoveSource(slot));
+            control.source_slots.release(slot);
+        }
+
+        let destinations: Vec<usize> = control
+            .destinations
+            .drain()
+            .map(|(_, info)| info.slot)
+            .collect();
+        for slot in destinations {
+            control.send(RouterCommand::RemoveDestination(slot));
+            control.dest_slots.release(slot);
+        }
+
+        control.compile();
     }
 }
 
+/// Number of frames `duration` spans at `sample_rate`
+fn duration_to_frames(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize
+}
+
 /// Soft clipping function to prevent harsh distortion
 ///
 /// Uses a tanh-based soft clipping curve that smoothly compresses
 /// signals exceeding [-1.0, 1.0] range.
-fn soft_clip(sample: f32) -> f32 {
+pub(crate) fn soft_clip(sample: f32) -> f32 {
     if sample.abs() <= 1.0 {
         sample
     } else {
@@ -662,6 +2049,610 @@ mod tests {
         assert_eq!(route.gain, 0.5);
     }
 
+    #[test]
+    fn test_route_resampling_inserted_for_rate_mismatch() {
+        use crate::audio::destinations::NullDestination;
+        use crate::audio::sources::SilenceSource;
+
+        let router = AudioRouter::new(512);
+
+        let source_id = router.add_source(Box::new(SilenceSource::new(44100, 1)));
+        let matched_id = router.add_destination(Box::new(NullDestination::new(44100, 1)));
+        let mismatched_id = router.add_destination(Box::new(NullDestination::new(48000, 1)));
+
+        let direct = router.create_route(source_id, matched_id, 1.0).unwrap();
+        let converted = router.create_route(source_id, mismatched_id, 1.0).unwrap();
+
+        assert!(!router.route_is_resampling(direct));
+        assert!(router.route_is_resampling(converted));
+        assert_eq!(router.route_latency_frames(direct), Some(0));
+        assert!(router.route_latency_frames(converted).unwrap() > 0);
+        assert_eq!(router.route_latency_frames(RouteId(999)), None);
+    }
+
+    #[test]
+    fn test_route_resampler_quality() {
+        use crate::audio::destinations::NullDestination;
+        use crate::audio::sources::SilenceSource;
+
+        let router = AudioRouter::new(512);
+        let source_id = router.add_source(Box::new(SilenceSource::new(44100, 2)));
+        let dest_id = router.add_destination(Box::new(NullDestination::new(48000, 2)));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+
+        router
+            .set_route_resampler_quality(route_id, ResamplerQuality::Fast)
+            .unwrap();
+        let fast_latency = router.route_latency_frames(route_id).unwrap();
+
+        router
+            .set_route_resampler_quality(route_id, ResamplerQuality::High)
+            .unwrap();
+        let high_latency = router.route_latency_frames(route_id).unwrap();
+
+        assert_eq!(
+            router.get_route(route_id).unwrap().resampler_quality,
+            ResamplerQuality::High
+        );
+        assert!(high_latency > fast_latency);
+    }
+
+    #[test]
+    fn test_resampled_route_preserves_pitch() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let buffer_size = 480;
+        let router = AudioRouter::new(buffer_size);
+
+        // One second of 1 kHz at 44.1 kHz, looped
+        let tone: Vec<f32> = (0..44100)
+            .map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let source = SignalGeneratorSource::from_buffer(tone, 44100.0, true);
+        let dest = RingBufferDestination::new(48000, 1, 0);
+        let reader = dest.get_reader();
+
+        let source_id = router.add_source(Box::new(source));
+        let dest_id = router.add_destination(Box::new(dest));
+        router.create_route(source_id, dest_id, 1.0).unwrap();
+
+        // Two seconds of output at 48 kHz
+        for _ in 0..200 {
+            router.process().unwrap();
+        }
+
+        let mut output = vec![0.0; 200 * buffer_size];
+        assert_eq!(reader.read(&mut output), output.len());
+
+        // Skip the first second (resampler warm-up), then count rising
+        // zero crossings over exactly one second of destination time.
+        let second = &output[48000..96000];
+        let crossings = second
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        assert!((crossings as i32 - 1000).abs() <= 2, "got {}", crossings);
+    }
+
+    #[test]
+    fn test_mono_route_to_stereo_destination() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let router = AudioRouter::new(8);
+        let source = SignalGeneratorSource::from_buffer(vec![0.1, 0.2, 0.3, 0.4], 44100.0, false);
+        let dest = RingBufferDestination::new(44100, 2, 0);
+        let reader = dest.get_reader();
+
+        let source_id = router.add_source(Box::new(source));
+        let dest_id = router.add_destination(Box::new(dest));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+        assert_eq!(
+            router.get_route(route_id).unwrap().channel_matrix,
+            ChannelMatrix::mono_to_stereo()
+        );
+
+        router.process().unwrap();
+
+        let mut output = vec![0.0; 8];
+        assert_eq!(reader.read(&mut output), 8);
+        assert_eq!(output, vec![0.1, 0.1, 0.2, 0.2, 0.3, 0.3, 0.4, 0.4]);
+    }
+
+    #[test]
+    fn test_set_route_channel_matrix() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let router = AudioRouter::new(4);
+        let source = SignalGeneratorSource::from_buffer_with_channels(
+            vec![0.2, 0.8, 0.4, 0.6],
+            44100.0,
+            2,
+            false,
+        );
+        let dest = RingBufferDestination::new(44100, 2, 0);
+        let reader = dest.get_reader();
+
+        let source_id = router.add_source(Box::new(source));
+        let dest_id = router.add_destination(Box::new(dest));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+
+        // Wrong dimensions are rejected
+        assert!(router
+            .set_route_channel_matrix(route_id, ChannelMatrix::stereo_to_mono())
+            .is_err());
+
+        // Swap left and right
+        let swap = ChannelMatrix::pick(2, &[Some(1), Some(0)]).unwrap();
+        router.set_route_channel_matrix(route_id, swap).unwrap();
+        router.process().unwrap();
+
+        let mut output = vec![0.0; 4];
+        reader.read(&mut output);
+        assert_eq!(output, vec![0.8, 0.2, 0.6, 0.4]);
+    }
+
+    /// Insert that scales every sample
+    struct ScaleProcessor(f32);
+
+    impl AudioProcessor for ScaleProcessor {
+        fn name(&self) -> &str {
+            "Scale"
+        }
+
+        fn prepare(&mut self, _sample_rate: u32, _channels: u16, _max_frames: usize) {}
+
+        fn process(&mut self, buffer: &mut [f32]) {
+            for sample in buffer.iter_mut() {
+                *sample *= self.0;
+            }
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn test_route_and_destination_inserts() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let router = AudioRouter::new(4);
+        let source = SignalGeneratorSource::from_buffer(vec![0.1; 64], 44100.0, false);
+        let dest = RingBufferDestination::new(44100, 2, 0);
+        let reader = dest.get_reader();
+
+        let source_id = router.add_source(Box::new(source));
+        let dest_id = router.add_destination(Box::new(dest));
+        let route_id = router.create_route(source_id, dest_id, 0.5).unwrap();
+
+        // Route inserts run on the mapped signal before the route gain
+        let route_point = InsertPoint::Route(route_id);
+        router
+            .set_processor_chain(route_point, vec![Box::new(ScaleProcessor(4.0))])
+            .unwrap();
+        router.process().unwrap();
+        let mut output = vec![0.0; 4];
+        reader.read(&mut output);
+        assert!(output.iter().all(|&s| (s - 0.2).abs() < 1e-6));
+
+        // Destination inserts run after mixing
+        let dest_point = InsertPoint::Destination(dest_id);
+        router
+            .set_processor_chain(dest_point, vec![Box::new(ScaleProcessor(0.5))])
+            .unwrap();
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert!(output.iter().all(|&s| (s - 0.1).abs() < 1e-6));
+
+        let chain = router.get_processor_chain(route_point).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].name, "Scale");
+
+        // An empty list removes the chain
+        router.set_processor_chain(dest_point, Vec::new()).unwrap();
+        assert_eq!(router.get_processor_chain(dest_point), Some(Vec::new()));
+        assert!(router
+            .get_processor_chain(InsertPoint::Route(RouteId(99)))
+            .is_none());
+    }
+
+    #[test]
+    fn test_insert_bypass_and_mix() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let router = AudioRouter::new(2);
+        let source = SignalGeneratorSource::from_buffer(vec![0.2; 64], 44100.0, false);
+        let dest = RingBufferDestination::new(44100, 1, 0);
+        let reader = dest.get_reader();
+
+        let source_id = router.add_source(Box::new(source));
+        let dest_id = router.add_destination(Box::new(dest));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+        let point = InsertPoint::Route(route_id);
+        router
+            .set_processor_chain(point, vec![Box::new(ScaleProcessor(3.0))])
+            .unwrap();
+
+        let mut output = vec![0.0; 2];
+
+        router.set_processor_bypassed(point, 0, true).unwrap();
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert!(output.iter().all(|&s| (s - 0.2).abs() < 1e-6));
+
+        // Half wet: 0.5 * 0.6 + 0.5 * 0.2
+        router.set_processor_bypassed(point, 0, false).unwrap();
+        router.set_processor_mix(point, 0, 0.5).unwrap();
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert!(output.iter().all(|&s| (s - 0.4).abs() < 1e-6));
+
+        let chain = router.get_processor_chain(point).unwrap();
+        assert!(!chain[0].bypassed);
+        assert_eq!(chain[0].mix, 0.5);
+
+        assert!(router.set_processor_bypassed(point, 1, true).is_err());
+        assert!(router.set_processor_mix(point, 1, 0.5).is_err());
+        assert!(router.reset_processors(point).is_ok());
+    }
+
+    #[test]
+    fn test_bus_chain_mixes_in_topological_order() {
+        use crate::audio::destinations::RingBufferDestination;
+        use crate::audio::sources::SignalGeneratorSource;
+
+        let router = AudioRouter::new(4);
+        router.set_gain_ramp(Duration::ZERO);
+
+        // Master is created before the drum bus that feeds it, so processing
+        // order must come from the routes, not from creation order
+        let master = router.add_bus("Master", 44100, 1).unwrap();
+        let drums = router.add_bus("Drums", 44100, 1).unwrap();
+        let master_bus = router.get_bus(master).unwrap();
+        let drum_bus = router.get_bus(drums).unwrap();
+
+        let kick = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.2; 64],
+            44100.0,
+            false,
+        )));
+        let snare = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.1; 64],
+            44100.0,
+            false,
+        )));
+        let dest = RingBufferDestination::new(44100, 1, 0);
+        let reader = dest.get_reader();
+        let dest_id = router.add_destination(Box::new(dest));
+
+        router.create_route(kick, drum_bus.input, 1.0).unwrap();
+        router.create_route(snare, drum_bus.input, 1.0).unwrap();
+        router
+            .create_route(drum_bus.output, master_bus.input, 0.5)
+            .unwrap();
+        router
+            .create_route(master_bus.output, dest_id, 1.0)
+            .unwrap();
+        router.set_bus_gain(master, 2.0).unwrap();
+
+        router.process().unwrap();
+        let mut output = vec![0.0; 4];
+        assert_eq!(reader.read(&mut output), 4);
+        // (0.2 + 0.1) * 0.5 * 2.0
+        assert!(output.iter().all(|&s| (s - 0.3).abs() < 1e-6));
+        assert!((router.bus_peak(drums).unwrap() - 0.3).abs() < 1e-6);
+        assert!((router.bus_peak(master).unwrap() - 0.3).abs() < 1e-6);
+
+        router.set_bus_muted(drums, true).unwrap();
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert!(output.iter().all(|&s| s == 0.0));
+        assert_eq!(router.bus_peak(drums), Some(0.0));
+
+        let names: Vec<String> = router.get_buses().into_iter().map(|b| b.name).collect();
+        assert_eq!(names, vec!["Master", "Drums"]);
+    }
+
+    #[test]
+    fn test_bus_cycles_rejected() {
+        let router = AudioRouter::new(4);
+        let a = router
+            .get_bus(router.add_bus("A", 44100, 2).unwrap())
+            .unwrap();
+        let b = router
+            .get_bus(router.add_bus("B", 44100, 2).unwrap())
+            .unwrap();
+        let c = router
+            .get_bus(router.add_bus("C", 44100, 2).unwrap())
+            .unwrap();
+
+        // Self-feedback
+        assert!(matches!(
+            router.create_route(a.output, a.input, 1.0),
+            Err(AudioBackendError::RoutingCycle(_))
+        ));
+
+        // A → B → C → A
+        router.create_route(a.output, b.input, 1.0).unwrap();
+        router.create_route(b.output, c.input, 1.0).unwrap();
+        assert!(matches!(
+            router.create_route(c.output, a.input, 1.0),
+            Err(AudioBackendError::RoutingCycle(_))
+        ));
+
+        // Parallel paths are fine
+        router.create_route(a.output, c.input, 1.0).unwrap();
+    }
+
+    #[test]
+    fn test_bus_output_rate_must_match() {
+        let router = AudioRouter::new(4);
+        let bus = router
+            .get_bus(router.add_bus("Bus", 48000, 1).unwrap())
+            .unwrap();
+        let dest = router.add_destination(Box::new(MockDestination::new()));
+
+        assert!(matches!(
+            router.create_route(bus.output, dest, 1.0),
+            Err(AudioBackendError::UnsupportedFormat(_))
+        ));
+        assert!(router.add_bus("Empty", 48000, 0).is_err());
+    }
+
+    #[test]
+    fn test_remove_bus() {
+        let router = AudioRouter::new(4);
+        let id = router.add_bus("Bus", 44100, 1).unwrap();
+        let bus = router.get_bus(id).unwrap();
+        let source = router.add_source(Box::new(MockSource::new(vec![0.5; 16])));
+        let dest = router.add_destination(Box::new(MockDestination::new()));
+        router.create_route(source, bus.input, 1.0).unwrap();
+        router.create_route(bus.output, dest, 1.0).unwrap();
+
+        // Bus endpoints can only be removed with the bus
+        assert!(!router.remove_source(bus.output));
+        assert!(!router.remove_destination(bus.input));
+
+        assert!(router.remove_bus(id));
+        assert!(!router.remove_bus(id));
+        assert!(router.get_routes().is_empty());
+        assert!(!router.get_source_ids().contains(&bus.output));
+        assert!(router.process().is_ok());
+    }
+
+    #[test]
+    fn test_bus_inserts() {
+        use crate::audio::destinations::RingBufferDestination;
+
+        let router = AudioRouter::new(2);
+        let id = router.add_bus("Bus", 44100, 1).unwrap();
+        let bus = router.get_bus(id).unwrap();
+        let source = router.add_source(Box::new(MockSource::new(vec![0.25; 16])));
+        let dest = RingBufferDestination::new(44100, 1, 0);
+        let reader = dest.get_reader();
+        let dest_id = router.add_destination(Box::new(dest));
+        router.create_route(source, bus.input, 1.0).unwrap();
+        router.create_route(bus.output, dest_id, 1.0).unwrap();
+
+        router
+            .set_processor_chain(InsertPoint::Bus(id), vec![Box::new(ScaleProcessor(2.0))])
+            .unwrap();
+        assert_eq!(
+            router.get_processor_chain(InsertPoint::Destination(bus.input)),
+            router.get_processor_chain(InsertPoint::Bus(id))
+        );
+
+        router.process().unwrap();
+        let mut output = vec![0.0; 2];
+        reader.read(&mut output);
+        assert_eq!(output, vec![0.5, 0.5]);
+    }
+
+    /// Router with a 1 kHz mono destination, so a 10 ms ramp is 10 frames
+    fn ramp_test_router() -> (
+        AudioRouter,
+        DestId,
+        crate::audio::destinations::RingBufferReader,
+    ) {
+        use crate::audio::destinations::RingBufferDestination;
+
+        let router = AudioRouter::new(10);
+        router.set_gain_ramp(Duration::from_millis(10));
+        let dest = RingBufferDestination::new(1000, 1, 0);
+        let reader = dest.get_reader();
+        let dest_id = router.add_destination(Box::new(dest));
+        (router, dest_id, reader)
+    }
+
+    fn constant_source(value: f32) -> Box<dyn AudioSource> {
+        Box::new(crate::audio::sources::SignalGeneratorSource::from_buffer(
+            vec![value; 1000],
+            1000.0,
+            false,
+        ))
+    }
+
+    #[test]
+    fn test_route_gain_ramps() {
+        let (router, dest_id, reader) = ramp_test_router();
+        let source_id = router.add_source(constant_source(1.0));
+        let route_id = router.create_route(source_id, dest_id, 0.0).unwrap();
+
+        router.set_route_gain(route_id, 1.0).unwrap();
+        router.process().unwrap();
+        let mut output = vec![0.0; 10];
+        assert_eq!(reader.read(&mut output), 10);
+        for (i, sample) in output.iter().enumerate() {
+            assert!((sample - (i + 1) as f32 / 10.0).abs() < 1e-5);
+        }
+
+        // Settled at the target afterwards
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert_eq!(output, vec![1.0; 10]);
+    }
+
+    #[test]
+    fn test_remove_route_fades_out() {
+        let (router, dest_id, reader) = ramp_test_router();
+        let source_id = router.add_source(constant_source(1.0));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+        router.process().unwrap();
+        let mut output = vec![0.0; 10];
+        reader.read(&mut output);
+
+        assert!(router.remove_route(route_id));
+        assert!(router.get_route(route_id).is_none());
+        assert_eq!(router.control().retiring.len(), 1);
+
+        router.process().unwrap();
+        reader.read(&mut output);
+        for (i, sample) in output.iter().enumerate() {
+            assert!((sample - (9 - i) as f32 / 10.0).abs() < 1e-5);
+        }
+
+        // The processor handed the faded route back and its slot is free
+        assert!(router.control().retiring.is_empty());
+        router.process().unwrap();
+        reader.read(&mut output);
+        assert_eq!(output, vec![0.0; 10]);
+    }
+
+    #[test]
+    fn test_crossfade_routes() {
+        let (router, dest_id, reader) = ramp_test_router();
+        let quiet = router.add_source(constant_source(0.5));
+        let loud = router.add_source(constant_source(1.0));
+        let from = router.create_route(quiet, dest_id, 1.0).unwrap();
+        let to = router.create_route(loud, dest_id, 1.0).unwrap();
+        router.set_route_enabled(to, false).unwrap();
+
+        router.process().unwrap();
+        let mut output = vec![0.0; 10];
+        reader.read(&mut output);
+        assert_eq!(output, vec![0.5; 10]);
+
+        router
+            .crossfade_routes(from, to, Duration::from_millis(10))
+            .unwrap();
+        assert!(router.get_route(from).unwrap().muted);
+        let to_route = router.get_route(to).unwrap();
+        assert!(to_route.enabled && !to_route.muted);
+
+        // Both ramps start on the same sample: 0.5 * (1 - t) + 1.0 * t
+        router.process().unwrap();
+        reader.read(&mut output);
+        for (i, sample) in output.iter().enumerate() {
+            let t = (i + 1) as f32 / 10.0;
+            assert!((sample - (0.5 * (1.0 - t) + t)).abs() < 1e-5);
+        }
+
+        assert!(router
+            .crossfade_routes(from, RouteId(999), Duration::ZERO)
+            .is_err());
+        // A route can't fade into itself; it keeps playing untouched
+        assert!(router.crossfade_routes(to, to, Duration::ZERO).is_err());
+        assert!(!router.get_route(to).unwrap().muted);
+    }
+
+    #[test]
+    fn test_take_processor() {
+        use crate::audio::destinations::RingBufferDestination;
+
+        let router = AudioRouter::new(4);
+        let mut processor = router.take_processor().unwrap();
+        assert!(router.take_processor().is_none());
+        assert!(router.process().is_err());
+
+        // Edits made after the processor was taken still reach it
+        let dest = RingBufferDestination::new(44100, 1, 0);
+        let reader = dest.get_reader();
+        let source_id = router.add_source(Box::new(MockSource::new(vec![0.5; 4])));
+        let dest_id = router.add_destination(Box::new(dest));
+        let route_id = router.create_route(source_id, dest_id, 1.0).unwrap();
+        router.set_gain_ramp(Duration::ZERO);
+        router.set_route_gain(route_id, 0.5).unwrap();
+
+        processor.process().unwrap();
+
+        let mut output = vec![0.0; 4];
+        assert_eq!(reader.read(&mut output), 4);
+        assert_eq!(output, vec![0.25; 4]);
+    }
+
+    #[test]
+    fn test_skipped_blocks_counted() {
+        let router = AudioRouter::new(4);
+        router.process().unwrap();
+        assert_eq!(router.skipped_blocks(), 0);
+
+        let busy = router.processor.lock();
+        router.process().unwrap();
+        drop(busy);
+        assert_eq!(router.skipped_blocks(), 1);
+    }
+
+    #[test]
+    fn test_edits_wait_for_garbage_room() {
+        let router = AudioRouter::new(4);
+        // Every plan replaces one the processor hands back; queue a full
+        // garbage queue's worth without collecting in between
+        for _ in 0..QUEUE_CAPACITY {
+            router.control.lock().compile();
+        }
+        router.process().unwrap();
+        router.control.lock().compile();
+        router.process().unwrap();
+
+        {
+            let control = router.control.lock();
+            assert!(control.garbage_stalled.load(Ordering::Relaxed));
+            assert_eq!(control.commands.slots(), QUEUE_CAPACITY - 1);
+        }
+
+        router.collect_garbage();
+        router.process().unwrap();
+        let control = router.control.lock();
+        assert!(!control.garbage_stalled.load(Ordering::Relaxed));
+        assert_eq!(control.commands.slots(), QUEUE_CAPACITY);
+    }
+
+    #[test]
+    fn test_slot_growth_and_reuse() {
+        let router = AudioRouter::new(64);
+        let dest_id = router.add_destination(Box::new(MockDestination::new()));
+
+        // More sources than the initial slot capacity
+        let source_ids: Vec<SourceId> = (0..INITIAL_NODE_SLOTS * 2 + 1)
+            .map(|_| router.add_source(Box::new(MockSource::new(vec![0.01; 64]))))
+            .collect();
+        for source_id in &source_ids {
+            router.create_route(*source_id, dest_id, 1.0).unwrap();
+        }
+        router.process().unwrap();
+
+        for source_id in source_ids.iter().skip(1) {
+            assert!(router.remove_source(*source_id));
+        }
+        assert_eq!(router.get_routes().len(), 1);
+
+        let source_id = router.add_source(Box::new(MockSource::new(vec![0.02; 64])));
+        router.create_route(source_id, dest_id, 1.0).unwrap();
+        router.process().unwrap();
+        assert_eq!(router.get_routes().len(), 2);
+    }
+
+    #[test]
+    fn test_router_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<AudioRouter>();
+        assert_send::<RouterProcessor>();
+    }
+
     #[test]
     fn test_soft_clip() {
         assert_eq!(soft_clip(0.0), 0.0);
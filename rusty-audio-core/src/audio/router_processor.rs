@@ -0,0 +1,843 @@
+//! Real-time side of the audio router
+//!
+//! [`RouterProcessor`] owns every source, destination, bus and route node and
+//! runs the compiled [`ExecutionPlan`] once per buffer. It never locks and never
+//! allocates while processing:
+//!
+//! - Graph edits arrive as [`RouterCommand`]s over a wait-free `rtrb` ring
+//!   buffer, and everything a command carries is built on the control thread.
+//! - Anything the processor lets go of (removed nodes, replaced buffers, old
+//!   plans) is sent back as [`Garbage`] so it is freed off the audio thread.
+//! - Node storage lives in boxed slots, so inserting and removing nodes only
+//!   moves pointers.
+//! - Route and bus gains move along per-frame [`GainRamp`]s, so fader moves,
+//!   mutes, removals and crossfades never click.
+//!
+//! The control half is [`AudioRouter`](super::router::AudioRouter), which
+//! keeps a shadow copy of the graph for queries and compiles a new plan
+//! whenever the topology changes.
+
+use super::backend::Result;
+use super::channel_matrix::ChannelMatrix;
+use super::effects::ProcessorChain;
+use super::resampler::RouteResampler;
+use super::router::{soft_clip, AudioDestination, AudioSource};
+use rtrb::{Consumer, Producer};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A source plus its preallocated read buffer
+pub(crate) struct SourceNode {
+    /// `None` for a bus output, whose buffer is filled by its bus
+    source: Option<Box<dyn AudioSource>>,
+    buffer: Vec<f32>,
+    samples_read: usize,
+    demand: usize,
+    /// The source reports a length, so it is expected to end
+    finite: bool,
+}
+
+impl SourceNode {
+    pub(crate) fn new(source: Box<dyn AudioSource>, buffer_len: usize) -> Self {
+        Self::with_source(Some(source), buffer_len)
+    }
+
+    /// Output side of a bus
+    pub(crate) fn bus_output(buffer_len: usize) -> Self {
+        Self::with_source(None, buffer_len)
+    }
+
+    fn with_source(source: Option<Box<dyn AudioSource>>, buffer_len: usize) -> Self {
+        Self {
+            finite: source
+                .as_ref()
+                .is_some_and(|source| source.length().is_some()),
+            source,
+            buffer: vec![0.0; buffer_len],
+            samples_read: 0,
+            demand: 0,
+        }
+    }
+
+    /// Check whether this is a finite source that has not ended yet
+    fn is_finite_and_playing(&self) -> bool {
+        self.finite
+            && self
+                .source
+                .as_deref()
+                .is_some_and(|source| source.has_more_samples())
+    }
+
+    fn read(&mut self) {
+        let Some(source) = self.source.as_deref_mut() else {
+            return;
+        };
+        let demand = self.demand.min(self.buffer.len());
+        self.samples_read = match self.buffer.get_mut(..demand) {
+            Some(buffer) => source.read_samples(buffer).min(demand),
+            None => 0,
+        };
+    }
+
+    fn samples(&self) -> &[f32] {
+        self.buffer.get(..self.samples_read).unwrap_or_default()
+    }
+}
+
+/// A destination plus its preallocated mix buffer and insert chain
+pub(crate) struct DestNode {
+    /// `None` for a bus input, which its bus reads instead
+    destination: Option<Box<dyn AudioDestination>>,
+    mix: Vec<f32>,
+    chain: Option<Box<ProcessorChain>>,
+}
+
+impl DestNode {
+    pub(crate) fn new(destination: Box<dyn AudioDestination>, buffer_size: usize) -> Self {
+        Self::with_destination(Some(destination), buffer_size)
+    }
+
+    /// Input side of a bus
+    pub(crate) fn bus_input(buffer_size: usize) -> Self {
+        Self::with_destination(None, buffer_size)
+    }
+
+    fn with_destination(
+        destination: Option<Box<dyn AudioDestination>>,
+        buffer_size: usize,
+    ) -> Self {
+        Self {
+            destination,
+            mix: vec![0.0; buffer_size],
+            chain: None,
+        }
+    }
+}
+
+/// Linear gain ramp, advanced once per frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct GainRamp {
+    current: f32,
+    target: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl GainRamp {
+    pub(crate) fn new(gain: f32) -> Self {
+        Self {
+            current: gain,
+            target: gain,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// Start moving towards `target` over `frames` frames (0 jumps there)
+    fn set(&mut self, target: f32, frames: usize) {
+        self.target = target;
+        if frames == 0 {
+            self.finish();
+        } else {
+            self.step = (target - self.current) / frames as f32;
+            self.remaining = frames;
+        }
+    }
+
+    /// Jump straight to the target
+    fn finish(&mut self) {
+        self.current = self.target;
+        self.remaining = 0;
+    }
+
+    fn is_ramping(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Settled at zero, so mixing can be skipped
+    fn is_silent(&self) -> bool {
+        !self.is_ramping() && self.current == 0.0
+    }
+
+    /// Advance one frame and return the gain for it
+    fn next_frame(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+        self.current
+    }
+}
+
+/// Gain change for one route or bus
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GainChange {
+    pub(crate) slot: usize,
+    pub(crate) gain: f32,
+    pub(crate) ramp_frames: usize,
+}
+
+/// Add `input` frames into `output` frames, scaled by a per-frame gain
+fn mix_ramped(input: &[f32], output: &mut [f32], channels: usize, gain: &mut GainRamp) {
+    if !gain.is_ramping() {
+        let gain = gain.current;
+        for (out, sample) in output.iter_mut().zip(input.iter()) {
+            *out += sample * gain;
+        }
+        return;
+    }
+
+    for (out_frame, in_frame) in output
+        .chunks_exact_mut(channels)
+        .zip(input.chunks_exact(channels))
+    {
+        let gain = gain.next_frame();
+        for (out, sample) in out_frame.iter_mut().zip(in_frame) {
+            *out += sample * gain;
+        }
+    }
+}
+
+/// A bus: moves its input's mix to its output source, through the bus fader
+pub(crate) struct BusNode {
+    /// Destination slot routes into the bus mix into
+    input: usize,
+    /// Source slot routes out of the bus read from
+    output: usize,
+    channels: usize,
+    gain: GainRamp,
+    /// Peak level of the last processed buffer, as `f32` bits
+    peak: Arc<AtomicU32>,
+}
+
+impl BusNode {
+    pub(crate) fn new(
+        input: usize,
+        output: usize,
+        channels: u16,
+        gain: f32,
+        peak: Arc<AtomicU32>,
+    ) -> Self {
+        Self {
+            input,
+            output,
+            channels: channels.max(1) as usize,
+            gain: GainRamp::new(gain),
+            peak,
+        }
+    }
+
+    /// Run the bus inserts and publish the result on the bus output
+    fn run(&mut self, input: &mut DestNode, output: &mut SourceNode) {
+        if let Some(chain) = input.chain.as_deref_mut() {
+            chain.process(&mut input.mix);
+        }
+
+        let len = input.mix.len().min(output.buffer.len());
+        let (Some(mix), Some(buffer)) = (input.mix.get(..len), output.buffer.get_mut(..len)) else {
+            return;
+        };
+        buffer.fill(0.0);
+        mix_ramped(mix, buffer, self.channels, &mut self.gain);
+        output.samples_read = len;
+
+        let peak = buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        self.peak.store(peak.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Sample-rate conversion state for a route, with its output scratch buffer
+pub(crate) struct Conversion {
+    resampler: RouteResampler,
+    scratch: Vec<f32>,
+}
+
+impl Conversion {
+    pub(crate) fn new(resampler: RouteResampler, channels: u16) -> Self {
+        let scratch = vec![0.0; resampler.chunk_frames() * channels as usize];
+        Self { resampler, scratch }
+    }
+
+    pub(crate) fn latency_frames(&self) -> usize {
+        self.resampler.latency_frames()
+    }
+
+    pub(crate) fn latency_ms(&self) -> f32 {
+        self.resampler.latency_ms()
+    }
+}
+
+/// Audio-thread state for a single route
+pub(crate) struct RouteNode {
+    /// Generation of the slot the route was inserted into
+    pub(crate) generation: u64,
+    source: usize,
+    destination: usize,
+    /// Source frames mixed per buffer when no conversion is needed
+    frames: usize,
+    gain: GainRamp,
+    /// Drop the route once its gain has ramped to silence
+    retiring: bool,
+    matrix: ChannelMatrix,
+    conversion: Option<Box<Conversion>>,
+    chain: Option<Box<ProcessorChain>>,
+    /// Destination-format buffer for inserts and gain ramps
+    staging: Vec<f32>,
+}
+
+impl RouteNode {
+    pub(crate) fn new(
+        generation: u64,
+        source: usize,
+        destination: usize,
+        frames: usize,
+        gain: f32,
+        matrix: ChannelMatrix,
+        conversion: Option<Box<Conversion>>,
+    ) -> Self {
+        let staging = vec![0.0; frames * matrix.output_channels() as usize];
+        Self {
+            generation,
+            source,
+            destination,
+            frames,
+            gain: GainRamp::new(gain),
+            retiring: false,
+            matrix,
+            conversion,
+            chain: None,
+            staging,
+        }
+    }
+
+    /// Check whether a retiring route has finished fading out
+    fn is_retired(&self) -> bool {
+        self.retiring && self.gain.is_silent()
+    }
+
+    /// Number of source samples this route wants for the next buffer
+    fn demand(&self) -> usize {
+        match self.conversion.as_deref() {
+            Some(conversion) => conversion.resampler.input_samples_needed(),
+            None => self.frames * self.matrix.input_channels() as usize,
+        }
+    }
+
+    /// Convert, map channels, run inserts and mix `input` into `output`
+    fn mix(&mut self, input: &[f32], output: &mut [f32]) {
+        // Resampling routes must consume their input even when silent so
+        // the FIFO stays in step with the source.
+        let samples = match self.conversion.as_deref_mut() {
+            Some(conversion) => {
+                conversion.resampler.push_input(input);
+                let produced = conversion.resampler.process(&mut conversion.scratch);
+                conversion.scratch.get(..produced).unwrap_or_default()
+            }
+            None => input,
+        };
+
+        if samples.is_empty() {
+            // Nothing audible to ramp over
+            self.gain.finish();
+            return;
+        }
+        if self.gain.is_silent() {
+            return;
+        }
+
+        if self.chain.is_none() && !self.gain.is_ramping() {
+            self.matrix.mix_into(samples, output, self.gain.current);
+            return;
+        }
+
+        // Inserts sit before the route gain, so they see the mapped signal
+        self.staging.fill(0.0);
+        let written = self.matrix.mix_into(samples, &mut self.staging, 1.0);
+        let Some(staged) = self.staging.get_mut(..written) else {
+            return;
+        };
+        if let Some(chain) = self.chain.as_deref_mut() {
+            chain.process(staged);
+        }
+        let channels = self.matrix.output_channels() as usize;
+        mix_ramped(staged, output, channels, &mut self.gain);
+    }
+}
+
+/// Slot storage for every node the processor owns
+///
+/// Slot indices are assigned by the control thread. When it runs out of
+/// slots it sends a larger, empty `Slots` and the processor moves the
+/// existing nodes across.
+pub(crate) struct Slots {
+    sources: Vec<Option<Box<SourceNode>>>,
+    destinations: Vec<Option<Box<DestNode>>>,
+    routes: Vec<Option<Box<RouteNode>>>,
+    buses: Vec<Option<Box<BusNode>>>,
+}
+
+impl Slots {
+    pub(crate) fn with_capacity(capacity: SlotCapacity) -> Self {
+        fn empty<T>(len: usize) -> Vec<Option<T>> {
+            std::iter::repeat_with(|| None).take(len).collect()
+        }
+        Self {
+            sources: empty(capacity.sources),
+            destinations: empty(capacity.destinations),
+            routes: empty(capacity.routes),
+            buses: empty(capacity.buses),
+        }
+    }
+
+    /// Move every node into `target`, which must be at least as large
+    fn move_into(&mut self, target: &mut Slots) {
+        move_slots(&mut self.sources, &mut target.sources);
+        move_slots(&mut self.destinations, &mut target.destinations);
+        move_slots(&mut self.routes, &mut target.routes);
+        move_slots(&mut self.buses, &mut target.buses);
+    }
+}
+
+/// Number of slots of each kind in a [`Slots`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlotCapacity {
+    pub(crate) sources: usize,
+    pub(crate) destinations: usize,
+    pub(crate) routes: usize,
+    pub(crate) buses: usize,
+}
+
+fn move_slots<T>(from: &mut [Option<T>], to: &mut [Option<T>]) {
+    for (from, to) in from.iter_mut().zip(to.iter_mut()) {
+        *to = from.take();
+    }
+}
+
+/// Node an insert chain is attached to
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ChainTarget {
+    Route(usize),
+    Destination(usize),
+}
+
+/// One step of the compiled mixing order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PlanStep {
+    /// Mix a route into its destination or bus
+    Route(usize),
+    /// Finish a bus so routes out of it can read its output
+    Bus(usize),
+}
+
+/// Compiled processing order
+///
+/// Lists the slots that take part in a cycle. Built by the control thread
+/// each time routes or buses are added, removed, enabled or disabled.
+#[derive(Debug, Default)]
+pub(crate) struct ExecutionPlan {
+    /// Sources (not bus outputs) feeding at least one enabled route
+    pub(crate) sources: Vec<usize>,
+    /// Enabled routes and buses in topological order: every route into a
+    /// bus comes before the bus, every route out of it after
+    pub(crate) steps: Vec<PlanStep>,
+    /// Every destination and bus input (those without routes receive silence)
+    pub(crate) destinations: Vec<usize>,
+}
+
+/// Graph edit sent from the control thread to the processor
+pub(crate) enum RouterCommand {
+    InsertSource(usize, Box<SourceNode>),
+    InsertDestination(usize, Box<DestNode>),
+    InsertRoute(usize, Box<RouteNode>),
+    InsertBus(usize, Box<BusNode>),
+    RemoveSource(usize),
+    RemoveDestination(usize),
+    RemoveRoute(usize),
+    RemoveBus(usize),
+    SetRouteGain(GainChange),
+    /// Fade a route out, then drop it and return it as [`Garbage::RetiredRoute`]
+    RetireRoute(GainChange),
+    /// Ramp `from` to its new gain and `to` up from silence in the same frame
+    Crossfade {
+        from: GainChange,
+        to: GainChange,
+    },
+    SetBusGain(GainChange),
+    SetRouteMatrix(usize, Box<ChannelMatrix>),
+    SetRouteConversion(usize, Option<Box<Conversion>>),
+    SetSourceBuffer(usize, Vec<f32>),
+    SetChain(ChainTarget, Option<Box<ProcessorChain>>),
+    SetInsertBypassed(ChainTarget, usize, bool),
+    SetInsertMix(ChainTarget, usize, f32),
+    ResetChain(ChainTarget),
+    GrowSlots(Box<Slots>),
+    SetPlan(Box<ExecutionPlan>),
+}
+
+/// Allocation released by the processor, to be dropped by the control thread
+#[allow(dead_code)] // Payloads are only held so they are dropped on the right thread
+pub(crate) enum Garbage {
+    Source(Box<SourceNode>),
+    Destination(Box<DestNode>),
+    Route(Box<RouteNode>),
+    /// A route that finished fading out; its slot can be reused if the
+    /// route's generation is still the slot's
+    RetiredRoute(usize, Box<RouteNode>),
+    Bus(Box<BusNode>),
+    Matrix(Box<ChannelMatrix>),
+    Conversion(Box<Conversion>),
+    Chain(Box<ProcessorChain>),
+    Buffer(Vec<f32>),
+    Slots(Box<Slots>),
+    Plan(Box<ExecutionPlan>),
+}
+
+/// Real-time half of the audio router
+///
+/// Obtained from [`AudioRouter::take_processor`](super::router::AudioRouter::take_processor)
+/// and moved into the audio callback. [`RouterProcessor::process`] performs
+/// no locking and no heap allocation.
+pub struct RouterProcessor {
+    commands: Consumer<RouterCommand>,
+    garbage: Producer<Garbage>,
+    slots: Box<Slots>,
+    plan: Box<ExecutionPlan>,
+    /// Finite sources still playing after the last buffer
+    finite_sources: Arc<AtomicUsize>,
+    /// Set when work waited because the garbage queue was full
+    garbage_stalled: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for RouterProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouterProcessor")
+            .field(
+                "finite_sources",
+                &self.finite_sources.load(Ordering::Relaxed),
+            )
+            .field(
+                "garbage_stalled",
+                &self.garbage_stalled.load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl RouterProcessor {
+    pub(crate) fn new(
+        commands: Consumer<RouterCommand>,
+        garbage: Producer<Garbage>,
+        slots: Slots,
+        finite_sources: Arc<AtomicUsize>,
+        garbage_stalled: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            commands,
+            garbage,
+            slots: Box::new(slots),
+            plan: Box::default(),
+            finite_sources,
+            garbage_stalled,
+        }
+    }
+
+    /// Process audio routing for one buffer
+    ///
+    /// Applies pending graph edits, reads every active source once, converts
+    /// and mixes each route into its destination or bus in topological
+    /// order, then soft-clips and writes every destination.
+    ///
+    /// # Errors
+    /// Returns the first error reported by a destination's `write_samples`.
+    pub fn process(&mut self) -> Result<()> {
+        self.apply_commands();
+
+        let Slots {
+            sources,
+            destinations,
+            routes,
+            buses,
+        } = &mut *self.slots;
+        let plan = &*self.plan;
+
+        // Each source is read once for proper fan-out, sized for its most
+        // demanding route
+        for &slot in &plan.sources {
+            if let Some(node) = slot_mut(sources, slot) {
+                node.demand = 0;
+            }
+        }
+        for &step in &plan.steps {
+            let PlanStep::Route(slot) = step else {
+                continue;
+            };
+            let Some(route) = slot_ref(routes, slot) else {
+                continue;
+            };
+            if let Some(node) = slot_mut(sources, route.source) {
+                node.demand = node.demand.max(route.demand());
+            }
+        }
+        let mut finite_playing = 0;
+        for &slot in &plan.sources {
+            if let Some(node) = slot_mut(sources, slot) {
+                node.read();
+                finite_playing += usize::from(node.is_finite_and_playing());
+            }
+        }
+        self.finite_sources.store(finite_playing, Ordering::Relaxed);
+
+        for &slot in &plan.destinations {
+            if let Some(node) = slot_mut(destinations, slot) {
+                node.mix.fill(0.0);
+            }
+        }
+
+        for &step in &plan.steps {
+            match step {
+                PlanStep::Route(slot) => {
+                    let Some(route) = slot_mut(routes, slot) else {
+                        continue;
+                    };
+                    let (Some(source), Some(destination)) = (
+                        slot_ref(sources, route.source),
+                        slot_mut(destinations, route.destination),
+                    ) else {
+                        continue;
+                    };
+                    route.mix(source.samples(), &mut destination.mix);
+
+                    // Faded-out routes leave the graph; the control side
+                    // reuses the slot once it collects them. A silent route
+                    // can wait for room in the garbage queue.
+                    if route.is_retired() && has_garbage_room(&self.garbage, &self.garbage_stalled)
+                    {
+                        if let Some(node) = replace_slot(routes, slot, None) {
+                            push_garbage(&mut self.garbage, Garbage::RetiredRoute(slot, node));
+                        }
+                    }
+                }
+                PlanStep::Bus(slot) => {
+                    let Some(bus) = slot_mut(buses, slot) else {
+                        continue;
+                    };
+                    let (Some(input), Some(output)) = (
+                        slot_mut(destinations, bus.input),
+                        slot_mut(sources, bus.output),
+                    ) else {
+                        continue;
+                    };
+                    bus.run(input, output);
+                }
+            }
+        }
+
+        for &slot in &plan.destinations {
+            let Some(node) = slot_mut(destinations, slot) else {
+                continue;
+            };
+            let Some(destination) = node.destination.as_deref_mut() else {
+                continue;
+            };
+            if let Some(chain) = node.chain.as_deref_mut() {
+                chain.process(&mut node.mix);
+            }
+            // Apply soft clipping to prevent severe distortion
+            for sample in node.mix.iter_mut() {
+                *sample = soft_clip(*sample);
+            }
+            destination.write_samples(&node.mix)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply every queued graph edit without processing audio
+    ///
+    /// [`RouterProcessor::process`] does this first on every call. An edit
+    /// can release at most one allocation, so edits stay queued while the
+    /// garbage queue is full instead of freeing memory on this thread.
+    pub fn apply_commands(&mut self) {
+        while has_garbage_room(&self.garbage, &self.garbage_stalled) {
+            let Ok(command) = self.commands.pop() else {
+                break;
+            };
+            match command {
+                RouterCommand::InsertSource(slot, node) => {
+                    let old = replace_slot(&mut self.slots.sources, slot, Some(node));
+                    self.discard_opt(old.map(Garbage::Source));
+                }
+                RouterCommand::InsertDestination(slot, node) => {
+                    let old = replace_slot(&mut self.slots.destinations, slot, Some(node));
+                    self.discard_opt(old.map(Garbage::Destination));
+                }
+                RouterCommand::InsertRoute(slot, node) => {
+                    let old = replace_slot(&mut self.slots.routes, slot, Some(node));
+                    self.discard_opt(old.map(Garbage::Route));
+                }
+                RouterCommand::InsertBus(slot, node) => {
+                    let old = replace_slot(&mut self.slots.buses, slot, Some(node));
+                    self.discard_opt(old.map(Garbage::Bus));
+                }
+                RouterCommand::RemoveSource(slot) => {
+                    let old = replace_slot(&mut self.slots.sources, slot, None);
+                    self.discard_opt(old.map(Garbage::Source));
+                }
+                RouterCommand::RemoveDestination(slot) => {
+                    let old = replace_slot(&mut self.slots.destinations, slot, None);
+                    self.discard_opt(old.map(Garbage::Destination));
+                }
+                RouterCommand::RemoveRoute(slot) => {
+                    let old = replace_slot(&mut self.slots.routes, slot, None);
+                    self.discard_opt(old.map(Garbage::Route));
+                }
+                RouterCommand::RemoveBus(slot) => {
+                    let old = replace_slot(&mut self.slots.buses, slot, None);
+                    self.discard_opt(old.map(Garbage::Bus));
+                }
+                RouterCommand::SetRouteGain(change) => {
+                    if let Some(route) = slot_mut(&mut self.slots.routes, change.slot) {
+                        route.gain.set(change.gain, change.ramp_frames);
+                    }
+                }
+                RouterCommand::RetireRoute(change) => {
+                    if let Some(route) = slot_mut(&mut self.slots.routes, change.slot) {
+                        route.retiring = true;
+                        route.gain.set(change.gain, change.ramp_frames);
+                    }
+                }
+                RouterCommand::Crossfade { from, to } => {
+                    if let Some(route) = slot_mut(&mut self.slots.routes, from.slot) {
+                        route.gain.set(from.gain, from.ramp_frames);
+                    }
+                    if let Some(route) = slot_mut(&mut self.slots.routes, to.slot) {
+                        route.gain.set(0.0, 0);
+                        route.gain.set(to.gain, to.ramp_frames);
+                    }
+                }
+                RouterCommand::SetBusGain(change) => {
+                    if let Some(bus) = slot_mut(&mut self.slots.buses, change.slot) {
+                        bus.gain.set(change.gain, change.ramp_frames);
+                    }
+                }
+                RouterCommand::SetRouteMatrix(slot, mut matrix) => {
+                    if let Some(route) = slot_mut(&mut self.slots.routes, slot) {
+                        std::mem::swap(&mut route.matrix, &mut *matrix);
+                    }
+                    self.discard(Garbage::Matrix(matrix));
+                }
+                RouterCommand::SetRouteConversion(slot, conversion) => {
+                    let old = match slot_mut(&mut self.slots.routes, slot) {
+                        Some(route) => std::mem::replace(&mut route.conversion, conversion),
+                        None => conversion,
+                    };
+                    self.discard_opt(old.map(Garbage::Conversion));
+                }
+                RouterCommand::SetSourceBuffer(slot, mut buffer) => {
+                    if let Some(node) = slot_mut(&mut self.slots.sources, slot) {
+                        std::mem::swap(&mut node.buffer, &mut buffer);
+                        node.samples_read = 0;
+                    }
+                    self.discard(Garbage::Buffer(buffer));
+                }
+                RouterCommand::SetChain(target, chain) => {
+                    let old = match self.chain_slot(target) {
+                        Some(slot) => std::mem::replace(slot, chain),
+                        None => chain,
+                    };
+                    self.discard_opt(old.map(Garbage::Chain));
+                }
+                RouterCommand::SetInsertBypassed(target, index, bypassed) => {
+                    if let Some(chain) = self.chain_slot(target).and_then(|c| c.as_deref_mut()) {
+                        chain.set_bypassed(index, bypassed);
+                    }
+                }
+                RouterCommand::SetInsertMix(target, index, mix) => {
+                    if let Some(chain) = self.chain_slot(target).and_then(|c| c.as_deref_mut()) {
+                        chain.set_mix(index, mix);
+                    }
+                }
+                RouterCommand::ResetChain(target) => {
+                    if let Some(chain) = self.chain_slot(target).and_then(|c| c.as_deref_mut()) {
+                        chain.reset();
+                    }
+                }
+                RouterCommand::GrowSlots(mut slots) => {
+                    self.slots.move_into(&mut slots);
+                    std::mem::swap(&mut self.slots, &mut slots);
+                    self.discard(Garbage::Slots(slots));
+                }
+                RouterCommand::SetPlan(mut plan) => {
+                    std::mem::swap(&mut self.plan, &mut plan);
+                    self.discard(Garbage::Plan(plan));
+                }
+            }
+        }
+    }
+
+    /// Find the insert chain slot for a route or destination
+    fn chain_slot(&mut self, target: ChainTarget) -> Option<&mut Option<Box<ProcessorChain>>> {
+        match target {
+            ChainTarget::Route(slot) => {
+                slot_mut(&mut self.slots.routes, slot).map(|route| &mut route.chain)
+            }
+            ChainTarget::Destination(slot) => {
+                slot_mut(&mut self.slots.destinations, slot).map(|dest| &mut dest.chain)
+            }
+        }
+    }
+
+    /// Hand an allocation back to the control thread for dropping
+    fn discard(&mut self, item: Garbage) {
+        push_garbage(&mut self.garbage, item);
+    }
+
+    fn discard_opt(&mut self, item: Option<Garbage>) {
+        if let Some(item) = item {
+            self.discard(item);
+        }
+    }
+}
+
+/// Check that the garbage queue can take another item, flagging it for the
+/// control side if not
+fn has_garbage_room(garbage: &Producer<Garbage>, stalled: &AtomicBool) -> bool {
+    if garbage.slots() > 0 {
+        return true;
+    }
+    stalled.store(true, Ordering::Relaxed);
+    false
+}
+
+/// Queue an item for the control thread to drop
+///
+/// Callers check [`has_garbage_room`] first. Should the queue be full anyway,
+/// the item is leaked rather than freed on the audio thread.
+fn push_garbage(garbage: &mut Producer<Garbage>, item: Garbage) {
+    if let Err(rtrb::PushError::Full(item)) = garbage.push(item) {
+        std::mem::forget(item);
+    }
+}
+
+fn slot_ref<T>(slots: &[Option<Box<T>>], slot: usize) -> Option<&T> {
+    slots.get(slot).and_then(|node| node.as_deref())
+}
+
+fn slot_mut<T>(slots: &mut [Option<Box<T>>], slot: usize) -> Option<&mut T> {
+    slots.get_mut(slot).and_then(|node| node.as_deref_mut())
+}
+
+fn replace_slot<T>(
+    slots: &mut [Option<Box<T>>],
+    slot: usize,
+    value: Option<Box<T>>,
+) -> Option<Box<T>> {
+    match slots.get_mut(slot) {
+        Some(current) => std::mem::replace(current, value),
+        None => value,
+    }
+}
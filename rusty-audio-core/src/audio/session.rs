@@ -0,0 +1,865 @@
+//! Router session files
+//!
+//! A [`RouterSession`] describes an [`AudioRouter`] graph: its sources,
+//! destinations, buses and routes, with route gains, mute and enable state,
+//! channel matrices and resampler quality. It can be captured from a live
+//! router, saved as TOML or JSON, and instantiated into a new router.
+//!
+//! Sources and destinations are trait objects, so a session records what
+//! each node *is* (a [`NodeKind`]) rather than its state. On load a
+//! [`NodeResolver`] turns every description back into a node; device-backed
+//! nodes are matched by device name. A node that cannot be resolved is
+//! replaced by a silent placeholder with the same format, so the rest of the
+//! graph still loads and saving the session again keeps the original
+//! description.
+//!
+//! Insert chains are not part of a session.
+//!
+//! ```rust,no_run
+//! # use rusty_audio_core::audio::router::AudioRouter;
+//! # use rusty_audio_core::audio::session::{NodeResolver, RouterSession};
+//! # fn example(router: &AudioRouter, resolver: &mut dyn NodeResolver) {
+//! let path = std::path::Path::new("session.toml");
+//! RouterSession::capture(router).save_to_file(path).unwrap();
+//!
+//! let restored = RouterSession::load_from_file(path)
+//!     .unwrap()
+//!     .instantiate(resolver)
+//!     .unwrap();
+//! # }
+//! ```
+
+use super::backend::{AudioBackendError, Result};
+use super::channel_matrix::ChannelMatrix;
+use super::resampler::ResamplerQuality;
+use super::router::{AudioDestination, AudioRouter, AudioSource, BusId, DestId, RouteId, SourceId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::backend::{AudioBackend, AudioConfig, StreamDirection};
+#[cfg(not(target_arch = "wasm32"))]
+use super::device_destination::OutputDeviceDestination;
+#[cfg(not(target_arch = "wasm32"))]
+use super::device_source::InputDeviceSource;
+
+/// Current session format version
+pub const SESSION_VERSION: u32 = 1;
+
+/// What a source or destination is, as recorded in a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NodeKind {
+    /// A hardware input or output, matched by name when loading
+    Device {
+        /// Device name as the backend reports it
+        name: String,
+    },
+    /// Any other node, identified by a name its type reports
+    ///
+    /// The name is part of the session format, so it must not change when
+    /// the type is renamed or moved. Built-in nodes use snake_case names
+    /// such as `signal_generator` or `file_recorder`.
+    Other {
+        /// Stable kind name, such as `file_recorder`
+        kind: String,
+    },
+    /// A node whose type doesn't report a kind; always restored as a
+    /// placeholder
+    Unknown,
+}
+
+impl NodeKind {
+    /// Describe a node by its stable kind name
+    pub fn other(kind: &str) -> Self {
+        Self::Other {
+            kind: kind.to_string(),
+        }
+    }
+}
+
+/// A saved source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSource {
+    /// ID of the source in the saved router
+    pub id: SourceId,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// What the source is
+    pub kind: NodeKind,
+}
+
+/// A saved destination
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionDestination {
+    /// ID of the destination in the saved router
+    pub id: DestId,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// What the destination is
+    pub kind: NodeKind,
+}
+
+/// A saved mix bus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionBus {
+    /// ID of the bus in the saved router
+    pub id: BusId,
+    /// Display name
+    pub name: String,
+    /// Destination side that routes mix into
+    pub input: DestId,
+    /// Source side that routes read the mix from
+    pub output: SourceId,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Fader gain (linear)
+    pub gain: f32,
+    /// Whether the bus is muted
+    pub muted: bool,
+}
+
+/// A saved route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRoute {
+    /// ID of the route in the saved router
+    pub id: RouteId,
+    /// Source the route reads from
+    pub source: SourceId,
+    /// Destination the route mixes into
+    pub destination: DestId,
+    /// Route gain (linear)
+    pub gain: f32,
+    /// Whether the route is processed
+    pub enabled: bool,
+    /// Whether the route is muted
+    pub muted: bool,
+    /// Quality of the resampler used when the rates differ
+    pub resampler_quality: ResamplerQuality,
+    /// Channel matrix rows, one per destination channel
+    pub channel_matrix: Vec<Vec<f32>>,
+}
+
+/// Serializable description of a router graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouterSession {
+    /// Session format version ([`SESSION_VERSION`] when saved)
+    pub version: u32,
+    /// Samples per destination buffer
+    pub buffer_size: usize,
+    /// Gain ramp time in milliseconds
+    pub gain_ramp_ms: f64,
+    /// Sources, excluding bus outputs
+    pub sources: Vec<SessionSource>,
+    /// Destinations, excluding bus inputs
+    pub destinations: Vec<SessionDestination>,
+    /// Mix buses
+    pub buses: Vec<SessionBus>,
+    /// Routes between sources, destinations and buses
+    pub routes: Vec<SessionRoute>,
+}
+
+/// Turns saved node descriptions back into sources and destinations
+///
+/// Returning `None` makes the session load a silent placeholder instead.
+pub trait NodeResolver {
+    /// Create a source for a saved description
+    fn resolve_source(&mut self, _source: &SessionSource) -> Option<Box<dyn AudioSource>> {
+        None
+    }
+
+    /// Create a destination for a saved description
+    fn resolve_destination(
+        &mut self,
+        _destination: &SessionDestination,
+    ) -> Option<Box<dyn AudioDestination>> {
+        None
+    }
+}
+
+/// A session instantiated into a new router
+#[derive(Debug)]
+pub struct RestoredSession {
+    /// The new router
+    pub router: AudioRouter,
+    /// Saved source IDs mapped to the new router's IDs (bus outputs included)
+    pub sources: HashMap<SourceId, SourceId>,
+    /// Saved destination IDs mapped to the new router's IDs (bus inputs included)
+    pub destinations: HashMap<DestId, DestId>,
+    /// Saved bus IDs mapped to the new router's IDs
+    pub buses: HashMap<BusId, BusId>,
+    /// Saved route IDs mapped to the new router's IDs
+    pub routes: HashMap<RouteId, RouteId>,
+    /// New sources standing in for nodes that could not be resolved
+    pub placeholder_sources: Vec<SourceId>,
+    /// New destinations standing in for nodes that could not be resolved
+    pub placeholder_destinations: Vec<DestId>,
+}
+
+impl RouterSession {
+    /// Capture the graph of a live router
+    pub fn capture(router: &AudioRouter) -> Self {
+        let mut source_ids = router.get_source_ids();
+        source_ids.sort();
+        let sources = source_ids
+            .into_iter()
+            .filter_map(|id| {
+                let info = router.get_source_info(id)?;
+                info.bus.is_none().then_some(SessionSource {
+                    id,
+                    sample_rate: info.sample_rate,
+                    channels: info.channels,
+                    kind: info.kind,
+                })
+            })
+            .collect();
+
+        let mut dest_ids = router.get_destination_ids();
+        dest_ids.sort();
+        let destinations = dest_ids
+            .into_iter()
+            .filter_map(|id| {
+                let info = router.get_destination_info(id)?;
+                info.bus.is_none().then_some(SessionDestination {
+                    id,
+                    sample_rate: info.sample_rate,
+                    channels: info.channels,
+                    kind: info.kind,
+                })
+            })
+            .collect();
+
+        let buses = router
+            .get_buses()
+            .into_iter()
+            .map(|bus| SessionBus {
+                id: bus.id,
+                name: bus.name,
+                input: bus.input,
+                output: bus.output,
+                sample_rate: bus.sample_rate,
+                channels: bus.channels,
+                gain: bus.gain,
+                muted: bus.muted,
+            })
+            .collect();
+
+        let mut routes = router.get_routes();
+        routes.sort_by_key(|route| route.id);
+        let routes = routes
+            .into_iter()
+            .map(|route| SessionRoute {
+                id: route.id,
+                source: route.source,
+                destination: route.destination,
+                gain: route.gain,
+                enabled: route.enabled,
+                muted: route.muted,
+                resampler_quality: route.resampler_quality,
+                channel_matrix: route.channel_matrix.rows(),
+            })
+            .collect();
+
+        Self {
+            version: SESSION_VERSION,
+            buffer_size: router.buffer_size(),
+            gain_ramp_ms: router.gain_ramp().as_secs_f64() * 1000.0,
+            sources,
+            destinations,
+            buses,
+            routes,
+        }
+    }
+
+    /// Build a new router from this session
+    ///
+    /// Every source and destination is passed to `resolver`; those it cannot
+    /// create are replaced by silent placeholders and listed in the result.
+    /// A saved channel matrix that no longer fits a resolved node's channel
+    /// count is replaced by the default mapping.
+    ///
+    /// # Errors
+    /// - `Session` if the version is unsupported or a route refers to a node
+    ///   that is not in the session
+    /// - Any error from creating buses or routes
+    pub fn instantiate(&self, resolver: &mut dyn NodeResolver) -> Result<RestoredSession> {
+        if self.version > SESSION_VERSION {
+            return Err(AudioBackendError::Session(format!(
+                "Session version {} is newer than supported version {}",
+                self.version, SESSION_VERSION
+            )));
+        }
+
+        let router = AudioRouter::new(self.buffer_size);
+        // Restore gains and mutes as they were, without ramping into them
+        router.set_gain_ramp(Duration::ZERO);
+
+        let mut sources = HashMap::new();
+        let mut placeholder_sources = Vec::new();
+        for saved in &self.sources {
+            let id = match resolver.resolve_source(saved) {
+                Some(source) => router.add_source(source),
+                None => {
+                    log::warn!(
+                        "Session source {:?} not available; using a placeholder",
+                        saved.kind
+                    );
+                    let id = router.add_source(Box::new(PlaceholderSource::new(
+                        saved.kind.clone(),
+                        saved.sample_rate,
+                        saved.channels,
+                    )));
+                    placeholder_sources.push(id);
+                    id
+                }
+            };
+            sources.insert(saved.id, id);
+        }
+
+        let mut destinations = HashMap::new();
+        let mut placeholder_destinations = Vec::new();
+        for saved in &self.destinations {
+            let id = match resolver.resolve_destination(saved) {
+                Some(destination) => router.add_destination(destination),
+                None => {
+                    log::warn!(
+                        "Session destination {:?} not available; using a placeholder",
+                        saved.kind
+                    );
+                    let id = router.add_destination(Box::new(PlaceholderDestination::new(
+                        saved.kind.clone(),
+                        saved.sample_rate,
+                        saved.channels,
+                    )));
+                    placeholder_destinations.push(id);
+                    id
+                }
+            };
+            destinations.insert(saved.id, id);
+        }
+
+        let mut buses = HashMap::new();
+        for saved in &self.buses {
+            let id = router.add_bus(&saved.name, saved.sample_rate, saved.channels)?;
+            router.set_bus_gain(id, saved.gain)?;
+            router.set_bus_muted(id, saved.muted)?;
+            if let Some(bus) = router.get_bus(id) {
+                sources.insert(saved.output, bus.output);
+                destinations.insert(saved.input, bus.input);
+            }
+            buses.insert(saved.id, id);
+        }
+
+        let mut routes = HashMap::new();
+        for saved in &self.routes {
+            let source = sources.get(&saved.source).copied().ok_or_else(|| {
+                AudioBackendError::Session(format!(
+                    "Route {:?} refers to unknown source {:?}",
+                    saved.id, saved.source
+                ))
+            })?;
+            let destination = destinations
+                .get(&saved.destination)
+                .copied()
+                .ok_or_else(|| {
+                    AudioBackendError::Session(format!(
+                        "Route {:?} refers to unknown destination {:?}",
+                        saved.id, saved.destination
+                    ))
+                })?;
+
+            let id = router.create_route(source, destination, saved.gain)?;
+            if saved.resampler_quality != ResamplerQuality::default() {
+                router.set_route_resampler_quality(id, saved.resampler_quality)?;
+            }
+            let matrix = ChannelMatrix::from_rows(&saved.channel_matrix)
+                .and_then(|matrix| router.set_route_channel_matrix(id, matrix));
+            if let Err(e) = matrix {
+                log::warn!(
+                    "Keeping the default channel matrix for route {:?}: {}",
+                    saved.id,
+                    e
+                );
+            }
+            if saved.muted {
+                router.set_route_muted(id, true)?;
+            }
+            if !saved.enabled {
+                router.set_route_enabled(id, false)?;
+            }
+            routes.insert(saved.id, id);
+        }
+
+        router.set_gain_ramp(Duration::from_secs_f64(self.gain_ramp_ms.max(0.0) / 1000.0));
+
+        Ok(RestoredSession {
+            router,
+            sources,
+            destinations,
+            buses,
+            routes,
+            placeholder_sources,
+            placeholder_destinations,
+        })
+    }
+
+    /// Serialize as TOML
+    ///
+    /// # Errors
+    /// Returns `Session` if serialization fails.
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| AudioBackendError::Session(e.to_string()))
+    }
+
+    /// Parse a TOML session
+    ///
+    /// # Errors
+    /// Returns `Session` if the text is not a valid session.
+    pub fn from_toml_str(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| AudioBackendError::Session(e.to_string()))
+    }
+
+    /// Serialize as pretty-printed JSON
+    ///
+    /// # Errors
+    /// Returns `Session` if serialization fails.
+    pub fn to_json_string(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| AudioBackendError::Session(e.to_string()))
+    }
+
+    /// Parse a JSON session
+    ///
+    /// # Errors
+    /// Returns `Session` if the text is not a valid session.
+    pub fn from_json_str(text: &str) -> Result<Self> {
+        serde_json::from_str(text).map_err(|e| AudioBackendError::Session(e.to_string()))
+    }
+
+    /// Save to a file, as JSON if the extension is `.json` and TOML otherwise
+    ///
+    /// # Errors
+    /// Returns `Session` if serialization or writing fails.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let contents = if is_json(path) {
+            self.to_json_string()?
+        } else {
+            self.to_toml_string()?
+        };
+        fs::write(path, contents).map_err(|e| {
+            AudioBackendError::Session(format!("Failed to write {}: {}", path.display(), e))
+        })
+    }
+
+    /// Load from a file, as JSON if the extension is `.json` and TOML otherwise
+    ///
+    /// # Errors
+    /// Returns `Session` if reading or parsing fails.
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            AudioBackendError::Session(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        if is_json(path) {
+            Self::from_json_str(&contents)
+        } else {
+            Self::from_toml_str(&contents)
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Silent stand-in for a source that could not be restored
+///
+/// Keeps the original description so the node survives a save.
+#[derive(Debug)]
+pub struct PlaceholderSource {
+    kind: NodeKind,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl PlaceholderSource {
+    /// Create a placeholder for `kind` with the saved format
+    pub fn new(kind: NodeKind, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            kind,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Get the description of the missing node
+    pub fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+}
+
+impl AudioSource for PlaceholderSource {
+    fn read_samples(&mut self, buffer: &mut [f32]) -> usize {
+        buffer.fill(0.0);
+        buffer.len()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn node_kind(&self) -> NodeKind {
+        self.kind.clone()
+    }
+}
+
+/// Stand-in for a destination that could not be restored; discards audio
+///
+/// Keeps the original description so the node survives a save.
+#[derive(Debug)]
+pub struct PlaceholderDestination {
+    kind: NodeKind,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl PlaceholderDestination {
+    /// Create a placeholder for `kind` with the saved format
+    pub fn new(kind: NodeKind, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            kind,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Get the description of the missing node
+    pub fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+}
+
+impl AudioDestination for PlaceholderDestination {
+    fn write_samples(&mut self, _buffer: &[f32]) -> Result<()> {
+        Ok(())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn node_kind(&self) -> NodeKind {
+        self.kind.clone()
+    }
+}
+
+/// Resolves [`NodeKind::Device`] nodes by device name on an audio backend
+///
+/// Other node kinds are left unresolved; wrap this in an application
+/// resolver to restore those too.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct DeviceResolver<'a> {
+    backend: &'a mut dyn AudioBackend,
+    buffer_size: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::fmt::Debug for DeviceResolver<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceResolver")
+            .field("buffer_size", &self.buffer_size)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> DeviceResolver<'a> {
+    /// Create a resolver opening devices on `backend` with `buffer_size`
+    /// frames per device buffer
+    pub fn new(backend: &'a mut dyn AudioBackend, buffer_size: usize) -> Self {
+        Self {
+            backend,
+            buffer_size,
+        }
+    }
+
+    /// Find a device ID by device name
+    fn find_device(&self, name: &str, direction: StreamDirection) -> Option<String> {
+        match self.backend.enumerate_devices(direction) {
+            Ok(devices) => devices
+                .into_iter()
+                .find(|device| device.name == name)
+                .map(|device| device.id),
+            Err(e) => {
+                log::warn!("Failed to enumerate {:?} devices: {}", direction, e);
+                None
+            }
+        }
+    }
+
+    fn config(&self, sample_rate: u32, channels: u16) -> AudioConfig {
+        AudioConfig {
+            sample_rate,
+            channels,
+            buffer_size: self.buffer_size,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NodeResolver for DeviceResolver<'_> {
+    fn resolve_source(&mut self, source: &SessionSource) -> Option<Box<dyn AudioSource>> {
+        let NodeKind::Device { name } = &source.kind else {
+            return None;
+        };
+        let device_id = self.find_device(name, StreamDirection::Input)?;
+        let config = self.config(source.sample_rate, source.channels);
+        match InputDeviceSource::new(&mut *self.backend, &device_id, config) {
+            Ok(source) => Some(Box::new(source)),
+            Err(e) => {
+                log::warn!("Failed to open input device {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    fn resolve_destination(
+        &mut self,
+        destination: &SessionDestination,
+    ) -> Option<Box<dyn AudioDestination>> {
+        let NodeKind::Device { name } = &destination.kind else {
+            return None;
+        };
+        let device_id = self.find_device(name, StreamDirection::Output)?;
+        let config = self.config(destination.sample_rate, destination.channels);
+        match OutputDeviceDestination::new(&mut *self.backend, &device_id, config, 0) {
+            Ok(destination) => Some(Box::new(destination)),
+            Err(e) => {
+                log::warn!("Failed to open output device {}: {}", name, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::destinations::RingBufferDestination;
+    use crate::audio::sources::SignalGeneratorSource;
+
+    /// Output device stand-in that discards audio
+    struct FakeDevice {
+        name: &'static str,
+        sample_rate: u32,
+        channels: u16,
+    }
+
+    impl AudioDestination for FakeDevice {
+        fn write_samples(&mut self, _buffer: &[f32]) -> Result<()> {
+            Ok(())
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn channels(&self) -> u16 {
+            self.channels
+        }
+
+        fn node_kind(&self) -> NodeKind {
+            NodeKind::Device {
+                name: self.name.to_string(),
+            }
+        }
+    }
+
+    /// Restores signal generators as constant 0.5 and nothing else
+    struct GeneratorResolver;
+
+    impl NodeResolver for GeneratorResolver {
+        fn resolve_source(&mut self, source: &SessionSource) -> Option<Box<dyn AudioSource>> {
+            (source.kind == NodeKind::other("signal_generator")).then(|| {
+                Box::new(SignalGeneratorSource::from_buffer(
+                    vec![0.5; 4096],
+                    source.sample_rate as f32,
+                    true,
+                )) as Box<dyn AudioSource>
+            })
+        }
+    }
+
+    fn build_router() -> (AudioRouter, RouteId) {
+        let router = AudioRouter::new(8);
+        let tone = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.5; 4096],
+            48000.0,
+            true,
+        )));
+        let speakers = router.add_destination(Box::new(FakeDevice {
+            name: "Speakers",
+            sample_rate: 48000,
+            channels: 2,
+        }));
+        let bus = router.add_bus("Music", 48000, 2).unwrap();
+        let bus = router.get_bus(bus).unwrap();
+        router.set_bus_gain(bus.id, 0.8).unwrap();
+
+        let direct = router.create_route(tone, speakers, 0.25).unwrap();
+        router.set_route_muted(direct, true).unwrap();
+        router
+            .set_route_channel_matrix(direct, ChannelMatrix::pick(1, &[Some(0), None]).unwrap())
+            .unwrap();
+        router.create_route(tone, bus.input, 1.0).unwrap();
+        router.create_route(bus.output, speakers, 0.5).unwrap();
+        (router, direct)
+    }
+
+    #[test]
+    fn test_capture_and_text_round_trip() {
+        let (router, direct) = build_router();
+        let session = RouterSession::capture(&router);
+
+        assert_eq!(session.sources.len(), 1);
+        assert_eq!(
+            session.destinations[0].kind,
+            NodeKind::Device {
+                name: "Speakers".to_string()
+            }
+        );
+        assert_eq!(session.buses.len(), 1);
+        assert_eq!(session.routes.len(), 3);
+        let route = &session.routes[0];
+        assert_eq!(route.id, direct);
+        assert!(route.muted);
+        assert_eq!(route.channel_matrix, vec![vec![1.0], vec![0.0]]);
+
+        let toml = session.to_toml_string().unwrap();
+        assert_eq!(RouterSession::from_toml_str(&toml).unwrap(), session);
+        let json = session.to_json_string().unwrap();
+        assert_eq!(RouterSession::from_json_str(&json).unwrap(), session);
+    }
+
+    #[test]
+    fn test_instantiate_with_placeholders() {
+        let (router, direct) = build_router();
+        let session = RouterSession::capture(&router);
+
+        let restored = session.instantiate(&mut GeneratorResolver).unwrap();
+        assert!(restored.placeholder_sources.is_empty());
+        assert_eq!(restored.placeholder_destinations.len(), 1);
+
+        let new_router = &restored.router;
+        let speakers = restored.placeholder_destinations[0];
+        assert_eq!(
+            new_router.get_destination_info(speakers).unwrap().kind,
+            NodeKind::Device {
+                name: "Speakers".to_string()
+            }
+        );
+        let route = new_router.get_route(restored.routes[&direct]).unwrap();
+        assert!(route.muted);
+        assert_eq!(route.gain, 0.25);
+        assert_eq!(route.channel_matrix.gain(1, 0), 0.0);
+        assert_eq!(new_router.get_buses()[0].gain, 0.8);
+        assert_eq!(new_router.gain_ramp(), router.gain_ramp());
+
+        // Saving the restored router keeps the missing device's description
+        let resaved = RouterSession::capture(new_router);
+        assert_eq!(resaved.destinations[0].kind, session.destinations[0].kind);
+        assert_eq!(resaved.routes.len(), session.routes.len());
+    }
+
+    #[test]
+    fn test_instantiated_graph_processes_audio() {
+        let router = AudioRouter::new(4);
+        let tone = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+            vec![0.5; 64],
+            44100.0,
+            true,
+        )));
+        let monitor = router.add_destination(Box::new(FakeDevice {
+            name: "Monitor",
+            sample_rate: 44100,
+            channels: 1,
+        }));
+        router.create_route(tone, monitor, 0.5).unwrap();
+        let session = RouterSession::capture(&router);
+
+        // Swap the missing device for a destination the test can read
+        struct ReaderResolver(Option<RingBufferDestination>);
+        impl NodeResolver for ReaderResolver {
+            fn resolve_source(&mut self, source: &SessionSource) -> Option<Box<dyn AudioSource>> {
+                GeneratorResolver.resolve_source(source)
+            }
+
+            fn resolve_destination(
+                &mut self,
+                _destination: &SessionDestination,
+            ) -> Option<Box<dyn AudioDestination>> {
+                self.0
+                    .take()
+                    .map(|dest| Box::new(dest) as Box<dyn AudioDestination>)
+            }
+        }
+        let dest = RingBufferDestination::new(44100, 1, 0);
+        let reader = dest.get_reader();
+
+        let restored = session
+            .instantiate(&mut ReaderResolver(Some(dest)))
+            .unwrap();
+        restored.router.process().unwrap();
+        let mut output = vec![0.0; 4];
+        assert_eq!(reader.read(&mut output), 4);
+        assert_eq!(output, vec![0.25; 4]);
+    }
+
+    #[test]
+    fn test_rejects_bad_sessions() {
+        let (router, _) = build_router();
+        let mut session = RouterSession::capture(&router);
+
+        session.version = SESSION_VERSION + 1;
+        assert!(session.instantiate(&mut GeneratorResolver).is_err());
+
+        session.version = SESSION_VERSION;
+        session.sources.clear();
+        assert!(matches!(
+            session.instantiate(&mut GeneratorResolver),
+            Err(AudioBackendError::Session(_))
+        ));
+
+        assert!(RouterSession::from_toml_str("version = \"one\"").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let (router, _) = build_router();
+        let session = RouterSession::capture(&router);
+        let dir = tempfile::tempdir().unwrap();
+
+        for name in ["session.toml", "session.json"] {
+            let path = dir.path().join(name);
+            session.save_to_file(&path).unwrap();
+            assert_eq!(RouterSession::load_from_file(&path).unwrap(), session);
+        }
+    }
+}
@@ -5,6 +5,7 @@
 
 use super::backend::{AudioBackendError, Result};
 use super::router::AudioSource;
+use super::session::NodeKind;
 use parking_lot::Mutex;
 use std::sync::Arc;
 
@@ -134,6 +135,10 @@ impl AudioSource for SignalGeneratorSource {
     fn length(&self) -> Option<u64> {
         Some(self.samples.len() as u64)
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("signal_generator")
+    }
 }
 
 /// Ring buffer source
@@ -215,6 +220,10 @@ impl AudioSource for RingBufferSource {
     fn has_more_samples(&self) -> bool {
         true // Continuous stream
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("ring_buffer_source")
+    }
 }
 
 /// Silence source
@@ -252,6 +261,10 @@ impl AudioSource for SilenceSource {
     fn has_more_samples(&self) -> bool {
         true
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("silence")
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,1016 @@
+//! Virtual audio backend for hardware-free testing
+//!
+//! [`VirtualBackend`] implements [`AudioBackend`] with fake input and output
+//! devices. Inputs play a generator or the contents of a WAV file; outputs
+//! capture what the stream callback produced into memory or a WAV file.
+//!
+//! Nothing runs on its own: stream callbacks are driven by a shared
+//! [`VirtualClock`], and only when a test advances it. Every playing stream
+//! runs one callback per buffer that fits into the advanced time, in time
+//! order (creation order for ties), on the thread calling
+//! [`VirtualClock::advance`]. The same test therefore always sees the same
+//! callbacks with the same data.
+//!
+//! ```rust,no_run
+//! # use rusty_audio_core::audio::{AudioBackend, AudioConfig, VirtualBackend, VirtualCapture,
+//! #     VirtualOutputDevice, CaptureBuffer};
+//! # use std::time::Duration;
+//! # fn example() -> rusty_audio_core::audio::Result<()> {
+//! let capture = CaptureBuffer::new();
+//! let mut backend = VirtualBackend::empty().with_output(VirtualOutputDevice::new(
+//!     "Speakers",
+//!     48000,
+//!     2,
+//!     VirtualCapture::Memory(capture.clone()),
+//! ));
+//! let config = AudioConfig { sample_rate: 48000, buffer_size: 480, ..AudioConfig::default() };
+//! let mut stream = backend.create_output_stream_with_callback(
+//!     "Speakers",
+//!     config,
+//!     Box::new(|buffer| buffer.fill(0.5)),
+//! )?;
+//! stream.play()?;
+//! backend.clock().advance(Duration::from_millis(100))?;
+//! assert_eq!(capture.len(), 4800 * 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::backend::{
+    AudioBackend, AudioBackendError, AudioConfig, AudioStream, DeviceInfo, InputCallback,
+    OutputCallback, Result, StreamDirection, StreamStatus,
+};
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// Name of the input device [`VirtualBackend::new`] provides
+pub const DEFAULT_VIRTUAL_INPUT: &str = "Virtual Input";
+
+/// Name of the output device [`VirtualBackend::new`] provides
+pub const DEFAULT_VIRTUAL_OUTPUT: &str = "Virtual Output";
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Signal produced by a virtual input device
+#[derive(Debug, Clone)]
+pub enum VirtualSignal {
+    /// All zeros
+    Silence,
+    /// The same sine wave on every channel
+    Sine {
+        /// Frequency in Hz
+        frequency: f32,
+        /// Peak amplitude (0.0 to 1.0)
+        amplitude: f32,
+    },
+    /// Interleaved samples, followed by silence unless `looping`
+    ///
+    /// Stream channel `c` plays sample channel `c % channels`, so a mono
+    /// signal feeds every channel of a stereo stream.
+    Samples {
+        /// Interleaved samples
+        samples: Arc<[f32]>,
+        /// Channels interleaved in `samples`
+        channels: u16,
+        /// Whether to start over once the samples run out
+        looping: bool,
+    },
+}
+
+impl VirtualSignal {
+    /// Load a WAV file as a [`VirtualSignal::Samples`] signal
+    ///
+    /// Returns the signal with the file's sample rate and channel count.
+    ///
+    /// # Errors
+    /// `DeviceUnavailable` if the file cannot be opened or decoded
+    pub fn from_wav(path: impl AsRef<Path>, looping: bool) -> Result<(Self, u32, u16)> {
+        let path = path.as_ref();
+        let read_error = |e: hound::Error| {
+            AudioBackendError::DeviceUnavailable(format!("Cannot read {}: {}", path.display(), e))
+        };
+        let mut reader = hound::WavReader::open(path).map_err(read_error)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(read_error)?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|s| s as f32 * scale))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(read_error)?
+            }
+        };
+
+        let signal = Self::Samples {
+            samples: samples.into(),
+            channels: spec.channels,
+            looping,
+        };
+        Ok((signal, spec.sample_rate, spec.channels))
+    }
+}
+
+/// Shared in-memory capture of an output device
+///
+/// Clones share the same buffer, so a test keeps one and hands the other to
+/// [`VirtualCapture::Memory`].
+#[derive(Debug, Clone, Default)]
+pub struct CaptureBuffer {
+    samples: Arc<Mutex<Vec<f32>>>,
+}
+
+impl CaptureBuffer {
+    /// Create an empty capture buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of everything captured so far (interleaved)
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples.lock().clone()
+    }
+
+    /// Take everything captured so far, leaving the buffer empty
+    pub fn take(&self) -> Vec<f32> {
+        std::mem::take(&mut *self.samples.lock())
+    }
+
+    /// Number of samples captured
+    pub fn len(&self) -> usize {
+        self.samples.lock().len()
+    }
+
+    /// Check if nothing has been captured
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discard everything captured so far
+    pub fn clear(&self) {
+        self.samples.lock().clear();
+    }
+
+    fn extend(&self, samples: &[f32]) {
+        self.samples.lock().extend_from_slice(samples);
+    }
+}
+
+/// Where a virtual output device puts its audio
+#[derive(Debug, Clone)]
+pub enum VirtualCapture {
+    /// Drop it
+    Discard,
+    /// Append it to a shared buffer
+    Memory(CaptureBuffer),
+    /// Write a 32-bit float WAV file per stream, finalized when the stream
+    /// stops or is dropped
+    WavFile(PathBuf),
+}
+
+/// A fake input device
+#[derive(Debug, Clone)]
+pub struct VirtualInputDevice {
+    /// Device name, which is also its ID
+    pub name: String,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// What the device plays
+    pub signal: VirtualSignal,
+}
+
+impl VirtualInputDevice {
+    /// Create an input device playing `signal`
+    pub fn new(name: &str, sample_rate: u32, channels: u16, signal: VirtualSignal) -> Self {
+        Self {
+            name: name.to_string(),
+            sample_rate,
+            channels,
+            signal,
+        }
+    }
+
+    /// Create an input device playing a WAV file, at the file's sample rate
+    /// and channel count
+    ///
+    /// # Errors
+    /// `DeviceUnavailable` if the file cannot be opened or decoded
+    pub fn from_wav(name: &str, path: impl AsRef<Path>, looping: bool) -> Result<Self> {
+        let (signal, sample_rate, channels) = VirtualSignal::from_wav(path, looping)?;
+        Ok(Self::new(name, sample_rate, channels, signal))
+    }
+}
+
+/// A fake output device
+#[derive(Debug, Clone)]
+pub struct VirtualOutputDevice {
+    /// Device name, which is also its ID
+    pub name: String,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Where the device puts what it's given
+    pub capture: VirtualCapture,
+}
+
+impl VirtualOutputDevice {
+    /// Create an output device capturing to `capture`
+    pub fn new(name: &str, sample_rate: u32, channels: u16, capture: VirtualCapture) -> Self {
+        Self {
+            name: name.to_string(),
+            sample_rate,
+            channels,
+            capture,
+        }
+    }
+}
+
+/// Deterministic clock driving the streams of a [`VirtualBackend`]
+///
+/// Clones share the same time and streams.
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+#[derive(Default)]
+struct ClockState {
+    now: Duration,
+    streams: Vec<Weak<Mutex<StreamCore>>>,
+}
+
+impl VirtualClock {
+    /// Create a clock at time zero with no streams
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Virtual time elapsed so far
+    pub fn now(&self) -> Duration {
+        self.state.lock().now
+    }
+
+    /// Move time forward, running every stream callback that falls due
+    ///
+    /// A callback is due once the end of its buffer lies within the elapsed
+    /// time. Callbacks run on the calling thread and must not advance the
+    /// clock themselves.
+    ///
+    /// # Errors
+    /// `StreamError` if an output stream fails to write its capture file.
+    /// Time still advances; the remaining callbacks run on the next call.
+    pub fn advance(&self, duration: Duration) -> Result<()> {
+        let (now, streams) = {
+            let mut state = self.state.lock();
+            state.now += duration;
+            state.streams.retain(|stream| stream.strong_count() > 0);
+            let streams: Vec<_> = state.streams.iter().filter_map(Weak::upgrade).collect();
+            (state.now, streams)
+        };
+        let now_nanos = now.as_nanos();
+
+        loop {
+            // Earliest due buffer across all playing streams
+            let mut next: Option<(u128, &Arc<Mutex<StreamCore>>)> = None;
+            for stream in &streams {
+                let core = stream.lock();
+                if core.status != StreamStatus::Playing {
+                    continue;
+                }
+                let due = core.next_buffer_end_nanos();
+                if due <= now_nanos && next.is_none_or(|(earliest, _)| due < earliest) {
+                    next = Some((due, stream));
+                }
+            }
+            match next {
+                Some((_, stream)) => stream.lock().run_cycle()?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Move time forward by `frames` at `sample_rate`
+    ///
+    /// # Errors
+    /// See [`VirtualClock::advance`]
+    pub fn advance_frames(&self, frames: u64, sample_rate: u32) -> Result<()> {
+        let nanos = u128::from(frames) * NANOS_PER_SECOND / u128::from(sample_rate.max(1));
+        self.advance(Duration::from_nanos(nanos as u64))
+    }
+
+    fn register(&self, stream: &Arc<Mutex<StreamCore>>) {
+        self.state.lock().streams.push(Arc::downgrade(stream));
+    }
+}
+
+impl std::fmt::Debug for VirtualClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualClock")
+            .field("now", &self.now())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Audio backend with fake devices and a virtual clock
+///
+/// Device IDs are the device names.
+#[derive(Debug)]
+pub struct VirtualBackend {
+    inputs: Vec<VirtualInputDevice>,
+    outputs: Vec<VirtualOutputDevice>,
+    clock: VirtualClock,
+}
+
+impl VirtualBackend {
+    /// Create a backend with a stereo 48 kHz input playing a 440 Hz sine
+    /// and a stereo 48 kHz output capturing to memory
+    ///
+    /// The output capture is available through [`VirtualBackend::capture`].
+    pub fn new() -> Self {
+        Self::empty()
+            .with_input(VirtualInputDevice::new(
+                DEFAULT_VIRTUAL_INPUT,
+                48000,
+                2,
+                VirtualSignal::Sine {
+                    frequency: 440.0,
+                    amplitude: 0.5,
+                },
+            ))
+            .with_output(VirtualOutputDevice::new(
+                DEFAULT_VIRTUAL_OUTPUT,
+                48000,
+                2,
+                VirtualCapture::Memory(CaptureBuffer::new()),
+            ))
+    }
+
+    /// Create a backend without any devices
+    pub fn empty() -> Self {
+        Self {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            clock: VirtualClock::new(),
+        }
+    }
+
+    /// Add an input device, replacing any input with the same name
+    ///
+    /// The first input device is the default.
+    pub fn with_input(mut self, device: VirtualInputDevice) -> Self {
+        match self.inputs.iter_mut().find(|d| d.name == device.name) {
+            Some(existing) => *existing = device,
+            None => self.inputs.push(device),
+        }
+        self
+    }
+
+    /// Add an output device, replacing any output with the same name
+    ///
+    /// The first output device is the default.
+    pub fn with_output(mut self, device: VirtualOutputDevice) -> Self {
+        match self.outputs.iter_mut().find(|d| d.name == device.name) {
+            Some(existing) => *existing = device,
+            None => self.outputs.push(device),
+        }
+        self
+    }
+
+    /// The clock driving this backend's streams
+    pub fn clock(&self) -> VirtualClock {
+        self.clock.clone()
+    }
+
+    /// The memory capture of an output device, if it captures to memory
+    pub fn capture(&self, device_id: &str) -> Option<CaptureBuffer> {
+        match &self.find_output(device_id).ok()?.capture {
+            VirtualCapture::Memory(buffer) => Some(buffer.clone()),
+            _ => None,
+        }
+    }
+
+    fn find_input(&self, device_id: &str) -> Result<&VirtualInputDevice> {
+        self.inputs
+            .iter()
+            .find(|d| d.name == device_id)
+            .ok_or_else(|| AudioBackendError::DeviceNotFound(device_id.to_string()))
+    }
+
+    fn find_output(&self, device_id: &str) -> Result<&VirtualOutputDevice> {
+        self.outputs
+            .iter()
+            .find(|d| d.name == device_id)
+            .ok_or_else(|| AudioBackendError::DeviceNotFound(device_id.to_string()))
+    }
+
+    fn device_info(
+        name: &str,
+        sample_rate: u32,
+        channels: u16,
+        direction: StreamDirection,
+        is_default: bool,
+    ) -> DeviceInfo {
+        let (max_input_channels, max_output_channels) = match direction {
+            StreamDirection::Input => (channels, 0),
+            StreamDirection::Output => (0, channels),
+        };
+        DeviceInfo {
+            id: name.to_string(),
+            name: name.to_string(),
+            is_default,
+            supported_configs: vec![AudioConfig {
+                sample_rate,
+                channels,
+                ..AudioConfig::default()
+            }],
+            min_sample_rate: sample_rate,
+            max_sample_rate: sample_rate,
+            max_input_channels,
+            max_output_channels,
+        }
+    }
+
+    /// Check a stream config against a device like real hardware would
+    fn check_config(
+        name: &str,
+        sample_rate: u32,
+        channels: u16,
+        config: &AudioConfig,
+    ) -> Result<()> {
+        if config.sample_rate != sample_rate {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "{} runs at {} Hz, not {} Hz",
+                name, sample_rate, config.sample_rate
+            )));
+        }
+        if config.channels == 0 || config.channels > channels {
+            return Err(AudioBackendError::UnsupportedFormat(format!(
+                "{} has {} channels, {} requested",
+                name, channels, config.channels
+            )));
+        }
+        if config.buffer_size == 0 {
+            return Err(AudioBackendError::UnsupportedFormat(
+                "Buffer size must be at least one frame".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn open_input(
+        &self,
+        device_id: &str,
+        config: AudioConfig,
+        callback: Option<InputCallback>,
+    ) -> Result<Box<dyn AudioStream>> {
+        let device = self.find_input(device_id)?;
+        Self::check_config(&device.name, device.sample_rate, device.channels, &config)?;
+        let kind = StreamKind::Input {
+            signal: SignalPlayer::new(device.signal.clone(), config.sample_rate),
+            callback,
+        };
+        Ok(Box::new(VirtualStream::new(&self.clock, config, kind)))
+    }
+
+    fn open_output(
+        &self,
+        device_id: &str,
+        config: AudioConfig,
+        callback: Option<OutputCallback>,
+    ) -> Result<Box<dyn AudioStream>> {
+        let device = self.find_output(device_id)?;
+        Self::check_config(&device.name, device.sample_rate, device.channels, &config)?;
+        let sink = CaptureSink::open(&device.capture, &config)?;
+        let kind = StreamKind::Output { callback, sink };
+        Ok(Box::new(VirtualStream::new(&self.clock, config, kind)))
+    }
+}
+
+impl Default for VirtualBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for VirtualBackend {
+    fn name(&self) -> &'static str {
+        "virtual"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn enumerate_devices(&self, direction: StreamDirection) -> Result<Vec<DeviceInfo>> {
+        let devices = match direction {
+            StreamDirection::Input => self
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(i, d)| {
+                    Self::device_info(&d.name, d.sample_rate, d.channels, direction, i == 0)
+                })
+                .collect(),
+            StreamDirection::Output => self
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(i, d)| {
+                    Self::device_info(&d.name, d.sample_rate, d.channels, direction, i == 0)
+                })
+                .collect(),
+        };
+        Ok(devices)
+    }
+
+    fn default_device(&self, direction: StreamDirection) -> Result<DeviceInfo> {
+        self.enumerate_devices(direction)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AudioBackendError::DeviceNotFound(format!("No virtual {:?} device", direction))
+            })
+    }
+
+    fn test_device(&self, device_id: &str) -> Result<bool> {
+        Ok(self.find_output(device_id).is_ok() || self.find_input(device_id).is_ok())
+    }
+
+    fn supported_configs(
+        &self,
+        device_id: &str,
+        direction: StreamDirection,
+    ) -> Result<Vec<AudioConfig>> {
+        let info = match direction {
+            StreamDirection::Input => {
+                let d = self.find_input(device_id)?;
+                Self::device_info(&d.name, d.sample_rate, d.channels, direction, false)
+            }
+            StreamDirection::Output => {
+                let d = self.find_output(device_id)?;
+                Self::device_info(&d.name, d.sample_rate, d.channels, direction, false)
+            }
+        };
+        Ok(info.supported_configs)
+    }
+
+    fn create_output_stream(
+        &mut self,
+        device_id: &str,
+        config: AudioConfig,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.open_output(device_id, config, None)
+    }
+
+    fn create_input_stream(
+        &mut self,
+        device_id: &str,
+        config: AudioConfig,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.open_input(device_id, config, None)
+    }
+
+    fn create_output_stream_with_callback(
+        &mut self,
+        device_id: &str,
+        config: AudioConfig,
+        callback: OutputCallback,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.open_output(device_id, config, Some(callback))
+    }
+
+    fn create_input_stream_with_callback(
+        &mut self,
+        device_id: &str,
+        config: AudioConfig,
+        callback: InputCallback,
+    ) -> Result<Box<dyn AudioStream>> {
+        self.open_input(device_id, config, Some(callback))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Plays a [`VirtualSignal`] into interleaved buffers
+struct SignalPlayer {
+    signal: VirtualSignal,
+    sample_rate: u32,
+    /// Frames produced since the last rewind
+    position: u64,
+}
+
+impl SignalPlayer {
+    fn new(signal: VirtualSignal, sample_rate: u32) -> Self {
+        Self {
+            signal,
+            sample_rate,
+            position: 0,
+        }
+    }
+
+    fn fill(&mut self, buffer: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        match &self.signal {
+            VirtualSignal::Silence => buffer.fill(0.0),
+            VirtualSignal::Sine {
+                frequency,
+                amplitude,
+            } => {
+                let step = f64::from(*frequency) / f64::from(self.sample_rate.max(1));
+                for (i, frame) in buffer.chunks_mut(channels).enumerate() {
+                    let phase = ((self.position + i as u64) as f64 * step).fract();
+                    let value = amplitude * (std::f64::consts::TAU * phase).sin() as f32;
+                    frame.fill(value);
+                }
+            }
+            VirtualSignal::Samples {
+                samples,
+                channels: signal_channels,
+                looping,
+            } => {
+                let signal_channels = usize::from((*signal_channels).max(1));
+                let signal_frames = (samples.len() / signal_channels) as u64;
+                for (i, frame) in buffer.chunks_mut(channels).enumerate() {
+                    let mut index = self.position + i as u64;
+                    if *looping && signal_frames > 0 {
+                        index %= signal_frames;
+                    }
+                    if index >= signal_frames {
+                        frame.fill(0.0);
+                        continue;
+                    }
+                    let start = index as usize * signal_channels;
+                    for (c, sample) in frame.iter_mut().enumerate() {
+                        *sample = samples
+                            .get(start + c % signal_channels)
+                            .copied()
+                            .unwrap_or(0.0);
+                    }
+                }
+            }
+        }
+        self.position += (buffer.len() / channels) as u64;
+    }
+}
+
+/// Destination of an output stream's audio
+enum CaptureSink {
+    Discard,
+    Memory(CaptureBuffer),
+    Wav(Option<hound::WavWriter<BufWriter<File>>>),
+}
+
+impl CaptureSink {
+    fn open(capture: &VirtualCapture, config: &AudioConfig) -> Result<Self> {
+        Ok(match capture {
+            VirtualCapture::Discard => Self::Discard,
+            VirtualCapture::Memory(buffer) => Self::Memory(buffer.clone()),
+            VirtualCapture::WavFile(path) => {
+                let spec = hound::WavSpec {
+                    channels: config.channels,
+                    sample_rate: config.sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                };
+                let writer = hound::WavWriter::create(path, spec).map_err(|e| {
+                    AudioBackendError::DeviceUnavailable(format!(
+                        "Cannot create {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                Self::Wav(Some(writer))
+            }
+        })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        match self {
+            Self::Discard => {}
+            Self::Memory(buffer) => buffer.extend(samples),
+            Self::Wav(Some(writer)) => {
+                for &sample in samples {
+                    writer.write_sample(sample).map_err(|e| {
+                        AudioBackendError::StreamError(format!("Capture write failed: {}", e))
+                    })?;
+                }
+            }
+            Self::Wav(None) => {}
+        }
+        Ok(())
+    }
+
+    /// Finish a WAV capture; later writes are dropped
+    fn finalize(&mut self) -> Result<()> {
+        if let Self::Wav(writer) = self {
+            if let Some(writer) = writer.take() {
+                writer.finalize().map_err(|e| {
+                    AudioBackendError::StreamError(format!("Capture finalize failed: {}", e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum StreamKind {
+    Input {
+        signal: SignalPlayer,
+        callback: Option<InputCallback>,
+    },
+    Output {
+        callback: Option<OutputCallback>,
+        sink: CaptureSink,
+    },
+}
+
+/// Stream state shared between a [`VirtualStream`] and the clock
+struct StreamCore {
+    kind: StreamKind,
+    status: StreamStatus,
+    sample_rate: u32,
+    channels: usize,
+    buffer_frames: u64,
+    /// Stream position on the clock, in frames
+    frames: u64,
+    buffer: Vec<f32>,
+}
+
+impl StreamCore {
+    /// Clock time at which the next buffer is complete
+    fn next_buffer_end_nanos(&self) -> u128 {
+        let end = u128::from(self.frames + self.buffer_frames);
+        (end * NANOS_PER_SECOND).div_ceil(u128::from(self.sample_rate.max(1)))
+    }
+
+    fn run_cycle(&mut self) -> Result<()> {
+        self.frames += self.buffer_frames;
+        match &mut self.kind {
+            StreamKind::Input { signal, callback } => {
+                signal.fill(&mut self.buffer, self.channels);
+                if let Some(callback) = callback {
+                    callback(&self.buffer);
+                }
+                Ok(())
+            }
+            StreamKind::Output { callback, sink } => {
+                self.buffer.fill(0.0);
+                if let Some(callback) = callback {
+                    callback(&mut self.buffer);
+                }
+                sink.write(&self.buffer)
+            }
+        }
+    }
+}
+
+/// Stream on a virtual device
+struct VirtualStream {
+    core: Arc<Mutex<StreamCore>>,
+    clock: VirtualClock,
+    config: AudioConfig,
+}
+
+impl VirtualStream {
+    fn new(clock: &VirtualClock, config: AudioConfig, kind: StreamKind) -> Self {
+        let channels = usize::from(config.channels);
+        let core = Arc::new(Mutex::new(StreamCore {
+            kind,
+            status: StreamStatus::Stopped,
+            sample_rate: config.sample_rate,
+            channels,
+            buffer_frames: config.buffer_size as u64,
+            frames: 0,
+            buffer: vec![0.0; config.buffer_size * channels],
+        }));
+        clock.register(&core);
+        Self {
+            core,
+            clock: clock.clone(),
+            config,
+        }
+    }
+}
+
+impl AudioStream for VirtualStream {
+    fn play(&mut self) -> Result<()> {
+        let now = self.clock.now().as_nanos();
+        let mut core = self.core.lock();
+        if core.status != StreamStatus::Playing {
+            // Start counting buffers from the current clock time
+            core.frames = (now * u128::from(core.sample_rate) / NANOS_PER_SECOND) as u64;
+            core.status = StreamStatus::Playing;
+        }
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.core.lock().status = StreamStatus::Paused;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let mut core = self.core.lock();
+        core.status = StreamStatus::Stopped;
+        match &mut core.kind {
+            StreamKind::Input { signal, .. } => {
+                signal.position = 0;
+                Ok(())
+            }
+            StreamKind::Output { sink, .. } => sink.finalize(),
+        }
+    }
+
+    fn status(&self) -> StreamStatus {
+        self.core.lock().status
+    }
+
+    fn config(&self) -> &AudioConfig {
+        &self.config
+    }
+
+    fn latency_samples(&self) -> Option<usize> {
+        Some(self.config.buffer_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::backend::SampleFormat;
+
+    fn config(sample_rate: u32, channels: u16, buffer_size: usize) -> AudioConfig {
+        AudioConfig {
+            sample_rate,
+            channels,
+            sample_format: SampleFormat::F32,
+            buffer_size,
+            exclusive_mode: false,
+        }
+    }
+
+    #[test]
+    fn test_devices() {
+        let backend = VirtualBackend::new();
+        let inputs = backend.enumerate_devices(StreamDirection::Input).unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].name, DEFAULT_VIRTUAL_INPUT);
+        assert!(inputs[0].is_default);
+        assert_eq!(
+            backend.default_device(StreamDirection::Output).unwrap().id,
+            DEFAULT_VIRTUAL_OUTPUT
+        );
+        assert!(backend.test_device(DEFAULT_VIRTUAL_INPUT).unwrap());
+        assert!(!backend.test_device("Missing").unwrap());
+        assert!(backend.capture(DEFAULT_VIRTUAL_OUTPUT).is_some());
+        assert!(VirtualBackend::empty()
+            .default_device(StreamDirection::Input)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_configs() {
+        let mut backend = VirtualBackend::new();
+        for bad in [
+            config(44100, 2, 256),
+            config(48000, 3, 256),
+            config(48000, 2, 0),
+        ] {
+            assert!(matches!(
+                backend.create_output_stream(DEFAULT_VIRTUAL_OUTPUT, bad),
+                Err(AudioBackendError::UnsupportedFormat(_))
+            ));
+        }
+        assert!(matches!(
+            backend.create_input_stream("Missing", config(48000, 2, 256)),
+            Err(AudioBackendError::DeviceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_callbacks_follow_clock() {
+        let capture = CaptureBuffer::new();
+        let mut backend = VirtualBackend::empty().with_output(VirtualOutputDevice::new(
+            "Out",
+            1000,
+            1,
+            VirtualCapture::Memory(capture.clone()),
+        ));
+        let calls = Arc::new(Mutex::new(0u32));
+        let counter = calls.clone();
+        let mut stream = backend
+            .create_output_stream_with_callback(
+                "Out",
+                config(1000, 1, 100),
+                Box::new(move |buffer| {
+                    *counter.lock() += 1;
+                    buffer.fill(1.0);
+                }),
+            )
+            .unwrap();
+        let clock = backend.clock();
+
+        // Nothing runs until the stream plays
+        clock.advance(Duration::from_millis(500)).unwrap();
+        assert_eq!(*calls.lock(), 0);
+
+        stream.play().unwrap();
+        clock.advance(Duration::from_millis(250)).unwrap();
+        assert_eq!(*calls.lock(), 2);
+        clock.advance(Duration::from_millis(50)).unwrap();
+        assert_eq!(*calls.lock(), 3);
+        assert_eq!(capture.take(), vec![1.0; 300]);
+
+        stream.pause().unwrap();
+        clock.advance(Duration::from_secs(1)).unwrap();
+        assert_eq!(*calls.lock(), 3);
+        assert_eq!(stream.status(), StreamStatus::Paused);
+
+        // Dropped streams leave the clock
+        drop(stream);
+        clock.advance_frames(1000, 1000).unwrap();
+        assert!(capture.is_empty());
+    }
+
+    #[test]
+    fn test_input_signal_reaches_callback() {
+        let samples: Arc<[f32]> = vec![0.1, 0.2, 0.3].into();
+        let mut backend = VirtualBackend::empty().with_input(VirtualInputDevice::new(
+            "In",
+            1000,
+            2,
+            VirtualSignal::Samples {
+                samples,
+                channels: 1,
+                looping: false,
+            },
+        ));
+        let received = CaptureBuffer::new();
+        let sink = received.clone();
+        let mut stream = backend
+            .create_input_stream_with_callback(
+                "In",
+                config(1000, 2, 2),
+                Box::new(move |data| sink.extend(data)),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        backend.clock().advance_frames(4, 1000).unwrap();
+
+        // Mono signal on both channels, then silence
+        assert_eq!(
+            received.samples(),
+            vec![0.1, 0.1, 0.2, 0.2, 0.3, 0.3, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_wav_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_path = dir.path().join("virtual_out.wav");
+        let mut backend = VirtualBackend::empty().with_output(VirtualOutputDevice::new(
+            "Out",
+            8000,
+            2,
+            VirtualCapture::WavFile(out_path.clone()),
+        ));
+        let mut stream = backend
+            .create_output_stream_with_callback(
+                "Out",
+                config(8000, 2, 80),
+                Box::new(|buffer| buffer.fill(0.25)),
+            )
+            .unwrap();
+        stream.play().unwrap();
+        backend.clock().advance(Duration::from_millis(100)).unwrap();
+        stream.stop().unwrap();
+
+        let input = VirtualInputDevice::from_wav("Recorded", &out_path, false).unwrap();
+        assert_eq!((input.sample_rate, input.channels), (8000, 2));
+        match input.signal {
+            VirtualSignal::Samples { samples, .. } => assert_eq!(&samples[..], &[0.25; 1600][..]),
+            other => panic!("Unexpected signal {:?}", other),
+        }
+    }
+}
@@ -8,6 +8,8 @@ use super::backend::Result;
 #[cfg(target_arch = "wasm32")]
 use super::router::AudioDestination;
 #[cfg(target_arch = "wasm32")]
+use super::session::NodeKind;
+#[cfg(target_arch = "wasm32")]
 use parking_lot::Mutex;
 #[cfg(target_arch = "wasm32")]
 use std::cell::RefCell;
@@ -221,6 +223,10 @@ impl AudioDestination for WebAudioDestination {
     fn flush(&mut self) -> Result<()> {
         self.flush_to_output()
     }
+
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::other("web_audio")
+    }
 }
 
 // Stub for non-WASM
@@ -3,6 +3,7 @@
 //! This module contains all audio-related functionality separated from the UI.
 //! It follows the Single Responsibility Principle by handling only audio operations.
 
+use crate::audio::crossfade::{Crossfade, CrossfadeCurve};
 use crate::error::{AudioError, ErrorContext, Result};
 use std::any::Any;
 use std::sync::Arc;
@@ -10,8 +11,13 @@ use std::time::Duration;
 use tracing::{debug, error, info, warn};
 use web_audio_api::context::{AudioContext, BaseAudioContext};
 use web_audio_api::node::{
-    AnalyserNode, AudioNode, AudioScheduledSourceNode, BiquadFilterNode, BiquadFilterType,
+    AnalyserNode, AudioBufferSourceNode, AudioNode, AudioScheduledSourceNode, BiquadFilterNode,
+    BiquadFilterType, GainNode,
 };
+use web_audio_api::{AudioBuffer, AudioParam};
+
+/// Linear segments used to approximate a crossfade curve on an `AudioParam`
+const FADE_SEGMENTS: usize = 32;
 
 /// Represents the current state of audio playback
 #[derive(Debug, Clone, PartialEq)]
@@ -99,13 +105,78 @@ pub trait AudioEngineInterface {
 
     /// Connect the engine output (post-processing) to a destination node
     fn connect_output_to(&mut self, dest: &dyn AudioNode) -> Result<()>;
+
+    /// Decode the track to play after the current one and schedule it to
+    /// start on the frame the current track ends, for gapless playback
+    ///
+    /// With a crossfade set (see `set_crossfade`) the track instead starts
+    /// that much earlier and the two overlap. Replaces any track queued
+    /// before. Nothing is scheduled while the current track loops.
+    ///
+    /// # Errors
+    /// `PlaybackFailed` if the file cannot be opened and `DecodeFailed` if
+    /// it cannot be decoded; the previously queued track is kept either way.
+    fn queue_next_file(&mut self, path: &str) -> Result<Duration>;
+
+    /// Drop the track queued with `queue_next_file`
+    fn clear_queued_file(&mut self);
+
+    /// Whether playback moved on to the queued track since the last call
+    ///
+    /// Call regularly (e.g. once per UI frame): this is where the queued
+    /// track becomes the current one for position, duration and waveform.
+    fn take_track_change(&mut self) -> bool;
+
+    /// Crossfade for the next track change, or `None` for a hard cut
+    ///
+    /// Taken into account by the next `queue_next_file`, and by
+    /// `load_audio_file` while playing, where the old track fades out under
+    /// the new one instead of stopping.
+    fn set_crossfade(&mut self, crossfade: Option<Crossfade>);
+
+    /// ReplayGain level (linear) for tracks loaded or queued from now on
+    ///
+    /// Applied per track ahead of the EQ, so tracks overlapping in a
+    /// crossfade each keep their own level.
+    fn set_replay_gain(&mut self, gain: f32);
+
+    /// Change the ReplayGain level of the track playing now, e.g. after the
+    /// user switches mode
+    fn set_current_replay_gain(&mut self, gain: f32);
+}
+
+/// A track decoded ahead of time for a gapless transition
+struct QueuedTrack {
+    buffer: AudioBuffer,
+    crossfade: Option<Crossfade>,
+    /// ReplayGain level
+    level: f32,
+    /// Nodes playing `buffer`, once scheduled
+    nodes: Option<TrackNodes>,
+    /// Context time at which the track starts
+    starts_at: f64,
+}
+
+/// A scheduled source and its own fader
+struct TrackNodes {
+    source: AudioBufferSourceNode,
+    gain: GainNode,
 }
 
 /// Web Audio API implementation of the audio engine
 pub struct WebAudioEngine {
     audio_context: AudioContext,
-    source_node: Option<web_audio_api::node::AudioBufferSourceNode>,
-    gain_node: web_audio_api::node::GainNode,
+    source_node: Option<AudioBufferSourceNode>,
+    /// Fader for the current track, ahead of the shared chain; rests at
+    /// `track_level`
+    track_gain: GainNode,
+    /// ReplayGain level of the current track
+    track_level: f32,
+    /// ReplayGain level for the next track loaded or queued
+    replay_gain: f32,
+    /// Fader of the previous track while it fades out
+    fading_gain: Option<GainNode>,
+    gain_node: GainNode,
     eq_bands: Vec<BiquadFilterNode>,
     analyser: AnalyserNode,
     playback_state: PlaybackState,
@@ -116,6 +187,13 @@ pub struct WebAudioEngine {
     spectrum: Vec<f32>,
     waveform_data: Option<Arc<Vec<f32>>>, // Cached full resolution waveform
     default_output_enabled: bool,
+    looping: bool,
+    /// Context time at which the current track's first frame played
+    track_started_at: f64,
+    queued_track: Option<QueuedTrack>,
+    crossfade: Option<Crossfade>,
+    /// Fade-in for the track started by the next `play()`
+    pending_fade_in: Option<Crossfade>,
 }
 
 impl WebAudioEngine {
@@ -123,6 +201,8 @@ impl WebAudioEngine {
         let analyser = audio_context.create_analyser();
         let gain_node = audio_context.create_gain();
         gain_node.gain().set_value(0.5);
+        let track_gain = audio_context.create_gain();
+        track_gain.connect(&gain_node);
 
         let mut eq_bands = Vec::new();
         for i in 0..8 {
@@ -139,6 +219,10 @@ impl WebAudioEngine {
         Self {
             audio_context,
             source_node: None,
+            track_gain,
+            track_level: 1.0,
+            replay_gain: 1.0,
+            fading_gain: None,
             gain_node,
             eq_bands,
             analyser,
@@ -150,6 +234,11 @@ impl WebAudioEngine {
             spectrum: vec![0.0; 1024],
             waveform_data: None,
             default_output_enabled: true,
+            looping: false,
+            track_started_at: 0.0,
+            queued_track: None,
+            crossfade: None,
+            pending_fade_in: None,
         }
     }
 
@@ -162,7 +251,7 @@ impl WebAudioEngine {
     /// Connect the audio chain: source -> EQ bands -> gain -> analyser -> output
     fn connect_audio_chain(&self) -> Result<()> {
         if let Some(source_node) = &self.source_node {
-            source_node.connect(&self.gain_node);
+            source_node.connect(&self.track_gain);
 
             let mut previous_node: &dyn AudioNode = &self.gain_node;
             for band in &self.eq_bands {
@@ -204,11 +293,175 @@ impl WebAudioEngine {
     /// Update playback position
     pub fn update_position(&mut self) {
         if self.playback_state == PlaybackState::Playing && !self.is_seeking {
-            self.playback_pos = Duration::from_secs_f64(self.audio_context.current_time());
+            self.playback_pos = self.track_position();
+        }
+    }
+
+    /// Position in the current track according to the context clock
+    fn track_position(&self) -> Duration {
+        let elapsed = self.audio_context.current_time() - self.track_started_at;
+        Duration::from_secs_f64(elapsed.max(0.0))
+    }
+
+    /// Create a source node for `buffer` feeding `gain`
+    fn create_track_node(&self, buffer: AudioBuffer, gain: &GainNode) -> AudioBufferSourceNode {
+        let mut source_node = self.audio_context.create_buffer_source();
+        source_node.set_buffer(buffer);
+        source_node.connect(gain);
+        source_node
+    }
+
+    /// Create a track fader feeding the EQ chain
+    fn create_track_gain(&self) -> GainNode {
+        let gain = self.audio_context.create_gain();
+        gain.connect(&self.gain_node);
+        gain
+    }
+
+    /// Give the next track a fresh fader, keeping the old one connected
+    /// only if it is fading out
+    fn replace_track_gain(&mut self, fading: bool) {
+        let new_gain = self.create_track_gain();
+        let old_gain = std::mem::replace(&mut self.track_gain, new_gain);
+        if let Some(previous) = self.fading_gain.take() {
+            previous.disconnect();
+        }
+        if fading {
+            self.fading_gain = Some(old_gain);
+        } else {
+            old_gain.disconnect();
+        }
+    }
+
+    /// Undo a fade-out of the current track planned from `from`, leaving
+    /// any earlier fade-in in place
+    fn cancel_fade_out(&self, from: f64) {
+        let at = from.max(self.audio_context.current_time());
+        self.track_gain
+            .gain()
+            .cancel_scheduled_values(from)
+            .set_value_at_time(self.track_level, at);
+    }
+
+    /// (Re)schedule the queued track to start where the current track ends,
+    /// or a crossfade's length before
+    fn schedule_queued_track(&mut self) {
+        if self.playback_state == PlaybackState::Stopped || self.looping {
+            self.unschedule_queued_track();
+            return;
+        }
+        let Some(current_length) = self
+            .source_node
+            .as_ref()
+            .and_then(|node| node.buffer())
+            .map(AudioBuffer::duration)
+        else {
+            return;
+        };
+        let Some(mut queued) = self.queued_track.take() else {
+            return;
+        };
+        if let Some(mut old) = queued.nodes.take() {
+            old.source.stop();
+            old.source.disconnect();
+            old.gain.disconnect();
+            self.cancel_fade_out(queued.starts_at);
+        }
+
+        let fade = queued.crossfade.and_then(|crossfade| {
+            let seconds = crossfade
+                .duration
+                .as_secs_f64()
+                .min(current_length)
+                .min(queued.buffer.duration());
+            (seconds > 0.0).then_some((crossfade.curve, seconds))
+        });
+        let fade_seconds = fade.map_or(0.0, |(_, seconds)| seconds);
+        let starts_at = self.track_started_at + current_length - fade_seconds;
+
+        let gain = self.create_track_gain();
+        gain.gain().set_value(queued.level);
+        let mut source = self.create_track_node(queued.buffer.clone(), &gain);
+        if let Some((curve, seconds)) = fade {
+            let fade_in = Fade::new(curve, true, queued.level);
+            fade_in.schedule(gain.gain(), starts_at, seconds);
+            let fade_out = Fade::new(curve, false, self.track_level);
+            fade_out.schedule(self.track_gain.gain(), starts_at, seconds);
+        }
+        // Queued late (e.g. after a seek near the end): join where the
+        // track would be by now
+        let now = self.audio_context.current_time();
+        source.start_at_with_offset(starts_at.max(now), (now - starts_at).max(0.0));
+
+        queued.nodes = Some(TrackNodes { source, gain });
+        queued.starts_at = starts_at;
+        self.queued_track = Some(queued);
+    }
+
+    /// Stop the queued track's nodes but keep its decoded audio
+    fn unschedule_queued_track(&mut self) {
+        let Some(queued) = self.queued_track.as_mut() else {
+            return;
+        };
+        if let Some(mut nodes) = queued.nodes.take() {
+            nodes.source.stop();
+            nodes.source.disconnect();
+            nodes.gain.disconnect();
+            let starts_at = queued.starts_at;
+            self.cancel_fade_out(starts_at);
+        }
+    }
+}
+
+/// One side of a crossfade on a track fader
+struct Fade {
+    curve: CrossfadeCurve,
+    incoming: bool,
+    /// Fader level outside the fade (the track's ReplayGain level)
+    level: f32,
+}
+
+impl Fade {
+    fn new(curve: CrossfadeCurve, incoming: bool, level: f32) -> Self {
+        Self {
+            curve,
+            incoming,
+            level,
+        }
+    }
+
+    fn gain_at(&self, progress: f32) -> f32 {
+        let (out_gain, in_gain) = self.curve.gains(progress);
+        let gain = if self.incoming { in_gain } else { out_gain };
+        gain * self.level
+    }
+
+    /// Automate `param` along the curve from `start` for `duration` seconds
+    fn schedule(&self, param: &AudioParam, start: f64, duration: f64) {
+        param.cancel_scheduled_values(start);
+        param.set_value_at_time(self.gain_at(0.0), start);
+        for segment in 1..=FADE_SEGMENTS {
+            let progress = segment as f32 / FADE_SEGMENTS as f32;
+            param.linear_ramp_to_value_at_time(
+                self.gain_at(progress),
+                start + duration * f64::from(progress),
+            );
         }
     }
 }
 
+/// Mix a buffer down to mono for waveform display
+fn mono_waveform(buffer: &AudioBuffer) -> Vec<f32> {
+    let channels = buffer.number_of_channels();
+    let mut waveform = vec![0.0; buffer.length()];
+    for i in 0..channels {
+        for (mixed, sample) in waveform.iter_mut().zip(buffer.get_channel_data(i)) {
+            *mixed += sample / channels as f32;
+        }
+    }
+    waveform
+}
+
 impl AudioEngineInterface for WebAudioEngine {
     fn as_any(&self) -> &dyn Any {
         self
@@ -229,23 +482,35 @@ impl AudioEngineInterface for WebAudioEngine {
             .decode_audio_data_sync(file)
             .map_err(|_| AudioError::DecodeFailed)?;
 
+        // The previous track either fades out under the new one or stops
+        let fade = self
+            .crossfade
+            .filter(|_| self.playback_state == PlaybackState::Playing);
+        self.clear_queued_file();
+        if let Some(fade) = fade {
+            let now = self.audio_context.current_time();
+            let seconds = fade.duration.as_secs_f64();
+            Fade::new(fade.curve, false, self.track_level).schedule(
+                self.track_gain.gain(),
+                now,
+                seconds,
+            );
+            if let Some(old_node) = &mut self.source_node {
+                old_node.stop_at(now + seconds);
+            }
+            self.playback_state = PlaybackState::Stopped;
+            self.pending_fade_in = Some(fade);
+        } else if self.playback_state != PlaybackState::Stopped {
+            self.stop()?;
+        }
+        self.replace_track_gain(fade.is_some());
+        self.track_level = self.replay_gain;
+        self.track_gain.gain().set_value(self.track_level);
+
         self.total_duration = Duration::from_secs_f64(buffer.duration());
 
         // Extract waveform data for visualization
-        let channels = buffer.number_of_channels();
-        let length = buffer.length();
-        let mut waveform = vec![0.0; length];
-
-        if channels > 0 {
-            // Mix down to mono for visualization
-            for i in 0..channels {
-                let channel_data = buffer.get_channel_data(i);
-                for (j, sample) in channel_data.iter().enumerate() {
-                    waveform[j] += sample / channels as f32;
-                }
-            }
-        }
-        self.waveform_data = Some(Arc::new(waveform));
+        self.waveform_data = Some(Arc::new(mono_waveform(&buffer)));
 
         let mut source_node = self.audio_context.create_buffer_source();
         source_node.set_buffer(buffer);
@@ -277,6 +542,15 @@ impl AudioEngineInterface for WebAudioEngine {
                     source_node.start();
                     self.playback_state = PlaybackState::Playing;
                     self.playback_pos = Duration::ZERO;
+                    self.track_started_at = self.audio_context.current_time();
+                    if let Some(fade) = self.pending_fade_in.take() {
+                        Fade::new(fade.curve, true, self.track_level).schedule(
+                            self.track_gain.gain(),
+                            self.track_started_at,
+                            fade.duration.as_secs_f64(),
+                        );
+                    }
+                    self.schedule_queued_track();
                 } else {
                     warn!("No audio source loaded");
                     return Err(AudioError::PlaybackFailed {
@@ -292,6 +566,7 @@ impl AudioEngineInterface for WebAudioEngine {
     fn pause(&mut self) -> Result<()> {
         if self.playback_state == PlaybackState::Playing {
             info!("Pausing playback");
+            self.playback_pos = self.track_position();
             self.audio_context.suspend_sync();
             self.playback_state = PlaybackState::Paused;
         }
@@ -299,9 +574,19 @@ impl AudioEngineInterface for WebAudioEngine {
     }
 
     fn stop(&mut self) -> Result<()> {
+        self.unschedule_queued_track();
+        self.pending_fade_in = None;
+        // Silence a track still fading out from the last change
+        if let Some(fading_gain) = self.fading_gain.take() {
+            fading_gain.disconnect();
+        }
         if let Some(source_node) = &mut self.source_node {
             info!("Stopping playback");
             source_node.stop();
+            if self.playback_state == PlaybackState::Paused {
+                // A later play() starts a new node, which needs a running clock
+                self.audio_context.resume_sync();
+            }
             self.playback_state = PlaybackState::Stopped;
             self.playback_pos = Duration::ZERO;
         }
@@ -313,11 +598,26 @@ impl AudioEngineInterface for WebAudioEngine {
         self.is_seeking = true;
         self.playback_pos = position.min(self.total_duration);
 
-        if let Some(source_node) = &mut self.source_node {
-            // Note: This is a simplified seek implementation
-            // In a real application, you might need to recreate the source node
-            source_node.stop();
-            source_node.start_at(self.audio_context.current_time() + position.as_secs_f64());
+        // A source node can only be started once, so seeking restarts the
+        // track on a fresh node at the new offset
+        let buffer = self
+            .source_node
+            .as_ref()
+            .filter(|_| self.playback_state != PlaybackState::Stopped)
+            .and_then(|node| node.buffer())
+            .cloned();
+        if let Some(buffer) = buffer {
+            if let Some(old_node) = &mut self.source_node {
+                old_node.stop();
+            }
+            let mut source_node = self.create_track_node(buffer, &self.track_gain);
+            source_node.set_loop(self.looping);
+            let now = self.audio_context.current_time();
+            let offset = self.playback_pos.as_secs_f64();
+            source_node.start_at_with_offset(now, offset);
+            self.source_node = Some(source_node);
+            self.track_started_at = now - offset;
+            self.schedule_queued_track();
         }
 
         self.is_seeking = false;
@@ -333,7 +633,11 @@ impl AudioEngineInterface for WebAudioEngine {
     }
 
     fn get_position(&self) -> Duration {
-        self.playback_pos
+        if self.playback_state == PlaybackState::Playing && !self.is_seeking {
+            self.track_position()
+        } else {
+            self.playback_pos
+        }
     }
 
     fn get_state(&self) -> PlaybackState {
@@ -386,12 +690,16 @@ impl AudioEngineInterface for WebAudioEngine {
         let mut source_node = self.audio_context.create_buffer_source();
         source_node.set_buffer(buffer);
         self.source_node = Some(source_node);
+        // Generated signals carry no loudness tags
+        self.replace_track_gain(false);
+        self.track_level = 1.0;
         self.connect_audio_chain()?;
 
         if let Some(node) = &mut self.source_node {
             node.start();
         }
 
+        self.track_started_at = self.audio_context.current_time();
         self.playback_state = PlaybackState::Playing;
         self.playback_pos = Duration::ZERO;
         self.total_duration = Duration::from_secs_f64(frame_count as f64 / sample_rate as f64);
@@ -425,6 +733,8 @@ impl AudioEngineInterface for WebAudioEngine {
         if let Some(source_node) = &mut self.source_node {
             source_node.set_loop(looping);
         }
+        self.looping = looping;
+        self.schedule_queued_track();
         Ok(())
     }
 
@@ -493,6 +803,84 @@ impl AudioEngineInterface for WebAudioEngine {
         self.analyser.connect(dest);
         Ok(())
     }
+
+    fn queue_next_file(&mut self, path: &str) -> Result<Duration> {
+        info!("Queueing next audio file: {}", path);
+
+        let file = std::fs::File::open(path).map_err(|e| AudioError::PlaybackFailed {
+            reason: format!("Failed to open file: {}", e),
+        })?;
+        let buffer = self
+            .audio_context
+            .decode_audio_data_sync(file)
+            .map_err(|_| AudioError::DecodeFailed)?;
+        let duration = Duration::from_secs_f64(buffer.duration());
+
+        self.clear_queued_file();
+        self.queued_track = Some(QueuedTrack {
+            buffer,
+            crossfade: self.crossfade,
+            level: self.replay_gain,
+            nodes: None,
+            starts_at: 0.0,
+        });
+        self.schedule_queued_track();
+        Ok(duration)
+    }
+
+    fn clear_queued_file(&mut self) {
+        self.unschedule_queued_track();
+        self.queued_track = None;
+    }
+
+    fn take_track_change(&mut self) -> bool {
+        let started = self.queued_track.as_ref().is_some_and(|queued| {
+            queued.nodes.is_some() && self.audio_context.current_time() >= queued.starts_at
+        });
+        if !started {
+            return false;
+        }
+        let Some(queued) = self.queued_track.take() else {
+            return false;
+        };
+
+        // The previous track has ended, or is fading out on its own fader
+        let Some(nodes) = queued.nodes else {
+            return false;
+        };
+        self.source_node = Some(nodes.source);
+        let old_gain = std::mem::replace(&mut self.track_gain, nodes.gain);
+        self.track_level = queued.level;
+        if let Some(previous) = self.fading_gain.replace(old_gain) {
+            previous.disconnect();
+        }
+        self.track_started_at = queued.starts_at;
+        self.total_duration = Duration::from_secs_f64(queued.buffer.duration());
+        self.waveform_data = Some(Arc::new(mono_waveform(&queued.buffer)));
+        self.playback_pos = Duration::ZERO;
+        debug!("Moved on to queued track at {:.3}s", queued.starts_at);
+        true
+    }
+
+    fn set_crossfade(&mut self, crossfade: Option<Crossfade>) {
+        self.crossfade = crossfade;
+    }
+
+    fn set_replay_gain(&mut self, gain: f32) {
+        self.replay_gain = gain;
+    }
+
+    fn set_current_replay_gain(&mut self, gain: f32) {
+        debug!("Setting current track ReplayGain to {:.3}", gain);
+        self.track_level = gain;
+        let now = self.audio_context.current_time();
+        self.track_gain
+            .gain()
+            .cancel_scheduled_values(now)
+            .set_value_at_time(gain, now);
+        // Re-plan the fade-out into the queued track at the new level
+        self.schedule_queued_track();
+    }
 }
 
 impl Default for WebAudioEngine {
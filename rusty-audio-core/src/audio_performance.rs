@@ -370,6 +370,27 @@ impl LockFreeRingBuffer {
     #[inline(always)]
     pub fn read(&self, output: &mut [f32]) -> usize {
         let current_read = self.read_pos.load(Ordering::Acquire);
+        let to_read = self.copy_from(current_read, output);
+        self.read_pos
+            .store((current_read + to_read) & self.mask, Ordering::Release);
+        to_read
+    }
+
+    /// Copy out the oldest unread data without consuming it (single consumer)
+    #[inline(always)]
+    pub fn peek(&self, output: &mut [f32]) -> usize {
+        self.copy_from(self.read_pos.load(Ordering::Acquire), output)
+    }
+
+    /// Discard all unread data (single consumer)
+    pub fn clear(&self) {
+        let current_write = self.write_pos.load(Ordering::Acquire);
+        self.read_pos.store(current_write, Ordering::Release);
+    }
+
+    /// Copy unread data starting at `current_read` into `output`
+    #[inline(always)]
+    fn copy_from(&self, current_read: usize, output: &mut [f32]) -> usize {
         let current_write = self.write_pos.load(Ordering::Acquire);
 
         let available_data = self.used_space(current_write, current_read);
@@ -407,8 +428,6 @@ impl LockFreeRingBuffer {
             }
         }
 
-        self.read_pos
-            .store((current_read + to_read) & self.mask, Ordering::Release);
         to_read
     }
 
@@ -28,6 +28,14 @@ pub enum AudioPlayerError {
     /// Errors related to UI operations
     #[error("UI operation failed: {0}")]
     UserInterface(#[from] UiError),
+
+    /// Errors related to the music library
+    #[error("Music library failed: {0}")]
+    Library(#[from] LibraryError),
+
+    /// Errors related to playlist files
+    #[error("Playlist failed: {0}")]
+    Playlist(#[from] PlaylistError),
 }
 
 /// File operation specific errors
@@ -91,6 +99,9 @@ pub enum MetadataError {
 
     #[error("Lofty library error: {0}")]
     LoftyError(String),
+
+    #[error("Invalid tag value: {0}")]
+    InvalidTag(#[from] crate::security::ValidationError),
 }
 
 /// Image processing errors (album art)
@@ -122,6 +133,26 @@ pub enum UiError {
     RenderingFailed { details: String },
 }
 
+/// Music library errors
+#[derive(Error, Debug)]
+pub enum LibraryError {
+    #[error("Library database error: {0}")]
+    Database(String),
+
+    #[error("Library folder not found: {path}")]
+    FolderNotFound { path: String },
+}
+
+/// Playlist file errors
+#[derive(Error, Debug)]
+pub enum PlaylistError {
+    #[error("Unsupported playlist format: {path}")]
+    UnsupportedFormat { path: String },
+
+    #[error("Malformed playlist {path}: {reason}")]
+    Malformed { path: String, reason: String },
+}
+
 /// Result type alias for convenience
 pub type Result<T> = std::result::Result<T, AudioPlayerError>;
 
@@ -118,6 +118,9 @@ pub mod error;
 /// Audio metadata extraction and management
 pub mod metadata;
 
+/// Synchronized and plain lyrics from LRC files and tags
+pub mod lyrics;
+
 /// AI-enhanced audio processing modules
 pub mod ai;
 
@@ -153,6 +156,13 @@ pub mod audio_pipeline_integration;
 /// Performance integration layer (native only - uses web_audio_api::AnalyserNode)
 pub mod audio_performance_integration;
 
+#[cfg(not(target_arch = "wasm32"))]
+/// Music library indexing and browsing (native only - uses SQLite)
+pub mod library;
+
+/// Playlist file import and export (M3U/M3U8, PLS, XSPF) and CUE sheets
+pub mod playlist;
+
 // ============================================================================
 // WASM-Only Modules
 // ============================================================================
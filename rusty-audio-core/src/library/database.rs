@@ -0,0 +1,576 @@
+//! SQLite storage of the music library
+//!
+//! Tracks are stored with their metadata, file size and modification time.
+//! An FTS5 index over title, artist, album, album artist and genre, kept up
+//! to date by triggers, serves text search.
+
+use super::scanner::FileStamp;
+use crate::audio::replay_gain::ReplayGain;
+use crate::error::{AudioPlayerError, LibraryError, Result};
+use crate::metadata::TrackMetadata;
+use rusqlite::{params, Connection, Params, Row};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS folders (
+    path TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS tracks (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    size INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    artist TEXT NOT NULL,
+    album TEXT NOT NULL,
+    album_artist TEXT,
+    genre TEXT,
+    year TEXT NOT NULL,
+    track_number INTEGER,
+    duration_ms INTEGER,
+    track_gain REAL,
+    track_peak REAL,
+    album_gain REAL,
+    album_peak REAL
+);
+CREATE INDEX IF NOT EXISTS tracks_artist ON tracks (artist COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS tracks_album ON tracks (album COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS tracks_genre ON tracks (genre COLLATE NOCASE);
+CREATE VIRTUAL TABLE IF NOT EXISTS tracks_search USING fts5 (
+    title, artist, album, album_artist, genre,
+    content = 'tracks', content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+CREATE TRIGGER IF NOT EXISTS tracks_search_insert AFTER INSERT ON tracks BEGIN
+    INSERT INTO tracks_search (rowid, title, artist, album, album_artist, genre)
+    VALUES (new.id, new.title, new.artist, new.album, new.album_artist, new.genre);
+END;
+CREATE TRIGGER IF NOT EXISTS tracks_search_delete AFTER DELETE ON tracks BEGIN
+    INSERT INTO tracks_search (tracks_search, rowid, title, artist, album, album_artist, genre)
+    VALUES ('delete', old.id, old.title, old.artist, old.album, old.album_artist, old.genre);
+END;
+CREATE TRIGGER IF NOT EXISTS tracks_search_update AFTER UPDATE ON tracks BEGIN
+    INSERT INTO tracks_search (tracks_search, rowid, title, artist, album, album_artist, genre)
+    VALUES ('delete', old.id, old.title, old.artist, old.album, old.album_artist, old.genre);
+    INSERT INTO tracks_search (rowid, title, artist, album, album_artist, genre)
+    VALUES (new.id, new.title, new.artist, new.album, new.album_artist, new.genre);
+END;
+";
+
+/// Columns read by [`track_from_row`]
+const TRACK_COLUMNS: &str = "tracks.id, tracks.path, tracks.title, tracks.artist, \
+    tracks.album, tracks.album_artist, tracks.genre, tracks.year, tracks.track_number, \
+    tracks.duration_ms, tracks.track_gain, tracks.track_peak, tracks.album_gain, \
+    tracks.album_peak";
+
+/// Album order, then track number within an album (there is no disc column,
+/// so multi-disc albums interleave), then title
+const TRACK_ORDER: &str =
+    "tracks.album COLLATE NOCASE, tracks.track_number, tracks.title COLLATE NOCASE";
+
+const UPSERT_TRACK: &str = "
+INSERT INTO tracks (
+    path, size, modified, title, artist, album, album_artist, genre, year,
+    track_number, duration_ms, track_gain, track_peak, album_gain, album_peak
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+ON CONFLICT (path) DO UPDATE SET
+    size = excluded.size, modified = excluded.modified, title = excluded.title,
+    artist = excluded.artist, album = excluded.album, album_artist = excluded.album_artist,
+    genre = excluded.genre, year = excluded.year, track_number = excluded.track_number,
+    duration_ms = excluded.duration_ms, track_gain = excluded.track_gain,
+    track_peak = excluded.track_peak, album_gain = excluded.album_gain,
+    album_peak = excluded.album_peak
+";
+
+impl From<rusqlite::Error> for AudioPlayerError {
+    fn from(error: rusqlite::Error) -> Self {
+        LibraryError::Database(error.to_string()).into()
+    }
+}
+
+/// A track in the library
+#[derive(Debug, Clone)]
+pub struct LibraryTrack {
+    /// Database row id
+    pub id: i64,
+    /// Audio file
+    pub path: PathBuf,
+    /// Tags as of the last scan
+    pub metadata: TrackMetadata,
+}
+
+/// An album in the library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumSummary {
+    /// Album title
+    pub album: String,
+    /// Album artist, or the track artist for untagged albums
+    pub album_artist: String,
+    /// Release year, empty if untagged
+    pub year: String,
+    /// Number of tracks in the library
+    pub tracks: usize,
+}
+
+/// Which tracks to list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackFilter {
+    /// Every track
+    All,
+    /// Tracks by an artist
+    Artist(String),
+    /// An album, told apart from albums of the same name by its artist
+    Album {
+        /// Album title
+        album: String,
+        /// Album artist
+        album_artist: String,
+    },
+    /// Tracks of a genre
+    Genre(String),
+}
+
+/// Music library database
+#[derive(Debug)]
+pub struct Library {
+    connection: Connection,
+}
+
+impl Library {
+    /// Open the library database at `path`, creating it if needed
+    ///
+    /// # Errors
+    /// - `FileOperation` if the parent directory can't be created
+    /// - `Library` if the database can't be opened or set up
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(crate::error::FileError::Io)?;
+        }
+        let connection = Connection::open(path)?;
+        // Let scans write while the UI reads
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        Self::with_connection(connection)
+    }
+
+    /// Open a library that only lives in memory
+    ///
+    /// # Errors
+    /// `Library` if the database can't be set up
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Default database location, in the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rusty-audio").join("library.db"))
+    }
+
+    /// Folders scanned into the library
+    ///
+    /// # Errors
+    /// `Library` if the database can't be read
+    pub fn folders(&self) -> Result<Vec<PathBuf>> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT path FROM folders ORDER BY path")?;
+        let folders = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|path| path.map(PathBuf::from))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(folders)
+    }
+
+    /// Add a folder to scan; its files are indexed by the next scan
+    ///
+    /// # Errors
+    /// - `Library` with `FolderNotFound` if `folder` isn't a directory
+    /// - `Library` if the database can't be written
+    pub fn add_folder(&self, folder: &Path) -> Result<()> {
+        if !folder.is_dir() {
+            return Err(LibraryError::FolderNotFound {
+                path: folder.display().to_string(),
+            }
+            .into());
+        }
+        self.connection.execute(
+            "INSERT OR IGNORE INTO folders (path) VALUES (?1)",
+            [folder.to_string_lossy()],
+        )?;
+        Ok(())
+    }
+
+    /// Stop scanning a folder and drop its tracks
+    ///
+    /// Tracks that are also under another library folder are kept.
+    ///
+    /// # Errors
+    /// `Library` if the database can't be written
+    pub fn remove_folder(&mut self, folder: &Path) -> Result<()> {
+        self.connection.execute(
+            "DELETE FROM folders WHERE path = ?1",
+            [folder.to_string_lossy()],
+        )?;
+        let folders = self.folders()?;
+        let orphans: Vec<PathBuf> = self
+            .file_stamps()?
+            .into_keys()
+            .filter(|path| !folders.iter().any(|folder| path.starts_with(folder)))
+            .collect();
+        self.apply_scan(&[], &orphans)
+    }
+
+    /// Number of tracks in the library
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn track_count(&self) -> Result<usize> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM tracks", [], |row| row.get(0))?;
+        Ok(usize::try_from(count).unwrap_or_default())
+    }
+
+    /// All track artists, alphabetically
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn artists(&self) -> Result<Vec<String>> {
+        self.query_names("SELECT DISTINCT artist FROM tracks ORDER BY artist COLLATE NOCASE")
+    }
+
+    /// All genres, alphabetically
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn genres(&self) -> Result<Vec<String>> {
+        self.query_names(
+            "SELECT DISTINCT genre FROM tracks WHERE genre IS NOT NULL \
+             ORDER BY genre COLLATE NOCASE",
+        )
+    }
+
+    /// Albums, by artist then title; only those `artist` appears on if given
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn albums(&self, artist: Option<&str>) -> Result<Vec<AlbumSummary>> {
+        let sql = format!(
+            "SELECT album, COALESCE(album_artist, artist) AS owner, MAX(year), COUNT(*) \
+             FROM tracks {} GROUP BY album, owner \
+             ORDER BY owner COLLATE NOCASE, album COLLATE NOCASE",
+            if artist.is_some() {
+                "WHERE artist = ?1 COLLATE NOCASE OR album_artist = ?1 COLLATE NOCASE"
+            } else {
+                ""
+            }
+        );
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let album_from_row = |row: &Row<'_>| {
+            Ok(AlbumSummary {
+                album: row.get(0)?,
+                album_artist: row.get(1)?,
+                year: row.get(2)?,
+                tracks: usize::try_from(row.get::<_, i64>(3)?).unwrap_or_default(),
+            })
+        };
+        let albums = match artist {
+            Some(artist) => statement.query_map([artist], album_from_row)?,
+            None => statement.query_map([], album_from_row)?,
+        }
+        .collect::<rusqlite::Result<_>>()?;
+        Ok(albums)
+    }
+
+    /// Tracks matching `filter`, in album order
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn tracks(&self, filter: &TrackFilter) -> Result<Vec<LibraryTrack>> {
+        let select = |condition: &str| {
+            format!("SELECT {TRACK_COLUMNS} FROM tracks {condition} ORDER BY {TRACK_ORDER}")
+        };
+        match filter {
+            TrackFilter::All => self.query_tracks(&select(""), []),
+            TrackFilter::Artist(artist) => {
+                self.query_tracks(&select("WHERE artist = ?1 COLLATE NOCASE"), [artist])
+            }
+            TrackFilter::Album {
+                album,
+                album_artist,
+            } => self.query_tracks(
+                &select("WHERE album = ?1 AND COALESCE(album_artist, artist) = ?2"),
+                [album, album_artist],
+            ),
+            TrackFilter::Genre(genre) => {
+                self.query_tracks(&select("WHERE genre = ?1 COLLATE NOCASE"), [genre])
+            }
+        }
+    }
+
+    /// Tracks whose title, artist, album, album artist or genre contain
+    /// words starting with every word of `query`, best matches first
+    ///
+    /// Case and diacritics are ignored.
+    ///
+    /// # Errors
+    /// See [`Library::folders`]
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<LibraryTrack>> {
+        let Some(pattern) = search_pattern(query) else {
+            return Ok(Vec::new());
+        };
+        let sql = format!(
+            "SELECT {TRACK_COLUMNS} FROM tracks_search \
+             JOIN tracks ON tracks.id = tracks_search.rowid \
+             WHERE tracks_search MATCH ?1 ORDER BY rank LIMIT ?2"
+        );
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        self.query_tracks(&sql, params![pattern, limit])
+    }
+
+    /// Size and modification time of every indexed file
+    pub(crate) fn file_stamps(&self) -> Result<HashMap<PathBuf, FileStamp>> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT path, size, modified FROM tracks")?;
+        let stamps = statement
+            .query_map([], |row| {
+                let stamp = FileStamp {
+                    size: row.get(1)?,
+                    modified: row.get(2)?,
+                };
+                Ok((PathBuf::from(row.get::<_, String>(0)?), stamp))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(stamps)
+    }
+
+    /// Store new and changed tracks and drop removed ones, atomically
+    pub(crate) fn apply_scan(
+        &mut self,
+        indexed: &[(PathBuf, FileStamp, TrackMetadata)],
+        removed: &[PathBuf],
+    ) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut upsert = transaction.prepare_cached(UPSERT_TRACK)?;
+            for (path, stamp, metadata) in indexed {
+                let duration_ms = metadata
+                    .duration
+                    .map(|duration| i64::try_from(duration.as_millis()).unwrap_or(i64::MAX));
+                let replay_gain = &metadata.replay_gain;
+                upsert.execute(params![
+                    path.to_string_lossy(),
+                    stamp.size,
+                    stamp.modified,
+                    metadata.title,
+                    metadata.artist,
+                    metadata.album,
+                    metadata.album_artist,
+                    metadata.genre,
+                    metadata.year,
+                    metadata.track_number,
+                    duration_ms,
+                    replay_gain.track_gain_db,
+                    replay_gain.track_peak,
+                    replay_gain.album_gain_db,
+                    replay_gain.album_peak,
+                ])?;
+            }
+            let mut delete = transaction.prepare_cached("DELETE FROM tracks WHERE path = ?1")?;
+            for path in removed {
+                delete.execute([path.to_string_lossy()])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn query_names(&self, sql: &str) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare_cached(sql)?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(names)
+    }
+
+    fn query_tracks(&self, sql: &str, params: impl Params) -> Result<Vec<LibraryTrack>> {
+        let mut statement = self.connection.prepare_cached(sql)?;
+        let tracks = statement
+            .query_map(params, track_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tracks)
+    }
+}
+
+fn track_from_row(row: &Row<'_>) -> rusqlite::Result<LibraryTrack> {
+    let duration_ms: Option<i64> = row.get(9)?;
+    Ok(LibraryTrack {
+        id: row.get(0)?,
+        path: PathBuf::from(row.get::<_, String>(1)?),
+        metadata: TrackMetadata {
+            title: row.get(2)?,
+            artist: row.get(3)?,
+            album: row.get(4)?,
+            album_artist: row.get(5)?,
+            genre: row.get(6)?,
+            year: row.get(7)?,
+            track_number: row.get(8)?,
+            duration: duration_ms
+                .and_then(|ms| u64::try_from(ms).ok())
+                .map(Duration::from_millis),
+            replay_gain: ReplayGain {
+                track_gain_db: row.get(10)?,
+                track_peak: row.get(11)?,
+                album_gain_db: row.get(12)?,
+                album_peak: row.get(13)?,
+            },
+        },
+    })
+}
+
+/// FTS5 query matching every word of `query` as a prefix
+///
+/// Words are quoted so that FTS5 operators typed by the user are searched
+/// for literally.
+fn search_pattern(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: &str, album: &str, number: u32) -> TrackMetadata {
+        TrackMetadata {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: album.to_string(),
+            album_artist: Some(artist.to_string()),
+            track_number: Some(number),
+            ..Default::default()
+        }
+    }
+
+    fn library() -> Library {
+        let tracks = [
+            track("Billie Jean", "Michael Jackson", "Thriller", 6),
+            track("Beat It", "Michael Jackson", "Thriller", 5),
+            track("Señorita", "Justin Timberlake", "Justified", 2),
+            TrackMetadata {
+                genre: Some("Jazz".to_string()),
+                duration: Some(Duration::from_millis(545_000)),
+                ..track("So What", "Miles Davis", "Kind of Blue", 1)
+            },
+        ];
+        let indexed: Vec<_> = tracks
+            .into_iter()
+            .enumerate()
+            .map(|(i, metadata)| {
+                let stamp = FileStamp {
+                    size: 1000,
+                    modified: 0,
+                };
+                (PathBuf::from(format!("/music/{i}.flac")), stamp, metadata)
+            })
+            .collect();
+        let mut library = Library::open_in_memory().unwrap();
+        library.apply_scan(&indexed, &[]).unwrap();
+        library
+    }
+
+    fn titles(tracks: &[LibraryTrack]) -> Vec<&str> {
+        tracks
+            .iter()
+            .map(|track| track.metadata.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_browse() {
+        let library = library();
+        assert_eq!(library.track_count().unwrap(), 4);
+        assert_eq!(
+            library.artists().unwrap(),
+            ["Justin Timberlake", "Michael Jackson", "Miles Davis"]
+        );
+        assert_eq!(library.genres().unwrap(), ["Jazz"]);
+
+        let albums = library.albums(Some("michael jackson")).unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].album, "Thriller");
+        assert_eq!(albums[0].tracks, 2);
+        assert_eq!(library.albums(None).unwrap().len(), 3);
+
+        // Album tracks come in track order
+        let thriller = TrackFilter::Album {
+            album: "Thriller".to_string(),
+            album_artist: "Michael Jackson".to_string(),
+        };
+        let tracks = library.tracks(&thriller).unwrap();
+        assert_eq!(titles(&tracks), ["Beat It", "Billie Jean"]);
+
+        let jazz = library
+            .tracks(&TrackFilter::Genre("jazz".to_string()))
+            .unwrap();
+        assert_eq!(titles(&jazz), ["So What"]);
+        assert_eq!(
+            jazz[0].metadata.duration,
+            Some(Duration::from_millis(545_000))
+        );
+        assert_eq!(library.tracks(&TrackFilter::All).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_search() {
+        let library = library();
+        assert_eq!(
+            titles(&library.search("billie", 10).unwrap()),
+            ["Billie Jean"]
+        );
+        // Every word must match, as a prefix, in any field
+        assert_eq!(
+            titles(&library.search("jack bea", 10).unwrap()),
+            ["Beat It"]
+        );
+        assert_eq!(library.search("thriller", 10).unwrap().len(), 2);
+        assert_eq!(library.search("thriller", 1).unwrap().len(), 1);
+        // Diacritics and case are ignored
+        assert_eq!(
+            titles(&library.search("SENORITA", 10).unwrap()),
+            ["Señorita"]
+        );
+        // FTS5 syntax is taken literally
+        assert!(library.search("\"so\" OR NEAR(", 10).unwrap().is_empty());
+        assert!(library.search("  ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_folders() {
+        let mut library = library();
+        let folder = std::env::temp_dir();
+        library.add_folder(&folder).unwrap();
+        library.add_folder(&folder).unwrap();
+        assert_eq!(library.folders().unwrap(), [folder.clone()]);
+        assert!(library
+            .add_folder(Path::new("/definitely/not/a/folder"))
+            .is_err());
+
+        // Dropping a folder drops the tracks outside the remaining folders
+        library.remove_folder(&folder).unwrap();
+        assert!(library.folders().unwrap().is_empty());
+        assert_eq!(library.track_count().unwrap(), 0);
+        assert!(library.search("thriller", 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+//! Music library
+//!
+//! [`Library`] indexes the audio files under a set of folders into a local
+//! SQLite database and answers browse and search queries from it. Metadata
+//! is read with [`LoftyMetadataExtractor`](crate::metadata::LoftyMetadataExtractor),
+//! in parallel, and rescans are incremental: files whose size and
+//! modification time haven't changed since the last scan are not read again.
+//!
+//! The database allows one writer next to any number of readers, so a scan
+//! can run on a background thread with its own [`Library::open`] while the UI
+//! keeps browsing.
+
+pub mod database;
+pub mod scanner;
+
+pub use database::{AlbumSummary, Library, LibraryTrack, TrackFilter};
+pub use scanner::ScanSummary;
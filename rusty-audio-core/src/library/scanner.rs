@@ -0,0 +1,266 @@
+//! Incremental scans of the library folders
+//!
+//! A scan walks every library folder, compares each supported audio file's
+//! size and modification time with the indexed ones, reads the metadata of
+//! new and changed files in parallel and drops files that are gone. Tracks
+//! under a folder that can't be read (an unplugged drive, say) are kept.
+
+use super::database::Library;
+use crate::error::Result;
+use crate::metadata::utils::is_supported_audio_format;
+use crate::metadata::{LoftyMetadataExtractor, MetadataExtractorInterface};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, warn};
+
+/// Size and modification time of a file, to tell whether it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    pub(crate) size: i64,
+    /// Nanoseconds since the Unix epoch
+    pub(crate) modified: i64,
+}
+
+impl FileStamp {
+    fn of(metadata: &fs::Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| {
+                i64::try_from(since.as_nanos()).unwrap_or(i64::MAX)
+            });
+        Self {
+            size: i64::try_from(metadata.len()).unwrap_or(i64::MAX),
+            modified,
+        }
+    }
+}
+
+/// Outcome of a library scan
+#[derive(Debug, Default)]
+pub struct ScanSummary {
+    /// Files indexed for the first time
+    pub added: usize,
+    /// Files read again because they changed
+    pub updated: usize,
+    /// Files left alone because they didn't change
+    pub unchanged: usize,
+    /// Files dropped because they are gone
+    pub removed: usize,
+    /// Library folders that couldn't be read; their tracks were kept
+    pub unavailable_folders: Vec<PathBuf>,
+    /// Files whose metadata couldn't be read, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl Library {
+    /// Scan the library folders, reading tags with [`LoftyMetadataExtractor`]
+    ///
+    /// # Errors
+    /// See [`Library::scan_with`]
+    pub fn scan(&mut self) -> Result<ScanSummary> {
+        self.scan_with(&LoftyMetadataExtractor::new())
+    }
+
+    /// Scan the library folders, reading new and changed files with
+    /// `extractor`
+    ///
+    /// Files that can't be read are reported in the summary rather than
+    /// failing the scan.
+    ///
+    /// # Errors
+    /// `Library` if the database can't be read or written
+    pub fn scan_with<E>(&mut self, extractor: &E) -> Result<ScanSummary>
+    where
+        E: MetadataExtractorInterface + Sync,
+    {
+        let mut summary = ScanSummary::default();
+        let mut found = HashMap::new();
+        for folder in self.folders()? {
+            if let Err(e) = find_audio_files(&folder, &mut found) {
+                warn!("Library folder {} unavailable: {}", folder.display(), e);
+                summary.unavailable_folders.push(folder);
+            }
+        }
+
+        let known = self.file_stamps()?;
+        let changed: Vec<(PathBuf, FileStamp)> = found
+            .iter()
+            .filter(|(path, stamp)| known.get(*path) != Some(*stamp))
+            .map(|(path, stamp)| (path.clone(), *stamp))
+            .collect();
+        summary.unchanged = found.len() - changed.len();
+
+        let extracted: Vec<_> = changed
+            .into_par_iter()
+            .map(|(path, stamp)| {
+                let metadata = extractor.extract_metadata(&path);
+                (path, stamp, metadata)
+            })
+            .collect();
+        let mut indexed = Vec::with_capacity(extracted.len());
+        for (path, stamp, metadata) in extracted {
+            match metadata {
+                Ok(metadata) => {
+                    if known.contains_key(&path) {
+                        summary.updated += 1;
+                    } else {
+                        summary.added += 1;
+                    }
+                    indexed.push((path, stamp, metadata));
+                }
+                // A changed file keeps its old entry, and is retried next scan
+                Err(e) => summary.failed.push((path, e.to_string())),
+            }
+        }
+
+        let removed: Vec<PathBuf> = known
+            .into_keys()
+            .filter(|path| {
+                !found.contains_key(path)
+                    && !summary
+                        .unavailable_folders
+                        .iter()
+                        .any(|folder| path.starts_with(folder))
+            })
+            .collect();
+        summary.removed = removed.len();
+
+        self.apply_scan(&indexed, &removed)?;
+        debug!("Library scan finished: {:?}", summary);
+        Ok(summary)
+    }
+}
+
+/// Add the supported audio files under `folder` to `found`
+///
+/// Only failing to read `folder` itself is an error; unreadable subfolders
+/// and files are skipped. Symlinked folders aren't followed, so links can't
+/// make the walk loop, and files with non-UTF-8 paths are skipped because
+/// the database stores paths as text.
+fn find_audio_files(folder: &Path, found: &mut HashMap<PathBuf, FileStamp>) -> std::io::Result<()> {
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == folder => return Err(e),
+            Err(e) => {
+                debug!("Skipping unreadable folder {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if path.to_str().is_some() && is_supported_audio_format(&path) {
+                // Follows symlinked files
+                if let Ok(metadata) = fs::metadata(&path) {
+                    if metadata.is_file() {
+                        found.insert(path, FileStamp::of(&metadata));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MetadataError;
+    use crate::library::TrackFilter;
+    use crate::metadata::{AlbumArt, TrackMetadata};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Names tracks after their files, counting the files it reads
+    #[derive(Default)]
+    struct FileNameExtractor {
+        reads: AtomicUsize,
+    }
+
+    impl MetadataExtractorInterface for FileNameExtractor {
+        fn extract_metadata(&self, path: &Path) -> Result<TrackMetadata> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            let title = path.file_stem().unwrap().to_string_lossy().to_string();
+            if title == "corrupt" {
+                return Err(MetadataError::ReadFailed.into());
+            }
+            Ok(TrackMetadata {
+                title,
+                ..Default::default()
+            })
+        }
+
+        fn extract_album_art(&self, _path: &Path) -> Result<Option<AlbumArt>> {
+            Ok(None)
+        }
+
+        fn process_album_art_for_ui(&self, _album_art: &AlbumArt) -> Result<egui::ColorImage> {
+            Err(MetadataError::NotFound.into())
+        }
+    }
+
+    #[test]
+    fn test_incremental_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("Artist").join("Album");
+        fs::create_dir_all(&album).unwrap();
+        fs::write(album.join("one.flac"), b"one").unwrap();
+        fs::write(album.join("two.mp3"), b"two").unwrap();
+        fs::write(album.join("corrupt.ogg"), b"?").unwrap();
+        fs::write(album.join("cover.jpg"), b"not audio").unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        library.add_folder(dir.path()).unwrap();
+        let extractor = FileNameExtractor::default();
+
+        let first = library.scan_with(&extractor).unwrap();
+        assert_eq!(first.added, 2);
+        assert_eq!(first.failed.len(), 1);
+        assert_eq!(library.track_count().unwrap(), 2);
+        assert_eq!(extractor.reads.load(Ordering::Relaxed), 3);
+
+        // Nothing changed: only the failed file is read again
+        let second = library.scan_with(&extractor).unwrap();
+        assert_eq!((second.added, second.unchanged), (0, 2));
+        assert_eq!(extractor.reads.load(Ordering::Relaxed), 4);
+
+        // A changed size is picked up, a deleted file dropped
+        fs::write(album.join("one.flac"), b"one, retagged").unwrap();
+        fs::remove_file(album.join("two.mp3")).unwrap();
+        let third = library.scan_with(&extractor).unwrap();
+        assert_eq!((third.updated, third.removed, third.unchanged), (1, 1, 0));
+        let tracks = library.tracks(&TrackFilter::All).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].path, album.join("one.flac"));
+        assert_eq!(tracks[0].metadata.title, "one");
+    }
+
+    #[test]
+    fn test_unavailable_folder_keeps_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let drive = dir.path().join("drive");
+        fs::create_dir(&drive).unwrap();
+        fs::write(drive.join("song.wav"), b"data").unwrap();
+
+        let mut library = Library::open_in_memory().unwrap();
+        library.add_folder(&drive).unwrap();
+        let extractor = FileNameExtractor::default();
+        assert_eq!(library.scan_with(&extractor).unwrap().added, 1);
+
+        fs::remove_dir_all(&drive).unwrap();
+        let summary = library.scan_with(&extractor).unwrap();
+        assert_eq!(summary.unavailable_folders, [drive]);
+        assert_eq!(summary.removed, 0);
+        assert_eq!(library.track_count().unwrap(), 1);
+    }
+}
@@ -0,0 +1,447 @@
+//! Song lyrics
+//!
+//! Lyrics come from an `.lrc` file next to the audio file, or from its tags:
+//! an ID3v2 `SYLT` frame for synchronized lyrics, or the unsynchronized
+//! lyrics tag (`USLT`, Vorbis `LYRICS`, MP4 `©lyr`) read through lofty.
+//! Taggers often store LRC text in the unsynchronized tag, so it is parsed
+//! as LRC too and only shown as plain text if it has no timestamps.
+//!
+//! Lofty's generic tag has no place for `SYLT` timings, so that frame is read
+//! straight from the ID3v2 tag at the start of the file.
+
+use crate::error::{FileError, MetadataError, Result};
+use crate::playlist::decode_text;
+use lofty::{file::TaggedFileExt, tag::ItemKey};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use tracing::debug;
+
+/// A line of synchronized lyrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricLine {
+    /// When the line starts
+    pub time: Duration,
+    /// Text of the line; empty lines clear the display
+    pub text: String,
+}
+
+/// Lyrics of a track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lyrics {
+    /// Timed lines, in time order
+    Synced(Vec<LyricLine>),
+    /// Text without timing
+    Plain(String),
+}
+
+impl Lyrics {
+    /// Lyrics for the audio file at `path`, from its `.lrc` sidecar or its
+    /// tags, in that order
+    ///
+    /// # Errors
+    /// `FileOperation` if the sidecar or the file's ID3v2 tag can't be read,
+    /// `Metadata` if lofty can't read the file's tags
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let sidecar = ["lrc", "LRC"]
+            .iter()
+            .map(|extension| path.with_extension(extension))
+            .find(|sidecar| sidecar.is_file());
+        if let Some(sidecar) = sidecar {
+            let text = decode_text(&fs::read(&sidecar).map_err(FileError::from)?);
+            debug!("Reading lyrics from {:?}", sidecar);
+            return Ok(Self::parse_lrc(&text));
+        }
+        if let Some(lines) = read_sylt(path)? {
+            return Ok(Some(Self::Synced(lines)));
+        }
+        Ok(read_lyrics_tag(path)?.and_then(|text| Self::parse_lrc(&text)))
+    }
+
+    /// Parse LRC text: `[mm:ss.xx]` timestamps before each line (several
+    /// for a repeated line), an optional `[offset:±ms]` tag, and enhanced
+    /// LRC `<mm:ss.xx>` word timings, which are dropped. Text without any
+    /// timestamps is returned as [`Lyrics::Plain`].
+    ///
+    /// Returns `None` if there is no text at all.
+    pub fn parse_lrc(text: &str) -> Option<Self> {
+        let mut lines = Vec::new();
+        let mut plain = Vec::new();
+        // Positive offsets show lines earlier
+        let mut offset_ms = 0i64;
+
+        for line in text.lines() {
+            let mut times = Vec::new();
+            let mut rest = line.trim();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|tag| tag.split_once(']'))
+            {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some((key, value)) = tag.split_once(':') {
+                    if key.trim().eq_ignore_ascii_case("offset") {
+                        offset_ms = value.trim().parse().unwrap_or(0);
+                    }
+                } else {
+                    break;
+                }
+                rest = after.trim_start();
+            }
+            let text = strip_word_times(rest);
+            if times.is_empty() {
+                if rest.len() == line.trim().len() {
+                    plain.push(text);
+                }
+                continue;
+            }
+            lines.extend(times.into_iter().map(|time| LyricLine {
+                time,
+                text: text.clone(),
+            }));
+        }
+
+        if lines.is_empty() {
+            let plain = plain.join("\n");
+            let plain = plain.trim();
+            return (!plain.is_empty()).then(|| Self::Plain(plain.to_string()));
+        }
+        for line in &mut lines {
+            line.time = shift(line.time, offset_ms);
+        }
+        lines.sort_by_key(|line| line.time);
+        Some(Self::Synced(lines))
+    }
+
+    /// Index of the synchronized line showing at `position`
+    pub fn line_at(&self, position: Duration) -> Option<usize> {
+        match self {
+            Self::Synced(lines) => lines
+                .partition_point(|line| line.time <= position)
+                .checked_sub(1),
+            Self::Plain(_) => None,
+        }
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx`
+///
+/// Times too long to count in milliseconds are rejected.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, ""));
+    let seconds: u64 = seconds.parse().ok()?;
+    if seconds >= 60 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Scale the fraction by its digits: "5" is 500 ms, "05" 50 ms
+    let mut millis = 0;
+    for (digit, scale) in fraction.bytes().zip([100, 10, 1]) {
+        millis += u64::from(digit - b'0') * scale;
+    }
+    let millis = minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(millis)?;
+    Some(Duration::from_millis(millis))
+}
+
+/// Line text without enhanced LRC `<mm:ss.xx>` word timings
+fn strip_word_times(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let (before, tag) = rest.split_at(start);
+        stripped.push_str(before);
+        match tag.split_once('>') {
+            Some((time, after)) if parse_timestamp(time.trim_start_matches('<')).is_some() => {
+                rest = after;
+            }
+            _ => {
+                stripped.push('<');
+                rest = tag.get(1..).unwrap_or_default();
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped.trim().to_string()
+}
+
+/// `time` moved earlier by `offset_ms`, or later for negative offsets
+fn shift(time: Duration, offset_ms: i64) -> Duration {
+    let offset = Duration::from_millis(offset_ms.unsigned_abs());
+    if offset_ms >= 0 {
+        time.saturating_sub(offset)
+    } else {
+        time + offset
+    }
+}
+
+/// The unsynchronized lyrics tag of the file
+fn read_lyrics_tag(path: &Path) -> Result<Option<String>> {
+    let tagged_file =
+        lofty::read_from_path(path).map_err(|e| MetadataError::LoftyError(e.to_string()))?;
+    Ok(tagged_file
+        .tags()
+        .iter()
+        .find_map(|tag| tag.get_string(&ItemKey::Lyrics))
+        .map(str::to_string))
+}
+
+/// Lines of the first `SYLT` frame with lyrics timed in milliseconds, from
+/// an ID3v2.3 or ID3v2.4 tag at the start of the file
+fn read_sylt(path: &Path) -> Result<Option<Vec<LyricLine>>> {
+    let mut file = fs::File::open(path).map_err(FileError::from)?;
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+    let [b'I', b'D', b'3', version @ (3 | 4), _, flags, size @ ..] = header else {
+        return Ok(None);
+    };
+    // Read through `take` so a bogus size can't allocate more than the file
+    // holds
+    let size = u64::from(syncsafe(size));
+    let mut tag = Vec::new();
+    (&mut file)
+        .take(size)
+        .read_to_end(&mut tag)
+        .map_err(FileError::from)?;
+    if (tag.len() as u64) < size {
+        return Err(
+            FileError::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)).into(),
+        );
+    }
+    if flags & 0x80 != 0 {
+        tag = remove_unsynchronisation(&tag);
+    }
+
+    let mut frames = tag.as_slice();
+    if flags & 0x40 != 0 {
+        // Extended header; its size excludes itself in v2.3 only
+        let Some((&size, _)) = frames.split_first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let size = match version {
+            3 => u32::from_be_bytes(size) as usize + 4,
+            _ => syncsafe(size) as usize,
+        };
+        frames = frames.get(size..).unwrap_or_default();
+    }
+
+    while let Some((&frame_header, rest)) = frames.split_first_chunk::<10>() {
+        let [i0, i1, i2, i3, s0, s1, s2, s3, _, format] = frame_header;
+        if i0 == 0 {
+            break; // Padding
+        }
+        let size = match version {
+            3 => u32::from_be_bytes([s0, s1, s2, s3]),
+            _ => syncsafe([s0, s1, s2, s3]),
+        };
+        let Some((data, after)) = rest.split_at_checked(size as usize) else {
+            break;
+        };
+        frames = after;
+        if [i0, i1, i2, i3] != *b"SYLT" {
+            continue;
+        }
+
+        // v2.4 frame format flags; v2.3 keeps compression and encryption
+        // in the same byte, at different bits
+        let (compressed, unsynchronised, length_indicator) = match version {
+            3 => (format & 0xC0 != 0, false, false),
+            _ => (format & 0x0C != 0, format & 0x02 != 0, format & 0x01 != 0),
+        };
+        if compressed {
+            continue;
+        }
+        let data = if length_indicator {
+            data.get(4..).unwrap_or_default()
+        } else {
+            data
+        };
+        let data = if unsynchronised {
+            remove_unsynchronisation(data)
+        } else {
+            data.to_vec()
+        };
+        if let Some(lines) = parse_sylt(&data) {
+            return Ok(Some(lines));
+        }
+    }
+    Ok(None)
+}
+
+/// Lines of a `SYLT` frame body, if it holds lyrics timed in milliseconds
+fn parse_sylt(data: &[u8]) -> Option<Vec<LyricLine>> {
+    let (&[encoding, _, _, _, timestamp_format, content_type], rest) =
+        data.split_first_chunk::<6>()?;
+    // Timestamps in MPEG frames would need the stream's frame rate
+    if timestamp_format != 2 || content_type > 1 {
+        return None;
+    }
+    let (_descriptor, mut rest) = split_terminated(rest, encoding)?;
+    let mut lines = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = split_terminated(rest, encoding)?;
+        let (&time, after) = after.split_first_chunk::<4>()?;
+        lines.push(LyricLine {
+            time: Duration::from_millis(u64::from(u32::from_be_bytes(time))),
+            text: decode_id3_text(text, encoding).trim().to_string(),
+        });
+        rest = after;
+    }
+    lines.sort_by_key(|line| line.time);
+    (!lines.is_empty()).then_some(lines)
+}
+
+/// A string terminated by NUL in `encoding`, and what follows the NUL
+fn split_terminated(data: &[u8], encoding: u8) -> Option<(&[u8], &[u8])> {
+    let (end, width) = match encoding {
+        0 | 3 => (data.iter().position(|&b| b == 0)?, 1),
+        _ => (data.chunks_exact(2).position(|pair| pair == [0, 0])? * 2, 2),
+    };
+    let (text, rest) = data.split_at_checked(end)?;
+    Some((text, rest.get(width..)?))
+}
+
+/// ID3v2 text in `encoding`: Latin-1, UTF-16 with a byte order mark,
+/// UTF-16BE or UTF-8
+fn decode_id3_text(bytes: &[u8], encoding: u8) -> String {
+    match encoding {
+        0 => bytes.iter().map(|&b| char::from(b)).collect(),
+        1 | 2 => {
+            let (little_endian, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (true, rest),
+                [0xFE, 0xFF, rest @ ..] => (false, rest),
+                _ => (false, bytes),
+            };
+            let units = bytes.chunks_exact(2).map(|pair| {
+                let pair = pair.try_into().unwrap_or_default();
+                if little_endian {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// A 28-bit ID3v2 "syncsafe" integer, seven bits per byte
+fn syncsafe(bytes: [u8; 4]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, &b| (value << 7) | u32::from(b & 0x7F))
+}
+
+/// Undo ID3v2 unsynchronisation, which puts a zero byte after every 0xFF
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut restored = Vec::with_capacity(data.len());
+    let mut previous = 0;
+    for &b in data {
+        if !(previous == 0xFF && b == 0) {
+            restored.push(b);
+        }
+        previous = b;
+    }
+    restored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lrc() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ar:Dawn Choir]\n\
+             [offset:+500]\n\
+             [00:12.00]First <00:12.50>line\n\
+             [00:05.5][01:00.250]Chorus\n\
+             [00:20:10]\n\
+             [bad]not a tag\n\
+             [99999999999999999:00.00]too late\n",
+        )
+        .unwrap();
+        let Lyrics::Synced(lines) = &lyrics else {
+            panic!("expected synchronized lyrics");
+        };
+        let times: Vec<u64> = lines.iter().map(|l| l.time.as_millis() as u64).collect();
+        assert_eq!(times, [5000, 11500, 19600, 59750]);
+        assert_eq!(lines[0].text, "Chorus");
+        assert_eq!(lines[1].text, "First line");
+        assert_eq!(lines[2].text, "");
+
+        assert_eq!(lyrics.line_at(Duration::from_secs(1)), None);
+        assert_eq!(lyrics.line_at(Duration::from_millis(11500)), Some(1));
+        assert_eq!(lyrics.line_at(Duration::from_secs(600)), Some(3));
+
+        assert_eq!(
+            Lyrics::parse_lrc("\n  Verse one\nVerse <two>\n"),
+            Some(Lyrics::Plain("Verse one\nVerse <two>".to_string()))
+        );
+        assert_eq!(Lyrics::parse_lrc("[ti:Only tags]\n\n"), None);
+    }
+
+    #[test]
+    fn test_sylt_frame() {
+        // UTF-16 text with BOMs, out of order, in an ID3v2.4 tag
+        let utf16 = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes.extend([0, 0]);
+            bytes
+        };
+        let mut frame = vec![1, b'e', b'n', b'g', 2, 1];
+        frame.extend(utf16(""));
+        frame.extend(utf16("Zweite Zeile"));
+        frame.extend(3000u32.to_be_bytes());
+        frame.extend(utf16("\nErste Zeile"));
+        frame.extend(1000u32.to_be_bytes());
+
+        let mut tag = b"TIT2".to_vec();
+        tag.extend([0, 0, 0, 4, 0, 0, 3, b'A', b'b', b'c']);
+        tag.extend(b"SYLT");
+        tag.extend((frame.len() as u32).to_be_bytes());
+        tag.extend([0, 0]);
+        tag.extend(&frame);
+        tag.extend([0; 16]);
+        let mut file = b"ID3\x04\x00\x00".to_vec();
+        file.extend([0, 0, 0, tag.len() as u8]);
+        file.extend(&tag);
+        file.extend([0xFF, 0xFB, 0x90, 0x00]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        fs::write(&path, file).unwrap();
+        let lines = read_sylt(&path).unwrap().unwrap();
+        assert_eq!(
+            lines,
+            [
+                LyricLine {
+                    time: Duration::from_secs(1),
+                    text: "Erste Zeile".to_string(),
+                },
+                LyricLine {
+                    time: Duration::from_secs(3),
+                    text: "Zweite Zeile".to_string(),
+                },
+            ]
+        );
+
+        // MPEG frame timestamps aren't supported
+        frame[4] = 1;
+        assert_eq!(parse_sylt(&frame), None);
+
+        // A header claiming a 256 MB tag in a tiny file
+        fs::write(&path, b"ID3\x04\x00\x00\x7F\x7F\x7F\x7FSYLT").unwrap();
+        assert!(read_sylt(&path).is_err());
+    }
+}
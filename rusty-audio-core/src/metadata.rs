@@ -3,13 +3,20 @@
 //! This module handles audio file metadata extraction and album art processing.
 //! It follows the Single Responsibility Principle by handling only metadata operations.
 
+use crate::audio::replay_gain::{
+    ReplayGain, ALBUM_GAIN_KEY, ALBUM_PEAK_KEY, TRACK_GAIN_KEY, TRACK_PEAK_KEY,
+};
 use crate::error::{ErrorContext, ImageError, MetadataError, Result};
+use crate::security::{InputValidator, ValidationError};
 use image::GenericImageView;
 use lofty::{
-    file::{AudioFile, TaggedFileExt},
-    tag::Accessor,
+    config::WriteOptions,
+    file::{AudioFile, TaggedFile, TaggedFileExt},
+    picture::{Picture, PictureType},
+    tag::{Accessor, ItemKey, Tag, TagExt},
 };
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
 /// Track metadata extracted from audio files
@@ -23,6 +30,8 @@ pub struct TrackMetadata {
     pub genre: Option<String>,
     pub track_number: Option<u32>,
     pub album_artist: Option<String>,
+    /// Loudness tags, if any
+    pub replay_gain: ReplayGain,
 }
 
 impl Default for TrackMetadata {
@@ -36,6 +45,7 @@ impl Default for TrackMetadata {
             genre: None,
             track_number: None,
             album_artist: None,
+            replay_gain: ReplayGain::default(),
         }
     }
 }
@@ -80,6 +90,7 @@ impl MetadataExtractorInterface for LoftyMetadataExtractor {
 
         let properties = tagged_file.file_type();
         let duration = tagged_file.properties().duration();
+        let replay_gain = read_replay_gain(&tagged_file);
 
         let metadata = if let Some(tag) = tagged_file.primary_tag() {
             TrackMetadata {
@@ -105,12 +116,17 @@ impl MetadataExtractorInterface for LoftyMetadataExtractor {
                 duration: Some(duration),
                 genre: tag.genre().as_deref().map(|s| s.to_string()),
                 track_number: tag.track().map(|t| t as u32),
-                album_artist: tag.artist().as_deref().map(|s| s.to_string()), // Use artist as fallback
+                album_artist: tag
+                    .get_string(&ItemKey::AlbumArtist)
+                    .map(str::to_string)
+                    .or_else(|| tag.artist().map(|s| s.to_string())), // Use artist as fallback
+                replay_gain,
             }
         } else {
             warn!("No primary tag found, using defaults");
             TrackMetadata {
                 duration: Some(duration),
+                replay_gain,
                 ..Default::default()
             }
         };
@@ -128,7 +144,15 @@ impl MetadataExtractorInterface for LoftyMetadataExtractor {
         let tagged_file =
             lofty::read_from_path(path).map_err(|e| MetadataError::LoftyError(e.to_string()))?;
 
-        if let Some(picture) = tagged_file.primary_tag().and_then(|t| t.pictures().get(0)) {
+        // Prefer the front cover, which is what the tag editor writes
+        let picture = tagged_file.primary_tag().and_then(|t| {
+            let pictures = t.pictures();
+            pictures
+                .iter()
+                .find(|p| p.pic_type() == PictureType::CoverFront)
+                .or_else(|| pictures.first())
+        });
+        if let Some(picture) = picture {
             debug!("Found album art, size: {} bytes", picture.data().len());
 
             // Try to get dimensions using image crate
@@ -181,12 +205,365 @@ impl MetadataExtractorInterface for LoftyMetadataExtractor {
     }
 }
 
+/// Read ReplayGain/R128 values from whichever of the file's tags has them
+///
+/// Files often carry several tags (e.g. ID3v2 and APE on MP3s) and taggers
+/// don't always write loudness to the primary one.
+pub(crate) fn read_replay_gain(tagged_file: &TaggedFile) -> ReplayGain {
+    ReplayGain::from_tags(|name| {
+        let key = match name {
+            TRACK_GAIN_KEY => ItemKey::ReplayGainTrackGain,
+            TRACK_PEAK_KEY => ItemKey::ReplayGainTrackPeak,
+            ALBUM_GAIN_KEY => ItemKey::ReplayGainAlbumGain,
+            ALBUM_PEAK_KEY => ItemKey::ReplayGainAlbumPeak,
+            other => ItemKey::Unknown(other.to_string()),
+        };
+        tagged_file
+            .tags()
+            .iter()
+            .find_map(|tag| tag.get_string(&key))
+    })
+}
+
 impl Default for LoftyMetadataExtractor {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// A tag field the tag editor can change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TagField {
+    /// Track title
+    Title,
+    /// Track artist
+    Artist,
+    /// Album title
+    Album,
+    /// Album artist, when it differs from the track artist
+    AlbumArtist,
+    /// Release year
+    Year,
+    /// Genre
+    Genre,
+    /// Track number within the album
+    TrackNumber,
+    /// Free-form comment
+    Comment,
+}
+
+impl TagField {
+    /// Every editable field, in display order
+    pub const ALL: [TagField; 8] = [
+        TagField::Title,
+        TagField::Artist,
+        TagField::Album,
+        TagField::AlbumArtist,
+        TagField::Year,
+        TagField::Genre,
+        TagField::TrackNumber,
+        TagField::Comment,
+    ];
+
+    /// Display name
+    pub fn label(self) -> &'static str {
+        match self {
+            TagField::Title => "Title",
+            TagField::Artist => "Artist",
+            TagField::Album => "Album",
+            TagField::AlbumArtist => "Album Artist",
+            TagField::Year => "Year",
+            TagField::Genre => "Genre",
+            TagField::TrackNumber => "Track",
+            TagField::Comment => "Comment",
+        }
+    }
+
+    /// Key passed to [`InputValidator::validate_metadata`]
+    fn key(self) -> &'static str {
+        match self {
+            TagField::Title => "title",
+            TagField::Artist => "artist",
+            TagField::Album => "album",
+            TagField::AlbumArtist => "album_artist",
+            TagField::Year => "year",
+            TagField::Genre => "genre",
+            TagField::TrackNumber => "track_number",
+            TagField::Comment => "comment",
+        }
+    }
+
+    /// Whether the field holds a positive number
+    fn is_numeric(self) -> bool {
+        matches!(self, TagField::Year | TagField::TrackNumber)
+    }
+}
+
+/// The editable fields of a file's primary tag, as text
+///
+/// Unlike [`TrackMetadata`] there are no placeholders: unset fields are
+/// empty, so they can be shown in an editor and written back as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFields {
+    fields: BTreeMap<TagField, String>,
+}
+
+impl TagFields {
+    /// Read the primary tag of `path`
+    ///
+    /// # Errors
+    /// `Metadata` if the file can't be read by lofty
+    pub fn read(path: &Path) -> Result<Self> {
+        let tagged_file =
+            lofty::read_from_path(path).map_err(|e| MetadataError::LoftyError(e.to_string()))?;
+        let mut fields = BTreeMap::new();
+        if let Some(tag) = tagged_file.primary_tag() {
+            for field in TagField::ALL {
+                let value = match field {
+                    TagField::Title => tag.title().map(|s| s.to_string()),
+                    TagField::Artist => tag.artist().map(|s| s.to_string()),
+                    TagField::Album => tag.album().map(|s| s.to_string()),
+                    TagField::AlbumArtist => {
+                        tag.get_string(&ItemKey::AlbumArtist).map(str::to_string)
+                    }
+                    TagField::Year => tag.year().map(|y| y.to_string()),
+                    TagField::Genre => tag.genre().map(|s| s.to_string()),
+                    TagField::TrackNumber => tag.track().map(|t| t.to_string()),
+                    TagField::Comment => tag.comment().map(|s| s.to_string()),
+                };
+                if let Some(value) = value {
+                    fields.insert(field, value);
+                }
+            }
+        }
+        Ok(Self { fields })
+    }
+
+    /// Text of `field`, empty if unset
+    pub fn get(&self, field: TagField) -> &str {
+        self.fields.get(&field).map_or("", String::as_str)
+    }
+}
+
+/// Change to the embedded front cover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverArtEdit {
+    /// Embed this image (PNG, JPEG, GIF, BMP or TIFF data)
+    Replace(Vec<u8>),
+    /// Remove the front cover
+    Remove,
+}
+
+impl CoverArtEdit {
+    /// Cover art from an image file
+    ///
+    /// # Errors
+    /// `FileOperation` if the file can't be read, `ImageProcessing` if it
+    /// isn't an image
+    pub fn from_image_file(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(crate::error::FileError::from)?;
+        image::load_from_memory(&data).map_err(|e| ImageError::ProcessingFailed {
+            details: e.to_string(),
+        })?;
+        Ok(CoverArtEdit::Replace(data))
+    }
+}
+
+/// Changes to write to the tags of one or more files
+///
+/// Fields that aren't set are left as they are in each file, which is what
+/// makes batch edits work: setting the album of ten tracks keeps their
+/// titles. Setting a field to an empty string removes it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagEdit {
+    fields: BTreeMap<TagField, String>,
+    cover_art: Option<CoverArtEdit>,
+}
+
+/// Outcome of writing a [`TagEdit`] to several files
+#[derive(Debug, Default)]
+pub struct TagWriteReport {
+    /// Files whose tags were written
+    pub written: Vec<PathBuf>,
+    /// Files that couldn't be tagged, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl TagEdit {
+    /// An edit that changes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `field` to `value`, or remove it if `value` is empty
+    pub fn set(&mut self, field: TagField, value: impl Into<String>) {
+        self.fields.insert(field, value.into());
+    }
+
+    /// Leave `field` as it is in each file
+    pub fn unset(&mut self, field: TagField) {
+        self.fields.remove(&field);
+    }
+
+    /// New value of `field`, if the edit changes it
+    pub fn get(&self, field: TagField) -> Option<&str> {
+        self.fields.get(&field).map(String::as_str)
+    }
+
+    /// Change the front cover
+    pub fn set_cover_art(&mut self, cover_art: Option<CoverArtEdit>) {
+        self.cover_art = cover_art;
+    }
+
+    /// Cover change, if any
+    pub fn cover_art(&self) -> Option<&CoverArtEdit> {
+        self.cover_art.as_ref()
+    }
+
+    /// Whether the edit changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.cover_art.is_none()
+    }
+
+    /// The edit as it will be written
+    ///
+    /// Text is sanitized with [`InputValidator::validate_metadata`], which
+    /// drops disallowed characters and truncates long values, so the result
+    /// can differ from what was typed.
+    ///
+    /// # Errors
+    /// `InvalidMetadata` if a year or track number isn't a positive number
+    pub fn validated(&self) -> std::result::Result<Self, ValidationError> {
+        let mut fields = BTreeMap::new();
+        for (&field, value) in &self.fields {
+            let (_, value) = InputValidator::validate_metadata(field.key(), value)?;
+            if field.is_numeric() && !value.is_empty() {
+                match value.parse::<u32>() {
+                    Ok(number) if number > 0 => {}
+                    _ => {
+                        return Err(ValidationError::InvalidMetadata {
+                            key: field.key().to_string(),
+                            reason: format!("'{}' is not a positive number", value),
+                        })
+                    }
+                }
+            }
+            fields.insert(field, value);
+        }
+        Ok(Self {
+            fields,
+            cover_art: self.cover_art.clone(),
+        })
+    }
+
+    /// Validate the edit and write it to the primary tag of `path`,
+    /// creating the tag if the file has none
+    ///
+    /// # Errors
+    /// `Metadata` if the edit is invalid or the file can't be tagged
+    pub fn write(&self, path: &Path) -> Result<()> {
+        self.validated().map_err(MetadataError::from)?.apply(path)
+    }
+
+    /// Validate the edit once and write it to every file in `paths`
+    ///
+    /// Files that fail are reported rather than stopping the batch.
+    ///
+    /// # Errors
+    /// `Metadata` if the edit is invalid, before any file is touched
+    pub fn write_all(&self, paths: &[PathBuf]) -> Result<TagWriteReport> {
+        let edit = self.validated().map_err(MetadataError::from)?;
+        let mut report = TagWriteReport::default();
+        for path in paths {
+            match edit.apply(path) {
+                Ok(()) => report.written.push(path.clone()),
+                Err(e) => {
+                    warn!("Failed to tag {:?}: {}", path, e);
+                    report.failed.push((path.clone(), e.to_string()));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Write an already validated edit
+    fn apply(&self, path: &Path) -> Result<()> {
+        debug!("Writing tags to: {:?}", path);
+        let lofty_error = |e: lofty::error::LoftyError| MetadataError::LoftyError(e.to_string());
+
+        let mut tagged_file = lofty::read_from_path(path).map_err(lofty_error)?;
+        let tag_type = tagged_file.primary_tag_type();
+        if tagged_file.tag(tag_type).is_none() {
+            tagged_file.insert_tag(Tag::new(tag_type));
+        }
+        let Some(tag) = tagged_file.tag_mut(tag_type) else {
+            return Err(MetadataError::InvalidFormat.into());
+        };
+
+        for (&field, value) in &self.fields {
+            if value.is_empty() {
+                remove_field(tag, field);
+            } else {
+                set_field(tag, field, value);
+            }
+        }
+
+        match &self.cover_art {
+            Some(CoverArtEdit::Replace(data)) => {
+                let mut picture =
+                    Picture::from_reader(&mut data.as_slice()).map_err(lofty_error)?;
+                picture.set_pic_type(PictureType::CoverFront);
+                tag.remove_picture_type(PictureType::CoverFront);
+                tag.push_picture(picture);
+            }
+            Some(CoverArtEdit::Remove) => tag.remove_picture_type(PictureType::CoverFront),
+            None => {}
+        }
+
+        tag.save_to_path(path, WriteOptions::default())
+            .map_err(lofty_error)?;
+        Ok(())
+    }
+}
+
+fn set_field(tag: &mut Tag, field: TagField, value: &str) {
+    let value = value.to_string();
+    match field {
+        TagField::Title => tag.set_title(value),
+        TagField::Artist => tag.set_artist(value),
+        TagField::Album => tag.set_album(value),
+        TagField::AlbumArtist => {
+            tag.insert_text(ItemKey::AlbumArtist, value);
+        }
+        TagField::Genre => tag.set_genre(value),
+        TagField::Comment => tag.set_comment(value),
+        // Checked by `TagEdit::validated`
+        TagField::Year => {
+            if let Ok(year) = value.parse() {
+                tag.set_year(year);
+            }
+        }
+        TagField::TrackNumber => {
+            if let Ok(track) = value.parse() {
+                tag.set_track(track);
+            }
+        }
+    }
+}
+
+fn remove_field(tag: &mut Tag, field: TagField) {
+    match field {
+        TagField::Title => tag.remove_title(),
+        TagField::Artist => tag.remove_artist(),
+        TagField::Album => tag.remove_album(),
+        TagField::AlbumArtist => tag.remove_key(&ItemKey::AlbumArtist),
+        TagField::Year => tag.remove_year(),
+        TagField::Genre => tag.remove_genre(),
+        TagField::TrackNumber => tag.remove_track(),
+        TagField::Comment => tag.remove_comment(),
+    }
+}
+
 /// Utility functions for metadata handling
 pub mod utils {
     use super::*;
@@ -262,4 +639,96 @@ mod tests {
             "Textwithcontrol"
         );
     }
+
+    #[test]
+    fn test_tag_edit_validation() {
+        let mut edit = TagEdit::new();
+        edit.set(TagField::Title, "Field <Recording> #1");
+        edit.set(TagField::Comment, "");
+        let validated = edit.validated().unwrap();
+        assert_eq!(validated.get(TagField::Title), Some("Field Recording 1"));
+        // Empty removes the field; unset fields are left alone
+        assert_eq!(validated.get(TagField::Comment), Some(""));
+        assert_eq!(validated.get(TagField::Artist), None);
+
+        edit.set(TagField::Year, "1999");
+        edit.set(TagField::TrackNumber, "");
+        assert!(edit.validated().is_ok());
+        edit.set(TagField::Year, "last year");
+        assert!(edit.validated().is_err());
+        edit.set(TagField::Year, "0");
+        assert!(edit.validated().is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_batch_tag_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let paths: Vec<PathBuf> = ["one.wav", "two.wav"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+                for _ in 0..800 {
+                    writer.write_sample(0i16).unwrap();
+                }
+                writer.finalize().unwrap();
+                path
+            })
+            .collect();
+
+        let mut title = TagEdit::new();
+        title.set(TagField::Title, "Dawn");
+        title.set(TagField::Comment, "Recorded at 5am");
+        title.write(&paths[0]).unwrap();
+
+        let mut cover = Vec::new();
+        image::DynamicImage::new_rgb8(2, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut cover),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        let mut batch = TagEdit::new();
+        batch.set(TagField::Album, "Dawn Chorus");
+        batch.set(TagField::Year, "2024");
+        batch.set(TagField::Comment, "");
+        batch.set_cover_art(Some(CoverArtEdit::Replace(cover)));
+        let missing = dir.path().join("missing.wav");
+        let mut targets = paths.clone();
+        targets.push(missing.clone());
+        let report = batch.write_all(&targets).unwrap();
+        assert_eq!(report.written, paths);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, missing);
+
+        let first = TagFields::read(&paths[0]).unwrap();
+        assert_eq!(first.get(TagField::Title), "Dawn");
+        assert_eq!(first.get(TagField::Album), "Dawn Chorus");
+        assert_eq!(first.get(TagField::Year), "2024");
+        assert_eq!(first.get(TagField::Comment), "");
+        let second = TagFields::read(&paths[1]).unwrap();
+        assert_eq!(second.get(TagField::Title), "");
+        assert_eq!(second.get(TagField::Album), "Dawn Chorus");
+
+        let extractor = LoftyMetadataExtractor::new();
+        let art = extractor.extract_album_art(&paths[1]).unwrap().unwrap();
+        assert_eq!((art.width, art.height), (2, 2));
+
+        let mut remove = TagEdit::new();
+        remove.set_cover_art(Some(CoverArtEdit::Remove));
+        remove.write(&paths[1]).unwrap();
+        assert!(extractor.extract_album_art(&paths[1]).unwrap().is_none());
+
+        // Invalid edits fail before touching any file
+        let mut invalid = TagEdit::new();
+        invalid.set(TagField::TrackNumber, "A1");
+        assert!(invalid.write_all(&paths).is_err());
+    }
 }
@@ -0,0 +1,657 @@
+//! CUE sheets
+//!
+//! A cue sheet splits one or more audio files into tracks, usually a whole
+//! album ripped to a single FLAC. Each [`CueTrack`] is a span of a file,
+//! from its `INDEX 01` to the next track's, with its own [`TrackMetadata`].
+//! Playing the file straight through plays the tracks back to back with no
+//! gaps, so players only need to seek to a track's start and follow the
+//! position to know which track is playing.
+//!
+//! Sheets come from `.cue` files, or are embedded in FLAC files either as a
+//! `CUESHEET` Vorbis comment holding the sheet's text or as a binary
+//! `CUESHEET` metadata block.
+
+use super::{decode_text, native_separators};
+use crate::audio::replay_gain::ReplayGain;
+use crate::error::{FileError, MetadataError, PlaylistError, Result};
+use crate::metadata::TrackMetadata;
+use lofty::{
+    file::{AudioFile, TaggedFileExt},
+    tag::{Accessor, ItemKey},
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// CD frames (sectors) per second, the unit of cue sheet times
+const FRAMES_PER_SECOND: u64 = 75;
+
+/// Extensions tried when a sheet names a file that isn't there, since rips
+/// are often re-encoded without updating the sheet ("Album.wav" next to
+/// "Album.flac")
+const FALLBACK_EXTENSIONS: [&str; 6] = ["flac", "wav", "wv", "ape", "m4a", "mp3"];
+
+/// A track of a cue sheet
+#[derive(Debug, Clone)]
+pub struct CueTrack {
+    /// Track number in the sheet
+    pub number: u32,
+    /// Audio file the track is part of
+    pub file: PathBuf,
+    /// Position of `INDEX 01` in the file
+    pub start: Duration,
+    /// Where the next track in the same file starts, or `None` if the track
+    /// runs to the end of the file
+    pub end: Option<Duration>,
+    /// Title, performer and album; `duration` is the length of the span
+    /// when it is known
+    pub metadata: TrackMetadata,
+}
+
+impl CueTrack {
+    /// Whether `position` in `file` is part of this track
+    pub fn contains(&self, file: &Path, position: Duration) -> bool {
+        self.file == file && position >= self.start && self.end.is_none_or(|end| position < end)
+    }
+}
+
+/// A parsed cue sheet
+#[derive(Debug, Clone, Default)]
+pub struct CueSheet {
+    /// Album title
+    pub title: Option<String>,
+    /// Album performer
+    pub performer: Option<String>,
+    /// Tracks in sheet order
+    pub tracks: Vec<CueTrack>,
+}
+
+/// Fields of a track while it is being parsed
+#[derive(Default)]
+struct ParsedTrack {
+    number: u32,
+    file: PathBuf,
+    start: Option<Duration>,
+    title: Option<String>,
+    performer: Option<String>,
+    comments: HashMap<String, String>,
+}
+
+impl CueSheet {
+    /// Read a `.cue` file, resolving its files against the sheet's folder
+    ///
+    /// The length of each file's last track is read from the audio file.
+    ///
+    /// # Errors
+    /// `FileOperation` if the sheet can't be read, `Playlist` if it isn't a
+    /// valid cue sheet
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = decode_text(&fs::read(path).map_err(FileError::from)?);
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut sheet = Self::parse(&text, base).map_err(|reason| PlaylistError::Malformed {
+            path: path.display().to_string(),
+            reason,
+        })?;
+        for track in &mut sheet.tracks {
+            track.file = existing_file(&track.file);
+        }
+        sheet.fill_last_durations();
+        debug!("Read cue sheet {:?}: {} tracks", path, sheet.tracks.len());
+        Ok(sheet)
+    }
+
+    /// Parse the text of a cue sheet; relative file names are resolved
+    /// against `base`
+    ///
+    /// # Errors
+    /// A description of the problem if there are no audio tracks, a track
+    /// has no `INDEX 01` or a time is malformed
+    pub fn parse(text: &str, base: &Path) -> std::result::Result<Self, String> {
+        let mut sheet = CueSheet::default();
+        let mut comments = HashMap::new();
+        let mut genre = None;
+        let mut date = None;
+        let mut file = None;
+        let mut parsed: Vec<ParsedTrack> = Vec::new();
+        // Inside a TRACK of a type other than AUDIO, whose commands we skip
+        let mut in_data_track = false;
+
+        for (line_number, line) in (1..).zip(text.lines()) {
+            let args = split_args(line);
+            let Some((command, args)) = args.split_first() else {
+                continue;
+            };
+            let arg = |index: usize| args.get(index).map(String::as_str);
+            // Before the first TRACK, TITLE and the like describe the album
+            let in_header = parsed.is_empty();
+            let track = parsed.last_mut().filter(|_| !in_data_track);
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    let name = arg(0)
+                        .ok_or_else(|| format!("Line {}: FILE without a name", line_number))?;
+                    let path = native_separators(name);
+                    file = Some(if path.is_absolute() {
+                        path
+                    } else {
+                        base.join(path)
+                    });
+                }
+                "TRACK" => {
+                    let file = file
+                        .clone()
+                        .ok_or_else(|| format!("Line {}: TRACK before FILE", line_number))?;
+                    in_data_track = !arg(1).is_some_and(|kind| kind.eq_ignore_ascii_case("AUDIO"));
+                    if !in_data_track {
+                        let number = arg(0).and_then(|n| n.parse().ok()).ok_or_else(|| {
+                            format!("Line {}: TRACK without a number", line_number)
+                        })?;
+                        parsed.push(ParsedTrack {
+                            number,
+                            file,
+                            ..Default::default()
+                        });
+                    }
+                }
+                "INDEX" => {
+                    if let Some(track) = track.filter(|_| arg(0) == Some("01")) {
+                        let time = arg(1).unwrap_or_default();
+                        let start = parse_time(time)
+                            .ok_or_else(|| format!("Line {}: bad time '{}'", line_number, time))?;
+                        track.start = Some(start);
+                    }
+                }
+                "TITLE" | "PERFORMER" => {
+                    let value = arg(0).map(str::to_string);
+                    let is_title = command.eq_ignore_ascii_case("TITLE");
+                    match (track, is_title) {
+                        (Some(track), true) => track.title = value,
+                        (Some(track), false) => track.performer = value,
+                        (None, true) if in_header => sheet.title = value,
+                        (None, false) if in_header => sheet.performer = value,
+                        (None, _) => {}
+                    }
+                }
+                "REM" => {
+                    let (Some(key), Some(value)) = (arg(0), arg(1)) else {
+                        continue;
+                    };
+                    let key = key.to_ascii_uppercase();
+                    match (track, key.as_str()) {
+                        (Some(track), _) => {
+                            track.comments.insert(key, value.to_string());
+                        }
+                        (None, "GENRE") => genre = Some(value.to_string()),
+                        (None, "DATE") => date = Some(value.to_string()),
+                        (None, _) => {
+                            comments.insert(key, value.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if parsed.is_empty() {
+            return Err("No audio tracks".to_string());
+        }
+        for track in parsed {
+            let start = track
+                .start
+                .ok_or_else(|| format!("Track {} has no INDEX 01", track.number))?;
+            let replay_gain = ReplayGain::from_tags(|key| {
+                track
+                    .comments
+                    .get(key)
+                    .or_else(|| comments.get(key))
+                    .map(String::as_str)
+            });
+            let metadata = TrackMetadata {
+                title: track
+                    .title
+                    .unwrap_or_else(|| format!("Track {:02}", track.number)),
+                artist: track
+                    .performer
+                    .or_else(|| sheet.performer.clone())
+                    .unwrap_or_else(|| "Unknown Artist".to_string()),
+                album: sheet
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| "Unknown Album".to_string()),
+                year: date.clone().unwrap_or_else(|| "----".to_string()),
+                duration: None,
+                genre: genre.clone(),
+                track_number: Some(track.number),
+                album_artist: sheet.performer.clone(),
+                replay_gain,
+            };
+            sheet.tracks.push(CueTrack {
+                number: track.number,
+                file: track.file,
+                start,
+                end: None,
+                metadata,
+            });
+        }
+        sheet.link_tracks();
+        Ok(sheet)
+    }
+
+    /// Read the cue sheet embedded in a FLAC file, if it has one
+    ///
+    /// A `CUESHEET` Vorbis comment is preferred since it has titles; tracks
+    /// from a binary `CUESHEET` block are named by number. Album, artist and
+    /// year missing from the sheet are taken from the file's tags.
+    ///
+    /// # Errors
+    /// `Metadata` if the file can't be read, `Playlist` if the embedded
+    /// sheet is malformed
+    pub fn from_flac(path: &Path) -> Result<Option<Self>> {
+        let tagged_file =
+            lofty::read_from_path(path).map_err(|e| MetadataError::LoftyError(e.to_string()))?;
+        let file_duration = tagged_file.properties().duration();
+        let tag = tagged_file.primary_tag();
+        let cue_text =
+            tag.and_then(|tag| tag.get_string(&ItemKey::Unknown("CUESHEET".to_string())));
+
+        let mut sheet = match cue_text {
+            Some(text) => {
+                let base = path.parent().unwrap_or_else(|| Path::new(""));
+                Self::parse(text, base).map_err(|reason| PlaylistError::Malformed {
+                    path: path.display().to_string(),
+                    reason,
+                })?
+            }
+            None => match read_flac_cuesheet_block(path)? {
+                Some(sheet) => sheet,
+                None => return Ok(None),
+            },
+        };
+
+        // The sheet describes this file, whatever name it was ripped under
+        for track in &mut sheet.tracks {
+            track.file = path.to_path_buf();
+        }
+        if let Some(tag) = tag {
+            let title = tag.album().map(|s| s.to_string());
+            let performer = tag
+                .get_string(&ItemKey::AlbumArtist)
+                .map(str::to_string)
+                .or_else(|| tag.artist().map(|s| s.to_string()));
+            let year = tag.year().map(|y| y.to_string());
+            let genre = tag.genre().map(|s| s.to_string());
+            sheet.title = sheet.title.or(title);
+            sheet.performer = sheet.performer.or(performer);
+            for track in &mut sheet.tracks {
+                let metadata = &mut track.metadata;
+                if let Some(album) = sheet
+                    .title
+                    .as_ref()
+                    .filter(|_| metadata.album == "Unknown Album")
+                {
+                    metadata.album = album.clone();
+                }
+                if let Some(artist) = sheet
+                    .performer
+                    .as_ref()
+                    .filter(|_| metadata.artist == "Unknown Artist")
+                {
+                    metadata.artist = artist.clone();
+                }
+                if let Some(year) = year.as_ref().filter(|_| metadata.year == "----") {
+                    metadata.year = year.clone();
+                }
+                metadata.genre = metadata.genre.take().or_else(|| genre.clone());
+                metadata.album_artist = metadata
+                    .album_artist
+                    .take()
+                    .or_else(|| sheet.performer.clone());
+            }
+        }
+        for track in sheet.tracks.iter_mut().filter(|track| track.end.is_none()) {
+            track.metadata.duration = file_duration.checked_sub(track.start);
+        }
+        Ok(Some(sheet))
+    }
+
+    /// The audio files of the sheet, in play order and without repeats
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files: Vec<&Path> = Vec::new();
+        for track in &self.tracks {
+            if !files.contains(&track.file.as_path()) {
+                files.push(&track.file);
+            }
+        }
+        files
+    }
+
+    /// Index of the track playing at `position` in `file`
+    pub fn track_at(&self, file: &Path, position: Duration) -> Option<usize> {
+        self.tracks
+            .iter()
+            .position(|track| track.contains(file, position))
+    }
+
+    /// End each track where the next one in the same file starts
+    fn link_tracks(&mut self) {
+        let starts: Vec<(PathBuf, Duration)> = self
+            .tracks
+            .iter()
+            .map(|track| (track.file.clone(), track.start))
+            .collect();
+        for (track, next) in self.tracks.iter_mut().zip(starts.iter().skip(1)) {
+            if track.file == next.0 {
+                track.end = Some(next.1);
+                track.metadata.duration = next.1.checked_sub(track.start);
+            }
+        }
+    }
+
+    /// Set the length of each file's last track from the file's duration
+    fn fill_last_durations(&mut self) {
+        for track in self.tracks.iter_mut().filter(|track| track.end.is_none()) {
+            match lofty::read_from_path(&track.file) {
+                Ok(file) => {
+                    track.metadata.duration = file.properties().duration().checked_sub(track.start);
+                }
+                Err(e) => debug!("No duration for {:?}: {}", track.file, e),
+            }
+        }
+    }
+}
+
+/// Split a cue sheet line into words, keeping quoted strings whole
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let (arg, after) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        args.push(arg.to_string());
+        rest = after.trim_start();
+    }
+    args
+}
+
+/// `MM:SS:FF`, with 75 frames a second; minutes may exceed 99
+///
+/// Times too long to count in seconds are rejected.
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= FRAMES_PER_SECOND {
+        return None;
+    }
+    let seconds = minutes.checked_mul(60)?.checked_add(seconds)?;
+    Some(
+        Duration::from_secs(seconds)
+            + Duration::from_nanos(frames * 1_000_000_000 / FRAMES_PER_SECOND),
+    )
+}
+
+/// `path`, or a file next to it with the same stem and an audio extension
+/// if `path` doesn't exist
+fn existing_file(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    FALLBACK_EXTENSIONS
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Cue sheet from the binary `CUESHEET` metadata block of a FLAC file
+fn read_flac_cuesheet_block(path: &Path) -> Result<Option<CueSheet>> {
+    let mut file = fs::File::open(path).map_err(FileError::from)?;
+    let mut marker = [0u8; 4];
+    file.read_exact(&mut marker).map_err(FileError::from)?;
+    if &marker != b"fLaC" {
+        return Ok(None);
+    }
+
+    let mut sample_rate = None;
+    let mut cuesheet = None;
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header).map_err(FileError::from)?;
+        let [flags, length @ ..] = header;
+        let length = u32::from_be_bytes([0, length[0], length[1], length[2]]);
+        match flags & 0x7F {
+            // STREAMINFO: the sample rate is 20 bits at byte 10
+            0 => {
+                let mut info = vec![0u8; length as usize];
+                file.read_exact(&mut info).map_err(FileError::from)?;
+                if let Some(&[a, b, c]) = info.get(10..13) {
+                    let rate = (u32::from(a) << 12) | (u32::from(b) << 4) | (u32::from(c) >> 4);
+                    sample_rate = Some(rate).filter(|&rate| rate > 0);
+                }
+            }
+            5 => {
+                let mut block = vec![0u8; length as usize];
+                file.read_exact(&mut block).map_err(FileError::from)?;
+                cuesheet = Some(block);
+            }
+            _ => {
+                file.seek(SeekFrom::Current(i64::from(length)))
+                    .map_err(FileError::from)?;
+            }
+        }
+        if flags & 0x80 != 0 {
+            break;
+        }
+    }
+
+    let (Some(sample_rate), Some(block)) = (sample_rate, cuesheet) else {
+        return Ok(None);
+    };
+    let malformed = || PlaylistError::Malformed {
+        path: path.display().to_string(),
+        reason: "Truncated CUESHEET block".to_string(),
+    };
+    let points = parse_cuesheet_block(&block).ok_or_else(malformed)?;
+    let to_duration =
+        |samples: u64| Duration::from_secs_f64(samples as f64 / f64::from(sample_rate));
+
+    let mut sheet = CueSheet::default();
+    for &(number, start) in &points.tracks {
+        sheet.tracks.push(CueTrack {
+            number,
+            file: path.to_path_buf(),
+            start: to_duration(start),
+            end: None,
+            metadata: TrackMetadata {
+                title: format!("Track {:02}", number),
+                track_number: Some(number),
+                ..Default::default()
+            },
+        });
+    }
+    sheet.link_tracks();
+    if let (Some(last), Some(lead_out)) = (sheet.tracks.last_mut(), points.lead_out) {
+        let end = to_duration(lead_out);
+        last.end = Some(end);
+        last.metadata.duration = end.checked_sub(last.start);
+    }
+    Ok(Some(sheet))
+}
+
+/// Track starts, in samples, from a binary `CUESHEET` block
+struct CuesheetPoints {
+    /// Track number and the sample its `INDEX 01` is at
+    tracks: Vec<(u32, u64)>,
+    /// Sample where the lead-out track starts, i.e. the end of the audio
+    lead_out: Option<u64>,
+}
+
+/// Parse a binary `CUESHEET` block; `None` if it is truncated
+fn parse_cuesheet_block(block: &[u8]) -> Option<CuesheetPoints> {
+    let mut rest = block;
+    let mut take = |count: usize| {
+        let (taken, after) = rest.split_at_checked(count)?;
+        rest = after;
+        Some(taken)
+    };
+    let be_u64 = |bytes: &[u8]| Some(u64::from_be_bytes(bytes.try_into().ok()?));
+
+    // Catalog number, lead-in samples, CD-DA flag, reserved
+    take(128 + 8 + 1 + 258)?;
+    let track_count = *take(1)?.first()?;
+    let mut points = CuesheetPoints {
+        tracks: Vec::new(),
+        lead_out: None,
+    };
+    for _ in 0..track_count {
+        let offset = be_u64(take(8)?)?;
+        let number = u32::from(*take(1)?.first()?);
+        // ISRC, then type and pre-emphasis flags and reserved bits
+        let flags = *take(12 + 14)?.get(12)?;
+        let is_audio = flags & 0x80 == 0;
+        let index_count = *take(1)?.first()?;
+        let mut index_01 = None;
+        for _ in 0..index_count {
+            let index_offset = be_u64(take(8)?)?;
+            let index_number = *take(4)?.first()?;
+            if index_number == 1 {
+                index_01 = Some(offset + index_offset);
+            }
+        }
+        // 170 is the CD-DA lead-out, 255 the lead-out of other sheets
+        if number == 170 || number == 255 {
+            points.lead_out = Some(offset);
+        } else if let Some(start) = index_01.filter(|_| is_audio) {
+            points.tracks.push((number, start));
+        }
+    }
+    Some(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"REM GENRE "Field Recording"
+REM DATE 2023
+REM REPLAYGAIN_ALBUM_GAIN -3.20 dB
+PERFORMER "Dawn Choir"
+TITLE "Morning"
+FILE "Morning.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "First Light"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Robin"
+    PERFORMER "Robin Solo"
+    REM REPLAYGAIN_TRACK_GAIN -1.50 dB
+    INDEX 00 03:58:50
+    INDEX 01 04:00:37
+FILE "Evening.wav" WAVE
+  TRACK 03 AUDIO
+    INDEX 01 00:00:00
+"#;
+
+    #[test]
+    fn test_parse_sheet() {
+        let base = Path::new("/albums");
+        let sheet = CueSheet::parse(SHEET, base).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("Morning"));
+        assert_eq!(sheet.tracks.len(), 3);
+
+        let first = &sheet.tracks[0];
+        assert_eq!(first.metadata.title, "First Light");
+        assert_eq!(first.metadata.artist, "Dawn Choir");
+        assert_eq!(first.metadata.album, "Morning");
+        assert_eq!(first.metadata.year, "2023");
+        assert_eq!(first.metadata.genre.as_deref(), Some("Field Recording"));
+        // The pregap (INDEX 00) belongs to the track before
+        let second_start = Duration::from_secs(240) + Duration::from_nanos(37_000_000_000 / 75);
+        assert_eq!(first.end, Some(second_start));
+        assert_eq!(first.metadata.duration, Some(second_start));
+
+        let second = &sheet.tracks[1];
+        assert_eq!(second.metadata.artist, "Robin Solo");
+        assert_eq!(second.metadata.track_number, Some(2));
+        assert_eq!(second.metadata.replay_gain.track_gain_db, Some(-1.5));
+        assert_eq!(second.metadata.replay_gain.album_gain_db, Some(-3.2));
+        assert_eq!(second.end, None);
+
+        let third = &sheet.tracks[2];
+        assert_eq!(third.file, base.join("Evening.wav"));
+        assert_eq!(third.metadata.title, "Track 03");
+        assert_eq!(
+            sheet.files(),
+            [base.join("Morning.wav"), base.join("Evening.wav")]
+        );
+
+        let morning = base.join("Morning.wav");
+        assert_eq!(sheet.track_at(&morning, Duration::from_secs(10)), Some(0));
+        assert_eq!(sheet.track_at(&morning, second_start), Some(1));
+        assert_eq!(sheet.track_at(&morning, Duration::from_secs(3600)), Some(1));
+
+        assert!(CueSheet::parse("FILE \"a.wav\" WAVE\n", base).is_err());
+        assert!(
+            CueSheet::parse("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 1:60:00\n", base).is_err()
+        );
+        // Minutes that fit a u64 but not once counted in seconds
+        assert!(CueSheet::parse(
+            "FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 307445734561825861:00:00\n",
+            base
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_flac_cuesheet_block() {
+        // Two tracks at 0 and 10 s (INDEX 00 pregap at 9 s), lead-out at 20 s
+        let rate = 44100u64;
+        let mut block = vec![0u8; 128 + 8 + 1 + 258];
+        block[128 + 8] = 0x80;
+        block.push(3);
+        let track = |block: &mut Vec<u8>, offset: u64, number: u8, indices: &[(u64, u8)]| {
+            block.extend_from_slice(&offset.to_be_bytes());
+            block.push(number);
+            block.extend_from_slice(&[0u8; 12 + 14]);
+            block.push(indices.len() as u8);
+            for &(index_offset, index_number) in indices {
+                block.extend_from_slice(&index_offset.to_be_bytes());
+                block.extend_from_slice(&[index_number, 0, 0, 0]);
+            }
+        };
+        track(&mut block, 0, 1, &[(0, 1)]);
+        track(&mut block, 9 * rate, 2, &[(0, 0), (rate, 1)]);
+        track(&mut block, 20 * rate, 170, &[]);
+
+        let points = parse_cuesheet_block(&block).unwrap();
+        assert_eq!(points.tracks, [(1, 0), (2, 10 * rate)]);
+        assert_eq!(points.lead_out, Some(20 * rate));
+        assert!(parse_cuesheet_block(&block[..block.len() - 20]).is_none());
+
+        // Wrapped in a FLAC file's metadata blocks
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("album.flac");
+        let mut flac = b"fLaC".to_vec();
+        let mut info = [0u8; 34];
+        info[10..13].copy_from_slice(&[0x0A, 0xC4, 0x40]);
+        flac.extend_from_slice(&[0, 0, 0, 34]);
+        flac.extend_from_slice(&info);
+        let length = (block.len() as u32).to_be_bytes();
+        flac.extend_from_slice(&[0x85, length[1], length[2], length[3]]);
+        flac.extend_from_slice(&block);
+        fs::write(&path, flac).unwrap();
+
+        let sheet = read_flac_cuesheet_block(&path).unwrap().unwrap();
+        assert_eq!(sheet.tracks.len(), 2);
+        assert_eq!(sheet.tracks[0].end, Some(Duration::from_secs(10)));
+        assert_eq!(sheet.tracks[1].start, Duration::from_secs(10));
+        assert_eq!(
+            sheet.tracks[1].metadata.duration,
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(sheet.tracks[1].metadata.title, "Track 02");
+    }
+}
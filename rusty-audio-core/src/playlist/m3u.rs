@@ -0,0 +1,86 @@
+//! M3U and M3U8 playlists
+//!
+//! One location per line. Extended M3U adds an `#EXTM3U` header and an
+//! `#EXTINF:<seconds>,<title>` line before each entry; other `#` lines are
+//! comments or directives we don't use.
+
+use super::PlaylistEntry;
+use std::time::Duration;
+
+pub(super) fn parse(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut info: Option<(Option<Duration>, Option<String>)> = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            info = Some(parse_extinf(extinf));
+        } else if !line.starts_with('#') {
+            let (duration, title) = info.take().unwrap_or_default();
+            entries.push(PlaylistEntry {
+                location: line.to_string(),
+                title,
+                duration,
+            });
+        }
+    }
+    entries
+}
+
+/// `<seconds> [attributes],<title>`; -1 seconds means unknown
+fn parse_extinf(extinf: &str) -> (Option<Duration>, Option<String>) {
+    let (head, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let duration = head
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64);
+    let title = Some(title.trim())
+        .filter(|title| !title.is_empty())
+        .map(str::to_string);
+    (duration, title)
+}
+
+pub(super) fn write(entries: &[PlaylistEntry]) -> String {
+    let mut text = String::from("#EXTM3U\n");
+    for entry in entries {
+        if entry.title.is_some() || entry.duration.is_some() {
+            let seconds = entry
+                .duration
+                .map_or(-1, |duration| duration.as_secs_f64().round() as i64);
+            let title = entry.title.as_deref().unwrap_or_default();
+            text.push_str(&format!("#EXTINF:{},{}\n", seconds, title));
+        }
+        text.push_str(&entry.location);
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_extinf() {
+        let entries = parse(
+            "#EXTM3U\r\n\
+             #EXTINF:123,Artist - Title\r\n\
+             a.mp3\r\n\
+             \r\n\
+             # a comment\r\n\
+             b.flac\r\n\
+             #EXTINF:-1 tvg-id=\"x\",Stream, with comma\r\n\
+             c.ogg\r\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].location, "a.mp3");
+        assert_eq!(entries[0].title.as_deref(), Some("Artist - Title"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(123)));
+        assert_eq!(entries[1].title, None);
+        assert_eq!(entries[2].title.as_deref(), Some("Stream, with comma"));
+        assert_eq!(entries[2].duration, None);
+    }
+}
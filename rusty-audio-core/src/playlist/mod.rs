@@ -0,0 +1,491 @@
+//! Playlist files
+//!
+//! Reads and writes M3U/M3U8 (with `#EXTINF` titles and durations), PLS and
+//! XSPF playlists, so playlists can move between machines and other players.
+//!
+//! Loading resolves each entry against the playlist's folder and runs it
+//! through [`FileValidator::validate_file_path`]. Entries that are missing,
+//! outside the sandbox or not local files are reported in
+//! [`PlaylistLoad::missing`] instead of failing the whole playlist. Saving
+//! writes tracks under the playlist's folder as relative paths, so a music
+//! folder with its playlists can be copied elsewhere as a whole.
+//!
+//! [`CueSheet`] splits single-file albums into tracks; sheets are only read.
+
+mod cue;
+mod m3u;
+mod pls;
+mod xspf;
+
+pub use cue::{CueSheet, CueTrack};
+
+use crate::error::{FileError, PlaylistError, Result};
+use crate::security::FileValidator;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Playlist file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// `.m3u`
+    M3u,
+    /// `.m3u8`, M3U in UTF-8
+    M3u8,
+    /// `.pls`
+    Pls,
+    /// `.xspf`
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Every supported format
+    pub const ALL: [PlaylistFormat; 4] = [
+        PlaylistFormat::M3u8,
+        PlaylistFormat::M3u,
+        PlaylistFormat::Pls,
+        PlaylistFormat::Xspf,
+    ];
+
+    /// Format of a playlist file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "m3u" => Some(PlaylistFormat::M3u),
+            "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf",
+        }
+    }
+}
+
+/// An entry as written in a playlist file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PlaylistEntry {
+    /// Path, relative path or URL, as written
+    pub(crate) location: String,
+    pub(crate) title: Option<String>,
+    pub(crate) duration: Option<Duration>,
+}
+
+/// A track in a playlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistTrack {
+    /// Audio file
+    pub path: PathBuf,
+    /// Display title, typically "Artist - Title"
+    pub title: Option<String>,
+    /// Track length
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistTrack {
+    /// A track with no title or duration
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            title: None,
+            duration: None,
+        }
+    }
+}
+
+/// A playlist entry that couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingEntry {
+    /// Path or URL, as written in the playlist
+    pub location: String,
+    /// Why it was left out
+    pub reason: String,
+}
+
+/// Outcome of loading a playlist
+#[derive(Debug, Default)]
+pub struct PlaylistLoad {
+    /// The entries that resolved to valid audio files
+    pub playlist: Playlist,
+    /// The entries that didn't, in playlist order
+    pub missing: Vec<MissingEntry>,
+}
+
+/// An ordered list of tracks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Playlist {
+    /// Tracks in play order
+    pub tracks: Vec<PlaylistTrack>,
+}
+
+impl Playlist {
+    /// A playlist of `paths`, without titles or durations
+    pub fn from_paths<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        Self {
+            tracks: paths.into_iter().map(PlaylistTrack::new).collect(),
+        }
+    }
+
+    /// Paths of the tracks, in play order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.tracks.iter().map(|track| track.path.as_path())
+    }
+
+    /// Read the playlist at `path`, in the format its extension names
+    ///
+    /// Every entry is resolved against the playlist's folder and checked
+    /// with `validator`; entries that fail are reported in
+    /// [`PlaylistLoad::missing`] rather than failing the load.
+    ///
+    /// # Errors
+    /// `Playlist` if the extension isn't a supported format or an XSPF file
+    /// isn't well-formed XML, `FileOperation` if the file can't be read
+    pub fn load(path: &Path, validator: &FileValidator) -> Result<PlaylistLoad> {
+        let format =
+            PlaylistFormat::from_path(path).ok_or_else(|| PlaylistError::UnsupportedFormat {
+                path: path.display().to_string(),
+            })?;
+        let text = decode_text(&fs::read(path).map_err(FileError::from)?);
+        let entries = match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::parse(&text),
+            PlaylistFormat::Pls => pls::parse(&text),
+            PlaylistFormat::Xspf => {
+                xspf::parse(&text).map_err(|reason| PlaylistError::Malformed {
+                    path: path.display().to_string(),
+                    reason,
+                })?
+            }
+        };
+        debug!("Read {} playlist entries from {:?}", entries.len(), path);
+
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut load = PlaylistLoad::default();
+        for entry in entries {
+            let resolved = resolve_location(&entry.location, format, base).and_then(|file| {
+                validator
+                    .validate_file_path(&file)
+                    .map_err(|e| e.to_string())
+            });
+            match resolved {
+                Ok(file) => load.playlist.tracks.push(PlaylistTrack {
+                    path: file,
+                    title: entry.title,
+                    duration: entry.duration,
+                }),
+                Err(reason) => {
+                    warn!("Skipping playlist entry {}: {}", entry.location, reason);
+                    load.missing.push(MissingEntry {
+                        location: entry.location,
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok(load)
+    }
+
+    /// Write the playlist to `path`, in the format its extension names
+    ///
+    /// Tracks under the playlist's folder are written relative to it, others
+    /// as absolute paths. Text is always written as UTF-8.
+    ///
+    /// # Errors
+    /// `Playlist` if the extension isn't a supported format,
+    /// `FileOperation` if the file can't be written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let format =
+            PlaylistFormat::from_path(path).ok_or_else(|| PlaylistError::UnsupportedFormat {
+                path: path.display().to_string(),
+            })?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let entries: Vec<PlaylistEntry> = self
+            .tracks
+            .iter()
+            .map(|track| PlaylistEntry {
+                location: location_for(&track.path, format, base),
+                title: track.title.clone(),
+                duration: track.duration,
+            })
+            .collect();
+        let text = match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::write(&entries),
+            PlaylistFormat::Pls => pls::write(&entries),
+            PlaylistFormat::Xspf => xspf::write(&entries),
+        };
+        fs::write(path, text).map_err(FileError::from)?;
+        Ok(())
+    }
+}
+
+/// Playlist text, as UTF-8 if it is valid UTF-8 and as Latin-1 otherwise,
+/// which is what older players write `.m3u` and `.pls` files in
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// The local file a playlist location refers to
+fn resolve_location(
+    location: &str,
+    format: PlaylistFormat,
+    base: &Path,
+) -> std::result::Result<PathBuf, String> {
+    let path = if let Some(file) = strip_file_scheme(location) {
+        percent_decode(file)
+    } else if has_scheme(location) {
+        return Err("Not a local file".to_string());
+    } else if format == PlaylistFormat::Xspf {
+        // XSPF locations are URIs, so relative ones are percent-encoded too
+        percent_decode(location)
+    } else {
+        location.to_string()
+    };
+    let path = native_separators(&path);
+    Ok(if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    })
+}
+
+/// The path part of a `file:` URL
+fn strip_file_scheme(location: &str) -> Option<&str> {
+    let scheme = location.get(..5)?;
+    if !scheme.eq_ignore_ascii_case("file:") {
+        return None;
+    }
+    let rest = location.get(5..)?;
+    let path = match rest.strip_prefix("//") {
+        // An authority; only the local host makes sense
+        Some(authority) => {
+            let path_start = authority.find('/').unwrap_or(authority.len());
+            authority.get(path_start..)?
+        }
+        None => rest,
+    };
+    // file:///C:/Music -> C:/Music
+    let bytes = path.as_bytes();
+    let is_drive = matches!(bytes, [b'/', drive, b':', ..] if drive.is_ascii_alphabetic());
+    Some(if is_drive { path.get(1..)? } else { path })
+}
+
+/// Whether `location` starts with a URL scheme such as `http://`
+fn has_scheme(location: &str) -> bool {
+    match location.split_once("://") {
+        Some((scheme, _)) => {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// A path written on any system, with this system's separators
+///
+/// Playlists made on Windows use backslashes, which other systems would
+/// read as part of a file name.
+fn native_separators(path: &str) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path)
+    } else {
+        PathBuf::from(path.replace('\\', "/"))
+    }
+}
+
+/// How `path` is written in a playlist at `base`
+fn location_for(path: &Path, format: PlaylistFormat, base: &Path) -> String {
+    let relative = path.strip_prefix(base).ok().filter(|relative| {
+        !base.as_os_str().is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+    });
+    let written = match relative {
+        Some(relative) => portable_path(relative),
+        None => path.display().to_string(),
+    };
+    match format {
+        PlaylistFormat::Xspf if relative.is_some() => percent_encode(&written),
+        PlaylistFormat::Xspf => {
+            let encoded = percent_encode(&written.replace('\\', "/"));
+            if encoded.starts_with('/') {
+                format!("file://{}", encoded)
+            } else {
+                // Windows drive paths
+                format!("file:///{}", encoded)
+            }
+        }
+        _ => written,
+    }
+}
+
+/// A relative path with `/` separators, which every player reads
+fn portable_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Percent-encode a path for a URI, leaving `/` and `:` as they are
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode `%XX` escapes; malformed escapes are kept as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = match tail {
+            [high, low, ..] if byte == b'%' => hex_value(*high)
+                .zip(hex_value(*low))
+                .map(|(high, low)| (high << 4) | low),
+            _ => None,
+        };
+        match escaped {
+            Some(value) => {
+                decoded.push(value);
+                rest = tail.get(2..).unwrap_or_default();
+            }
+            None => {
+                decoded.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    char::from(digit)
+        .to_digit(16)
+        .and_then(|value| u8::try_from(value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a minimal valid WAV file
+    fn write_wav(path: &Path) {
+        let mut data = b"RIFF\x24\x00\x00\x00WAVEfmt ".to_vec();
+        data.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&[2, 0, 16, 0]);
+        data.extend_from_slice(b"data\x00\x00\x00\x00");
+        fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_locations() {
+        let base = Path::new("/music/lists");
+        let resolve = |location| resolve_location(location, PlaylistFormat::M3u, base);
+        assert_eq!(resolve("a b.mp3").unwrap(), base.join("a b.mp3"));
+        assert_eq!(
+            resolve("file:///music/a%20b.mp3").unwrap(),
+            PathBuf::from("/music/a b.mp3")
+        );
+        assert_eq!(
+            resolve("file://localhost/music/x.mp3").unwrap(),
+            PathBuf::from("/music/x.mp3")
+        );
+        assert!(resolve("http://radio.example/stream").is_err());
+        assert_eq!(
+            resolve_location("Live%20Set/01.flac", PlaylistFormat::Xspf, base).unwrap(),
+            base.join("Live Set/01.flac")
+        );
+        assert_eq!(
+            strip_file_scheme("file:///C:/Music/x.mp3"),
+            Some("C:/Music/x.mp3")
+        );
+        assert_eq!(percent_decode("100%25 %zz"), "100% %zz");
+        assert_eq!(percent_encode("/a b/ü#.mp3"), "/a%20b/%C3%BC%23.mp3");
+
+        let track = Path::new("/music/lists/Live Set/01.flac");
+        assert_eq!(
+            location_for(track, PlaylistFormat::M3u8, base),
+            "Live Set/01.flac"
+        );
+        assert_eq!(
+            location_for(Path::new("/other/x.mp3"), PlaylistFormat::Xspf, base),
+            "file:///other/x.mp3"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_with_missing_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("Field Recordings")).unwrap();
+        let dawn = root.join("Field Recordings").join("dawn chorus.wav");
+        let rain = root.join("rain.wav");
+        write_wav(&dawn);
+        write_wav(&rain);
+        let validator = FileValidator::new(root.clone());
+
+        let mut playlist = Playlist::from_paths([&dawn, &rain]);
+        playlist.tracks[0].title = Some("Birds - Dawn Chorus".to_string());
+        playlist.tracks[0].duration = Some(Duration::from_secs(95));
+        for format in PlaylistFormat::ALL {
+            let path = root.join(format!("list.{}", format.extension()));
+            playlist.save(&path).unwrap();
+            let load = Playlist::load(&path, &validator).unwrap();
+            assert_eq!(load.playlist, playlist, "{:?}", format);
+            assert!(load.missing.is_empty());
+        }
+
+        // Missing files, streams and paths outside the sandbox are reported
+        let path = root.join("shared.m3u8");
+        fs::write(
+            &path,
+            "#EXTM3U\n\
+             #EXTINF:95,Birds - Dawn Chorus\n\
+             Field Recordings\\dawn chorus.wav\n\
+             gone.wav\n\
+             http://radio.example/stream\n\
+             ../outside.wav\n\
+             rain.wav\n",
+        )
+        .unwrap();
+        let load = Playlist::load(&path, &validator).unwrap();
+        assert_eq!(load.playlist.paths().collect::<Vec<_>>(), [&dawn, &rain]);
+        let missing: Vec<&str> = load.missing.iter().map(|m| m.location.as_str()).collect();
+        assert_eq!(
+            missing,
+            ["gone.wav", "http://radio.example/stream", "../outside.wav"]
+        );
+
+        assert!(Playlist::load(&root.join("list.txt"), &validator).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! PLS playlists
+//!
+//! An INI-style `[playlist]` section with numbered `FileN`, `TitleN` and
+//! `LengthN` keys. Entries are ordered by their number, not by where they
+//! appear in the file.
+
+use super::PlaylistEntry;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub(super) fn parse(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let (name, number) = key.split_at(split);
+        let Ok(number) = number.parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match name {
+            "file" => entry.location = value.to_string(),
+            "title" if !value.is_empty() => entry.title = Some(value.to_string()),
+            "length" => {
+                entry.duration = value.parse::<u64>().ok().map(Duration::from_secs);
+            }
+            _ => {}
+        }
+    }
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
+
+pub(super) fn write(entries: &[PlaylistEntry]) -> String {
+    let mut text = String::from("[playlist]\n");
+    for (number, entry) in (1..).zip(entries) {
+        text.push_str(&format!("File{}={}\n", number, entry.location));
+        if let Some(title) = &entry.title {
+            text.push_str(&format!("Title{}={}\n", number, title));
+        }
+        let seconds = entry
+            .duration
+            .map_or(-1, |duration| duration.as_secs_f64().round() as i64);
+        text.push_str(&format!("Length{}={}\n", number, seconds));
+    }
+    text.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    text
+}
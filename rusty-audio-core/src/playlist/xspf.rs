@@ -0,0 +1,222 @@
+//! XSPF playlists
+//!
+//! XML with a `<track>` per entry under `<playlist><trackList>`; we read
+//! each track's first `<location>` URI, its `<title>` and its `<duration>` in
+//! milliseconds. XSPF needs so little of XML that a small reader does: it
+//! handles comments, CDATA, character references and namespace prefixes, and
+//! skips elements it doesn't know, such as `<extension>`.
+
+use super::PlaylistEntry;
+use std::time::Duration;
+
+pub(super) fn parse(text: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let mut entries = Vec::new();
+    // Names of the open elements, without namespace prefixes
+    let mut open: Vec<&str> = Vec::new();
+    let mut track: Option<PlaylistEntry> = None;
+    let mut content = String::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.split_once("-->").ok_or("Unterminated comment")?.1;
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let (data, after) = after.split_once("]]>").ok_or("Unterminated CDATA")?;
+            content.push_str(data);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("<?") {
+            rest = after.split_once("?>").ok_or("Unterminated declaration")?.1;
+        } else if let Some(after) = rest.strip_prefix("<!") {
+            rest = after.split_once('>').ok_or("Unterminated declaration")?.1;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let (tag, after) = after.split_once('>').ok_or("Unterminated end tag")?;
+            let name = local_name(tag.trim());
+            if open.pop() != Some(name) {
+                return Err(format!("Unexpected </{}>", tag.trim()));
+            }
+            let parent = open.last().copied();
+            if let Some(entry) = track.as_mut().filter(|_| parent == Some("track")) {
+                let value = content.trim();
+                match name {
+                    "location" if entry.location.is_empty() => entry.location = value.to_string(),
+                    "title" if !value.is_empty() => entry.title = Some(value.to_string()),
+                    "duration" => {
+                        entry.duration = value.parse::<u64>().ok().map(Duration::from_millis);
+                    }
+                    _ => {}
+                }
+            }
+            if name == "track" && parent == Some("trackList") {
+                if let Some(entry) = track.take().filter(|entry| !entry.location.is_empty()) {
+                    entries.push(entry);
+                }
+            }
+            content.clear();
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after).ok_or("Unterminated start tag")?;
+            let tag = after.get(..end).unwrap_or_default();
+            let self_closing = tag.ends_with('/');
+            let name = local_name(tag.split_whitespace().next().unwrap_or_default());
+            let name = name.trim_end_matches('/');
+            if name == "track" && open.last() == Some(&"trackList") {
+                track = Some(PlaylistEntry::default());
+            }
+            if !self_closing {
+                open.push(name);
+            }
+            content.clear();
+            rest = after.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let (text, after) = rest.split_at(end);
+            content.push_str(&unescape(text));
+            rest = after;
+        }
+    }
+
+    if let Some(name) = open.last() {
+        return Err(format!("Unclosed <{}>", name));
+    }
+    Ok(entries)
+}
+
+/// Position of the `>` closing a start tag, skipping quoted attribute values
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Element name without its namespace prefix
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+/// Replace entity and character references; unknown ones are kept
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        let (before, reference) = rest.split_at(start);
+        unescaped.push_str(before);
+        let decoded = reference.find(';').and_then(|end| {
+            let name = reference.get(1..end)?;
+            let c = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => name.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, length)) => {
+                unescaped.push(c);
+                rest = reference.get(length..).unwrap_or_default();
+            }
+            None => {
+                unescaped.push('&');
+                rest = reference.get(1..).unwrap_or_default();
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(super) fn write(entries: &[PlaylistEntry]) -> String {
+    let mut text = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+    for entry in entries {
+        text.push_str("    <track>\n");
+        text.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(&entry.location)
+        ));
+        if let Some(title) = &entry.title {
+            text.push_str(&format!("      <title>{}</title>\n", escape(title)));
+        }
+        if let Some(duration) = entry.duration {
+            text.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                duration.as_millis()
+            ));
+        }
+        text.push_str("    </track>\n");
+    }
+    text.push_str("  </trackList>\n</playlist>\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xspf() {
+        let entries = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!-- exported by another player -->
+            <x:playlist version="1" xmlns:x="http://xspf.org/ns/0/">
+              <x:title>Mix</x:title>
+              <x:trackList>
+                <x:track>
+                  <x:location>file:///music/Tom%20%26%20Jerry.mp3</x:location>
+                  <x:location>http://mirror.example/t.mp3</x:location>
+                  <x:title>Tom &amp; Jerry &#8211; Live</x:title>
+                  <x:duration>61500</x:duration>
+                  <x:extension application="a>b"><x:title>Not this</x:title></x:extension>
+                </x:track>
+                <x:track>
+                  <x:location><![CDATA[b <1>.flac]]></x:location>
+                  <x:image/>
+                </x:track>
+                <x:track><x:title>No location</x:title></x:track>
+              </x:trackList>
+            </x:playlist>"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location, "file:///music/Tom%20%26%20Jerry.mp3");
+        assert_eq!(entries[0].title.as_deref(), Some("Tom & Jerry – Live"));
+        assert_eq!(entries[0].duration, Some(Duration::from_millis(61500)));
+        assert_eq!(entries[1].location, "b <1>.flac");
+        assert_eq!(entries[1].title, None);
+
+        assert!(parse("<playlist><trackList></playlist>").is_err());
+        assert!(parse("<playlist><!-- open").is_err());
+    }
+}
@@ -49,11 +49,12 @@ impl AudioSafetyLimiter {
         // Apply safe volume limit
         let safe_volume = volume.min(self.config.max_volume);
 
-        // Track volume for history
-        self.volume_history.push_back(safe_volume);
-        if self.volume_history.len() > 100 {
+        // Track volume for history (trim first so the buffer never grows
+        // past its preallocated capacity on the audio thread)
+        if self.volume_history.len() >= 100 {
             self.volume_history.pop_front();
         }
+        self.volume_history.push_back(safe_volume);
 
         // Check for sustained high volume (hearing protection)
         if self.is_sustained_high_volume() {
@@ -32,6 +32,8 @@ pub enum PanelId {
     Transport,
     /// Settings and preferences
     Settings,
+    /// Lyrics of the playing track
+    Lyrics,
 }
 
 impl PanelId {
@@ -48,6 +50,7 @@ impl PanelId {
             PanelId::Mixer => "🎚️ Mixer",
             PanelId::Transport => "⏯️ Transport",
             PanelId::Settings => "⚙️ Settings",
+            PanelId::Lyrics => "🎤 Lyrics",
         }
     }
 
@@ -64,6 +67,7 @@ impl PanelId {
             PanelId::Mixer => "mixer",
             PanelId::Transport => "transport",
             PanelId::Settings => "settings",
+            PanelId::Lyrics => "lyrics",
         }
     }
 }
@@ -129,6 +133,9 @@ pub trait PanelContent {
 
     /// Render the settings panel
     fn show_settings(&mut self, ui: &mut Ui);
+
+    /// Render the lyrics panel
+    fn show_lyrics(&mut self, ui: &mut Ui);
 }
 
 /// Docking layout manager with workspace support
@@ -196,11 +203,12 @@ impl DockLayoutManager {
                 .main_surface_mut()
                 .split_left(NodeIndex::root(), 0.2, vec![PanelId::FileBrowser]);
 
-        // Right sidebar: Inspector
-        let [center, _right] =
-            state
-                .main_surface_mut()
-                .split_right(NodeIndex::root(), 0.2, vec![PanelId::Inspector]);
+        // Right sidebar: Inspector and Lyrics
+        let [center, _right] = state.main_surface_mut().split_right(
+            NodeIndex::root(),
+            0.2,
+            vec![PanelId::Inspector, PanelId::Lyrics],
+        );
 
         // Center area: Waveform and Spectrum tabs
         state
@@ -208,10 +216,11 @@ impl DockLayoutManager {
             .push_to_focused_leaf(PanelId::Spectrum);
 
         // Bottom area: Transport and Mixer
-        let [_main, _bottom] =
-            state
-                .main_surface_mut()
-                .split_below(center, 0.15, vec![PanelId::Transport]);
+        let [_main, _bottom] = state.main_surface_mut().split_below(
+            center,
+            0.15,
+            vec![PanelId::Transport, PanelId::Mixer],
+        );
 
         state
     }
@@ -303,7 +312,11 @@ impl DockLayoutManager {
     fn create_playback_layout(&self) -> DockState<PanelId> {
         let mut state = DockState::new(vec![PanelId::Waveform]);
 
-        // Simple: Waveform and transport
+        // Simple: Waveform and transport, with lyrics alongside
+        let [_main, _right] =
+            state
+                .main_surface_mut()
+                .split_right(NodeIndex::root(), 0.7, vec![PanelId::Lyrics]);
         let [_main, _bottom] =
             state
                 .main_surface_mut()
@@ -381,6 +394,7 @@ impl<'a, T: PanelContent> TabViewer for AppTabViewer<'a, T> {
             PanelId::Mixer => self.app_state.show_mixer(ui),
             PanelId::Transport => self.app_state.show_transport(ui),
             PanelId::Settings => self.app_state.show_settings(ui),
+            PanelId::Lyrics => self.app_state.show_lyrics(ui),
         }
     }
 }
@@ -402,6 +416,7 @@ mod tests {
             PanelId::Mixer,
             PanelId::Transport,
             PanelId::Settings,
+            PanelId::Lyrics,
         ];
 
         let ids: Vec<&str> = panels.iter().map(|p| p.id()).collect();
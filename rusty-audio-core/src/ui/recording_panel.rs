@@ -5,19 +5,27 @@
 
 use chrono::Local;
 use egui::{Color32, RichText, Ui, Vec2};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use super::{theme::ThemeColors, utils::ColorUtils};
 use crate::audio::backend::DeviceInfo;
+use crate::audio::flac_encoder::FlacConfig;
 use crate::audio::manager::AudioDeviceManager;
 use crate::audio::recorder::{
     AudioRecorder, MonitoringMode, RecordingConfig, RecordingFormat, RecordingState,
 };
+use crate::audio::recording_recovery::recover_recordings;
+use crate::audio::recording_trigger::{TriggerEvent, TriggerMode};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TakeSource {
     Live,
     Generated,
+    /// Found on startup, left behind by a crash
+    Recovered,
+    /// Written straight from the pre-roll
+    Captured,
 }
 
 impl TakeSource {
@@ -25,10 +33,18 @@ impl TakeSource {
         match self {
             TakeSource::Live => "Live",
             TakeSource::Generated => "Generated",
+            TakeSource::Recovered => "Recovered",
+            TakeSource::Captured => "Captured",
         }
     }
 }
 
+/// Seconds of input kept while armed, for pre-roll and captures
+const PRE_ROLL_SECS: u64 = 30;
+
+/// Trigger events kept in the log
+const TRIGGER_LOG_LEN: usize = 50;
+
 #[derive(Debug, Clone)]
 struct RecordedTake {
     id: usize,
@@ -41,6 +57,10 @@ struct RecordedTake {
     timestamp_label: String,
     waveform: Vec<f32>,
     notes: String,
+    /// File the take was streamed to
+    path: Option<PathBuf>,
+    /// Frames missing from the file because the disk fell behind
+    dropped_frames: u64,
 }
 
 /// Recording panel state
@@ -50,9 +70,15 @@ pub struct RecordingPanel {
     available_input_devices: Vec<DeviceInfo>,
     selected_input_device_id: Option<String>,
     monitoring_gain: f32,
-    show_save_dialog: bool,
     save_path: String,
     save_format: RecordingFormat,
+    /// Compression level and bit depth for FLAC exports; tags come from the take
+    flac_config: FlacConfig,
+    save_status: Option<String>,
+    /// How much of the pre-roll "Capture" writes to a take
+    capture_secs: u64,
+    /// Timestamped trigger events, oldest first
+    trigger_log: Vec<String>,
 
     // Level metering
     peak_levels: Vec<f32>,      // Per channel
@@ -61,6 +87,9 @@ pub struct RecordingPanel {
     last_meter_update: Instant,
     takes: Vec<RecordedTake>,
     selected_take: Option<usize>,
+    /// ID of the take whose audio is in the recorder's buffer, which is
+    /// what "Save Recording" writes
+    buffer_take: Option<usize>,
     last_state: RecordingState,
     next_take_id: usize,
 }
@@ -82,15 +111,19 @@ impl Default for RecordingPanel {
             available_input_devices,
             selected_input_device_id: None,
             monitoring_gain: 1.0,
-            show_save_dialog: false,
             save_path: String::new(),
             save_format: RecordingFormat::Wav,
+            flac_config: FlacConfig::default(),
+            save_status: None,
+            capture_secs: PRE_ROLL_SECS,
+            trigger_log: Vec::new(),
             peak_levels: vec![0.0; 2], // Stereo default
             rms_levels: vec![0.0; 2],
             clip_indicators: vec![false; 2],
             last_meter_update: Instant::now(),
             takes: Vec::new(),
             selected_take: None,
+            buffer_take: None,
             last_state: RecordingState::Idle,
             next_take_id: 1,
         }
@@ -100,11 +133,74 @@ impl Default for RecordingPanel {
 impl RecordingPanel {
     pub fn new() -> Self {
         let mut panel = Self::default();
-        // Initialize recorder with default configuration
-        panel.initialize_recorder(RecordingConfig::default());
+        // Initialize recorder with default configuration, streaming takes to disk
+        panel.initialize_recorder(RecordingConfig {
+            recordings_dir: RecordingConfig::default_recordings_dir(),
+            pre_roll_secs: PRE_ROLL_SECS,
+            ..RecordingConfig::default()
+        });
+        panel.recover_takes();
         panel
     }
 
+    /// Repair takes a crash left in the recordings directory and list them
+    pub fn recover_takes(&mut self) {
+        let Some(dir) = self
+            .recorder
+            .as_ref()
+            .and_then(|recorder| recorder.config().recordings_dir.clone())
+        else {
+            return;
+        };
+        let recovered = match recover_recordings(&dir) {
+            Ok(recovered) => recovered,
+            Err(e) => {
+                eprintln!("Failed to scan {} for recordings: {}", dir.display(), e);
+                return;
+            }
+        };
+        if recovered.is_empty() {
+            return;
+        }
+
+        for recording in &recovered {
+            let label = recording.path.file_stem().map_or_else(
+                || "Recovered take".to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+            // When the take was last written to
+            let timestamp_label = std::fs::metadata(&recording.path)
+                .and_then(|metadata| metadata.modified())
+                .map(|time| {
+                    chrono::DateTime::<Local>::from(time)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            self.takes.push(RecordedTake {
+                id: self.next_take_id,
+                label,
+                source: TakeSource::Recovered,
+                duration: recording.duration(),
+                peak: 0.0,
+                rms: 0.0,
+                clip_events: 0,
+                timestamp_label,
+                waveform: Vec::new(),
+                notes: String::new(),
+                path: Some(recording.path.clone()),
+                dropped_frames: 0,
+            });
+            self.next_take_id += 1;
+        }
+        self.selected_take = Some(self.takes.len().saturating_sub(1));
+        self.save_status = Some(format!(
+            "Recovered {} take{} from an interrupted session",
+            recovered.len(),
+            if recovered.len() == 1 { "" } else { "s" }
+        ));
+    }
+
     /// Initialize recorder with configuration
     pub fn initialize_recorder(&mut self, config: RecordingConfig) {
         let channels = config.channels as usize;
@@ -152,6 +248,10 @@ impl RecordingPanel {
 
     /// Update level meters from recorder
     pub fn update_levels(&mut self) {
+        let elapsed = self.last_meter_update.elapsed();
+        self.last_meter_update = Instant::now();
+        self.update_trigger(elapsed);
+
         let mut current_state = RecordingState::Idle;
         if let Some(recorder) = &self.recorder {
             current_state = recorder.state();
@@ -172,12 +272,48 @@ impl RecordingPanel {
         self.handle_state_transition(current_state);
     }
 
+    /// Let the level trigger start or stop takes, logging what it does
+    fn update_trigger(&mut self, elapsed: Duration) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let message = match recorder.update_trigger(elapsed) {
+            Ok(Some(TriggerEvent::Started { level_db })) => format!(
+                "Take {} triggered at {:.1} dBFS",
+                self.next_take_id, level_db
+            ),
+            Ok(Some(TriggerEvent::Stopped { level_db })) => {
+                format!("Stopped after silence ({:.1} dBFS)", level_db)
+            }
+            Ok(None) => return,
+            Err(e) => format!("Trigger failed: {}", e),
+        };
+        self.log_trigger_event(message);
+    }
+
+    fn log_trigger_event(&mut self, message: String) {
+        if self.trigger_log.len() >= TRIGGER_LOG_LEN {
+            self.trigger_log.remove(0);
+        }
+        self.trigger_log
+            .push(format!("{}  {}", Local::now().format("%H:%M:%S"), message));
+    }
+
     /// Clear clip indicators
     pub fn clear_clips(&mut self) {
         self.clip_indicators.fill(false);
     }
 
     fn handle_state_transition(&mut self, current_state: RecordingState) {
+        if current_state == RecordingState::Recording
+            && !matches!(
+                self.last_state,
+                RecordingState::Recording | RecordingState::Paused
+            )
+        {
+            // A new take is replacing the buffer
+            self.buffer_take = None;
+        }
         if self.last_state == RecordingState::Recording && current_state == RecordingState::Stopped
         {
             // Capture data from recorder before calling capture_live_take
@@ -189,6 +325,9 @@ impl RecordingPanel {
                 if !samples.is_empty() {
                     let channels = recorder.config().channels.max(1) as usize;
                     let sample_rate = recorder.config().sample_rate as f32;
+                    let path = recorder.take_path().map(Path::to_path_buf);
+                    let dropped_frames = recorder.dropped_frames();
+                    let take_id = self.next_take_id;
 
                     // Now call the method with extracted data (no borrow conflict)
                     self.add_take_from_samples(
@@ -198,6 +337,17 @@ impl RecordingPanel {
                         sample_rate,
                         channels,
                     );
+                    if let Some(take) = self.takes.last_mut().filter(|take| take.id == take_id) {
+                        take.path = path;
+                        take.dropped_frames = dropped_frames;
+                        self.buffer_take = Some(take_id);
+                    }
+                    if dropped_frames > 0 {
+                        self.save_status = Some(format!(
+                            "Disk fell behind: {} frames missing from the take's file",
+                            dropped_frames
+                        ));
+                    }
                 }
             }
         }
@@ -244,6 +394,8 @@ impl RecordingPanel {
             timestamp_label: Local::now().format("%H:%M:%S").to_string(),
             waveform,
             notes: String::new(),
+            path: None,
+            dropped_frames: 0,
         };
 
         self.next_take_id += 1;
@@ -330,6 +482,35 @@ impl RecordingPanel {
         self.add_take_from_samples(label, TakeSource::Generated, samples, sample_rate, channels);
     }
 
+    /// Write the last `capture_secs` of pre-roll to a new take
+    pub fn capture_pre_roll(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let samples = match recorder.capture_pre_roll(Duration::from_secs(self.capture_secs)) {
+            Ok(samples) => samples,
+            Err(e) => {
+                self.save_status = Some(format!("Capture failed: {}", e));
+                return;
+            }
+        };
+        let channels = recorder.config().channels.max(1) as usize;
+        let sample_rate = recorder.config().sample_rate as f32;
+        let path = recorder.take_path().map(Path::to_path_buf);
+        let take_id = self.next_take_id;
+
+        self.add_take_from_samples(
+            format!("Capture {}", take_id),
+            TakeSource::Captured,
+            &samples,
+            sample_rate,
+            channels,
+        );
+        if let Some(take) = self.takes.last_mut().filter(|take| take.id == take_id) {
+            take.path = path;
+        }
+    }
+
     /// Draw the recording panel
     pub fn draw(&mut self, ui: &mut Ui, colors: &ThemeColors) {
         ui.vertical(|ui| {
@@ -341,6 +522,11 @@ impl RecordingPanel {
 
             ui.add_space(15.0);
 
+            // Level-triggered takes
+            self.draw_trigger_controls(ui, colors);
+
+            ui.add_space(15.0);
+
             // Level meters
             self.draw_level_meters(ui, colors);
 
@@ -436,6 +622,56 @@ impl RecordingPanel {
 
             ui.add_space(5.0);
 
+            // Pre-roll: keep the last seconds of input while armed
+            let armed = self.recorder.as_ref().is_some_and(|r| r.is_armed());
+            let mut capture = false;
+            ui.horizontal(|ui| {
+                let mut arm = armed;
+                if ui
+                    .toggle_value(&mut arm, "🎯 Arm")
+                    .on_hover_text(format!(
+                        "Keep the last {}s of input and start takes with it",
+                        PRE_ROLL_SECS
+                    ))
+                    .changed()
+                {
+                    if let Some(recorder) = &mut self.recorder {
+                        if arm {
+                            recorder.arm();
+                        } else {
+                            recorder.disarm();
+                        }
+                    }
+                }
+
+                capture = ui
+                    .add_enabled(
+                        armed,
+                        egui::Button::new(format!("⏪ Capture last {}s", self.capture_secs)),
+                    )
+                    .on_hover_text("Save what was just heard as a take")
+                    .clicked();
+                ui.add(egui::Slider::new(&mut self.capture_secs, 1..=PRE_ROLL_SECS).suffix("s"));
+            });
+            if capture {
+                self.capture_pre_roll();
+            }
+
+            if armed {
+                if let Some(recorder) = &self.recorder {
+                    ui.label(
+                        RichText::new(format!(
+                            "Pre-roll: {:.1}s buffered",
+                            recorder.pre_roll().duration().as_secs_f32()
+                        ))
+                        .size(12.0)
+                        .color(colors.text_secondary),
+                    );
+                }
+            }
+
+            ui.add_space(5.0);
+
             // Status display
             let status_text = match state {
                 RecordingState::Idle => "⚪ Idle",
@@ -477,6 +713,92 @@ impl RecordingPanel {
         });
     }
 
+    /// Draw trigger mode, levels and event log
+    fn draw_trigger_controls(&mut self, ui: &mut Ui, colors: &ThemeColors) {
+        ui.group(|ui| {
+            ui.label(RichText::new("🎚️ Trigger").strong());
+            ui.add_space(5.0);
+
+            let Some(recorder) = &mut self.recorder else {
+                return;
+            };
+            let mut trigger = recorder.config().trigger;
+
+            ui.horizontal(|ui| {
+                ui.label("Mode:");
+                for mode in [
+                    TriggerMode::Manual,
+                    TriggerMode::Threshold,
+                    TriggerMode::Voice,
+                ] {
+                    ui.radio_value(&mut trigger.mode, mode, mode.label());
+                }
+            });
+
+            if trigger.mode != TriggerMode::Manual {
+                ui.horizontal(|ui| {
+                    ui.label("Threshold:");
+                    ui.add(
+                        egui::Slider::new(&mut trigger.threshold_db, -80.0..=0.0).suffix(" dBFS"),
+                    );
+                    ui.label("Hysteresis:");
+                    ui.add(egui::Slider::new(&mut trigger.hysteresis_db, 0.0..=24.0).suffix(" dB"))
+                        .on_hover_text("How far below the threshold counts as silence");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Attack:");
+                    ui.add(egui::Slider::new(&mut trigger.attack_ms, 0..=1000).suffix(" ms"));
+                    ui.label("Hold:");
+                    ui.add(egui::Slider::new(&mut trigger.hold_ms, 100..=10_000).suffix(" ms"))
+                        .on_hover_text("How long silence lasts before the take stops");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pre-roll:");
+                    ui.add(
+                        egui::Slider::new(&mut trigger.pre_roll_ms, 0..=PRE_ROLL_SECS * 1000)
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("Audio from before the trigger to start each take with");
+                });
+            }
+
+            if trigger != recorder.config().trigger {
+                // The trigger listens to the input kept while armed
+                if trigger.mode != TriggerMode::Manual {
+                    recorder.arm();
+                }
+                recorder.set_trigger(trigger);
+            }
+
+            if trigger.mode != TriggerMode::Manual && !recorder.is_armed() {
+                ui.label(
+                    RichText::new("Arm the recorder for the trigger to listen")
+                        .size(11.0)
+                        .color(colors.text_secondary),
+                );
+            }
+
+            if !self.trigger_log.is_empty() {
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Events").color(colors.text_secondary));
+                    if ui.small_button("Clear").clicked() {
+                        self.trigger_log.clear();
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .id_salt("trigger_log")
+                    .max_height(100.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for entry in &self.trigger_log {
+                            ui.label(RichText::new(entry).size(11.0).monospace());
+                        }
+                    });
+            }
+        });
+    }
+
     /// Draw level meters
     fn draw_level_meters(&mut self, ui: &mut Ui, colors: &ThemeColors) {
         ui.group(|ui| {
@@ -781,6 +1103,45 @@ impl RecordingPanel {
                     });
             });
 
+            if self.save_format == RecordingFormat::Flac {
+                ui.horizontal(|ui| {
+                    ui.label("Compression:");
+                    ui.add(egui::Slider::new(
+                        &mut self.flac_config.compression_level,
+                        0..=8,
+                    ))
+                    .on_hover_text("Higher levels make smaller files and take longer to encode");
+                    ui.add_space(10.0);
+                    ui.label("Bit depth:");
+                    egui::ComboBox::from_id_salt("flac_bit_depth")
+                        .selected_text(format!("{}-bit", self.flac_config.bits_per_sample))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.flac_config.bits_per_sample,
+                                16,
+                                "16-bit",
+                            );
+                            ui.selectable_value(
+                                &mut self.flac_config.bits_per_sample,
+                                24,
+                                "24-bit",
+                            );
+                        });
+                });
+            }
+
+            // Takes are streamed to disk in the chosen format too
+            let bits_per_sample = match self.save_format {
+                RecordingFormat::Wav => 32,
+                RecordingFormat::Flac => self.flac_config.bits_per_sample,
+            };
+            if let Some(recorder) = &mut self.recorder {
+                let config = recorder.config();
+                if config.format != self.save_format || config.bits_per_sample != bits_per_sample {
+                    recorder.set_take_format(self.save_format, bits_per_sample);
+                }
+            }
+
             ui.add_space(5.0);
 
             // Save button (lock-free access)
@@ -795,17 +1156,25 @@ impl RecordingPanel {
                     .add_enabled(can_save, egui::Button::new("💾 Save Recording..."))
                     .clicked()
                 {
-                    // TODO: Open file dialog
-                    self.show_save_dialog = true;
+                    self.save_recording();
                 }
 
                 if ui.button("🗑️ Clear Buffer").clicked() {
                     if let Some(recorder) = &self.recorder {
                         recorder.buffer().clear();
                     }
+                    self.buffer_take = None;
                 }
             });
 
+            if let Some(status) = &self.save_status {
+                ui.label(
+                    RichText::new(status)
+                        .size(11.0)
+                        .color(colors.text_secondary),
+                );
+            }
+
             ui.add_space(5.0);
             ui.label(
                 RichText::new("ℹ️ Recording will be saved as multi-channel interleaved audio")
@@ -815,6 +1184,59 @@ impl RecordingPanel {
         });
     }
 
+    /// Ask where to save the recording and write it in the chosen format
+    ///
+    /// The recorder's buffer holds the last live take, so that's the take
+    /// the file is named after and FLAC files are tagged with, whichever
+    /// take is selected.
+    fn save_recording(&mut self) {
+        let Some(recorder) = &self.recorder else {
+            return;
+        };
+        let (filter, extension) = match self.save_format {
+            RecordingFormat::Wav => ("WAV audio", "wav"),
+            RecordingFormat::Flac => ("FLAC audio", "flac"),
+        };
+        let take = self
+            .buffer_take
+            .and_then(|id| self.takes.iter().find(|take| take.id == id));
+        let file_name = format!(
+            "{}.{}",
+            take.map_or("Recording", |take| take.label.as_str()),
+            extension
+        );
+
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter(filter, &[extension])
+            .set_file_name(file_name);
+        if let Some(directory) = Path::new(&self.save_path).parent() {
+            if !directory.as_os_str().is_empty() {
+                dialog = dialog.set_directory(directory);
+            }
+        }
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        let result = match self.save_format {
+            RecordingFormat::Wav => recorder.save_to_wav(&path),
+            RecordingFormat::Flac => {
+                let mut config = self.flac_config.clone();
+                if let Some(take) = take {
+                    config = config
+                        .with_tag("TITLE", &take.label)
+                        .with_tag("COMMENT", &take.notes);
+                }
+                recorder.save_to_flac(&path, &config)
+            }
+        };
+        self.save_status = Some(match result {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => format!("Save failed: {:#}", e),
+        });
+        self.save_path = path.display().to_string();
+    }
+
     fn draw_take_manager(&mut self, ui: &mut Ui, colors: &ThemeColors) {
         ui.group(|ui| {
             ui.horizontal(|ui| {
@@ -884,26 +1306,47 @@ impl RecordingPanel {
     fn draw_take_details(&self, ui: &mut Ui, colors: &ThemeColors, take: &RecordedTake) {
         ui.indent(format!("take_detail_{}", take.id), |ui| {
             ui.horizontal(|ui| {
+                // Recovered takes aren't loaded, so there are no levels to show
+                if take.source != TakeSource::Recovered {
+                    ui.label(
+                        RichText::new(format!("Peak {:.1} dBFS", Self::linear_to_db(take.peak)))
+                            .color(colors.text_secondary),
+                    );
+                    ui.add_space(10.0);
+                    ui.label(
+                        RichText::new(format!("RMS {:.1} dBFS", Self::linear_to_db(take.rms)))
+                            .color(colors.text_secondary),
+                    );
+                    ui.add_space(10.0);
+                    ui.label(
+                        RichText::new(format!("Clips {}", take.clip_events))
+                            .color(colors.text_secondary),
+                    );
+                    ui.add_space(10.0);
+                }
                 ui.label(
-                    RichText::new(format!("Peak {:.1} dBFS", Self::linear_to_db(take.peak)))
-                        .color(colors.text_secondary),
-                );
-                ui.add_space(10.0);
-                ui.label(
-                    RichText::new(format!("RMS {:.1} dBFS", Self::linear_to_db(take.rms)))
+                    RichText::new(format!("Captured {}", take.timestamp_label))
                         .color(colors.text_secondary),
                 );
-                ui.add_space(10.0);
+            });
+
+            if let Some(path) = &take.path {
                 ui.label(
-                    RichText::new(format!("Clips {}", take.clip_events))
+                    RichText::new(format!("File {}", path.display()))
+                        .size(11.0)
                         .color(colors.text_secondary),
                 );
-                ui.add_space(10.0);
+            }
+            if take.dropped_frames > 0 {
                 ui.label(
-                    RichText::new(format!("Captured {}", take.timestamp_label))
-                        .color(colors.text_secondary),
+                    RichText::new(format!(
+                        "⚠ {} frames missing from the file; the disk fell behind",
+                        take.dropped_frames
+                    ))
+                    .size(11.0)
+                    .color(colors.warning),
                 );
-            });
+            }
 
             let (rect, _) =
                 ui.allocate_exact_size(Vec2::new(ui.available_width(), 48.0), egui::Sense::hover());
@@ -0,0 +1,154 @@
+//! Verifies that `RouterProcessor::process` never touches the heap
+//!
+//! A counting global allocator records allocations made by the current
+//! thread while counting is switched on, so tests running in parallel on
+//! other threads do not interfere.
+
+use rusty_audio_core::audio::{
+    AudioRouter, ChannelMatrix, EqBand, EqProcessor, InsertPoint, LimiterProcessor,
+    NullDestination, ResamplerQuality, SignalGeneratorSource,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+fn record_allocation() {
+    if COUNTING.with(Cell::get) {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_allocation();
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Run `f` and return how many heap operations it performed on this thread
+fn count_allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(0));
+    COUNTING.with(|counting| counting.set(true));
+    f();
+    COUNTING.with(|counting| counting.set(false));
+    ALLOCATIONS.with(Cell::get)
+}
+
+fn tone(sample_rate: u32) -> Vec<f32> {
+    (0..sample_rate)
+        .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+        .collect()
+}
+
+#[test]
+fn test_process_does_not_allocate() {
+    let router = AudioRouter::new(512);
+
+    // Same-rate mono → stereo route, a 44.1 → 48 kHz resampling route and a
+    // stereo fan-out, all sharing one destination
+    let direct = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+        tone(48000),
+        48000.0,
+        true,
+    )));
+    let converted = router.add_source(Box::new(SignalGeneratorSource::from_buffer(
+        tone(44100),
+        44100.0,
+        true,
+    )));
+    let output = router.add_destination(Box::new(NullDestination::new(48000, 2)));
+    let monitor = router.add_destination(Box::new(NullDestination::new(48000, 1)));
+
+    let direct_route = router.create_route(direct, output, 0.5).unwrap();
+    let converted_route = router.create_route(converted, output, 0.5).unwrap();
+    let monitor_route = router.create_route(converted, monitor, 1.0).unwrap();
+
+    // A submix bus between a source and the output
+    let submix = router.add_bus("Submix", 48000, 2).unwrap();
+    let submix_bus = router.get_bus(submix).unwrap();
+    router.create_route(direct, submix_bus.input, 1.0).unwrap();
+    router.create_route(submix_bus.output, output, 0.5).unwrap();
+    router
+        .set_route_resampler_quality(converted_route, ResamplerQuality::High)
+        .unwrap();
+
+    // Insert chains on a route and on the shared destination
+    let eq = EqProcessor::new(&[EqBand::new(1000.0, 1.0, 6.0), EqBand::new(80.0, 0.7, -3.0)]);
+    let eq_handle = eq.handle();
+    router
+        .set_processor_chain(InsertPoint::Route(direct_route), vec![Box::new(eq)])
+        .unwrap();
+    router
+        .set_processor_chain(
+            InsertPoint::Destination(output),
+            vec![Box::new(LimiterProcessor::default())],
+        )
+        .unwrap();
+
+    let mut processor = router.take_processor().unwrap();
+
+    // Let the processor apply the queued graph edits and fill its FIFOs
+    for _ in 0..4 {
+        processor.process().unwrap();
+    }
+
+    let allocations = count_allocations(|| {
+        for _ in 0..200 {
+            processor.process().unwrap();
+        }
+    });
+    assert_eq!(allocations, 0);
+
+    // Parameter edits made on another thread must not make processing
+    // allocate either, including applying them on the audio thread
+    router.set_route_gain(direct_route, 0.25).unwrap();
+    router.set_route_muted(converted_route, true).unwrap();
+    router.set_bus_gain(submix, 0.8).unwrap();
+    router
+        .set_route_channel_matrix(
+            direct_route,
+            ChannelMatrix::pick(1, &[Some(0), None]).unwrap(),
+        )
+        .unwrap();
+    router
+        .set_processor_mix(InsertPoint::Route(direct_route), 0, 0.5)
+        .unwrap();
+    eq_handle.set_band(0, EqBand::new(2000.0, 2.0, -6.0));
+
+    // Gain ramps, crossfades and routes fading out before removal
+    router
+        .crossfade_routes(direct_route, converted_route, Duration::from_millis(20))
+        .unwrap();
+    assert!(router.remove_route(monitor_route));
+
+    let allocations = count_allocations(|| {
+        for _ in 0..200 {
+            processor.process().unwrap();
+        }
+    });
+    assert_eq!(allocations, 0);
+
+    // Released memory is collected on the control side
+    router.collect_garbage();
+}
@@ -7,6 +7,8 @@ use lofty::{file::TaggedFileExt, tag::Accessor};
 #[cfg(not(target_arch = "wasm32"))]
 use rfd::FileHandle;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -21,8 +23,22 @@ use web_audio_api::node::{
 // Import hybrid audio backend (native only for now)
 #[cfg(not(target_arch = "wasm32"))]
 use rusty_audio_core::audio::{
-    AudioConfig, AudioDeviceManager, BackendHealth, FallbackPolicy, HybridAudioBackend, HybridMode,
-    StreamDirection, WebAudioBridge, WebAudioBridgeConfig,
+    AudioConfig, AudioDeviceManager, BackendHealth, Crossfade, CrossfadeConfig, CrossfadeCurve,
+    FallbackPolicy, HybridAudioBackend, HybridMode, LoudnessScanner, PlayQueue, RepeatMode,
+    ReplayGainConfig, ReplayGainMode, ScanReport, StreamDirection, WebAudioBridge,
+    WebAudioBridgeConfig,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use rusty_audio_core::{
+    audio::crossfade::is_continuous,
+    library::{AlbumSummary, Library, LibraryTrack, ScanSummary, TrackFilter},
+    lyrics::Lyrics,
+    metadata::{
+        CoverArtEdit, LoftyMetadataExtractor, MetadataExtractorInterface, TagEdit, TagField,
+        TagFields,
+    },
+    playlist::{CueSheet, Playlist, PlaylistFormat, PlaylistTrack},
+    security::{FileValidator, SecureConfig},
 };
 
 // Use library modules instead of declaring them locally
@@ -82,6 +98,143 @@ struct TrackMetadata {
 
 const WAVEFORM_PREVIEW_SAMPLES: usize = 1024;
 
+/// Most search results listed by the library browser
+const LIBRARY_SEARCH_LIMIT: usize = 500;
+
+/// Grouping listed by the library browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum LibraryView {
+    #[default]
+    Artists,
+    Albums,
+    Genres,
+}
+
+impl LibraryView {
+    const ALL: [LibraryView; 3] = [
+        LibraryView::Artists,
+        LibraryView::Albums,
+        LibraryView::Genres,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            LibraryView::Artists => "Artists",
+            LibraryView::Albums => "Albums",
+            LibraryView::Genres => "Genres",
+        }
+    }
+}
+
+/// What the library browser cached from the library database
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+struct LibraryBrowser {
+    view: LibraryView,
+    search: String,
+    folders: Vec<PathBuf>,
+    track_count: usize,
+    artists: Vec<String>,
+    albums: Vec<AlbumSummary>,
+    genres: Vec<String>,
+    /// Selected artist, album or genre
+    selection: Option<TrackFilter>,
+    /// Search results, or the tracks of the selection
+    tracks: Vec<LibraryTrack>,
+    /// Listed tracks selected for the tag editor
+    selected: BTreeSet<PathBuf>,
+}
+
+/// Library browser input, applied after drawing
+#[cfg(not(target_arch = "wasm32"))]
+enum LibraryAction {
+    AddFolder,
+    RemoveFolder(PathBuf),
+    Rescan,
+    Search,
+    Select(TrackFilter),
+    /// Select a listed track, adding it to the selection if `extend`
+    SelectTrack {
+        index: usize,
+        extend: bool,
+    },
+    Play(usize),
+}
+
+/// Inspector tag editor for the selected library tracks or the current file
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+struct TagEditor {
+    /// Files being edited
+    paths: Vec<PathBuf>,
+    /// Per field, the value every file shares, or `None` if they differ
+    shared: BTreeMap<TagField, Option<String>>,
+    /// Text in the editor's fields
+    text: BTreeMap<TagField, String>,
+    /// Pending change to the front cover
+    cover_art: Option<CoverArtEdit>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TagEditor {
+    /// Read the tags of `paths`, leaving out files that can't be read
+    fn load(paths: Vec<PathBuf>) -> Self {
+        let mut editor = Self::default();
+        let mut tags = Vec::with_capacity(paths.len());
+        for path in paths {
+            match TagFields::read(&path) {
+                Ok(fields) => {
+                    tags.push(fields);
+                    editor.paths.push(path);
+                }
+                Err(e) => eprintln!("Warning: Could not read tags of {}: {}", path.display(), e),
+            }
+        }
+        for field in TagField::ALL {
+            let mut values = tags.iter().map(|fields| fields.get(field));
+            let first = values.next().unwrap_or("");
+            let shared = values
+                .all(|value| value == first)
+                .then(|| first.to_string());
+            editor
+                .text
+                .insert(field, shared.clone().unwrap_or_default());
+            editor.shared.insert(field, shared);
+        }
+        editor
+    }
+
+    /// The changes typed in: fields whose text differs from what the files
+    /// share, and mixed fields that were typed into
+    fn edit(&self) -> TagEdit {
+        let mut edit = TagEdit::new();
+        for (&field, text) in &self.text {
+            let changed = match self.shared.get(&field).and_then(Option::as_ref) {
+                Some(shared) => shared != text,
+                None => !text.is_empty(),
+            };
+            if changed {
+                edit.set(field, text.clone());
+            }
+        }
+        edit.set_cover_art(self.cover_art.clone());
+        edit
+    }
+}
+
+/// Lyrics of the playing track and the line being sung
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default)]
+struct LyricsView {
+    lyrics: Option<Lyrics>,
+    /// Index of the synchronized line at the playback position
+    line: Option<usize>,
+    /// The line changed since the panel last scrolled to it
+    scroll_to_line: bool,
+    /// Announce each line through the screen reader
+    announce: bool,
+}
+
 // ============================================================================
 // Native Application (Desktop)
 // ============================================================================
@@ -94,6 +247,26 @@ struct AudioPlayerApp {
     // Playback state (kept in UI for responsiveness)
     playback_state: PlaybackState,
     current_file: Option<Arc<FileHandle>>,
+    play_queue: PlayQueue,
+    crossfade: CrossfadeConfig,
+    replay_gain: ReplayGainConfig,
+    /// Tags of queued tracks, read once for crossfade and ReplayGain
+    /// decisions; `None` if a file's tags couldn't be read
+    track_tags: HashMap<PathBuf, Option<rusty_audio_core::metadata::TrackMetadata>>,
+    /// ReplayGain scan of the queue running in the background
+    loudness_scan: Option<std::thread::JoinHandle<ScanReport>>,
+    /// Music library, if its database could be opened
+    library: Option<Library>,
+    /// Library folder scan running in the background
+    library_scan: Option<std::thread::JoinHandle<rusty_audio_core::Result<ScanSummary>>>,
+    library_browser: LibraryBrowser,
+    tag_editor: TagEditor,
+    /// Cue sheet splitting the playing file(s) into tracks
+    cue_sheet: Option<CueSheet>,
+    /// Index of the cue sheet track playing
+    cue_track: Option<usize>,
+    /// Lyrics of the playing track
+    lyrics: LyricsView,
     metadata: Option<TrackMetadata>,
     volume: f32,
     panning: f32,
@@ -197,13 +370,25 @@ impl Default for AudioPlayerApp {
             );
         }
 
-        Self {
+        let mut app = Self {
             // Audio Engine (replaces 12 audio fields)
             audio_engine,
 
             // Playback state
             playback_state: PlaybackState::Stopped,
             current_file: None,
+            play_queue: PlayQueue::new(),
+            crossfade: CrossfadeConfig::default(),
+            replay_gain: ReplayGainConfig::default(),
+            track_tags: HashMap::new(),
+            loudness_scan: None,
+            library: Self::open_library(),
+            library_scan: None,
+            library_browser: LibraryBrowser::default(),
+            tag_editor: TagEditor::default(),
+            cue_sheet: None,
+            cue_track: None,
+            lyrics: LyricsView::default(),
             metadata: None,
             volume: 0.5,
             panning: 0.5,
@@ -285,7 +470,9 @@ impl Default for AudioPlayerApp {
             _async_loader: AsyncAudioLoader::new(AsyncLoadConfig::default()),
             _tokio_runtime: Self::build_async_runtime(),
             load_progress: None,
-        }
+        };
+        app.refresh_library_browser();
+        app
     }
 }
 
@@ -855,22 +1042,6 @@ impl AudioPlayerApp {
                         }
                     });
             });
-
-            ui.add_space(15.0);
-
-            // Audio effects controls placeholder
-            ui.group(|ui| {
-                ui.label(RichText::new("Audio Effects").color(colors.text));
-                ui.add_space(5.0);
-                ui.label("Additional audio effects will be implemented here");
-
-                // Placeholder for future effects
-                ui.horizontal(|ui| {
-                    ui.checkbox(&mut false, "Reverb");
-                    ui.checkbox(&mut false, "Chorus");
-                    ui.checkbox(&mut false, "Delay");
-                });
-            });
         });
     }
 
@@ -1204,11 +1375,45 @@ impl AudioPlayerApp {
         // Get spectrum data from audio engine
         // The AudioEngine internally handles spectrum processing and normalization
 
+        if self.audio_engine.take_track_change() {
+            self.on_queued_track_started();
+        }
+
+        if self
+            .loudness_scan
+            .as_ref()
+            .is_some_and(|scan| scan.is_finished())
+        {
+            self.finish_loudness_scan();
+        }
+
+        if self
+            .library_scan
+            .as_ref()
+            .is_some_and(|scan| scan.is_finished())
+        {
+            self.finish_library_scan();
+        }
+
         if self.playback_state == PlaybackState::Playing && !self.is_seeking {
-            // Use audio_context from engine to get current time
-            self.playback_pos =
-                Duration::from_secs_f64(self.audio_engine.get_context().current_time());
+            self.playback_pos = self.audio_engine.get_position();
+
+            // Nothing was queued to follow on gaplessly (end of the queue, or
+            // the next track failed to decode), so move on the slow way
+            let track_ended = !self.is_looping
+                && self.current_file.is_some()
+                && self.total_duration > Duration::ZERO
+                && self.playback_pos >= self.total_duration;
+            if track_ended && !self.play_queue.is_empty() {
+                match self.play_queue.advance().map(Path::to_path_buf) {
+                    Some(path) => self.load_queue_track(path),
+                    None => self.stop_playback_main(),
+                }
+            }
         }
+
+        self.update_cue_track();
+        self.update_lyrics_line();
     }
 
     fn handle_keyboard_input(&mut self, ctx: &egui::Context) {
@@ -1222,6 +1427,18 @@ impl AudioPlayerApp {
             if i.key_pressed(egui::Key::L) {
                 self.toggle_loop_main();
             }
+            if i.key_pressed(egui::Key::N) {
+                self.next_track_main();
+            }
+            if i.key_pressed(egui::Key::P) {
+                self.previous_track_main();
+            }
+            if i.key_pressed(egui::Key::H) {
+                self.toggle_shuffle_main();
+            }
+            if i.key_pressed(egui::Key::R) {
+                self.cycle_repeat_main();
+            }
             if i.key_pressed(egui::Key::ArrowUp) {
                 self.volume = (self.volume + 0.05).min(1.0);
                 self.audio_engine.set_volume(self.volume);
@@ -1300,7 +1517,7 @@ impl AudioPlayerApp {
     }
 
     fn load_current_file(&mut self) {
-        if let Some(handle) = &self.current_file {
+        if let Some(handle) = self.current_file.clone() {
             let path = handle.path();
             let filename = path
                 .file_name()
@@ -1312,24 +1529,15 @@ impl AudioPlayerApp {
             self.load_progress = Some(0.0);
 
             // Load metadata (quick operation)
-            if let Ok(tagged_file) = lofty::read_from_path(path) {
-                if let Some(tag) = tagged_file.primary_tag() {
-                    self.metadata = Some(TrackMetadata {
-                        title: tag.title().as_deref().unwrap_or("Unknown Title").into(),
-                        artist: tag.artist().as_deref().unwrap_or("Unknown Artist").into(),
-                        album: tag.album().as_deref().unwrap_or("Unknown Album").into(),
-                        year: tag
-                            .year()
-                            .map(|y| y.to_string())
-                            .unwrap_or_else(|| "----".into()),
-                    });
-                }
-                self.album_art = None; // Album art loaded separately
-            }
+            self.read_track_metadata(path);
+            self.update_cue_sheet(path);
+            self.load_lyrics(path);
 
             self.load_progress = Some(0.3); // Metadata loaded
 
             // Load audio file via AudioEngine
+            let gain = self.replay_gain_for(path, self.play_queue.current_index());
+            self.audio_engine.set_replay_gain(gain);
             let path_str = path.to_str().unwrap_or("");
             match self.audio_engine.load_audio_file(path_str) {
                 Ok(duration) => {
@@ -1359,6 +1567,8 @@ impl AudioPlayerApp {
                             format!("Audio file loaded: {}", filename),
                             ui::accessibility::AnnouncementPriority::Medium,
                         );
+
+                        self.queue_upcoming_track();
                     }
 
                     self.load_progress = None; // Loading complete
@@ -1386,6 +1596,776 @@ impl AudioPlayerApp {
         }
     }
 
+    fn read_track_metadata(&mut self, path: &Path) {
+        if let Ok(tagged_file) = lofty::read_from_path(path) {
+            if let Some(tag) = tagged_file.primary_tag() {
+                self.metadata = Some(TrackMetadata {
+                    title: tag.title().as_deref().unwrap_or("Unknown Title").into(),
+                    artist: tag.artist().as_deref().unwrap_or("Unknown Artist").into(),
+                    album: tag.album().as_deref().unwrap_or("Unknown Album").into(),
+                    year: tag
+                        .year()
+                        .map(|y| y.to_string())
+                        .unwrap_or_else(|| "----".into()),
+                });
+            }
+            self.album_art = None; // Album art loaded separately
+        }
+    }
+
+    /// Keep the cue sheet if it covers `path`, otherwise use the one
+    /// embedded in it, if any
+    fn update_cue_sheet(&mut self, path: &Path) {
+        // The file's own tags were just read; tick() puts the track's back
+        self.cue_track = None;
+        let covered = self
+            .cue_sheet
+            .as_ref()
+            .is_some_and(|sheet| sheet.tracks.iter().any(|track| track.file == path));
+        if covered {
+            return;
+        }
+        let is_flac = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("flac"));
+        self.cue_sheet = if is_flac {
+            CueSheet::from_flac(path).unwrap_or_else(|e| {
+                eprintln!(
+                    "Warning: Could not read cue sheet of {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Follow the playback position through the cue sheet tracks, showing
+    /// the metadata of the one playing
+    fn update_cue_track(&mut self) {
+        let (Some(sheet), Some(handle)) = (&self.cue_sheet, &self.current_file) else {
+            return;
+        };
+        let index = sheet.track_at(handle.path(), self.playback_pos);
+        if index == self.cue_track {
+            return;
+        }
+        let track = index.and_then(|index| sheet.tracks.get(index));
+        let metadata = track.map(|track| TrackMetadata {
+            title: track.metadata.title.clone(),
+            artist: track.metadata.artist.clone(),
+            album: track.metadata.album.clone(),
+            year: track.metadata.year.clone(),
+        });
+        self.cue_track = index;
+        if let Some(metadata) = metadata {
+            self.accessibility_manager.announce(
+                format!("Now playing: {}", metadata.title),
+                ui::accessibility::AnnouncementPriority::Low,
+            );
+            self.metadata = Some(metadata);
+        }
+    }
+
+    /// Play cue sheet track `index` from its start, loading its file first
+    /// if another one is playing
+    fn play_cue_track(&mut self, index: usize) {
+        let Some(track) = self
+            .cue_sheet
+            .as_ref()
+            .and_then(|sheet| sheet.tracks.get(index))
+        else {
+            return;
+        };
+        let (file, start) = (track.file.clone(), track.start);
+        let playing = self
+            .current_file
+            .as_ref()
+            .is_some_and(|handle| handle.path() == file);
+        if !playing {
+            let position = self
+                .play_queue
+                .tracks()
+                .iter()
+                .position(|path| *path == file);
+            let Some(path) = position
+                .and_then(|position| self.play_queue.play_index(position))
+                .map(Path::to_path_buf)
+            else {
+                return;
+            };
+            self.load_queue_track(path);
+        }
+        self.seek_to_position_main(start.as_secs_f32());
+        self.update_cue_track();
+    }
+
+    /// Read the lyrics of `path`, from its `.lrc` file or its tags
+    fn load_lyrics(&mut self, path: &Path) {
+        let lyrics = Lyrics::load(path).unwrap_or_else(|e| {
+            eprintln!(
+                "Warning: Could not read lyrics of {}: {}",
+                path.display(),
+                e
+            );
+            None
+        });
+        self.lyrics = LyricsView {
+            lyrics,
+            announce: self.lyrics.announce,
+            ..LyricsView::default()
+        };
+    }
+
+    /// Follow the playback position through synchronized lyrics
+    fn update_lyrics_line(&mut self) {
+        let view = &mut self.lyrics;
+        let Some(lyrics) = &view.lyrics else {
+            return;
+        };
+        let line = lyrics.line_at(self.playback_pos);
+        if line == view.line {
+            return;
+        }
+        view.line = line;
+        view.scroll_to_line = true;
+
+        let text = match (lyrics, line) {
+            (Lyrics::Synced(lines), Some(index)) => lines.get(index).map(|line| &line.text),
+            _ => None,
+        };
+        if let Some(text) = text.filter(|text| view.announce && !text.is_empty()) {
+            self.accessibility_manager
+                .announce(text.clone(), ui::accessibility::AnnouncementPriority::Low);
+        }
+    }
+
+    /// Pre-decode the track the queue plays next so the engine can start it
+    /// on the exact frame the current one ends
+    fn queue_upcoming_track(&mut self) {
+        let upcoming = self.play_queue.upcoming().map(Path::to_path_buf);
+        match upcoming {
+            Some(path) if !self.is_looping => {
+                let crossfade = self.crossfade_into(&path);
+                self.audio_engine.set_crossfade(crossfade);
+                let gain = self.replay_gain_for(&path, self.play_queue.upcoming_index());
+                self.audio_engine.set_replay_gain(gain);
+                let path_str = path.to_str().unwrap_or("");
+                if let Err(e) = self.audio_engine.queue_next_file(path_str) {
+                    // tick() falls back to loading it when the current track ends
+                    eprintln!("Warning: Could not pre-decode {}: {}", path.display(), e);
+                    self.audio_engine.clear_queued_file();
+                }
+            }
+            _ => self.audio_engine.clear_queued_file(),
+        }
+    }
+
+    /// The engine moved on to the pre-decoded track
+    fn on_queued_track_started(&mut self) {
+        let Some(path) = self.play_queue.advance().map(Path::to_path_buf) else {
+            return;
+        };
+        self.read_track_metadata(&path);
+        self.update_cue_sheet(&path);
+        self.load_lyrics(&path);
+        self.current_file = Some(Arc::new(FileHandle::from(path.clone())));
+        self.total_duration = self.audio_engine.get_duration();
+        self.playback_pos = Duration::ZERO;
+        if let Some(waveform) = self.audio_engine.get_waveform(WAVEFORM_PREVIEW_SAMPLES) {
+            self.waveform_preview = waveform;
+            self.waveform_dirty = true;
+        }
+
+        let filename = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Unknown file");
+        self.accessibility_manager.announce(
+            format!("Now playing: {}", filename),
+            ui::accessibility::AnnouncementPriority::Low,
+        );
+
+        self.queue_upcoming_track();
+    }
+
+    fn load_queue_track(&mut self, path: PathBuf) {
+        let crossfade = self.crossfade_into(&path);
+        self.audio_engine.set_crossfade(crossfade);
+        self.current_file = Some(Arc::new(FileHandle::from(path)));
+        self.load_current_file();
+    }
+
+    /// Tags of the track at `path`, read the first time they're asked for
+    fn track_tags(&mut self, path: &Path) -> Option<rusty_audio_core::metadata::TrackMetadata> {
+        if let Some(metadata) = self.track_tags.get(path) {
+            return metadata.clone();
+        }
+        let metadata = LoftyMetadataExtractor::new().extract_metadata(path).ok();
+        self.track_tags.insert(path.to_path_buf(), metadata.clone());
+        metadata
+    }
+
+    /// Crossfade from the current track into `next`, if the settings call
+    /// for one; consecutive tracks of an album keep their gapless join
+    fn crossfade_into(&mut self, next: &Path) -> Option<Crossfade> {
+        if !self.crossfade.enabled {
+            return None;
+        }
+        let from = self
+            .current_file
+            .clone()
+            .and_then(|handle| self.track_tags(handle.path()));
+        let to = self.track_tags(next);
+        self.crossfade.between(from.as_ref(), to.as_ref())
+    }
+
+    /// ReplayGain level for the track at `path`, queue entry `index`
+    fn replay_gain_for(&mut self, path: &Path, index: Option<usize>) -> f32 {
+        if self.replay_gain.mode == ReplayGainMode::Off {
+            return 1.0;
+        }
+        let Some(metadata) = self.track_tags(path) else {
+            return 1.0;
+        };
+        let album_order =
+            self.replay_gain.mode == ReplayGainMode::Auto && self.in_album_order(&metadata, index);
+        self.replay_gain.gain(&metadata.replay_gain, album_order)
+    }
+
+    /// Whether queue entry `index` is being played as part of its album:
+    /// unshuffled, next to the track before or after it on the album
+    fn in_album_order(
+        &mut self,
+        metadata: &rusty_audio_core::metadata::TrackMetadata,
+        index: Option<usize>,
+    ) -> bool {
+        let Some(index) = index.filter(|_| !self.play_queue.shuffle()) else {
+            return false;
+        };
+        let track = |index: Option<usize>| {
+            index.and_then(|index| self.play_queue.tracks().get(index).cloned())
+        };
+        let (previous, next) = (track(index.checked_sub(1)), track(index.checked_add(1)));
+        previous
+            .and_then(|path| self.track_tags(&path))
+            .is_some_and(|previous| is_continuous(&previous, metadata))
+            || next
+                .and_then(|path| self.track_tags(&path))
+                .is_some_and(|next| is_continuous(metadata, &next))
+    }
+
+    /// Measure the queued files and tag them with ReplayGain values
+    fn start_loudness_scan(&mut self) {
+        let tracks = self.play_queue.tracks().to_vec();
+        match std::thread::Builder::new()
+            .name("loudness-scan".to_string())
+            .spawn(move || LoudnessScanner::default().scan(&tracks))
+        {
+            Ok(handle) => {
+                self.loudness_scan = Some(handle);
+                self.audio_status_message = Some((
+                    "Scanning queue for ReplayGain...".to_string(),
+                    Instant::now(),
+                ));
+            }
+            Err(e) => eprintln!("Warning: Could not start ReplayGain scan: {}", e),
+        }
+    }
+
+    fn finish_loudness_scan(&mut self) {
+        let Some(handle) = self.loudness_scan.take() else {
+            return;
+        };
+        let message = match handle.join() {
+            Ok(report) => {
+                for (path, e) in &report.failed {
+                    eprintln!(
+                        "Warning: ReplayGain scan of {} failed: {}",
+                        path.display(),
+                        e
+                    );
+                }
+                format!(
+                    "ReplayGain scan: {} tagged, {} already tagged, {} failed",
+                    report.tracks.len(),
+                    report.skipped.len(),
+                    report.failed.len()
+                )
+            }
+            Err(_) => "ReplayGain scan crashed".to_string(),
+        };
+        self.audio_status_message = Some((message, Instant::now()));
+        // Pick up the new tags
+        self.track_tags.clear();
+        self.apply_replay_gain_settings();
+    }
+
+    /// Open the music library database, or run without a library
+    fn open_library() -> Option<Library> {
+        let path = Library::default_path()?;
+        match Library::open(&path) {
+            Ok(library) => Some(library),
+            Err(e) => {
+                eprintln!("Warning: Could not open music library: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Re-read everything the library browser shows
+    fn refresh_library_browser(&mut self) {
+        let Some(library) = &self.library else {
+            return;
+        };
+        let browser = &mut self.library_browser;
+        let result = (|| -> rusty_audio_core::Result<()> {
+            browser.folders = library.folders()?;
+            browser.track_count = library.track_count()?;
+            browser.artists = library.artists()?;
+            browser.albums = library.albums(None)?;
+            browser.genres = library.genres()?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            eprintln!("Warning: Could not read music library: {}", e);
+        }
+        self.refresh_library_tracks();
+    }
+
+    /// Re-run the search, or list the selection if there is no search
+    fn refresh_library_tracks(&mut self) {
+        let Some(library) = &self.library else {
+            return;
+        };
+        let browser = &mut self.library_browser;
+        let tracks = if !browser.search.trim().is_empty() {
+            library.search(&browser.search, LIBRARY_SEARCH_LIMIT)
+        } else if let Some(filter) = &browser.selection {
+            library.tracks(filter)
+        } else {
+            Ok(Vec::new())
+        };
+        match tracks {
+            Ok(tracks) => {
+                browser
+                    .selected
+                    .retain(|path| tracks.iter().any(|track| &track.path == path));
+                browser.tracks = tracks;
+            }
+            Err(e) => eprintln!("Warning: Could not read music library: {}", e),
+        }
+    }
+
+    fn add_library_folder(&mut self) {
+        let Some(library) = &self.library else {
+            return;
+        };
+        let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+        if let Err(e) = library.add_folder(&folder) {
+            eprintln!("Warning: Could not add library folder: {}", e);
+            return;
+        }
+        self.refresh_library_browser();
+        self.start_library_scan();
+    }
+
+    fn remove_library_folder(&mut self, folder: &Path) {
+        let Some(library) = &mut self.library else {
+            return;
+        };
+        if let Err(e) = library.remove_folder(folder) {
+            eprintln!("Warning: Could not remove library folder: {}", e);
+        }
+        self.refresh_library_browser();
+    }
+
+    /// Rescan the library folders in the background, on a connection of
+    /// its own so the browser stays usable
+    fn start_library_scan(&mut self) {
+        let Some(path) = Library::default_path() else {
+            return;
+        };
+        match std::thread::Builder::new()
+            .name("library-scan".to_string())
+            .spawn(move || Library::open(&path)?.scan())
+        {
+            Ok(handle) => {
+                self.library_scan = Some(handle);
+                self.audio_status_message =
+                    Some(("Scanning music library...".to_string(), Instant::now()));
+            }
+            Err(e) => eprintln!("Warning: Could not start library scan: {}", e),
+        }
+    }
+
+    fn finish_library_scan(&mut self) {
+        let Some(handle) = self.library_scan.take() else {
+            return;
+        };
+        let message = match handle.join() {
+            Ok(Ok(summary)) => {
+                for (path, e) in &summary.failed {
+                    eprintln!("Warning: Could not index {}: {}", path.display(), e);
+                }
+                for folder in &summary.unavailable_folders {
+                    eprintln!("Warning: Library folder {} unavailable", folder.display());
+                }
+                format!(
+                    "Library scan: {} added, {} updated, {} removed, {} failed",
+                    summary.added,
+                    summary.updated,
+                    summary.removed,
+                    summary.failed.len()
+                )
+            }
+            Ok(Err(e)) => format!("Library scan failed: {}", e),
+            Err(_) => "Library scan crashed".to_string(),
+        };
+        self.audio_status_message = Some((message, Instant::now()));
+        self.refresh_library_browser();
+    }
+
+    /// Replace the queue with the listed library tracks, starting at `index`
+    fn play_library_tracks(&mut self, index: usize) {
+        let paths = self
+            .library_browser
+            .tracks
+            .iter()
+            .map(|track| track.path.clone());
+        self.play_queue.clear();
+        self.play_queue.extend(paths);
+        if let Some(path) = self.play_queue.play_index(index).map(Path::to_path_buf) {
+            self.load_queue_track(path);
+        }
+    }
+
+    /// Library folders, browsing by artist, album or genre, and search
+    fn draw_library_browser(&mut self, ui: &mut egui::Ui, colors: &ThemeColors) {
+        if self.library.is_none() {
+            ui.label(RichText::new("Music library unavailable").color(colors.text_secondary));
+            return;
+        }
+        let scanning = self.library_scan.is_some();
+        let browser = &mut self.library_browser;
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Library").color(colors.accent).strong());
+            ui.label(
+                RichText::new(format!("{} tracks", browser.track_count))
+                    .color(colors.text_secondary),
+            );
+            if scanning {
+                ui.spinner();
+            }
+        });
+        for folder in &browser.folders {
+            ui.horizontal(|ui| {
+                let remove = ui
+                    .add_enabled(!scanning, egui::Button::new("❌").small())
+                    .on_hover_text("Remove from library");
+                if remove.clicked() {
+                    action = Some(LibraryAction::RemoveFolder(folder.clone()));
+                }
+                ui.label(folder.display().to_string());
+            });
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!scanning, egui::Button::new("Add Folder..."))
+                .clicked()
+            {
+                action = Some(LibraryAction::AddFolder);
+            }
+            let can_rescan = !scanning && !browser.folders.is_empty();
+            if ui
+                .add_enabled(can_rescan, egui::Button::new("Rescan"))
+                .clicked()
+            {
+                action = Some(LibraryAction::Rescan);
+            }
+        });
+
+        ui.add_space(5.0);
+        let search = egui::TextEdit::singleline(&mut browser.search)
+            .hint_text("Search title, artist, album...");
+        if ui.add(search).changed() {
+            action = Some(LibraryAction::Search);
+        }
+        ui.horizontal(|ui| {
+            for view in LibraryView::ALL {
+                ui.selectable_value(&mut browser.view, view, view.name());
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("library_groups")
+            .max_height(150.0)
+            .show(ui, |ui| {
+                let mut entry = |ui: &mut egui::Ui, filter: TrackFilter, text: String| {
+                    let selected = browser.selection.as_ref() == Some(&filter);
+                    if ui.selectable_label(selected, text).clicked() {
+                        action = Some(LibraryAction::Select(filter));
+                    }
+                };
+                match browser.view {
+                    LibraryView::Artists => {
+                        for artist in &browser.artists {
+                            entry(ui, TrackFilter::Artist(artist.clone()), artist.clone());
+                        }
+                    }
+                    LibraryView::Albums => {
+                        for album in &browser.albums {
+                            let filter = TrackFilter::Album {
+                                album: album.album.clone(),
+                                album_artist: album.album_artist.clone(),
+                            };
+                            let text = format!("{} - {}", album.album, album.album_artist);
+                            entry(ui, filter, text);
+                        }
+                    }
+                    LibraryView::Genres => {
+                        for genre in &browser.genres {
+                            entry(ui, TrackFilter::Genre(genre.clone()), genre.clone());
+                        }
+                    }
+                }
+            });
+
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .id_salt("library_tracks")
+            .show(ui, |ui| {
+                for (index, track) in browser.tracks.iter().enumerate() {
+                    let metadata = &track.metadata;
+                    let text = match metadata.track_number {
+                        Some(number) => {
+                            format!("{}. {} - {}", number, metadata.title, metadata.artist)
+                        }
+                        None => format!("{} - {}", metadata.title, metadata.artist),
+                    };
+                    let selected = browser.selected.contains(&track.path);
+                    let row = ui
+                        .selectable_label(selected, text)
+                        .on_hover_text(track.path.display().to_string());
+                    if row.double_clicked() {
+                        action = Some(LibraryAction::Play(index));
+                    } else if row.clicked() {
+                        let extend = ui.input(|i| i.modifiers.command);
+                        action = Some(LibraryAction::SelectTrack { index, extend });
+                    }
+                }
+            });
+
+        match action {
+            Some(LibraryAction::AddFolder) => self.add_library_folder(),
+            Some(LibraryAction::RemoveFolder(folder)) => self.remove_library_folder(&folder),
+            Some(LibraryAction::Rescan) => self.start_library_scan(),
+            Some(LibraryAction::Search) => {
+                self.library_browser.selection = None;
+                self.refresh_library_tracks();
+            }
+            Some(LibraryAction::Select(filter)) => {
+                self.library_browser.search.clear();
+                self.library_browser.selection = Some(filter);
+                self.refresh_library_tracks();
+            }
+            Some(LibraryAction::SelectTrack { index, extend }) => {
+                let browser = &mut self.library_browser;
+                if let Some(track) = browser.tracks.get(index) {
+                    if !extend {
+                        browser.selected.clear();
+                        browser.selected.insert(track.path.clone());
+                    } else if !browser.selected.remove(&track.path) {
+                        browser.selected.insert(track.path.clone());
+                    }
+                }
+            }
+            Some(LibraryAction::Play(index)) => self.play_library_tracks(index),
+            None => {}
+        }
+    }
+
+    /// Files the tag editor works on: the selected library tracks, or the
+    /// current file if none are selected
+    fn tag_editor_targets(&self) -> Vec<PathBuf> {
+        if self.library_browser.selected.is_empty() {
+            self.current_file
+                .iter()
+                .map(|handle| handle.path().to_path_buf())
+                .collect()
+        } else {
+            self.library_browser.selected.iter().cloned().collect()
+        }
+    }
+
+    /// Write the typed tag changes to every edited file
+    fn save_tags(&mut self, edit: &TagEdit) {
+        let report = match edit.write_all(&self.tag_editor.paths) {
+            Ok(report) => report,
+            Err(e) => {
+                self.audio_status_message =
+                    Some((format!("Tags not saved: {}", e), Instant::now()));
+                return;
+            }
+        };
+        for (path, e) in &report.failed {
+            eprintln!("Warning: Could not tag {}: {}", path.display(), e);
+        }
+        let message = if report.failed.is_empty() {
+            format!("Saved tags of {} files", report.written.len())
+        } else {
+            format!(
+                "Saved tags of {} files, {} failed",
+                report.written.len(),
+                report.failed.len()
+            )
+        };
+        self.audio_status_message = Some((message, Instant::now()));
+        for path in &report.written {
+            self.track_tags.remove(path);
+        }
+
+        let current = self
+            .current_file
+            .as_ref()
+            .map(|handle| handle.path().to_path_buf());
+        if let Some(current) = current.filter(|path| report.written.contains(path)) {
+            self.read_track_metadata(&current);
+        }
+        self.tag_editor = TagEditor::load(self.tag_editor.paths.clone());
+        // Rescanning only reads the files that changed
+        if self.library_scan.is_none() && !self.library_browser.folders.is_empty() {
+            self.start_library_scan();
+        }
+    }
+
+    /// Title, artist, album and the other tags, and the front cover, of the
+    /// files [`Self::tag_editor_targets`] picks
+    fn draw_tag_editor(&mut self, ui: &mut egui::Ui, colors: &ThemeColors) {
+        let targets = self.tag_editor_targets();
+        if targets != self.tag_editor.paths {
+            self.tag_editor = TagEditor::load(targets);
+        }
+        let editor = &mut self.tag_editor;
+        match editor.paths.as_slice() {
+            [] => {
+                ui.label(RichText::new("No file selected").color(colors.text_secondary));
+                return;
+            }
+            [path] => {
+                ui.label(
+                    RichText::new(rusty_audio_core::metadata::utils::get_display_filename(
+                        path,
+                    ))
+                    .color(colors.text),
+                );
+            }
+            paths => {
+                ui.label(
+                    RichText::new(format!("{} files selected", paths.len())).color(colors.text),
+                );
+            }
+        }
+
+        egui::Grid::new("tag_editor_fields")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for field in TagField::ALL {
+                    let mixed = editor.shared.get(&field).is_some_and(Option::is_none);
+                    let text = editor.text.entry(field).or_default();
+                    ui.label(field.label());
+                    let mut edit = egui::TextEdit::singleline(text);
+                    if mixed {
+                        edit = edit.hint_text("<multiple values>");
+                    }
+                    ui.add(edit);
+                    ui.end_row();
+                }
+            });
+
+        ui.horizontal(|ui| {
+            if ui.button("Choose Cover...").clicked() {
+                if let Some(image) = rfd::FileDialog::new()
+                    .add_filter(
+                        "Images",
+                        &["png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff"],
+                    )
+                    .pick_file()
+                {
+                    match CoverArtEdit::from_image_file(&image) {
+                        Ok(cover_art) => editor.cover_art = Some(cover_art),
+                        Err(e) => eprintln!("Warning: Could not use {}: {}", image.display(), e),
+                    }
+                }
+            }
+            if ui.button("Remove Cover").clicked() {
+                editor.cover_art = Some(CoverArtEdit::Remove);
+            }
+        });
+        match &editor.cover_art {
+            Some(CoverArtEdit::Replace(data)) => {
+                ui.label(format!("New cover: {} KB", data.len().div_ceil(1024)));
+            }
+            Some(CoverArtEdit::Remove) => {
+                ui.label("Cover will be removed");
+            }
+            None => {}
+        }
+
+        // Show what validation will change before anything is written
+        let edit = editor.edit();
+        let validated = edit.validated();
+        match &validated {
+            Ok(validated) => {
+                for field in TagField::ALL {
+                    if let (Some(typed), Some(saved)) = (edit.get(field), validated.get(field)) {
+                        if typed != saved {
+                            ui.label(
+                                RichText::new(format!("{} saved as \"{}\"", field.label(), saved))
+                                    .color(colors.text_secondary),
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                ui.label(RichText::new(e.to_string()).color(Color32::from_rgb(255, 120, 120)));
+            }
+        }
+
+        let mut save = false;
+        ui.horizontal(|ui| {
+            let can_save = !edit.is_empty() && validated.is_ok();
+            save = ui
+                .add_enabled(can_save, egui::Button::new("Save Tags"))
+                .clicked();
+            if ui
+                .add_enabled(!edit.is_empty(), egui::Button::new("Revert"))
+                .clicked()
+            {
+                *editor = TagEditor::load(std::mem::take(&mut editor.paths));
+            }
+        });
+        if save {
+            self.save_tags(&edit);
+        }
+    }
+
+    /// Re-apply ReplayGain after the settings changed
+    fn apply_replay_gain_settings(&mut self) {
+        if let Some(handle) = self.current_file.clone() {
+            let gain = self.replay_gain_for(handle.path(), self.play_queue.current_index());
+            self.audio_engine.set_current_replay_gain(gain);
+        }
+        self.queue_upcoming_track();
+    }
+
     fn reset_all_settings(&mut self) {
         // Reset equalizer via AudioEngine
         for i in 0..8 {
@@ -1449,15 +2429,196 @@ impl AudioPlayerApp {
 
     // Audio control methods
     fn open_file_dialog(&mut self) {
-        if let Some(file) = rfd::FileDialog::new()
+        if let Some(files) = rfd::FileDialog::new()
             .add_filter("Audio Files", &["mp3", "wav", "flac", "ogg", "m4a"])
-            .pick_file()
+            .add_filter("Cue Sheets", &["cue"])
+            .pick_files()
         {
-            self.current_file = Some(Arc::new(rfd::FileHandle::from(file)));
-            self.load_current_file();
+            let is_cue = |path: &PathBuf| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("cue"))
+            };
+            if let Some(sheet) = files.iter().find(|path| is_cue(path)) {
+                self.open_cue_sheet(sheet);
+                return;
+            }
+            // The selection replaces the queue, played in the order picked
+            self.play_queue.clear();
+            self.play_queue.extend(files);
+            if let Some(first) = self.play_queue.current().map(Path::to_path_buf) {
+                self.load_queue_track(first);
+            }
         }
     }
 
+    /// Replace the queue with the tracks of a playlist file
+    fn open_playlist_dialog(&mut self) {
+        let extensions = PlaylistFormat::ALL.map(PlaylistFormat::extension);
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Playlists", &extensions)
+            .pick_file()
+        else {
+            return;
+        };
+        // Entries go through the same sandbox as everything else we open
+        let sandbox = SecureConfig::load_or_default()
+            .unwrap_or_default()
+            .security
+            .sandbox_path;
+        let load = match Playlist::load(&path, &FileValidator::new(sandbox)) {
+            Ok(load) => load,
+            Err(e) => {
+                self.audio_status_message =
+                    Some((format!("Could not open playlist: {}", e), Instant::now()));
+                return;
+            }
+        };
+        for missing in &load.missing {
+            eprintln!(
+                "Warning: Skipped playlist entry {}: {}",
+                missing.location, missing.reason
+            );
+        }
+        let mut message = format!("Loaded {} tracks", load.playlist.tracks.len());
+        if !load.missing.is_empty() {
+            message.push_str(&format!(", {} missing", load.missing.len()));
+        }
+        self.audio_status_message = Some((message, Instant::now()));
+
+        self.play_queue.clear();
+        self.play_queue
+            .extend(load.playlist.paths().map(Path::to_path_buf));
+        if let Some(first) = self.play_queue.current().map(Path::to_path_buf) {
+            self.load_queue_track(first);
+        }
+    }
+
+    /// Replace the queue with the files of a cue sheet, whose tracks are
+    /// then listed in the file browser
+    fn open_cue_sheet(&mut self, path: &Path) {
+        let sheet = match CueSheet::load(path) {
+            Ok(sheet) => sheet,
+            Err(e) => {
+                self.audio_status_message =
+                    Some((format!("Could not open cue sheet: {}", e), Instant::now()));
+                return;
+            }
+        };
+        self.audio_status_message = Some((
+            format!("Loaded cue sheet of {} tracks", sheet.tracks.len()),
+            Instant::now(),
+        ));
+
+        self.play_queue.clear();
+        self.play_queue
+            .extend(sheet.files().into_iter().map(Path::to_path_buf));
+        self.cue_sheet = Some(sheet);
+        if let Some(first) = self.play_queue.current().map(Path::to_path_buf) {
+            self.load_queue_track(first);
+        }
+    }
+
+    /// Save the queue as a playlist file, in the format of the extension
+    /// picked (M3U8 if there is none)
+    fn save_playlist_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new().set_file_name("playlist.m3u8");
+        for format in PlaylistFormat::ALL {
+            dialog = dialog.add_filter(format.extension().to_uppercase(), &[format.extension()]);
+        }
+        let Some(mut path) = dialog.save_file() else {
+            return;
+        };
+        if PlaylistFormat::from_path(&path).is_none() {
+            path.set_extension(PlaylistFormat::M3u8.extension());
+        }
+
+        let extractor = LoftyMetadataExtractor::new();
+        let tracks = self
+            .play_queue
+            .tracks()
+            .iter()
+            .map(|track| {
+                let metadata = extractor.extract_metadata(track).ok();
+                PlaylistTrack {
+                    path: track.clone(),
+                    title: metadata
+                        .as_ref()
+                        .map(|metadata| format!("{} - {}", metadata.artist, metadata.title)),
+                    duration: metadata.and_then(|metadata| metadata.duration),
+                }
+            })
+            .collect();
+        let message = match (Playlist { tracks }).save(&path) {
+            Ok(()) => format!("Saved playlist of {} tracks", self.play_queue.len()),
+            Err(e) => format!("Could not save playlist: {}", e),
+        };
+        self.audio_status_message = Some((message, Instant::now()));
+    }
+
+    fn next_track_main(&mut self) {
+        // Cue sheet tracks first, so a single-file album skips like any other
+        if let Some(next) = self.cue_track.map(|index| index + 1) {
+            if self
+                .cue_sheet
+                .as_ref()
+                .is_some_and(|sheet| next < sheet.tracks.len())
+            {
+                self.play_cue_track(next);
+                return;
+            }
+        }
+        match self.play_queue.next_track().map(Path::to_path_buf) {
+            Some(path) => self.load_queue_track(path),
+            None if !self.play_queue.is_empty() => {
+                self.accessibility_manager.announce(
+                    "End of queue".to_string(),
+                    ui::accessibility::AnnouncementPriority::Low,
+                );
+            }
+            None => {}
+        }
+    }
+
+    fn previous_track_main(&mut self) {
+        // Like most players, "previous" first restarts a track that has been
+        // playing for a while
+        let track_start = self
+            .cue_track
+            .and_then(|index| self.cue_sheet.as_ref()?.tracks.get(index))
+            .map_or(Duration::ZERO, |track| track.start);
+        if self.playback_pos > track_start + Duration::from_secs(3) {
+            self.seek_to_position_main(track_start.as_secs_f32());
+            return;
+        }
+        if let Some(index) = self.cue_track.and_then(|index| index.checked_sub(1)) {
+            self.play_cue_track(index);
+            return;
+        }
+        if let Some(path) = self.play_queue.previous_track().map(Path::to_path_buf) {
+            self.load_queue_track(path);
+        }
+    }
+
+    fn toggle_shuffle_main(&mut self) {
+        let shuffle = !self.play_queue.shuffle();
+        self.play_queue.set_shuffle(shuffle);
+        self.queue_upcoming_track();
+        self.accessibility_manager.announce(
+            format!("Shuffle {}", if shuffle { "on" } else { "off" }),
+            ui::accessibility::AnnouncementPriority::Low,
+        );
+    }
+
+    fn cycle_repeat_main(&mut self) {
+        let repeat = self.play_queue.repeat().cycle();
+        self.play_queue.set_repeat(repeat);
+        self.queue_upcoming_track();
+        self.accessibility_manager.announce(
+            format!("Repeat {}", repeat_label(repeat)),
+            ui::accessibility::AnnouncementPriority::Low,
+        );
+    }
+
     fn play_pause_main(&mut self) {
         match self.playback_state {
             PlaybackState::Playing => {
@@ -1501,6 +2662,7 @@ impl AudioPlayerApp {
             // Revert state on error
             self.is_looping = !self.is_looping;
         }
+        self.queue_upcoming_track();
     }
 
     fn seek_to_position_main(&mut self, position_seconds: f32) {
@@ -1672,6 +2834,16 @@ impl AudioPlayerApp {
                     |this| this.open_file_dialog(),
                 );
 
+                self.transport_button(
+                    ui,
+                    colors,
+                    "⏮ Prev",
+                    secondary_width,
+                    button_height,
+                    false,
+                    |this| this.previous_track_main(),
+                );
+
                 let play_label = if self.playback_state == PlaybackState::Playing {
                     "⏸ Pause"
                 } else {
@@ -1687,6 +2859,16 @@ impl AudioPlayerApp {
                     |this| this.play_pause_main(),
                 );
 
+                self.transport_button(
+                    ui,
+                    colors,
+                    "⏭ Next",
+                    secondary_width,
+                    button_height,
+                    false,
+                    |this| this.next_track_main(),
+                );
+
                 self.transport_button(
                     ui,
                     colors,
@@ -1712,6 +2894,32 @@ impl AudioPlayerApp {
                     |this| this.toggle_loop_main(),
                 );
 
+                let shuffle_label = if self.play_queue.shuffle() {
+                    "🔀 Shuffle On"
+                } else {
+                    "🔀 Shuffle Off"
+                };
+                self.transport_button(
+                    ui,
+                    colors,
+                    shuffle_label,
+                    secondary_width,
+                    button_height,
+                    false,
+                    |this| this.toggle_shuffle_main(),
+                );
+
+                let repeat_text = format!("🔂 Repeat {}", repeat_label(self.play_queue.repeat()));
+                self.transport_button(
+                    ui,
+                    colors,
+                    &repeat_text,
+                    secondary_width,
+                    button_height,
+                    false,
+                    |this| this.cycle_repeat_main(),
+                );
+
                 let (record_badge, record_color) = self.recording_panel.status_badge();
                 let record_label = if self.recording_panel.is_recording() {
                     format!("{} Stop Rec", record_badge)
@@ -1922,6 +3130,105 @@ impl AudioPlayerApp {
 
             ui.add_space(15.0);
 
+            // Crossfade Settings
+            let mut crossfade_changed = false;
+            ui.group(|ui| {
+                ui.label(RichText::new("🔀 Crossfade").strong());
+                ui.add_space(5.0);
+                crossfade_changed |= ui
+                    .checkbox(&mut self.crossfade.enabled, "Crossfade between tracks")
+                    .changed();
+                ui.add_enabled_ui(self.crossfade.enabled, |ui| {
+                    egui::ComboBox::from_label("Curve")
+                        .selected_text(self.crossfade.curve.name())
+                        .show_ui(ui, |ui| {
+                            for curve in CrossfadeCurve::ALL {
+                                crossfade_changed |= ui
+                                    .selectable_value(
+                                        &mut self.crossfade.curve,
+                                        curve,
+                                        curve.name(),
+                                    )
+                                    .changed();
+                            }
+                        });
+                    let mut seconds = self.crossfade.duration.as_secs_f32();
+                    let slider =
+                        ui.add(egui::Slider::new(&mut seconds, 0.5..=12.0).text("Duration (s)"));
+                    if slider.changed() {
+                        self.crossfade.duration = Duration::from_secs_f32(seconds);
+                    }
+                    // Re-decoding the next track on every drag step would stall the UI
+                    crossfade_changed |=
+                        slider.drag_stopped() || (slider.changed() && !slider.dragged());
+                    ui.label(
+                        RichText::new("Consecutive tracks of an album always play gapless")
+                            .small()
+                            .color(colors.text_secondary),
+                    );
+                });
+            });
+            if crossfade_changed {
+                // Re-plan the transition already scheduled
+                self.queue_upcoming_track();
+            }
+
+            ui.add_space(15.0);
+
+            // ReplayGain Settings
+            let mut replay_gain_changed = false;
+            ui.group(|ui| {
+                ui.label(RichText::new("📏 ReplayGain").strong());
+                ui.add_space(5.0);
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(self.replay_gain.mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in ReplayGainMode::ALL {
+                            replay_gain_changed |= ui
+                                .selectable_value(&mut self.replay_gain.mode, mode, mode.name())
+                                .changed();
+                        }
+                    });
+                ui.add_enabled_ui(self.replay_gain.mode != ReplayGainMode::Off, |ui| {
+                    let preamp = ui.add(
+                        egui::Slider::new(&mut self.replay_gain.preamp_db, -15.0..=15.0)
+                            .text("Preamp (dB)"),
+                    );
+                    replay_gain_changed |=
+                        preamp.drag_stopped() || (preamp.changed() && !preamp.dragged());
+                    replay_gain_changed |= ui
+                        .checkbox(&mut self.replay_gain.prevent_clipping, "Prevent clipping")
+                        .changed();
+                    ui.label(
+                        RichText::new("Auto uses album gain while an album plays in order")
+                            .small()
+                            .color(colors.text_secondary),
+                    );
+                });
+                ui.add_space(5.0);
+                let scanning = self.loudness_scan.is_some();
+                let scan_label = if scanning {
+                    "⏳ Scanning..."
+                } else {
+                    "🔍 Scan queue"
+                };
+                if ui
+                    .add_enabled(
+                        !scanning && !self.play_queue.is_empty(),
+                        egui::Button::new(scan_label),
+                    )
+                    .on_hover_text("Measure untagged files in the queue and write ReplayGain tags")
+                    .clicked()
+                {
+                    self.start_loudness_scan();
+                }
+            });
+            if replay_gain_changed {
+                self.apply_replay_gain_settings();
+            }
+
+            ui.add_space(15.0);
+
             // Audio Backend Settings (Phase 3.1 Enhanced UI)
             let mut should_setup_hybrid = false;
 
@@ -2294,6 +3601,21 @@ impl AudioPlayerApp {
                         ui.label("Toggle Loop");
                     });
 
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("N/P").color(colors.accent).strong());
+                        ui.label("Next/Previous Track");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("H").color(colors.accent).strong());
+                        ui.label("Toggle Shuffle");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("R").color(colors.accent).strong());
+                        ui.label("Cycle Repeat (Off/All/One)");
+                    });
+
                     ui.horizontal(|ui| {
                         ui.label(RichText::new("Ctrl+O").color(colors.accent).strong());
                         ui.label("Open File");
@@ -2355,6 +3677,15 @@ impl AudioPlayerApp {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn repeat_label(repeat: RepeatMode) -> &'static str {
+    match repeat {
+        RepeatMode::Off => "Off",
+        RepeatMode::All => "All",
+        RepeatMode::One => "One",
+    }
+}
+
 // ============================================================================
 // Platform-specific entry points
 // ============================================================================
@@ -15,6 +15,18 @@ impl PanelContent for AudioPlayerApp {
             if ui.button("Open Audio File...").clicked() {
                 self.open_file_dialog();
             }
+            ui.horizontal(|ui| {
+                if ui.button("Open Playlist...").clicked() {
+                    self.open_playlist_dialog();
+                }
+                let can_save = !self.play_queue.is_empty();
+                if ui
+                    .add_enabled(can_save, egui::Button::new("Save Queue as Playlist..."))
+                    .clicked()
+                {
+                    self.save_playlist_dialog();
+                }
+            });
 
             ui.add_space(10.0);
 
@@ -28,6 +40,37 @@ impl PanelContent for AudioPlayerApp {
             } else {
                 ui.label(RichText::new("No file loaded").color(colors.text_secondary));
             }
+
+            if let Some(sheet) = &self.cue_sheet {
+                ui.add_space(10.0);
+                let heading = sheet.title.as_deref().unwrap_or("Cue Sheet");
+                ui.label(RichText::new(heading).color(colors.accent).strong());
+                let mut clicked = None;
+                egui::ScrollArea::vertical()
+                    .id_salt("cue_tracks")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for (index, track) in sheet.tracks.iter().enumerate() {
+                            let text = format!(
+                                "{:02}. {}  {}",
+                                track.number,
+                                track.metadata.title,
+                                format_duration(track.start)
+                            );
+                            let playing = self.cue_track == Some(index);
+                            if ui.selectable_label(playing, text).clicked() {
+                                clicked = Some(index);
+                            }
+                        }
+                    });
+                if let Some(index) = clicked {
+                    self.play_cue_track(index);
+                }
+            }
+
+            ui.add_space(10.0);
+            ui.separator();
+            self.draw_library_browser(ui, &colors);
         });
     }
     fn show_waveform(&mut self, ui: &mut egui::Ui) {
@@ -193,6 +236,14 @@ impl PanelContent for AudioPlayerApp {
 
             ui.add_space(10.0);
 
+            ui.group(|ui| {
+                ui.label(RichText::new("Tags").color(colors.accent).strong());
+                ui.separator();
+                self.draw_tag_editor(ui, &colors);
+            });
+
+            ui.add_space(10.0);
+
             ui.group(|ui| {
                 ui.label(RichText::new("Performance").color(colors.accent).strong());
                 ui.separator();
@@ -277,6 +328,15 @@ impl PanelContent for AudioPlayerApp {
 
                 ui.add_space(5.0);
 
+                if ui
+                    .add_sized(button_size, egui::Button::new("⏮️ Prev"))
+                    .clicked()
+                {
+                    self.previous_track_main();
+                }
+
+                ui.add_space(5.0);
+
                 let play_pause_text = if self.playback_state == PlaybackState::Playing {
                     "⏸️ Pause"
                 } else {
@@ -291,6 +351,15 @@ impl PanelContent for AudioPlayerApp {
 
                 ui.add_space(5.0);
 
+                if ui
+                    .add_sized(button_size, egui::Button::new("⏭️ Next"))
+                    .clicked()
+                {
+                    self.next_track_main();
+                }
+
+                ui.add_space(5.0);
+
                 if ui
                     .add_sized(button_size, egui::Button::new("⏹️ Stop"))
                     .clicked()
@@ -319,6 +388,72 @@ impl PanelContent for AudioPlayerApp {
 
         self.draw_settings_panel_main(ui, &colors);
     }
+
+    fn show_lyrics(&mut self, ui: &mut egui::Ui) {
+        let colors = self.theme_manager.get_colors();
+        let colors = self.accessibility_manager.get_accessible_colors(&colors);
+        let mut seek_to = None;
+
+        ui.vertical(|ui| {
+            ui.heading(RichText::new("🎤 Lyrics").color(colors.text));
+            ui.checkbox(&mut self.lyrics.announce, "Announce lines")
+                .on_hover_text("Read each line out through the screen reader as it is sung");
+            ui.add_space(10.0);
+
+            let view = &mut self.lyrics;
+            match &view.lyrics {
+                None => {
+                    ui.label(
+                        RichText::new("No lyrics for this track").color(colors.text_secondary),
+                    );
+                }
+                Some(Lyrics::Plain(text)) => {
+                    egui::ScrollArea::vertical()
+                        .id_salt("lyrics_text")
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(text).color(colors.text));
+                        });
+                }
+                Some(Lyrics::Synced(lines)) => {
+                    egui::ScrollArea::vertical()
+                        .id_salt("lyrics_lines")
+                        .show(ui, |ui| {
+                            for (index, line) in lines.iter().enumerate() {
+                                // Empty lines mark instrumental breaks
+                                let text = if line.text.is_empty() {
+                                    "♪"
+                                } else {
+                                    line.text.as_str()
+                                };
+                                let current = view.line == Some(index);
+                                let text = if current {
+                                    RichText::new(text).color(colors.accent).strong()
+                                } else {
+                                    RichText::new(text).color(colors.text_secondary)
+                                };
+                                let response = ui
+                                    .add(egui::Label::new(text).sense(egui::Sense::click()))
+                                    .on_hover_text(format!(
+                                        "Seek to {}",
+                                        format_duration(line.time)
+                                    ));
+                                if current && view.scroll_to_line {
+                                    response.scroll_to_me(Some(egui::Align::Center));
+                                    view.scroll_to_line = false;
+                                }
+                                if response.clicked() {
+                                    seek_to = Some(line.time);
+                                }
+                            }
+                        });
+                }
+            }
+        });
+
+        if let Some(time) = seek_to {
+            self.seek_to_position_main(time.as_secs_f32());
+        }
+    }
 }
 
 // Helper function to format duration